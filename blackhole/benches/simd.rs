@@ -0,0 +1,64 @@
+use cgmath::Vector3;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use blackhole::framebuffer::Pixel;
+use blackhole::simd::{self, scalar};
+use blackhole::texture::{NoiseTexture3D, Texture3D};
+
+fn points(count: usize) -> Vec<Vector3<f64>> {
+    (0..count)
+        .map(|i| {
+            let f = i as f64 * 0.37;
+            Vector3::new(f.sin() * 3.0, f.cos() * 2.0, (f * 0.1).fract())
+        })
+        .collect()
+}
+
+pub fn sphere_batch(c: &mut Criterion) {
+    let points = points(1024);
+    let mut out = vec![0.0; points.len()];
+    let center = Vector3::new(0.5, 0.0, -0.5);
+
+    c.bench_function("sphere_batch_scalar", |b| {
+        b.iter(|| scalar::sphere_dist(black_box(&points), center, 1.0, &mut out))
+    });
+
+    c.bench_function(&format!("sphere_batch_{:?}", simd::level()), |b| {
+        b.iter(|| simd::sphere_dist(black_box(&points), center, 1.0, &mut out))
+    });
+}
+
+pub fn perlin(c: &mut Criterion) {
+    let points = points(9);
+    let gradients = std::array::from_fn(|i| points[i]);
+
+    c.bench_function("perlin_filter_scalar", |b| {
+        b.iter(|| scalar::perlin_filter(black_box(&gradients), 0.3, 0.6, 0.9))
+    });
+
+    c.bench_function(&format!("perlin_filter_{:?}", simd::level()), |b| {
+        b.iter(|| simd::perlin_filter(black_box(&gradients), 0.3, 0.6, 0.9))
+    });
+
+    let noise = NoiseTexture3D::new(10.0, 0, 3);
+
+    c.bench_function("noise_texture", |b| {
+        b.iter(|| noise.color_at(black_box(Vector3::new(0.1, 0.2, 0.3))))
+    });
+}
+
+pub fn blend(c: &mut Criterion) {
+    let src = vec![Pixel::new(0.5, 0.25, 1.0, 1.0); 1280];
+    let mut dst = vec![Pixel::black(); 1280];
+
+    c.bench_function("blend_scalar", |b| {
+        b.iter(|| scalar::blend_pixels(&mut dst, black_box(&src), 0.75, 0.25))
+    });
+
+    c.bench_function(&format!("blend_{:?}", simd::level()), |b| {
+        b.iter(|| simd::blend_pixels(&mut dst, black_box(&src), 0.75, 0.25))
+    });
+}
+
+criterion_group!(benches, sphere_batch, perlin, blend);
+criterion_main!(benches);
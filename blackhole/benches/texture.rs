@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use cgmath::Vector3;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use blackhole::object::AABB;
+use blackhole::texture::{
+    CurlNoiseTexture3D, NoiseTexture3D, Texture3D, TextureKind, WorleyTexture3D,
+};
+
+fn positions() -> Vec<Vector3<f64>> {
+    (0..64)
+        .map(|i| Vector3::new(i as f64 * 0.013, (i as f64 * 0.7).sin(), 0.25))
+        .collect()
+}
+
+fn sum(texture: &impl Texture3D<Output = f64>, positions: &[Vector3<f64>]) -> f64 {
+    positions.iter().map(|p| texture.color_at(*p)).sum()
+}
+
+fn sum_dyn(texture: &dyn Texture3D<Output = f64>, positions: &[Vector3<f64>]) -> f64 {
+    positions.iter().map(|p| texture.color_at(*p)).sum()
+}
+
+pub fn dispatch(c: &mut Criterion) {
+    let positions = positions();
+
+    let perlin = NoiseTexture3D::new(10.0, 0, 1);
+    let worley = WorleyTexture3D::new(8.0, 0);
+    let curl = CurlNoiseTexture3D::new(1.0, 0, 2);
+    let bounds = AABB {
+        x_min: 0.0,
+        x_max: 1.0,
+        y_min: -1.0,
+        y_max: 1.0,
+        z_min: 0.0,
+        z_max: 0.5,
+    };
+    let baked = TextureKind::from(NoiseTexture3D::new(10.0, 0, 4)).bake(bounds, 64);
+
+    for (name, texture, kind) in [
+        (
+            "perlin",
+            Arc::new(perlin.clone()) as Arc<dyn Texture3D<Output = f64>>,
+            TextureKind::from(perlin.clone()),
+        ),
+        (
+            "worley",
+            Arc::new(worley.clone()),
+            TextureKind::from(worley.clone()),
+        ),
+        (
+            "curl",
+            Arc::new(TextureKind::from(curl.clone())),
+            TextureKind::from(curl),
+        ),
+        ("baked", Arc::new(baked.clone()), baked),
+    ] {
+        c.bench_function(&format!("{name}_dyn"), |b| {
+            b.iter(|| sum_dyn(texture.as_ref(), black_box(&positions)))
+        });
+
+        c.bench_function(&format!("{name}_kind"), |b| {
+            b.iter(|| sum(&kind, black_box(&positions)))
+        });
+    }
+
+    c.bench_function("perlin_concrete", |b| {
+        b.iter(|| sum(&perlin, black_box(&positions)))
+    });
+
+    c.bench_function("worley_concrete", |b| {
+        b.iter(|| sum(&worley, black_box(&positions)))
+    });
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
@@ -0,0 +1,435 @@
+//! Scene changes over time, used for rendering sequences.
+//!
+//! Time is in seconds, frame `i` of a sequence is at `i / fps`.
+
+use cgmath::{Deg, InnerSpace, Matrix3, One, Quaternion, Rad, Rotation3, Vector3, VectorSpace};
+
+use crate::camera::{Camera, CameraMotion, CameraPose};
+
+/// Camera poses sampled over the shutter interval, keeps curved camera paths curved in the blur.
+const SHUTTER_POSES: usize = 9;
+
+#[derive(Clone)]
+pub struct Animation {
+    pub fps: f64,
+    pub camera: Option<CameraTrack>,
+    pub sky: Option<SkyRotation>,
+    /// Interval the camera moves through while exposing a frame, no camera motion blur if `None`
+    pub shutter: Option<Shutter>,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Self {
+            fps: 24.0,
+            camera: None,
+            sky: None,
+            shutter: None,
+        }
+    }
+
+    /// Time of given frame in seconds.
+    pub fn frame_time(&self, frame: u64) -> f64 {
+        frame as f64 / self.fps
+    }
+
+    /// Length of the animation in seconds, `None` when nothing is animated.
+    ///
+    /// Orbits last one revolution.
+    pub fn duration(&self) -> Option<f64> {
+        let duration = match self.camera.as_ref()? {
+            CameraTrack::Keyframes(keyframes) => keyframes.last()?.time,
+            CameraTrack::Orbit(orbit) => orbit.period.abs(),
+        };
+
+        (duration > 0.0).then_some(duration)
+    }
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Movement of the camera.
+#[derive(Clone)]
+pub enum CameraTrack {
+    /// Linear interpolation between keyframes sorted by time, holding the first and last pose
+    /// outside of them
+    Keyframes(Vec<CameraKeyframe>),
+    Orbit(Orbit),
+}
+
+impl CameraTrack {
+    /// Moves camera to its pose at given time, settings not animated by the track are kept.
+    pub fn apply(&self, camera: &mut Camera, time: f64) {
+        match self {
+            Self::Keyframes(keyframes) => {
+                let next = keyframes.partition_point(|k| k.time <= time);
+
+                match (
+                    next.checked_sub(1).map(|i| &keyframes[i]),
+                    keyframes.get(next),
+                ) {
+                    (Some(a), Some(b)) => {
+                        let factor = (time - a.time) / (b.time - a.time);
+                        a.lerp(b, factor).apply(camera);
+                    }
+                    (Some(k), None) | (None, Some(k)) => k.apply(camera),
+                    (None, None) => {}
+                }
+            }
+            Self::Orbit(orbit) => orbit.apply(camera, time),
+        }
+    }
+
+    /// Poses of the camera from time `open` to `close`, settings not animated are kept.
+    pub fn motion(&self, camera: &Camera, open: f64, close: f64) -> CameraMotion {
+        let mut camera = Camera {
+            motion: None,
+            ..camera.clone()
+        };
+
+        let poses = (0..SHUTTER_POSES)
+            .map(|i| {
+                let factor = i as f64 / (SHUTTER_POSES - 1) as f64;
+                self.apply(&mut camera, open + (close - open) * factor);
+
+                CameraPose::of(&camera)
+            })
+            .collect();
+
+        CameraMotion::new(poses)
+    }
+}
+
+/// Poses around a shutter time from 0 at open to 1 at close, when `count` poses are spread evenly
+/// over the interval. Returns indices of both poses and the factor between them.
+pub(crate) fn shutter_segment(count: usize, time: f64) -> (usize, usize, f64) {
+    let last = count - 1;
+    let position = time.clamp(0.0, 1.0) * last as f64;
+    let index = (position as usize).min(last.saturating_sub(1));
+
+    (index, (index + 1).min(last), position - index as f64)
+}
+
+/// Movement of an object over time, relative to its shape at time 0.
+#[derive(Copy, Clone, Debug)]
+pub enum ObjectTrack {
+    /// Straight line with velocity in units per second
+    Linear { velocity: Vector3<f64> },
+    /// Turning around an axis through `center` with seconds per revolution, counter-clockwise
+    /// when looking against the axis. Objects keep facing the center like tidally locked moons,
+    /// ones around the axis spin in place like accretion disks
+    Orbit {
+        center: Vector3<f64>,
+        axis: Vector3<f64>,
+        period: f64,
+    },
+}
+
+impl ObjectTrack {
+    /// Placement of the object at given time in seconds.
+    pub fn pose_at(&self, time: f64) -> RigidPose {
+        match *self {
+            Self::Linear { velocity } => RigidPose {
+                rotation: Quaternion::one(),
+                translation: velocity * time,
+            },
+            Self::Orbit {
+                center,
+                axis,
+                period,
+            } => {
+                let angle = Rad(time / period * std::f64::consts::TAU);
+                let rotation = Quaternion::from_axis_angle(axis.normalize(), angle);
+
+                RigidPose {
+                    rotation,
+                    translation: center - rotation * center,
+                }
+            }
+        }
+    }
+
+    /// Placements of the object from time `open` to `close`.
+    pub fn motion(&self, open: f64, close: f64) -> ObjectMotion {
+        let poses = (0..SHUTTER_POSES)
+            .map(|i| {
+                let factor = i as f64 / (SHUTTER_POSES - 1) as f64;
+
+                self.pose_at(open + (close - open) * factor)
+            })
+            .collect();
+
+        ObjectMotion { poses }
+    }
+}
+
+/// Rotation and translation moving an object from its place at rest.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RigidPose {
+    pub rotation: Quaternion<f64>,
+    pub translation: Vector3<f64>,
+}
+
+impl RigidPose {
+    /// Position of a point at rest in the scene.
+    pub fn apply(&self, point: Vector3<f64>) -> Vector3<f64> {
+        self.rotation * point + self.translation
+    }
+
+    /// Position at rest of a point in the scene.
+    pub fn to_rest(&self, point: Vector3<f64>) -> Vector3<f64> {
+        self.rotation.conjugate() * (point - self.translation)
+    }
+}
+
+/// Object placements spread evenly over the shutter interval, interpolated linearly between them.
+#[derive(Clone, Debug)]
+pub struct ObjectMotion {
+    poses: Vec<RigidPose>,
+}
+
+impl ObjectMotion {
+    pub fn poses(&self) -> &[RigidPose] {
+        &self.poses
+    }
+
+    /// Placement at shutter time from 0 at open to 1 at close.
+    pub fn pose_at(&self, time: f64) -> RigidPose {
+        let (a, b, factor) = shutter_segment(self.poses.len(), time);
+        let (a, b) = (self.poses[a], self.poses[b]);
+
+        RigidPose {
+            rotation: a.rotation.nlerp(b.rotation, factor),
+            translation: a.translation.lerp(b.translation, factor),
+        }
+    }
+
+    /// Whether the object moves while the shutter is open.
+    pub fn is_moving(&self) -> bool {
+        self.poses.windows(2).any(|pair| pair[0] != pair[1])
+    }
+}
+
+/// Open part of a frame, in frames relative to the frame time.
+///
+/// Open from `0.0` to `0.5` is the 180° shutter of film cameras, `-0.25` to `0.25` centers the
+/// blur on the frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Shutter {
+    pub open: f64,
+    pub close: f64,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct CameraKeyframe {
+    pub time: f64,
+    pub location: Vector3<f64>,
+    pub orientation: Orientation,
+    pub hor_fov: Option<f64>,
+}
+
+/// Direction the camera looks in at a keyframe.
+#[derive(Copy, Clone, Debug)]
+pub enum Orientation {
+    /// Euler angles in degrees, see [`Camera::set_rotation`]
+    Rotation(Vector3<f64>),
+    /// Point the camera looks at
+    Target(Vector3<f64>),
+}
+
+impl CameraKeyframe {
+    fn apply(&self, camera: &mut Camera) {
+        camera.location = self.location;
+
+        match self.orientation {
+            Orientation::Rotation(rotation) => camera.set_rotation(rotation),
+            Orientation::Target(target) => camera.look_at(target),
+        }
+
+        if let Some(fov) = self.hor_fov {
+            camera.hor_fov = fov;
+        }
+    }
+
+    /// Interpolated keyframe, orientations of different kinds switch halfway.
+    fn lerp(&self, other: &Self, factor: f64) -> Self {
+        let orientation = match (self.orientation, other.orientation) {
+            (Orientation::Rotation(a), Orientation::Rotation(b)) => {
+                Orientation::Rotation(a.lerp(b, factor))
+            }
+            (Orientation::Target(a), Orientation::Target(b)) => {
+                Orientation::Target(a.lerp(b, factor))
+            }
+            (a, b) => {
+                if factor < 0.5 {
+                    a
+                } else {
+                    b
+                }
+            }
+        };
+
+        let hor_fov = match (self.hor_fov, other.hor_fov) {
+            (Some(a), Some(b)) => Some(a + (b - a) * factor),
+            (a, b) => a.or(b),
+        };
+
+        Self {
+            time: self.time + (other.time - self.time) * factor,
+            location: self.location.lerp(other.location, factor),
+            orientation,
+            hor_fov,
+        }
+    }
+}
+
+/// Circular path around a point in the XZ plane, with the camera looking at the center.
+#[derive(Copy, Clone, Debug)]
+pub struct Orbit {
+    pub center: Vector3<f64>,
+    pub radius: f64,
+    /// Height of the camera above the center
+    pub height: f64,
+    /// Seconds per revolution, negative values orbit in the opposite direction
+    pub period: f64,
+    /// Angle at time 0, the camera is on the +Z side of the center at 0 degrees
+    pub start_angle: Deg<f64>,
+}
+
+impl Orbit {
+    fn apply(&self, camera: &mut Camera, time: f64) {
+        let angle = self.start_angle.0.to_radians() + time / self.period * std::f64::consts::TAU;
+
+        camera.location = self.center + Vector3::new(angle.sin(), 0.0, angle.cos()) * self.radius;
+        camera.location.y += self.height;
+        camera.look_at(self.center);
+    }
+}
+
+/// Turning of the background around a celestial pole, like the sky seen from a spinning planet.
+#[derive(Copy, Clone, Debug)]
+pub struct SkyRotation {
+    /// Axis the sky turns around
+    pub pole: Vector3<f64>,
+    /// Degrees per second, counter-clockwise when looking against the pole
+    pub rate: f64,
+}
+
+impl SkyRotation {
+    /// Direction into the background at time 0 which is seen along `direction` at given time.
+    pub fn direction_at(&self, direction: Vector3<f64>, time: f64) -> Vector3<f64> {
+        Matrix3::from_axis_angle(self.pole.normalize(), Deg(-self.rate * time)) * direction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::InnerSpace;
+
+    #[test]
+    fn keyframes_interpolate_and_hold() {
+        let keyframe = |time, x| CameraKeyframe {
+            time,
+            location: Vector3::new(x, 0.0, 0.0),
+            orientation: Orientation::Rotation(Vector3::new(0.0, 0.0, 0.0)),
+            hor_fov: None,
+        };
+
+        let track = CameraTrack::Keyframes(vec![keyframe(1.0, 0.0), keyframe(3.0, 4.0)]);
+        let mut camera = Camera::new();
+
+        for (time, x) in [(0.0, 0.0), (1.0, 0.0), (2.5, 3.0), (3.0, 4.0), (10.0, 4.0)] {
+            track.apply(&mut camera, time);
+            assert!((camera.location.x - x).abs() < 1e-12, "at {time}");
+        }
+    }
+
+    #[test]
+    fn rays_sample_camera_over_shutter() {
+        let keyframe = |time, x| CameraKeyframe {
+            time,
+            location: Vector3::new(x, 0.0, 0.0),
+            orientation: Orientation::Rotation(Vector3::new(0.0, 0.0, 0.0)),
+            hor_fov: None,
+        };
+
+        let track = CameraTrack::Keyframes(vec![keyframe(0.0, 0.0), keyframe(1.0, 4.0)]);
+        let mut camera = Camera::new();
+        camera.motion = Some(track.motion(&camera, 0.25, 0.75));
+
+        for _ in 0..16 {
+            let ray = camera.cast_ray(0.5, 0.5, 1.0);
+
+            assert!((ray.location.x - (1.0 + 2.0 * ray.time)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn animated_shapes_follow_track() {
+        use crate::object::shape::{Animated, Shape, Sphere};
+        use std::sync::Arc;
+
+        let mut sphere = Sphere::new();
+        sphere.set_center(Vector3::new(2.0, 0.0, 0.0));
+        sphere.set_radius(0.5);
+
+        let track = ObjectTrack::Orbit {
+            center: Vector3::new(0.0, 0.0, 0.0),
+            axis: Vector3::unit_y(),
+            period: 4.0,
+        };
+
+        // quarter of the orbit during the shutter interval
+        let shape = Animated::new(Arc::new(sphere), Arc::new(track.motion(1.0, 2.0)));
+
+        assert!((shape.dist_fn_at(Vector3::new(0.0, 0.0, -2.0), 0.0) + 0.5).abs() < 1e-9);
+        assert!((shape.dist_fn_at(Vector3::new(-2.0, 0.0, 0.0), 1.0) + 0.5).abs() < 1e-9);
+        assert!(shape.is_moving());
+
+        let bb = shape.bounding_box();
+        assert!(
+            bb.x_min <= -2.5 && bb.z_min <= -2.5 && bb.x_max < 1.0,
+            "{bb:?}"
+        );
+    }
+
+    #[test]
+    fn orbit_looks_at_center() {
+        let orbit = Orbit {
+            center: Vector3::new(1.0, 0.0, 0.0),
+            radius: 10.0,
+            height: 2.0,
+            period: 4.0,
+            start_angle: Deg(0.0),
+        };
+        let mut camera = Camera::new();
+
+        orbit.apply(&mut camera, 1.0);
+
+        assert!((camera.location - Vector3::new(11.0, 2.0, 0.0)).magnitude() < 1e-9);
+
+        let to_center = (orbit.center - camera.location).normalize();
+        assert!((camera.forward() - to_center).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn sky_turns_around_pole() {
+        let sky = SkyRotation {
+            pole: Vector3::new(0.0, 2.0, 0.0),
+            rate: 90.0,
+        };
+
+        let pole = sky.direction_at(Vector3::unit_y(), 1.0);
+        assert!((pole - Vector3::unit_y()).magnitude() < 1e-12);
+
+        let turned = sky.direction_at(Vector3::unit_x(), 1.0);
+        assert!((turned - Vector3::unit_z()).magnitude() < 1e-12);
+        assert!((sky.direction_at(turned, -1.0) - Vector3::unit_x()).magnitude() < 1e-12);
+    }
+}
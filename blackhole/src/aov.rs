@@ -0,0 +1,70 @@
+//! Render passes collected from camera rays together with the color, saving separate renders in
+//! the debug [`crate::RenderMode`]s.
+
+use cgmath::Vector3;
+
+use crate::framebuffer::Pixel;
+use crate::marcher::RayResult;
+
+/// Arbitrary output value, a buffer besides the color filled from every camera ray.
+///
+/// Alpha of passes other than depth is the part of samples which hit an object.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Aov {
+    /// Shading normal of the first hit in world space, from -1 to 1
+    Normal,
+    /// Length of the bent primary ray path, nearest of all samples
+    Depth,
+    /// Color of the first hit without lighting
+    Albedo,
+    /// Steps of the primary ray and its bounces
+    Steps,
+}
+
+impl Aov {
+    pub const ALL: [Self; 4] = [Self::Normal, Self::Depth, Self::Albedo, Self::Steps];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Depth => "depth",
+            Self::Albedo => "albedo",
+            Self::Steps => "steps",
+        }
+    }
+
+    /// Adds the ray of the sample with given index to the pixel holding all earlier samples.
+    pub fn accumulate(&self, pixel: &mut Pixel, result: &RayResult, sample: usize) {
+        // depth keeps the nearest hit, averaging would push edges to infinity
+        if let Self::Depth = self {
+            let depth = result.distance as f32;
+
+            if sample == 0 || depth < pixel.r {
+                *pixel = Pixel::new(depth, depth, depth, 1.0);
+            }
+
+            return;
+        }
+
+        let coverage = if result.surface.is_some() { 1.0 } else { 0.0 };
+        let color = |c: Vector3<f64>| Pixel::new(c.x as f32, c.y as f32, c.z as f32, 1.0);
+
+        let value = match (self, &result.surface) {
+            (Self::Normal, Some(s)) => color(s.normal),
+            (Self::Albedo, Some(s)) => color(s.albedo),
+            (Self::Steps, _) => {
+                let steps = result.steps as f32;
+
+                Pixel::new(steps, steps, steps, coverage)
+            }
+            _ => Pixel::new(0.0, 0.0, 0.0, 0.0),
+        };
+
+        let (keep, add) = (
+            sample as f32 / (sample as f32 + 1.0),
+            1.0 / (sample as f32 + 1.0),
+        );
+
+        *pixel = *pixel * keep + value * add;
+    }
+}
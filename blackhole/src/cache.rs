@@ -0,0 +1,337 @@
+//! Disk cache for generated data, like lookup tables and star catalogs.
+//!
+//! Entries are stored in the platform cache directory and keyed by a hash of whatever the data
+//! was generated from. Data read from user files is stored in slots, one per file, so editing the
+//! file replaces its entry instead of adding another one. Setting `BLACKHOLE_CACHE=0` or building
+//! without the `disk-cache` feature disables the cache.
+
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, Read, Result, Write};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use cgmath::Vector3;
+use once_cell::sync::Lazy;
+
+/// Bump when serialized format or generation of any cached data changes.
+const CACHE_VERSION: u64 = 4;
+
+static CACHE_DIR: Lazy<RwLock<Option<PathBuf>>> = Lazy::new(|| RwLock::new(cache_dir()));
+
+/// Data which can be saved in the disk cache.
+pub trait Cacheable: Sized {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()>;
+    fn read_from(r: &mut dyn Read) -> Result<Self>;
+}
+
+/// Loads entry from cache, or creates it and stores it for next time.
+///
+/// Cache errors are not fatal, the data is just created again.
+pub fn load_or_create<T, F>(name: &str, key: u64, create: F) -> T
+where
+    T: Cacheable,
+    F: FnOnce() -> T,
+{
+    match try_load_or_create(name, key, || Ok::<_, std::convert::Infallible>(create())) {
+        Ok(value) => value,
+        Err(never) => match never {},
+    }
+}
+
+/// Like [`load_or_create`], for data which may fail to be created. Errors are not stored.
+pub fn try_load_or_create<T, E, F>(name: &str, key: u64, create: F) -> std::result::Result<T, E>
+where
+    T: Cacheable,
+    F: FnOnce() -> std::result::Result<T, E>,
+{
+    try_load_or_replace(name, key, key, create)
+}
+
+/// Like [`try_load_or_create`], but keeps single entry in `slot`. Entry with different `key` is
+/// replaced by the newly created data.
+///
+/// Slots are meant for data read from user files, so edits of a file don't fill up the cache.
+pub fn try_load_or_replace<T, E, F>(
+    name: &str,
+    slot: u64,
+    key: u64,
+    create: F,
+) -> std::result::Result<T, E>
+where
+    T: Cacheable,
+    F: FnOnce() -> std::result::Result<T, E>,
+{
+    let path = match &*CACHE_DIR.read().unwrap() {
+        Some(dir) => dir.join(format!("{name}-{slot:016x}.bin")),
+        None => return create(),
+    };
+
+    if let Ok(file) = File::open(&path) {
+        let mut reader = BufReader::new(file);
+
+        if u64::read_from(&mut reader).is_ok_and(|stored| stored == key) {
+            if let Ok(value) = T::read_from(&mut reader) {
+                return Ok(value);
+            }
+        }
+    }
+
+    let value = create()?;
+
+    // write to temporary file first, so parallel processes don't read half written entries
+    let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+    let res = File::create(&tmp_path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        key.write_to(&mut writer)?;
+        value.write_to(&mut writer)?;
+        writer.flush()
+    });
+
+    match res {
+        Ok(()) => {
+            let _ = std::fs::rename(&tmp_path, &path);
+        }
+        Err(_) => {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+    }
+
+    Ok(value)
+}
+
+/// Hashes value with FNV-1a, which unlike the std hasher is stable between runs and builds.
+pub fn content_hash<H: Hash + ?Sized>(value: &H) -> u64 {
+    let mut hasher = ContentHasher(0xcbf29ce484222325);
+    CACHE_VERSION.hash(&mut hasher);
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+struct ContentHasher(u64);
+
+impl Hasher for ContentHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Moves the cache to `dir`, `None` disables it.
+///
+/// Tests use it to keep their entries out of the user cache directory.
+pub fn set_dir(dir: Option<PathBuf>) {
+    if let Some(dir) = &dir {
+        let _ = std::fs::create_dir_all(dir);
+    }
+
+    *CACHE_DIR.write().unwrap() = dir;
+}
+
+/// Temporary directory private to the running process, for tests.
+pub fn temporary_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("blackhole-cache-{}", std::process::id()))
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    if !cfg!(feature = "disk-cache") {
+        return None;
+    }
+
+    if std::env::var_os("BLACKHOLE_CACHE").is_some_and(|v| v == "0") {
+        return None;
+    }
+
+    if cfg!(test) {
+        let dir = temporary_dir();
+        std::fs::create_dir_all(&dir).ok()?;
+
+        return Some(dir);
+    }
+
+    let dir = user_dir(UserDir::Cache)?;
+    std::fs::create_dir_all(&dir).ok()?;
+
+    Some(dir)
+}
+
+/// Kinds of per-user directories, see [`user_dir`].
+#[derive(Copy, Clone, Debug)]
+pub enum UserDir {
+    Cache,
+    Config,
+}
+
+/// Directory for files of the user under the platform base directory of the kind, not created.
+pub fn user_dir(kind: UserDir) -> Option<PathBuf> {
+    let (windows, macos, xdg, home) = match kind {
+        UserDir::Cache => ("LOCALAPPDATA", "Library/Caches", "XDG_CACHE_HOME", ".cache"),
+        UserDir::Config => (
+            "APPDATA",
+            "Library/Application Support",
+            "XDG_CONFIG_HOME",
+            ".config",
+        ),
+    };
+    let in_home = |dir| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(dir));
+
+    let base = if cfg!(windows) {
+        std::env::var_os(windows).map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        in_home(macos)
+    } else {
+        std::env::var_os(xdg)
+            .map(PathBuf::from)
+            .or_else(|| in_home(home))
+    };
+
+    Some(base?.join("blackhole"))
+}
+
+impl Cacheable for f64 {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+
+    fn read_from(r: &mut dyn Read) -> Result<Self> {
+        let mut buf = [0; 8];
+        r.read_exact(&mut buf)?;
+        Ok(f64::from_le_bytes(buf))
+    }
+}
+
+impl Cacheable for u64 {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+
+    fn read_from(r: &mut dyn Read) -> Result<Self> {
+        let mut buf = [0; 8];
+        r.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+}
+
+impl Cacheable for u32 {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+
+    fn read_from(r: &mut dyn Read) -> Result<Self> {
+        let mut buf = [0; 4];
+        r.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+}
+
+impl Cacheable for Vector3<f64> {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        self.x.write_to(w)?;
+        self.y.write_to(w)?;
+        self.z.write_to(w)
+    }
+
+    fn read_from(r: &mut dyn Read) -> Result<Self> {
+        Ok(Vector3::new(
+            f64::read_from(r)?,
+            f64::read_from(r)?,
+            f64::read_from(r)?,
+        ))
+    }
+}
+
+impl<A: Cacheable, B: Cacheable> Cacheable for (A, B) {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        self.0.write_to(w)?;
+        self.1.write_to(w)
+    }
+
+    fn read_from(r: &mut dyn Read) -> Result<Self> {
+        Ok((A::read_from(r)?, B::read_from(r)?))
+    }
+}
+
+impl<T: Cacheable, const N: usize> Cacheable for [T; N] {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        self.iter().try_for_each(|item| item.write_to(w))
+    }
+
+    fn read_from(r: &mut dyn Read) -> Result<Self> {
+        let items = (0..N)
+            .map(|_| T::read_from(r))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(items.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+}
+
+impl<T: Cacheable> Cacheable for Vec<T> {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        (self.len() as u64).write_to(w)?;
+
+        for item in self {
+            item.write_to(w)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_from(r: &mut dyn Read) -> Result<Self> {
+        let len = u64::read_from(r)? as usize;
+
+        // don't trust the length for preallocation, the file may be corrupted
+        let mut vec = Vec::with_capacity(len.min(1 << 16));
+
+        for _ in 0..len {
+            vec.push(T::read_from(r)?);
+        }
+
+        Ok(vec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let data = vec![
+            (0.5, Vector3::new(1.0, 2.0, 3.0)),
+            (-1.0, Vector3::new(0.0, 0.0, 1e-9)),
+        ];
+
+        let mut buf = Vec::new();
+        data.write_to(&mut buf).unwrap();
+
+        let read = Vec::<(f64, Vector3<f64>)>::read_from(&mut buf.as_slice()).unwrap();
+
+        assert_eq!(data, read);
+        assert!(Vec::<(f64, Vector3<f64>)>::read_from(&mut &buf[..buf.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn slot_keeps_latest_entry() {
+        let slot = content_hash("slot_keeps_latest_entry");
+        let load = |key: u64| try_load_or_replace("test", slot, key, || Ok::<_, ()>(key)).unwrap();
+
+        assert_eq!(load(1), 1);
+        assert_eq!(load(2), 2);
+
+        // old entry was replaced, so it is created again
+        let created = try_load_or_replace("test", slot, 1, || Ok::<_, ()>(10_u64)).unwrap();
+        assert_eq!(created, 10);
+
+        let entries = std::fs::read_dir(temporary_dir())
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name() == *format!("test-{slot:016x}.bin"))
+            .count();
+        assert_eq!(entries, 1);
+    }
+}
@@ -1,11 +1,64 @@
+use crate::animation::shutter_segment;
+use crate::math::{euler_rotation, rand_unit};
 use crate::{Ray, RayKind};
-use cgmath::{Deg, InnerSpace, Matrix3, SquareMatrix, Vector3, Zero};
+use cgmath::{InnerSpace, Matrix, Matrix3, Quaternion, SquareMatrix, Vector3, Zero};
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
 
 #[derive(Clone)]
 pub struct Camera {
     pub location: Vector3<f64>,
     pub hor_fov: f64,
     pub rot_mat: Matrix3<f64>,
+    pub exposure: Exposure,
+    pub projection: Projection,
+    /// Radius of the thin lens, 0 for a pinhole camera with everything in focus
+    pub aperture: f64,
+    /// Distance from the camera to the plane in focus, along the forward direction
+    pub focus_distance: f64,
+    /// Distance along every ray where marching starts, skips objects right at the camera
+    pub clip_start: f64,
+    /// Path length from the camera under which marched rays ignore objects, for cutaway views.
+    ///
+    /// Unlike [`Camera::clip_start`] the ray is still bent by distortions on the way there.
+    pub clip_near: f64,
+    /// Path length from the camera past which rays end as background
+    pub clip_far: f64,
+    /// Poses the camera moves through while the shutter is open, set by [`crate::scene::Scene`]
+    /// for animated cameras. Rays then start at a random time of the shutter interval
+    pub motion: Option<CameraMotion>,
+}
+
+/// Maps image coordinates to camera rays.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Rays start at the camera location and spread out by the horizontal FOV
+    Perspective,
+    /// Observer at infinity, rays are parallel to the camera forward direction and start on an
+    /// image plane of given width centered at the camera location.
+    ///
+    /// Distance of a pixel from the image center is the impact parameter of its ray.
+    Parallel { width: f64 },
+    /// Whole sphere around the camera, longitude across the image width and latitude across its
+    /// height. Images should have the aspect ratio of 2
+    Equirectangular,
+    /// Angle from the forward direction grows linearly with distance from the image center, up to
+    /// half of the horizontal FOV at the image sides. Use 180° FOV on square images for domes
+    Fisheye,
+    /// Omni-directional stereo for VR, two equirectangular images with the left eye on top.
+    ///
+    /// Eyes are `separation` apart and turn around the camera location with the longitude, so
+    /// every direction of the horizon has the full parallax.
+    Stereo360 { separation: f64 },
+}
+
+impl Projection {
+    /// Projections without an image plane, which can see all around the camera.
+    pub fn is_panoramic(&self) -> bool {
+        matches!(
+            self,
+            Self::Equirectangular | Self::Fisheye | Self::Stereo360 { .. }
+        )
+    }
 }
 
 impl Camera {
@@ -14,13 +67,104 @@ impl Camera {
             location: Vector3::zero(),
             hor_fov: 90.0,
             rot_mat: Matrix3::identity(),
+            exposure: Exposure::default(),
+            projection: Projection::Perspective,
+            aperture: 0.0,
+            focus_distance: 1.0,
+            clip_start: 0.0,
+            clip_near: 0.0,
+            clip_far: f64::INFINITY,
+            motion: None,
         }
     }
 
     pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
-        self.rot_mat = Matrix3::from_angle_y(Deg(rotation.y))
-            * Matrix3::from_angle_x(Deg(rotation.x))
-            * Matrix3::from_angle_z(Deg(rotation.z));
+        self.rot_mat = euler_rotation(rotation);
+    }
+
+    /// Euler angles in degrees which give the current orientation, see [`Camera::set_rotation`].
+    ///
+    /// When looking straight up or down the roll is folded into the Y angle.
+    pub fn rotation(&self) -> Vector3<f64> {
+        // cgmath matrices are indexed by column first
+        let m = &self.rot_mat;
+        let x = (-m[2][1]).clamp(-1.0, 1.0).asin();
+
+        let (y, z) = if x.cos() > 1e-9 {
+            (m[2][0].atan2(m[2][2]), m[0][1].atan2(m[1][1]))
+        } else {
+            ((-m[0][2]).atan2(m[0][0]), 0.0)
+        };
+
+        Vector3::new(x.to_degrees(), y.to_degrees(), z.to_degrees())
+    }
+
+    /// Turns the camera towards a point, keeping the horizon level.
+    pub fn look_at(&mut self, target: Vector3<f64>) {
+        let forward = (target - self.location).normalize();
+
+        let world_up = if forward.y.abs() > 0.999_999 {
+            Vector3::unit_z()
+        } else {
+            Vector3::unit_y()
+        };
+
+        let side = forward.cross(world_up).normalize();
+        let up = side.cross(forward);
+
+        self.rot_mat = Matrix3::from_cols(side, up, -forward);
+    }
+
+    /// Moves the camera on the line from `center` through its location so a sphere fills the image
+    /// width, and turns it to the center.
+    ///
+    /// Parallel cameras instead get an image plane as wide as the sphere and keep their distance,
+    /// at least the sphere diameter, as do 360° cameras. Cameras at the center back off along
+    /// their forward direction.
+    pub fn frame_sphere(&mut self, center: Vector3<f64>, radius: f64) {
+        let offset = self.location - center;
+        let direction = if offset.magnitude2() > 0.0 {
+            offset.normalize()
+        } else {
+            -self.forward()
+        };
+
+        let distance = match &mut self.projection {
+            Projection::Perspective | Projection::Fisheye => {
+                let half_fov = (self.hor_fov.to_radians() / 2.0).min(FRAC_PI_2);
+
+                radius / half_fov.sin()
+            }
+            Projection::Parallel { width } => {
+                *width = 2.0 * radius;
+
+                offset.magnitude().max(2.0 * radius)
+            }
+            Projection::Equirectangular | Projection::Stereo360 { .. } => {
+                offset.magnitude().max(2.0 * radius)
+            }
+        };
+
+        self.location = center + direction * distance;
+        self.look_at(center);
+    }
+
+    /// Left and right eye cameras `separation` apart, looking in the same direction.
+    ///
+    /// With parallel eyes anything at infinity, like background seen past distortions, has zero
+    /// parallax, while objects and background lensed by distortions get disparity from their
+    /// distance.
+    pub fn stereo_eyes(&self, separation: f64) -> [Self; 2] {
+        [-0.5, 0.5].map(|offset| {
+            let mut eye = self.clone();
+            eye.location += self.side() * separation * offset;
+
+            if let Some(motion) = &mut eye.motion {
+                motion.move_sideways(separation * offset);
+            }
+
+            eye
+        })
     }
 
     pub fn side(&self) -> Vector3<f64> {
@@ -36,38 +180,245 @@ impl Camera {
     }
 
     pub fn cast_ray(&self, x: f64, y: f64, aspect_ratio: f64) -> Ray {
+        let motion = match &self.motion {
+            Some(motion) => motion,
+            None => return self.cast_still_ray(x, y, aspect_ratio, 0.5),
+        };
+
+        let time = rand_unit();
+        let pose = motion.pose_at(time);
+
+        let camera = Camera {
+            location: pose.location,
+            rot_mat: Matrix3::from(pose.rotation),
+            hor_fov: pose.hor_fov,
+            motion: None,
+            ..*self
+        };
+
+        camera.cast_still_ray(x, y, aspect_ratio, time)
+    }
+
+    /// Ray of the current pose with given shutter time.
+    fn cast_still_ray(&self, x: f64, y: f64, aspect_ratio: f64, time: f64) -> Ray {
         let side = self.rot_mat * Vector3::new(1.0, 0.0, 0.0);
         let up = self.rot_mat * Vector3::new(0.0, 1.0, 0.0);
         let forward = self.rot_mat * Vector3::new(0.0, 0.0, -1.0);
 
-        let side = side * (self.hor_fov / 360.0 * std::f64::consts::PI).tan();
-        let up = up * (self.hor_fov / 360.0 * std::f64::consts::PI).tan() / aspect_ratio;
+        let (location, direction) = match self.projection {
+            Projection::Perspective => {
+                let side = side * (self.hor_fov / 360.0 * std::f64::consts::PI).tan();
+                let up = up * (self.hor_fov / 360.0 * std::f64::consts::PI).tan() / aspect_ratio;
+
+                let direction =
+                    (forward + side * (2.0 * x - 1.0) - up * (2.0 * y - 1.0)).normalize();
+
+                (self.location, direction)
+            }
+            Projection::Parallel { width } => {
+                let side = side * (width / 2.0);
+                let up = up * (width / 2.0 / aspect_ratio);
+
+                let location = self.location + side * (2.0 * x - 1.0) - up * (2.0 * y - 1.0);
+
+                (location, forward)
+            }
+            Projection::Equirectangular => (self.location, self.rot_mat * sphere_direction(x, y)),
+            Projection::Fisheye => {
+                let (u, v) = (2.0 * x - 1.0, (2.0 * y - 1.0) / aspect_ratio);
+                let r = u.hypot(v);
+                let angle = r * self.hor_fov.to_radians() / 2.0;
+
+                let local = if r > 0.0 {
+                    Vector3::new(u / r * angle.sin(), -v / r * angle.sin(), -angle.cos())
+                } else {
+                    Vector3::new(0.0, 0.0, -1.0)
+                };
+
+                (self.location, self.rot_mat * local)
+            }
+            Projection::Stereo360 { separation } => {
+                let (eye, y) = if y < 0.5 {
+                    (-0.5, y * 2.0)
+                } else {
+                    (0.5, y * 2.0 - 1.0)
+                };
+
+                let local = sphere_direction(x, y);
+                let horizontal = Vector3::new(local.x, 0.0, local.z);
 
-        let direction = (forward + side * (2.0 * x - 1.0) - up * (2.0 * y - 1.0)).normalize();
+                // eyes sit on a circle, to the sides of the horizontal view direction
+                let offset = if horizontal.magnitude2() > 0.0 {
+                    horizontal.normalize().cross(Vector3::unit_y()) * separation * eye
+                } else {
+                    Vector3::zero()
+                };
+
+                (self.location + self.rot_mat * offset, self.rot_mat * local)
+            }
+        };
+
+        // panoramic projections have no plane to focus on
+        let (location, direction) = if self.aperture > 0.0 && !self.projection.is_panoramic() {
+            // all rays through the lens meet on the focus plane
+            let focus = location + direction * (self.focus_distance / direction.dot(forward));
+
+            let (u, v) = sample_disk(rand_unit(), rand_unit());
+            let location = location + (side * u + up * v) * self.aperture;
+
+            (location, (focus - location).normalize())
+        } else {
+            (location, direction)
+        };
 
         Ray {
-            location: self.location,
+            location: location + direction * self.clip_start,
             direction,
             steps_taken: 0,
             kind: RayKind::Primary,
+            time,
         }
     }
 
-    pub fn cast_ray_panoramic(&self, x: f64, y: f64) -> Ray {
-        let angle_y = (1.0 - y) * 2.0 - 1.0;
+    /// Inverse of [`Camera::cast_ray`], returns relative image coordinates of given point.
+    ///
+    /// Points behind planar cameras have no coordinates. Stereo 360° cameras give coordinates in
+    /// the left eye image, without the eye offset.
+    pub fn project(&self, point: Vector3<f64>, aspect_ratio: f64) -> Option<(f64, f64)> {
+        let local = self.rot_mat.transpose() * (point - self.location);
 
-        let angle_x = (x * 2.0 * std::f64::consts::PI).cos() * (1.0 - angle_y.abs().powi(2));
-        let angle_z = (x * 2.0 * std::f64::consts::PI).sin() * (1.0 - angle_y.abs().powi(2));
+        if local.magnitude2() == 0.0 {
+            return None;
+        }
 
-        let direction = Vector3::new(angle_x, angle_y, angle_z).normalize();
+        match self.projection {
+            Projection::Equirectangular => return Some(sphere_coords(local)),
+            Projection::Stereo360 { .. } => {
+                let (x, y) = sphere_coords(local);
 
-        Ray {
-            location: self.location,
-            direction,
-            steps_taken: 0,
-            kind: RayKind::Primary,
+                return Some((x, y / 2.0));
+            }
+            Projection::Fisheye => {
+                let angle = (-local.z / local.magnitude()).clamp(-1.0, 1.0).acos();
+                let r = angle / (self.hor_fov.to_radians() / 2.0);
+                let around = Vector3::new(local.x, -local.y, 0.0);
+
+                let (u, v) = if around.magnitude2() > 0.0 {
+                    let around = around.normalize() * r;
+
+                    (around.x, around.y * aspect_ratio)
+                } else {
+                    (0.0, 0.0)
+                };
+
+                return Some(((u + 1.0) / 2.0, (v + 1.0) / 2.0));
+            }
+            Projection::Perspective | Projection::Parallel { .. } => {}
+        }
+
+        if local.z >= 0.0 {
+            return None;
+        }
+
+        let (x, y) = match self.projection {
+            Projection::Perspective => {
+                let tan = (self.hor_fov / 360.0 * std::f64::consts::PI).tan();
+
+                (
+                    local.x / -local.z / tan,
+                    -local.y / -local.z / (tan / aspect_ratio),
+                )
+            }
+            Projection::Parallel { width } => {
+                let half = width / 2.0;
+
+                (local.x / half, -local.y / (half / aspect_ratio))
+            }
+            _ => unreachable!("panoramic projections are handled above"),
+        };
+
+        Some(((x + 1.0) / 2.0, (y + 1.0) / 2.0))
+    }
+}
+
+/// Placement of the camera at one moment.
+#[derive(Copy, Clone, Debug)]
+pub struct CameraPose {
+    pub location: Vector3<f64>,
+    pub rotation: Quaternion<f64>,
+    pub hor_fov: f64,
+}
+
+impl CameraPose {
+    pub fn of(camera: &Camera) -> Self {
+        Self {
+            location: camera.location,
+            rotation: Quaternion::from(camera.rot_mat),
+            hor_fov: camera.hor_fov,
+        }
+    }
+}
+
+/// Camera poses spread evenly over the shutter interval, interpolated linearly between them.
+#[derive(Clone, Debug)]
+pub struct CameraMotion {
+    poses: Vec<CameraPose>,
+}
+
+impl CameraMotion {
+    /// Motion through poses at shutter open, close and evenly between them, at least one.
+    pub fn new(poses: Vec<CameraPose>) -> Self {
+        assert!(!poses.is_empty(), "camera motion needs a pose");
+
+        Self { poses }
+    }
+
+    /// Moves every pose to its right side, or left for negative distances.
+    pub fn move_sideways(&mut self, distance: f64) {
+        for pose in &mut self.poses {
+            pose.location += pose.rotation * Vector3::unit_x() * distance;
         }
     }
+
+    /// Pose at shutter time from 0 at open to 1 at close.
+    pub fn pose_at(&self, time: f64) -> CameraPose {
+        let (a, b, factor) = shutter_segment(self.poses.len(), time);
+        let (a, b) = (self.poses[a], self.poses[b]);
+
+        CameraPose {
+            location: a.location + (b.location - a.location) * factor,
+            rotation: a.rotation.nlerp(b.rotation, factor),
+            hor_fov: a.hor_fov + (b.hor_fov - a.hor_fov) * factor,
+        }
+    }
+}
+
+/// Direction in camera space of equirectangular image coordinates, the image center looks forward.
+fn sphere_direction(x: f64, y: f64) -> Vector3<f64> {
+    let longitude = (x - 0.5) * TAU;
+    let latitude = (0.5 - y) * PI;
+
+    Vector3::new(
+        longitude.sin() * latitude.cos(),
+        latitude.sin(),
+        -longitude.cos() * latitude.cos(),
+    )
+}
+
+/// Inverse of [`sphere_direction`] for non-zero vectors.
+fn sphere_coords(local: Vector3<f64>) -> (f64, f64) {
+    let longitude = local.x.atan2(-local.z);
+    let latitude = (local.y / local.magnitude()).clamp(-1.0, 1.0).asin();
+
+    (longitude / TAU + 0.5, 0.5 - latitude / PI)
+}
+
+/// Maps two uniform random numbers to a uniformly distributed point on the unit disk.
+fn sample_disk(a: f64, b: f64) -> (f64, f64) {
+    let r = a.sqrt();
+    let angle = b * std::f64::consts::TAU;
+
+    (r * angle.cos(), r * angle.sin())
 }
 
 impl Default for Camera {
@@ -75,3 +426,182 @@ impl Default for Camera {
         Self::new()
     }
 }
+
+/// Maps scene radiance to pixel values.
+///
+/// Emission of shaders is radiance in W·sr⁻¹·m⁻², radiance of 1.0 maps to pixel value of 1.0
+/// at EV100 of 0. The post chain can expose it like a camera sensor instead, see
+/// [`Pass::Sensor`](crate::post::Pass::Sensor).
+#[derive(Copy, Clone, Debug)]
+pub struct Exposure {
+    pub ev100: f64,
+}
+
+impl Exposure {
+    pub fn from_ev100(ev100: f64) -> Self {
+        Self { ev100 }
+    }
+
+    /// Computes exposure value from physical camera settings, shutter time is in seconds.
+    pub fn from_settings(f_number: f64, shutter: f64, iso: f64) -> Self {
+        let ev100 = (f_number.powi(2) / shutter).log2() - (iso / 100.0).log2();
+
+        Self::from_ev100(ev100)
+    }
+
+    pub fn multiplier(&self) -> f64 {
+        2.0_f64.powf(-self.ev100)
+    }
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Self::from_ev100(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_inverts_cast_ray() {
+        let mut camera = Camera::new();
+        camera.location = Vector3::new(1.0, 2.0, 3.0);
+        camera.hor_fov = 60.0;
+        camera.set_rotation(Vector3::new(-10.0, 30.0, 5.0));
+
+        let ray = camera.cast_ray(0.2, 0.7, 16.0 / 9.0);
+        let (x, y) = camera
+            .project(ray.location + ray.direction * 5.0, 16.0 / 9.0)
+            .unwrap();
+
+        assert!((x - 0.2).abs() < 1e-9);
+        assert!((y - 0.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rotation_inverts_set_rotation() {
+        let mut camera = Camera::new();
+
+        for angles in [[-10.0, 30.0, 5.0], [80.0, -170.0, 0.0], [0.0, 0.0, -45.0]] {
+            camera.set_rotation(Vector3::from(angles));
+
+            let mut copy = Camera::new();
+            copy.set_rotation(camera.rotation());
+
+            for (a, b) in [
+                (camera.side(), copy.side()),
+                (camera.up(), copy.up()),
+                (camera.forward(), copy.forward()),
+            ] {
+                assert!((a - b).magnitude() < 1e-9, "{angles:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn lens_rays_meet_at_focus() {
+        let mut camera = Camera::new();
+        camera.hor_fov = 60.0;
+        camera.aperture = 0.5;
+        camera.focus_distance = 7.0;
+
+        for _ in 0..16 {
+            let ray = camera.cast_ray(0.3, 0.6, 1.5);
+            let focus = ray.location + ray.direction * (7.0 / -ray.direction.z);
+
+            assert!(ray.location.magnitude() <= 0.5 + 1e-9);
+            assert!((camera.project(focus, 1.5).unwrap().0 - 0.3).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn parallel_rays_keep_offset() {
+        let mut camera = Camera::new();
+        camera.location = Vector3::new(0.0, 0.0, 10.0);
+        camera.projection = Projection::Parallel { width: 8.0 };
+
+        let ray = camera.cast_ray(1.0, 0.5, 2.0);
+
+        assert_eq!(ray.direction, Vector3::new(0.0, 0.0, -1.0));
+        assert!((ray.location - Vector3::new(4.0, 0.0, 10.0)).magnitude() < 1e-9);
+
+        let (x, y) = camera
+            .project(ray.location + ray.direction * 5.0, 2.0)
+            .unwrap();
+
+        assert!((x - 1.0).abs() < 1e-9);
+        assert!((y - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn framed_sphere_touches_image_sides() {
+        let mut camera = Camera::new();
+        camera.location = Vector3::new(1.0, 3.0, 2.0);
+        camera.hor_fov = 50.0;
+
+        let center = Vector3::new(1.0, 1.0, -2.0);
+        camera.frame_sphere(center, 2.0);
+
+        let (x, y) = camera.project(center, 1.5).unwrap();
+        assert!((x - 0.5).abs() < 1e-9 && (y - 0.5).abs() < 1e-9);
+
+        // tangent from the camera crosses the plane of the center further out than the radius
+        let edge = center + camera.side() * 2.0 / 25.0_f64.to_radians().cos();
+        assert!((camera.project(edge, 1.5).unwrap().0 - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn panoramic_projections_invert() {
+        let mut camera = Camera::new();
+        camera.location = Vector3::new(1.0, 2.0, 3.0);
+        camera.hor_fov = 180.0;
+        camera.set_rotation(Vector3::new(-10.0, 30.0, 5.0));
+
+        for projection in [Projection::Equirectangular, Projection::Fisheye] {
+            camera.projection = projection;
+
+            let forward = camera.cast_ray(0.5, 0.5, 1.0);
+            assert!((forward.direction - camera.forward()).magnitude() < 1e-9);
+
+            // behind the camera for the panorama, off to the side for the fisheye
+            let ray = camera.cast_ray(0.9, 0.3, 1.0);
+            let (x, y) = camera
+                .project(ray.location + ray.direction * 5.0, 1.0)
+                .unwrap();
+
+            assert!(
+                (x - 0.9).abs() < 1e-9 && (y - 0.3).abs() < 1e-9,
+                "{projection:?}"
+            );
+        }
+
+        camera.projection = Projection::Stereo360 { separation: 0.5 };
+
+        let left = camera.cast_ray(0.5, 0.25, 2.0);
+        let right = camera.cast_ray(0.5, 0.75, 2.0);
+
+        assert!((left.direction - right.direction).magnitude() < 1e-9);
+        assert!((right.location - left.location - camera.side() * 0.5).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn stereo_eyes_have_no_parallax_at_infinity() {
+        let mut camera = Camera::new();
+        camera.set_rotation(Vector3::new(-10.0, 30.0, 0.0));
+
+        let [left, right] = camera.stereo_eyes(0.5);
+
+        assert!(((left.location - right.location).magnitude() - 0.5).abs() < 1e-9);
+
+        let far = camera.forward() * 1e9 + camera.up() * 1e8;
+        let near = camera.forward() * 5.0;
+
+        let disparity =
+            |point| left.project(point, 1.5).unwrap().0 - right.project(point, 1.5).unwrap().0;
+
+        assert!(disparity(far).abs() < 1e-6);
+        assert!(disparity(near) > 0.01);
+    }
+}
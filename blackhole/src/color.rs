@@ -0,0 +1,207 @@
+//! Conversions from light spectra to linear RGB.
+
+use cgmath::{ElementWise, Vector3, Zero};
+use once_cell::sync::Lazy;
+
+/// Visible range sampled by [`spectrum_to_rgb`], in nanometers.
+pub const WAVELENGTH_MIN: f64 = 380.0;
+pub const WAVELENGTH_MAX: f64 = 780.0;
+const WAVELENGTH_STEP: f64 = 5.0;
+
+/// RGB weight of every sampled wavelength, normalized so flat spectrum of 1 is white.
+static SPECTRAL_WEIGHTS: Lazy<Vec<(f64, Vector3<f64>)>> = Lazy::new(|| {
+    let count = ((WAVELENGTH_MAX - WAVELENGTH_MIN) / WAVELENGTH_STEP) as usize + 1;
+
+    let weights: Vec<_> = (0..count)
+        .map(|i| {
+            let wavelength = WAVELENGTH_MIN + i as f64 * WAVELENGTH_STEP;
+
+            (wavelength, xyz_to_rgb(wavelength_to_xyz(wavelength)))
+        })
+        .collect();
+
+    let sum = weights.iter().fold(Vector3::zero(), |acc, (_, w)| acc + w);
+
+    weights
+        .into_iter()
+        .map(|(l, w)| (l, w.div_element_wise(sum)))
+        .collect()
+});
+
+/// CIE 1931 color matching functions, using the multi-lobe fit by Wyman, Sloan and Shirley.
+pub fn wavelength_to_xyz(wavelength: f64) -> Vector3<f64> {
+    let g = |mean: f64, sigma_low: f64, sigma_high: f64| {
+        let sigma = if wavelength < mean {
+            sigma_low
+        } else {
+            sigma_high
+        };
+
+        (-0.5 * ((wavelength - mean) / sigma).powi(2)).exp()
+    };
+
+    Vector3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+/// Converts CIE XYZ to linear sRGB.
+pub fn xyz_to_rgb(xyz: Vector3<f64>) -> Vector3<f64> {
+    Vector3::new(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+}
+
+/// Integrates spectrum given by function of wavelength in nanometers into linear RGB.
+///
+/// Constant spectrum of value `v` results in `(v, v, v)`.
+pub fn spectrum_to_rgb<S: Spectrum>(spectrum: S) -> Vector3<f64> {
+    SPECTRAL_WEIGHTS
+        .iter()
+        .fold(Vector3::zero(), |acc, (wavelength, weight)| {
+            acc + weight * spectrum.radiance(*wavelength)
+        })
+}
+
+/// Linear sRGB color of a black body, brightest channel is 1.
+///
+/// Bodies colder than 1000 K fade to black at 500 K, as they barely glow in visible light.
+pub fn blackbody_rgb(temperature: f64) -> Vector3<f64> {
+    let rgb = xyz_to_rgb(Blackbody { temperature }.to_xyz()).map(|c| c.max(0.0));
+    let max = rgb.x.max(rgb.y).max(rgb.z);
+
+    if max <= 0.0 {
+        return Vector3::zero();
+    }
+
+    let fade = ((temperature - 500.0) / 500.0).clamp(0.0, 1.0);
+
+    rgb * (fade / max)
+}
+
+/// Light given by its spectral radiance at every wavelength in nanometers.
+///
+/// Functions of wavelength are spectra too, so shaders can build them from closures.
+pub trait Spectrum {
+    fn radiance(&self, wavelength: f64) -> f64;
+
+    /// CIE XYZ color of the light, integrated over the visible range.
+    fn to_xyz(&self) -> Vector3<f64> {
+        let count = ((WAVELENGTH_MAX - WAVELENGTH_MIN) / WAVELENGTH_STEP) as usize + 1;
+
+        (0..count).fold(Vector3::zero(), |acc, i| {
+            let wavelength = WAVELENGTH_MIN + i as f64 * WAVELENGTH_STEP;
+
+            acc + wavelength_to_xyz(wavelength) * self.radiance(wavelength) * WAVELENGTH_STEP
+        })
+    }
+
+    /// The light seen by an observer with Doppler factor `factor`, the ratio of observed and
+    /// emitted frequency, above 1 for approaching emitters.
+    ///
+    /// Every wavelength is shifted and radiance scales with the fifth power of the factor, which
+    /// makes approaching emitters brighter and bluer.
+    fn doppler_shifted(self, factor: f64) -> DopplerShifted<Self>
+    where
+        Self: Sized,
+    {
+        DopplerShifted {
+            spectrum: self,
+            factor,
+        }
+    }
+}
+
+impl<F: Fn(f64) -> f64> Spectrum for F {
+    fn radiance(&self, wavelength: f64) -> f64 {
+        self(wavelength)
+    }
+}
+
+/// Planck spectrum of a black body, in W·sr⁻¹·m⁻²·nm⁻¹.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Blackbody {
+    /// Kelvin
+    pub temperature: f64,
+}
+
+impl Spectrum for Blackbody {
+    fn radiance(&self, wavelength: f64) -> f64 {
+        // Planck, Boltzmann constants and speed of light
+        const H: f64 = 6.626_070_15e-34;
+        const K: f64 = 1.380_649e-23;
+        const C: f64 = 299_792_458.0;
+
+        if self.temperature <= 0.0 {
+            return 0.0;
+        }
+
+        let meters = wavelength * 1e-9;
+        let exponent = H * C / (meters * K * self.temperature);
+
+        2.0 * H * C * C / meters.powi(5) / exponent.exp_m1() * 1e-9
+    }
+}
+
+/// Spectrum seen through the Doppler effect, see [`Spectrum::doppler_shifted`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DopplerShifted<S> {
+    spectrum: S,
+    factor: f64,
+}
+
+impl<S: Spectrum> Spectrum for DopplerShifted<S> {
+    fn radiance(&self, wavelength: f64) -> f64 {
+        self.factor.powi(5) * self.spectrum.radiance(wavelength * self.factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_spectrum_is_grey() {
+        let rgb = spectrum_to_rgb(|_| 0.5);
+
+        for c in [rgb.x, rgb.y, rgb.z] {
+            assert!((c - 0.5).abs() < 1e-9);
+        }
+
+        let red = spectrum_to_rgb(|l| if l > 620.0 { 1.0 } else { 0.0 });
+        assert!(red.x > red.y && red.x > red.z);
+    }
+
+    #[test]
+    fn blackbody_colors() {
+        // the sRGB white point is close to a black body of 6500 K
+        let white = blackbody_rgb(6504.0);
+        assert!(white.x.min(white.y).min(white.z) > 0.9, "{white:?}");
+
+        let ember = blackbody_rgb(1500.0);
+        assert!(
+            ember.x == 1.0 && ember.y < 0.5 && ember.z < ember.y,
+            "{ember:?}"
+        );
+        assert_eq!(blackbody_rgb(400.0), Vector3::zero());
+
+        // shifted black bodies look like hotter or colder ones
+        let body = Blackbody {
+            temperature: 3000.0,
+        };
+        let hotter = Blackbody {
+            temperature: 4500.0,
+        };
+
+        for wavelength in [400.0, 550.0, 700.0] {
+            let shifted = body.doppler_shifted(1.5).radiance(wavelength);
+            let expected = hotter.radiance(wavelength);
+
+            assert!((shifted / expected - 1.0).abs() < 1e-9);
+        }
+    }
+}
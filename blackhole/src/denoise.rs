@@ -0,0 +1,191 @@
+//! Removing noise of unfinished renders by filtering them guided by noise free buffers.
+//!
+//! Uses the edge-avoiding à-trous wavelet filter from "Edge-Avoiding À-Trous Wavelet Transform for
+//! fast Global Illumination Filtering" by Dammertz et al. Normals and albedo of surfaces seen by
+//! camera rays are cheap to render with little noise, they keep edges of objects and textures sharp.
+
+use cgmath::{ElementWise, InnerSpace, Vector3};
+
+use crate::framebuffer::{FrameBuffer, Pixel};
+
+/// Weights of the 5 taps of the filter along each axis.
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Albedo below this is treated as missing, light there is filtered without dividing by it.
+const MIN_ALBEDO: f32 = 0.01;
+
+/// Buffers guiding the filter, with the size of the filtered image.
+#[derive(Copy, Clone, Default)]
+pub struct Guides<'a> {
+    /// Normals of first hits, encoded in any way as long as the same normals have the same colors
+    pub normal: Option<&'a FrameBuffer>,
+    /// Colors of first hits without lighting, see [`crate::RenderMode::Albedo`]
+    pub albedo: Option<&'a FrameBuffer>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Denoiser {
+    /// Filtering passes, every one doubles the filter radius
+    pub iterations: usize,
+    /// Tolerated difference of log luminance, halved in every pass
+    pub sigma_color: f32,
+    /// Tolerated difference of normal buffer colors
+    pub sigma_normal: f32,
+    /// Tolerated difference of albedo buffer colors
+    pub sigma_albedo: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_color: 2.0,
+            sigma_normal: 0.2,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+impl Denoiser {
+    /// Filters the image in place, alpha is kept.
+    ///
+    /// Light is divided by albedo before filtering and multiplied back after, so the filter
+    /// doesn't blur textures.
+    pub fn run(&self, fb: &mut FrameBuffer, guides: Guides) {
+        let (width, height) = (fb.width(), fb.height());
+        let size_matches =
+            |g: Option<&FrameBuffer>| g.is_none_or(|g| g.width() == width && g.height() == height);
+        assert!(
+            size_matches(guides.normal) && size_matches(guides.albedo),
+            "guides have the size of the image"
+        );
+
+        let color = |p: &Pixel| Vector3::new(p.r, p.g, p.b);
+        let guide = |g: Option<&FrameBuffer>| -> Option<Vec<Vector3<f32>>> {
+            g.map(|g| g.buffer().iter().map(color).collect())
+        };
+        let normals = guide(guides.normal);
+        let albedo = guide(guides.albedo);
+
+        let modulation = |i: usize| {
+            albedo.as_ref().map_or(Vector3::new(1.0, 1.0, 1.0), |a| {
+                a[i].map(|c| if c > MIN_ALBEDO { c } else { 1.0 })
+            })
+        };
+
+        let mut light = fb
+            .buffer()
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let m = modulation(i);
+                Vector3::new(p.r / m.x, p.g / m.y, p.b / m.z)
+            })
+            .collect::<Vec<_>>();
+
+        let mut filtered = light.clone();
+
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            let sigma_color = self.sigma_color / (1 << iteration) as f32;
+
+            for y in 0..height {
+                for x in 0..width {
+                    let p = x + y * width;
+                    let log_p = log_luminance(light[p]);
+
+                    let mut sum = Vector3::new(0.0, 0.0, 0.0);
+                    let mut weights = 0.0;
+
+                    for (ky, hy) in KERNEL.iter().enumerate() {
+                        let qy = y as isize + (ky as isize - 2) * step;
+                        if qy < 0 || qy >= height as isize {
+                            continue;
+                        }
+
+                        for (kx, hx) in KERNEL.iter().enumerate() {
+                            let qx = x as isize + (kx as isize - 2) * step;
+                            if qx < 0 || qx >= width as isize {
+                                continue;
+                            }
+
+                            let q = qx as usize + qy as usize * width;
+
+                            let mut weight = hx * hy;
+                            weight *= edge_weight(log_p - log_luminance(light[q]), sigma_color);
+
+                            if let Some(n) = &normals {
+                                weight *= edge_weight((n[p] - n[q]).magnitude(), self.sigma_normal);
+                            }
+                            if let Some(a) = &albedo {
+                                weight *= edge_weight((a[p] - a[q]).magnitude(), self.sigma_albedo);
+                            }
+
+                            sum += light[q] * weight;
+                            weights += weight;
+                        }
+                    }
+
+                    // the center tap always has non-zero weight
+                    filtered[p] = sum / weights;
+                }
+            }
+
+            std::mem::swap(&mut light, &mut filtered);
+        }
+
+        for (i, pixel) in fb.buffer_mut().iter_mut().enumerate() {
+            let c = light[i].mul_element_wise(modulation(i));
+
+            *pixel = Pixel::new(c.x, c.y, c.z, pixel.a);
+        }
+    }
+}
+
+fn log_luminance(c: Vector3<f32>) -> f32 {
+    (c.dot(Vector3::new(0.2126, 0.7152, 0.0722)).max(0.0) + 1.0).ln()
+}
+
+fn edge_weight(difference: f32, sigma: f32) -> f32 {
+    (-difference * difference / (sigma * sigma).max(1e-12)).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_edges_of_guides() {
+        // noisy flat image split by an edge in the albedo
+        let (width, height) = (16, 8);
+        let mut fb = FrameBuffer::new(width, height);
+        let mut albedo = FrameBuffer::new(width, height);
+
+        for (i, (pixel, a)) in fb
+            .buffer_mut()
+            .iter_mut()
+            .zip(albedo.buffer_mut())
+            .enumerate()
+        {
+            let base = if i % width < width / 2 { 0.2 } else { 0.8 };
+            let noise = if (i * 7919) % 5 < 2 { 0.5 } else { 1.5 };
+
+            *pixel = Pixel::new(base * noise, base * noise, base * noise, 1.0);
+            *a = Pixel::new(base, base, base, 1.0);
+        }
+
+        Denoiser::default().run(
+            &mut fb,
+            Guides {
+                normal: None,
+                albedo: Some(&albedo),
+            },
+        );
+
+        for (i, pixel) in fb.buffer().iter().enumerate() {
+            let base = if i % width < width / 2 { 0.2 } else { 0.8 };
+
+            assert!((pixel.r - base).abs() < base * 0.3, "{i}: {}", pixel.r);
+        }
+    }
+}
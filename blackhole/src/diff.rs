@@ -0,0 +1,176 @@
+//! Comparison of two renders of the same scene.
+
+use std::fmt::{Display, Formatter};
+
+use crate::framebuffer::{FrameBuffer, Pixel};
+
+/// Per-pixel relative error of two images.
+pub struct Difference {
+    pub width: usize,
+    pub height: usize,
+    /// Errors in row-major order, from 0 for equal pixels up to 2
+    pub errors: Vec<f32>,
+}
+
+/// Summary of a [`Difference`].
+#[derive(Copy, Clone, Debug)]
+pub struct DiffStats {
+    pub mean: f32,
+    /// Root mean square, more sensitive to outliers than `mean`
+    pub rms: f32,
+    /// Error not exceeded by 99% of pixels
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl Display for DiffStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "mean: {:.5}, rms: {:.5}, 99th percentile: {:.5}, max: {:.5}",
+            self.mean, self.rms, self.p99, self.max
+        )
+    }
+}
+
+/// Computes symmetric relative error `|a - b| / ((|a| + |b|) / 2)` averaged over color channels.
+///
+/// The denominator is offset by a hundredth of the mean image value to keep noise in black areas
+/// from dominating. The offset scales with the images too, so multiplying both by the same
+/// exposure doesn't change the result. Alpha is ignored.
+///
+/// Returns `None` when image sizes differ.
+pub fn relative(a: &FrameBuffer, b: &FrameBuffer) -> Option<Difference> {
+    if a.width() != b.width() || a.height() != b.height() {
+        return None;
+    }
+
+    let channels = |p: &Pixel| [p.r, p.g, p.b];
+
+    let total = a
+        .buffer()
+        .iter()
+        .chain(b.buffer())
+        .flat_map(channels)
+        .map(|c| c.abs() as f64)
+        .sum::<f64>();
+    let mean = total / (a.buffer().len() * 6).max(1) as f64;
+    let offset = (mean * 0.01).max(f32::MIN_POSITIVE as f64) as f32;
+
+    let errors = a
+        .buffer()
+        .iter()
+        .zip(b.buffer())
+        .map(|(pa, pb)| {
+            channels(pa)
+                .into_iter()
+                .zip(channels(pb))
+                .map(|(ca, cb)| (ca - cb).abs() / ((ca.abs() + cb.abs()) * 0.5 + offset))
+                .sum::<f32>()
+                / 3.0
+        })
+        .collect();
+
+    Some(Difference {
+        width: a.width(),
+        height: a.height(),
+        errors,
+    })
+}
+
+impl Difference {
+    pub fn stats(&self) -> DiffStats {
+        let count = self.errors.len().max(1) as f64;
+
+        let mean = self.errors.iter().map(|&e| e as f64).sum::<f64>() / count;
+        let rms = (self.errors.iter().map(|&e| (e * e) as f64).sum::<f64>() / count).sqrt();
+
+        let mut sorted = self.errors.clone();
+        sorted.sort_by(f32::total_cmp);
+
+        let p99 = sorted
+            .get(((sorted.len() as f64 * 0.99) as usize).min(sorted.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or(0.0);
+
+        DiffStats {
+            mean: mean as f32,
+            rms: rms as f32,
+            p99,
+            max: sorted.last().copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Maps errors to false colors from black through purple and orange to white.
+    ///
+    /// `scale` is the error shown as white. With `log` set, colors cover three orders of
+    /// magnitude below `scale` instead of a linear range.
+    pub fn false_color(&self, scale: f32, log: bool) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(self.width, self.height);
+
+        for (pixel, &error) in fb.buffer_mut().iter_mut().zip(&self.errors) {
+            let t = if log {
+                ((error / scale).log10() / 3.0 + 1.0).max(0.0)
+            } else {
+                error / scale
+            };
+
+            *pixel = ramp(t.clamp(0.0, 1.0));
+        }
+
+        fb
+    }
+}
+
+fn ramp(t: f32) -> Pixel {
+    const STOPS: [[f32; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [0.25, 0.05, 0.5],
+        [0.8, 0.2, 0.4],
+        [1.0, 0.6, 0.1],
+        [1.0, 1.0, 0.9],
+    ];
+
+    let position = t * (STOPS.len() - 1) as f32;
+    let index = (position as usize).min(STOPS.len() - 2);
+    let fraction = position - index as f32;
+
+    let [r, g, b] =
+        [0, 1, 2].map(|c| STOPS[index][c] + (STOPS[index + 1][c] - STOPS[index][c]) * fraction);
+
+    Pixel::new(r, g, b, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_ignores_exposure() {
+        let mut a = FrameBuffer::new(2, 1);
+        a.buffer_mut()[0] = Pixel::new(1.0, 0.5, 0.25, 1.0);
+        a.buffer_mut()[1] = Pixel::new(0.0, 0.1, 2.0, 1.0);
+
+        let mut b = FrameBuffer::new(2, 1);
+        b.buffer_mut()[0] = Pixel::new(1.1, 0.5, 0.2, 1.0);
+        b.buffer_mut()[1] = Pixel::new(0.05, 0.1, 2.0, 1.0);
+
+        let scaled = |fb: &FrameBuffer, k: f32| {
+            let mut out = FrameBuffer::new(fb.width(), fb.height());
+            for (o, p) in out.buffer_mut().iter_mut().zip(fb.buffer()) {
+                *o = *p * k;
+            }
+            out
+        };
+
+        let base = relative(&a, &b).unwrap().errors;
+        let exposed = relative(&scaled(&a, 8.0), &scaled(&b, 8.0)).unwrap().errors;
+
+        for (e, x) in base.iter().zip(&exposed) {
+            assert!((e - x).abs() < 1e-5, "{e} vs {x}");
+        }
+
+        assert_eq!(relative(&a, &a).unwrap().stats().max, 0.0);
+        assert!(relative(&a, &FrameBuffer::new(1, 2)).is_none());
+    }
+}
@@ -1,8 +1,12 @@
+use crate::cache;
 use crate::lut::LookupTable;
 use crate::math::blackman_harris;
 use rand::prelude::*;
 use rand_xoshiro::Xoshiro256StarStar;
 
+/// Samples of the inverted cumulative distribution of the Blackman-Harris window.
+const LUT_SAMPLES: usize = 1000;
+
 pub trait PixelFilter: Iterator<Item = (f64, f64)> + Send + Sync {
     fn set_filter_size(&mut self, filter_size: f64);
     fn reset(&mut self);
@@ -68,7 +72,11 @@ impl BlackmanHarrisFilter {
     pub fn new(filter_size: f64) -> Self {
         let generator = Xoshiro256StarStar::seed_from_u64(0);
 
-        let lut = Self::generate_lut();
+        let lut = cache::load_or_create(
+            "blackman_harris_lut",
+            cache::content_hash(&LUT_SAMPLES),
+            Self::generate_lut,
+        );
 
         Self {
             generator,
@@ -84,12 +92,14 @@ impl BlackmanHarrisFilter {
         let mut integral = 0.0;
         let mut last_integral = 0.0;
 
-        for i in 0..1000 {
-            let x = i as f64 / 1000.0;
+        let step = 1.0 / LUT_SAMPLES as f64;
+
+        for i in 0..LUT_SAMPLES {
+            let x = i as f64 * step;
 
             let f = blackman_harris(x, 1.0);
 
-            integral += f * 0.001 + ((last_integral - f) / 2.0 * 0.001);
+            integral += f * step + ((last_integral - f) / 2.0 * step);
 
             last_integral = f;
 
@@ -1,22 +1,269 @@
+use std::sync::Arc;
+
+use crate::framebuffer::FrameBuffer;
+
 pub struct Frame {
     pub width: usize,
     pub height: usize,
     pub region: Region,
+    /// Pixels around a window region which are rendered too, so filters and denoisers have context
+    /// at its edges. The output is still cropped to the region
+    pub border: usize,
 }
 
 impl Frame {
     pub fn aspect_ratio(&self) -> f64 {
         self.width as f64 / self.height as f64
     }
+
+    /// Pixels rays are traced in, the region grown by the border.
+    pub fn traced_region(&self) -> Region {
+        self.region.grow(self.border, self.width, self.height)
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Region {
     Whole,
+    /// Pixels from min up to, but not including max
     Window {
         x_min: usize,
         y_min: usize,
         x_max: usize,
         y_max: usize,
     },
+    /// Pixels set in a mask with the size of the image
+    Mask(Arc<Mask>),
+}
+
+impl Region {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        match *self {
+            Self::Whole => true,
+            Self::Window {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => (x_min..x_max).contains(&x) && (y_min..y_max).contains(&y),
+            Self::Mask(ref mask) => mask.contains(x, y),
+        }
+    }
+
+    /// Smallest window holding the region in an image of given size, as
+    /// `(x_min, y_min, x_max, y_max)`. Empty masks give an empty window.
+    pub fn bounds(&self, width: usize, height: usize) -> (usize, usize, usize, usize) {
+        match *self {
+            Self::Whole => (0, 0, width, height),
+            Self::Window {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => (x_min, y_min, x_max, y_max),
+            Self::Mask(ref mask) => mask.bounds(),
+        }
+    }
+
+    /// Window with given pixels added on every side, clamped to an image of given size.
+    pub fn grow(&self, pixels: usize, width: usize, height: usize) -> Self {
+        match *self {
+            Self::Whole => Self::Whole,
+            Self::Window {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => Self::Window {
+                x_min: x_min.saturating_sub(pixels),
+                y_min: y_min.saturating_sub(pixels),
+                x_max: (x_max + pixels).min(width),
+                y_max: (y_max + pixels).min(height),
+            },
+            Self::Mask(ref mask) if pixels > 0 => Self::Mask(Arc::new(mask.grow(pixels))),
+            Self::Mask(ref mask) => Self::Mask(mask.clone()),
+        }
+    }
+}
+
+/// Pixels to render, for several windows or any shape painted in an image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Mask {
+    /// Mask with no pixels set.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    /// Mask of all pixels in any of the windows, windows are clamped to the image.
+    pub fn from_windows(width: usize, height: usize, windows: &[Region]) -> Self {
+        let mut mask = Self::new(width, height);
+
+        for window in windows {
+            let (x_min, y_min, x_max, y_max) = window.bounds(width, height);
+
+            for y in y_min..y_max.min(height) {
+                for x in x_min..x_max.min(width) {
+                    if window.contains(x, y) {
+                        mask.set(x, y);
+                    }
+                }
+            }
+        }
+
+        mask
+    }
+
+    /// Mask of pixels brighter than half in the image, transparent pixels are not set.
+    pub fn from_framebuffer(fb: &FrameBuffer) -> Self {
+        Self {
+            width: fb.width(),
+            height: fb.height(),
+            pixels: fb
+                .buffer()
+                .iter()
+                .map(|p| (p.r + p.g + p.b) / 3.0 * p.a > 0.5)
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn set(&mut self, x: usize, y: usize) {
+        self.pixels[x + y * self.width] = true;
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[x + y * self.width]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.pixels.contains(&true)
+    }
+
+    fn bounds(&self) -> (usize, usize, usize, usize) {
+        let mut bounds = (self.width, self.height, 0, 0);
+
+        for (i, _) in self.pixels.iter().enumerate().filter(|(_, set)| **set) {
+            let (x, y) = (i % self.width, i / self.width);
+
+            bounds = (
+                bounds.0.min(x),
+                bounds.1.min(y),
+                bounds.2.max(x + 1),
+                bounds.3.max(y + 1),
+            );
+        }
+
+        match bounds {
+            (x_min, y_min, x_max, y_max) if x_min < x_max => (x_min, y_min, x_max, y_max),
+            _ => (0, 0, 0, 0),
+        }
+    }
+
+    /// Mask with pixels within given distance of set pixels set too.
+    ///
+    /// The square around every pixel is separable, so rows are grown first and then columns of
+    /// the result, each in a single pass.
+    fn grow(&self, pixels: usize) -> Self {
+        let mut rows = Self::new(self.width, self.height);
+        let mut grown = Self::new(self.width, self.height);
+
+        for y in 0..self.height {
+            dilate(
+                &self.pixels,
+                &mut rows.pixels,
+                self.width,
+                |x| y * self.width + x,
+                pixels,
+            );
+        }
+
+        for x in 0..self.width {
+            dilate(
+                &rows.pixels,
+                &mut grown.pixels,
+                self.height,
+                |y| y * self.width + x,
+                pixels,
+            );
+        }
+
+        grown
+    }
+}
+
+/// Sets pixels of `to` within `reach` of pixels set in `from`, along a line of `len` pixels at
+/// indices given by `index`.
+fn dilate(
+    from: &[bool],
+    to: &mut [bool],
+    len: usize,
+    index: impl Fn(usize) -> usize,
+    reach: usize,
+) {
+    // distance to the nearest set pixel before, then after
+    let mut last = None;
+
+    for i in 0..len {
+        if from[index(i)] {
+            last = Some(i);
+        }
+
+        to[index(i)] = last.is_some_and(|last| i - last <= reach);
+    }
+
+    let mut next = None;
+
+    for i in (0..len).rev() {
+        if from[index(i)] {
+            next = Some(i);
+        }
+
+        to[index(i)] |= next.is_some_and(|next| next - i <= reach);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grown_mask_covers_squares_around_pixels() {
+        let (width, height) = (16, 12);
+        let set = [(0, 0), (7, 5), (8, 5), (15, 3), (3, 11)];
+
+        let windows = set.map(|(x, y)| Region::Window {
+            x_min: x,
+            y_min: y,
+            x_max: x + 1,
+            y_max: y + 1,
+        });
+        let grown = Mask::from_windows(width, height, &windows).grow(2);
+
+        for y in 0..height {
+            for x in 0..width {
+                let near = set
+                    .iter()
+                    .any(|&(sx, sy)| x.abs_diff(sx) <= 2 && y.abs_diff(sy) <= 2);
+
+                assert_eq!(grown.contains(x, y), near, "{x} {y}");
+            }
+        }
+    }
 }
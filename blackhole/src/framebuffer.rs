@@ -1,10 +1,21 @@
+use crate::frame::Region;
 use cgmath::Vector3;
-use std::ops::{Add, AddAssign, Mul};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Write};
+use std::ops::{Add, AddAssign, Deref, DerefMut, Mul};
+use std::path::Path;
 
+#[cfg(feature = "mmap")]
+mod mapped;
+
+/// Leading bytes of checkpoint files.
+const CHECKPOINT_MAGIC: &[u8; 8] = b"BHCKPT01";
+
+#[derive(Clone)]
 pub struct FrameBuffer {
     width: usize,
     height: usize,
-    buffer: Vec<Pixel>,
+    buffer: Storage,
 }
 
 impl FrameBuffer {
@@ -12,18 +23,45 @@ impl FrameBuffer {
         Self {
             width,
             height,
-            buffer: vec![Pixel::black(); width * height],
+            buffer: Storage::Memory(vec![Pixel::black(); width * height]),
         }
     }
 
-    pub fn buffer_mut(&mut self) -> &mut Vec<Pixel> {
+    /// Creates framebuffer kept in a temporary file in given directory instead of memory.
+    ///
+    /// The system keeps only recently used rows in memory, so images larger than it still
+    /// render, only slower. The file is removed when the framebuffer is dropped. Clones and crops
+    /// get their own files in the same directory, falling back to memory when those can't be
+    /// created.
+    #[cfg(feature = "mmap")]
+    pub fn new_mapped<P: AsRef<Path>>(
+        width: usize,
+        height: usize,
+        dir: P,
+    ) -> std::io::Result<Self> {
+        let mut pixels = mapped::MappedPixels::new(dir.as_ref(), width * height)?;
+        pixels.fill(Pixel::black());
+
+        Ok(Self {
+            width,
+            height,
+            buffer: Storage::Mapped(pixels),
+        })
+    }
+
+    pub fn buffer_mut(&mut self) -> &mut [Pixel] {
         &mut self.buffer
     }
 
-    pub fn buffer(&self) -> &Vec<Pixel> {
+    pub fn buffer(&self) -> &[Pixel] {
         &self.buffer
     }
 
+    /// Pixels of the row `y`.
+    pub fn row(&self, y: usize) -> &[Pixel] {
+        &self.buffer[y * self.width..(y + 1) * self.width]
+    }
+
     pub fn pixel_mut(&mut self, x: usize, y: usize) -> Option<&mut Pixel> {
         let index = x + y * self.width;
 
@@ -41,12 +79,245 @@ impl FrameBuffer {
 
         std::slice::from_raw_parts(self.buffer.as_ptr() as *const f32, size)
     }
+    /// Copies pixels of the region into a new framebuffer of its size.
+    ///
+    /// Masks are cropped to their bounds, pixels outside of the mask are kept.
+    pub fn crop(&self, region: &Region) -> Self {
+        let (x_min, y_min, x_max, y_max) = region.bounds(self.width, self.height);
+        let width = x_max - x_min;
+
+        let mut buffer = self.buffer.with_len(width * (y_max - y_min));
+
+        for (y, row) in (y_min..y_max).zip(buffer.chunks_exact_mut(width.max(1))) {
+            row.copy_from_slice(&self.row(y)[x_min..x_max]);
+        }
+
+        Self {
+            width,
+            height: y_max - y_min,
+            buffer,
+        }
+    }
+
+    /// Box filtered copy, every pixel is the average of a block of `factor` by `factor` pixels.
+    ///
+    /// Blocks on the right and bottom edge are cut by the image and average fewer pixels.
+    pub fn downscale(&self, factor: usize) -> Self {
+        let factor = factor.max(1);
+        let width = self.width.div_ceil(factor);
+        let height = self.height.div_ceil(factor);
+
+        let mut sums = vec![Pixel::new(0.0, 0.0, 0.0, 0.0); width * height];
+        let mut counts = vec![0.0_f32; width * height];
+
+        for (i, pixel) in self.buffer.iter().enumerate() {
+            let block = (i % self.width) / factor + (i / self.width) / factor * width;
+
+            // adding in place keeps the alpha
+            sums[block] += *pixel;
+            sums[block].a += pixel.a;
+            counts[block] += 1.0;
+        }
+
+        let mut out = Self::new(width, height);
+        for ((pixel, sum), count) in out.buffer_mut().iter_mut().zip(sums).zip(counts) {
+            *pixel = sum * (1.0 / count);
+        }
+
+        out
+    }
+
     pub fn width(&self) -> usize {
         self.width
     }
     pub fn height(&self) -> usize {
         self.height
     }
+
+    /// Writes linear RGBA values as 32-bit float OpenEXR image, without any clamping.
+    #[cfg(feature = "exr")]
+    pub fn write_exr<P: AsRef<std::path::Path>>(&self, path: P) -> exr::error::UnitResult {
+        self.write_exr_with_text(path, &[])
+    }
+
+    /// Writes the image like [`FrameBuffer::write_exr`], with text attributes in its header.
+    #[cfg(feature = "exr")]
+    pub fn write_exr_with_text<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        text: &[(&str, &str)],
+    ) -> exr::error::UnitResult {
+        use exr::prelude::*;
+
+        let channels = SpecificChannels::rgba(|Vec2(x, y)| {
+            let pixel: Pixel = self.buffer[x + y * self.width];
+
+            (pixel.r, pixel.g, pixel.b, pixel.a)
+        });
+        let mut image = Image::from_channels((self.width, self.height), channels);
+
+        for (name, value) in text {
+            image.layer_data.attributes.other.insert(
+                Text::from_slice_unchecked(name.as_bytes()),
+                AttributeValue::Text(Text::from_slice_unchecked(value.as_bytes())),
+            );
+        }
+
+        image.write().to_file(path)
+    }
+
+    /// Text attributes of the first layer of an OpenEXR file, without reading its pixels.
+    #[cfg(feature = "exr")]
+    pub fn read_exr_text<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> exr::error::Result<Vec<(String, String)>> {
+        use exr::meta::attribute::AttributeValue;
+
+        let meta = exr::meta::MetaData::read_from_file(path, false)?;
+        let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+
+        Ok(meta
+            .headers
+            .first()
+            .map(|header| {
+                header
+                    .own_attributes
+                    .other
+                    .iter()
+                    .filter_map(|(name, value)| match value {
+                        AttributeValue::Text(value) => {
+                            Some((text(name.bytes()), text(value.bytes())))
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Reads the first RGBA layer of an OpenEXR file, missing alpha is read as 1.
+    #[cfg(feature = "exr")]
+    pub fn read_exr<P: AsRef<std::path::Path>>(path: P) -> exr::error::Result<Self> {
+        let image = exr::prelude::read_first_rgba_layer_from_file(
+            path,
+            |resolution, _| Self::new(resolution.width(), resolution.height()),
+            |fb: &mut Self, position, (r, g, b, a): (f32, f32, f32, f32)| {
+                fb.buffer[position.x() + position.y() * fb.width] = Pixel::new(r, g, b, a);
+            },
+        )?;
+
+        Ok(image.layer_data.channel_data.pixels)
+    }
+
+    /// Writes raw pixel values with the number of samples accumulated in them, to continue the
+    /// render later.
+    ///
+    /// The file is written next to the target first and renamed over it when complete, so an
+    /// interruption while writing keeps the previous checkpoint.
+    pub fn write_checkpoint<P: AsRef<Path>>(&self, path: P, samples: usize) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+
+        let mut writer = BufWriter::new(File::create(&partial)?);
+
+        writer.write_all(CHECKPOINT_MAGIC)?;
+        for value in [self.width, self.height, samples] {
+            writer.write_all(&(value as u64).to_le_bytes())?;
+        }
+        for pixel in self.buffer.iter() {
+            for value in [pixel.r, pixel.g, pixel.b, pixel.a] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        writer.into_inner()?.sync_all()?;
+
+        std::fs::rename(partial, path)
+    }
+
+    /// Reads framebuffer and sample count saved by [`FrameBuffer::write_checkpoint`].
+    pub fn read_checkpoint<P: AsRef<Path>>(path: P) -> std::io::Result<(Self, usize)> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a checkpoint file"));
+        }
+
+        let mut header = [0; 3];
+        for value in &mut header {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            *value = u64::from_le_bytes(bytes) as usize;
+        }
+        let [width, height, samples] = header;
+
+        let mut fb = Self::new(width, height);
+        for pixel in fb.buffer.iter_mut() {
+            let mut bytes = [0; 16];
+            reader.read_exact(&mut bytes)?;
+
+            let value = |i: usize| f32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+            *pixel = Pixel::new(value(0), value(1), value(2), value(3));
+        }
+
+        Ok((fb, samples))
+    }
+}
+
+/// Pixels of a framebuffer, in memory or in a file.
+enum Storage {
+    Memory(Vec<Pixel>),
+    #[cfg(feature = "mmap")]
+    Mapped(mapped::MappedPixels),
+}
+
+impl Storage {
+    /// Storage of the same kind for `len` black pixels.
+    fn with_len(&self, len: usize) -> Self {
+        #[cfg(feature = "mmap")]
+        if let Self::Mapped(pixels) = self {
+            if let Ok(mut pixels) = pixels.sibling(len) {
+                pixels.fill(Pixel::black());
+                return Self::Mapped(pixels);
+            }
+        }
+
+        Self::Memory(vec![Pixel::black(); len])
+    }
+}
+
+impl Clone for Storage {
+    fn clone(&self) -> Self {
+        let mut storage = self.with_len(self.len());
+        storage.copy_from_slice(self);
+
+        storage
+    }
+}
+
+impl Deref for Storage {
+    type Target = [Pixel];
+
+    fn deref(&self) -> &[Pixel] {
+        match self {
+            Self::Memory(pixels) => pixels,
+            #[cfg(feature = "mmap")]
+            Self::Mapped(pixels) => pixels,
+        }
+    }
+}
+
+impl DerefMut for Storage {
+    fn deref_mut(&mut self) -> &mut [Pixel] {
+        match self {
+            Self::Memory(pixels) => pixels,
+            #[cfg(feature = "mmap")]
+            Self::Mapped(pixels) => pixels,
+        }
+    }
 }
 
 impl Default for FrameBuffer {
@@ -56,6 +327,7 @@ impl Default for FrameBuffer {
 }
 
 #[derive(Copy, Clone)]
+#[repr(C)]
 pub struct Pixel {
     pub r: f32,
     pub g: f32,
@@ -118,3 +390,57 @@ impl From<Vector3<f64>> for Pixel {
         Self::new(v.x as f32, v.y as f32, v.z as f32, 1.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downscale_averages_blocks() {
+        let mut fb = FrameBuffer::new(5, 2);
+        for (i, pixel) in fb.buffer_mut().iter_mut().enumerate() {
+            *pixel = Pixel::new(i as f32, 0.0, 0.0, 1.0);
+        }
+
+        let small = fb.downscale(2);
+        let red = small.buffer().iter().map(|p| p.r).collect::<Vec<_>>();
+
+        assert_eq!((small.width(), small.height()), (3, 1));
+        // last column is cut by the image
+        assert_eq!(red, [3.0, 5.0, 6.5]);
+        assert!(small.buffer().iter().all(|p| p.a == 1.0));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mapped_copies_stay_on_disk() {
+        let dir = std::env::temp_dir().join(format!("blackhole-fb-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = || std::fs::read_dir(&dir).unwrap().count();
+
+        let mut fb = FrameBuffer::new_mapped(4, 3, &dir).unwrap();
+        for (i, pixel) in fb.buffer_mut().iter_mut().enumerate() {
+            *pixel = Pixel::new(i as f32, 0.0, 0.0, 1.0);
+        }
+
+        let clone = fb.clone();
+        let crop = fb.crop(&Region::Window {
+            x_min: 1,
+            y_min: 1,
+            x_max: 3,
+            y_max: 3,
+        });
+        assert_eq!(files(), 3);
+
+        let reds = |fb: &FrameBuffer, y| fb.row(y).iter().map(|p| p.r).collect::<Vec<_>>();
+        assert_eq!(reds(&clone, 2), [8.0, 9.0, 10.0, 11.0]);
+        assert_eq!((crop.width(), crop.height()), (2, 2));
+        assert_eq!(reds(&crop, 0), [5.0, 6.0]);
+        assert_eq!(reds(&crop, 1), [9.0, 10.0]);
+
+        drop((fb, clone, crop));
+        assert_eq!(files(), 0);
+
+        std::fs::remove_dir(dir).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs::{File, OpenOptions};
+use std::io::Result;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use memmap2::MmapMut;
+
+use super::Pixel;
+
+/// Distinguishes files of framebuffers created by one process.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Pixels stored in a memory mapped temporary file.
+pub struct MappedPixels {
+    map: MmapMut,
+    len: usize,
+    path: PathBuf,
+    _file: File,
+}
+
+impl MappedPixels {
+    pub fn new(dir: &Path, len: usize) -> Result<Self> {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("blackhole-{}-{id}.fb", std::process::id()));
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        let mapped = file
+            .set_len((len * std::mem::size_of::<Pixel>()) as u64)
+            // the file was just created and isn't shared, so nothing else resizes it
+            .and_then(|_| unsafe { MmapMut::map_mut(&file) });
+
+        match mapped {
+            Ok(map) => Ok(Self {
+                map,
+                len,
+                path,
+                _file: file,
+            }),
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                Err(e)
+            }
+        }
+    }
+
+    /// New pixels of given length in the directory of these.
+    pub fn sibling(&self, len: usize) -> Result<Self> {
+        Self::new(self.path.parent().unwrap_or(Path::new(".")), len)
+    }
+}
+
+impl Deref for MappedPixels {
+    type Target = [Pixel];
+
+    fn deref(&self) -> &[Pixel] {
+        // mappings are page aligned and the file holds `len` pixels of plain floats
+        unsafe { std::slice::from_raw_parts(self.map.as_ptr() as *const Pixel, self.len) }
+    }
+}
+
+impl DerefMut for MappedPixels {
+    fn deref_mut(&mut self) -> &mut [Pixel] {
+        unsafe { std::slice::from_raw_parts_mut(self.map.as_mut_ptr() as *mut Pixel, self.len) }
+    }
+}
+
+impl Drop for MappedPixels {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,266 @@
+//! Integration of ray paths bent by distortions.
+
+use cgmath::{InnerSpace, Vector3, Zero};
+
+/// Largest relative error of a substep accepted by [`Integrator::Rkf45`].
+///
+/// Location errors are relative to the distance moved, direction errors to how much the direction
+/// turned, but at least to the distance moved, so nearly straight rays aren't split needlessly.
+const TOLERANCE: f64 = 1e-7;
+
+/// Shortest substep of [`Integrator::Rkf45`] relative to the whole step.
+const MIN_SUBSTEP: f64 = 1.0 / 1024.0;
+
+/// Runge-Kutta-Fehlberg coefficients of the intermediate stages.
+const FEHLBERG_STAGES: [&[f64]; 5] = [
+    &[1.0 / 4.0],
+    &[3.0 / 32.0, 9.0 / 32.0],
+    &[1932.0 / 2197.0, -7200.0 / 2197.0, 7296.0 / 2197.0],
+    &[439.0 / 216.0, -8.0, 3680.0 / 513.0, -845.0 / 4104.0],
+    &[
+        -8.0 / 27.0,
+        2.0,
+        -3544.0 / 2565.0,
+        1859.0 / 4104.0,
+        -11.0 / 40.0,
+    ],
+];
+
+/// Weights of the stages in the fourth order solution.
+const FEHLBERG_4: [f64; 6] = [
+    25.0 / 216.0,
+    0.0,
+    1408.0 / 2565.0,
+    2197.0 / 4104.0,
+    -1.0 / 5.0,
+    0.0,
+];
+
+/// Weights of the stages in the fifth order solution.
+const FEHLBERG_5: [f64; 6] = [
+    16.0 / 135.0,
+    0.0,
+    6656.0 / 12825.0,
+    28561.0 / 56430.0,
+    -9.0 / 50.0,
+    2.0 / 55.0,
+];
+
+/// Scheme for advancing a ray by one step through a distortion.
+///
+/// Rays follow `x' = d`, `d' = a(x)` with the direction `d` renormalized after every step,
+/// where `a` is the bending of all distortions the ray is in, see
+/// [`crate::object::Distortion::acceleration`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Integrator {
+    /// Bends the direction by the acceleration at the start of the step, then moves along it
+    ///
+    /// Error is proportional to the step length, so it needs short steps near distortions.
+    #[default]
+    Euler,
+    /// Classic fourth order Runge-Kutta, four acceleration evaluations per step
+    ///
+    /// Error falls with the fourth power of the step length, so much longer steps keep the same
+    /// accuracy as Euler.
+    Rk4,
+    /// Runge-Kutta-Fehlberg with fourth and fifth order solutions, splitting the step into as
+    /// many substeps as needed to keep their difference under a relative tolerance
+    ///
+    /// Takes a few substeps far from the center and many close to it, where bending changes
+    /// quickly.
+    Rkf45,
+}
+
+impl Integrator {
+    /// Advances location and direction by a step of length `h`, returns them with the direction
+    /// normalized.
+    pub fn step<A>(
+        &self,
+        location: Vector3<f64>,
+        direction: Vector3<f64>,
+        h: f64,
+        acceleration: A,
+    ) -> (Vector3<f64>, Vector3<f64>)
+    where
+        A: Fn(Vector3<f64>) -> Vector3<f64>,
+    {
+        match self {
+            Self::Euler => {
+                let direction = (direction + acceleration(location) * h).normalize();
+
+                (location + direction * h, direction)
+            }
+            Self::Rk4 => {
+                let turn = |x, d| turn(&acceleration, x, d);
+
+                let (x1, d1) = (location, direction);
+                let a1 = turn(x1, d1);
+
+                let (x2, d2) = (location + d1 * (h / 2.0), direction + a1 * (h / 2.0));
+                let a2 = turn(x2, d2);
+
+                let (x3, d3) = (location + d2 * (h / 2.0), direction + a2 * (h / 2.0));
+                let a3 = turn(x3, d3);
+
+                let d4 = direction + a3 * h;
+                let a4 = turn(location + d3 * h, d4);
+
+                let location = location + (d1 + d2 * 2.0 + d3 * 2.0 + d4) * (h / 6.0);
+                let direction = direction + (a1 + a2 * 2.0 + a3 * 2.0 + a4) * (h / 6.0);
+
+                (location, direction.normalize())
+            }
+            Self::Rkf45 => rkf45(location, direction, h, |x, d| turn(&acceleration, x, d)),
+        }
+    }
+}
+
+/// Part of the bending perpendicular to the direction.
+///
+/// Only this part turns the direction, the rest would be removed by normalization and lower the
+/// order of the higher order schemes.
+fn turn<A>(acceleration: &A, x: Vector3<f64>, d: Vector3<f64>) -> Vector3<f64>
+where
+    A: Fn(Vector3<f64>) -> Vector3<f64>,
+{
+    let a = acceleration(x);
+
+    a - d * a.dot(d) / d.magnitude2()
+}
+
+/// Adaptive substeps of [`Integrator::Rkf45`] over a step of length `h`.
+fn rkf45<T>(
+    mut location: Vector3<f64>,
+    mut direction: Vector3<f64>,
+    h: f64,
+    turn: T,
+) -> (Vector3<f64>, Vector3<f64>)
+where
+    T: Fn(Vector3<f64>, Vector3<f64>) -> Vector3<f64>,
+{
+    let min_substep = h * MIN_SUBSTEP;
+    let mut remaining = h;
+    let mut substep = h;
+
+    while remaining > 0.0 {
+        substep = substep.min(remaining);
+
+        // derivatives of location and direction at every stage
+        let mut k = [(Vector3::zero(), Vector3::zero()); 6];
+
+        for stage in 0..6 {
+            let (mut x, mut d) = (location, direction);
+
+            if stage > 0 {
+                for (j, weight) in FEHLBERG_STAGES[stage - 1].iter().enumerate() {
+                    x += k[j].0 * (weight * substep);
+                    d += k[j].1 * (weight * substep);
+                }
+            }
+
+            k[stage] = (d, turn(x, d));
+        }
+
+        let solution = |weights: &[f64; 6]| {
+            weights
+                .iter()
+                .zip(&k)
+                .fold((location, direction), |(x, d), (weight, (dx, dd))| {
+                    (x + dx * (weight * substep), d + dd * (weight * substep))
+                })
+        };
+
+        let (x4, d4) = solution(&FEHLBERG_4);
+        let (x5, d5) = solution(&FEHLBERG_5);
+
+        let turned = (d5 - direction).magnitude().max(substep);
+        let error = ((x5 - x4).magnitude() / substep).max((d5 - d4).magnitude() / turned);
+        let allowed = TOLERANCE;
+
+        if error <= allowed || substep <= min_substep {
+            location = x5;
+            direction = d5.normalize();
+            remaining -= substep;
+        }
+
+        // usual safety factor, with growth and shrinking limited for stability
+        let factor = if error > 0.0 {
+            0.9 * (allowed / error).powf(0.2)
+        } else {
+            5.0
+        };
+        substep = (substep * factor.clamp(0.2, 5.0)).max(min_substep);
+    }
+
+    (location, direction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::object::Distortion;
+
+    /// Half of the integrated path, the path starts and ends this far from the distortion plane.
+    const HALF_PATH: f64 = 20.0;
+
+    /// Angle by which a ray passing the center at `impact` gets deflected over the path.
+    ///
+    /// Bending is not cut off at the distortion radius, so every integrator sees the same field
+    /// over the same path length.
+    fn deflection(integrator: Integrator, steps: usize, strength: f64, impact: f64) -> f64 {
+        let mut distortion = Distortion::new();
+        distortion.strength = strength;
+
+        let step = 2.0 * HALF_PATH / steps as f64;
+        let mut location = Vector3::new(impact, 0.0, -HALF_PATH);
+        let mut direction = Vector3::unit_z();
+
+        for _ in 0..steps {
+            (location, direction) =
+                integrator.step(location, direction, step, |p| distortion.acceleration(p));
+        }
+
+        direction.angle(Vector3::unit_z()).0
+    }
+
+    #[test]
+    fn weak_field_deflection_matches_analytic() {
+        let (strength, impact) = (0.01, 2.0);
+
+        // 2s/b for an infinite straight line, cut to the path length
+        let analytic = 2.0 * strength / impact * HALF_PATH / HALF_PATH.hypot(impact);
+
+        for integrator in [Integrator::Euler, Integrator::Rk4, Integrator::Rkf45] {
+            let angle = deflection(integrator, 400, strength, impact);
+
+            assert!(
+                (angle / analytic - 1.0).abs() < 0.02,
+                "{integrator:?}: {angle} vs {analytic}"
+            );
+        }
+    }
+
+    #[test]
+    fn rk4_keeps_accuracy_with_long_steps() {
+        let reference = deflection(Integrator::Rk4, 40_000, 0.3, 1.2);
+
+        let error = |integrator, steps| (deflection(integrator, steps, 0.3, 1.2) - reference).abs();
+
+        // a quarter of the steps, each with four evaluations
+        let (euler, rk4) = (error(Integrator::Euler, 400), error(Integrator::Rk4, 100));
+
+        assert!(rk4 < euler / 10.0, "{rk4} vs {euler}");
+    }
+
+    #[test]
+    fn rkf45_adapts_long_steps() {
+        let reference = deflection(Integrator::Rk4, 40_000, 0.3, 1.2);
+
+        let error = |integrator, steps| (deflection(integrator, steps, 0.3, 1.2) - reference).abs();
+
+        let (rk4, rkf45) = (error(Integrator::Rk4, 20), error(Integrator::Rkf45, 20));
+
+        assert!(rkf45 < 1e-6 && rkf45 < rk4 / 10.0, "{rkf45} vs {rk4}");
+    }
+}
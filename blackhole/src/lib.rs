@@ -2,22 +2,39 @@ use cgmath::{InnerSpace, Vector3};
 
 use once_cell::sync::Lazy;
 
+pub mod animation;
+pub mod aov;
+pub mod cache;
 pub mod camera;
+pub mod color;
+pub mod denoise;
+pub mod diff;
 pub mod filter;
 pub mod frame;
 pub mod framebuffer;
+pub mod integrator;
 pub mod lut;
 pub mod marcher;
 pub mod material;
 pub mod math;
 pub mod object;
+pub mod post;
+pub mod postprocess;
+pub mod prepass;
 pub mod scene;
+pub mod section;
 pub mod shader;
+pub mod simd;
 pub mod texture;
+pub mod units;
 
 use crate::lut::LookupTable;
 
-pub static GAUSS_LUT: Lazy<LookupTable<f64>> = Lazy::new(gen_gauss_dist);
+pub static GAUSS_LUT: Lazy<LookupTable<f64>> = Lazy::new(|| {
+    let key = cache::content_hash(&(-500_i32, 500_i32, 100_u32));
+    cache::load_or_create("gauss_lut", key, gen_gauss_dist)
+});
+/// Colors of black bodies by temperature in Kelvin, see [`color::blackbody_rgb`].
 pub static BLACKBODY_LUT: Lazy<LookupTable<Vector3<f64>>> = Lazy::new(gen_bb_dist);
 
 #[derive(Debug, Copy, Clone)]
@@ -32,6 +49,10 @@ pub struct Ray {
     pub direction: Vector3<f64>,
     pub steps_taken: usize,
     pub kind: RayKind,
+    /// Time within the camera shutter interval, 0 at open and 1 at close
+    ///
+    /// Moving objects are at their nominal position at 0.5.
+    pub time: f64,
 }
 
 impl Ray {
@@ -46,6 +67,7 @@ impl Ray {
             direction: self.direction - 2.0 * self.direction.dot(normal) * normal,
             steps_taken: 0,
             kind: RayKind::Secondary,
+            time: self.time,
         }
     }
 }
@@ -55,6 +77,14 @@ pub enum RenderMode {
     Samples,
     Normal,
     Shaded,
+    /// Shading without randomness, volumes only absorb and emit and solids reflect like mirrors
+    Deterministic,
+    /// Colors pixels by the rule which stopped marching of the primary ray
+    Termination,
+    /// Length of the bent primary ray path, see [`marcher::RayResult::distance`]
+    Depth,
+    /// Color of the first hit without lighting, background is seen as is
+    Albedo,
 }
 
 fn gen_gauss_dist() -> LookupTable<f64> {
@@ -84,11 +114,10 @@ fn gen_gauss_dist() -> LookupTable<f64> {
 }
 
 fn gen_bb_dist() -> LookupTable<Vector3<f64>> {
-    LookupTable::from_vec(vec![
-        (500.0, Vector3::new(0.0, 0.0, 0.0)),
-        (1000.0, Vector3::new(1.0, 0.0, 0.0)),
-        (2000.0, Vector3::new(1.0, 0.2, 0.0)),
-        (3000.0, Vector3::new(1.0, 0.8, 0.2)),
-        (6500.0, Vector3::new(1.0, 1.0, 1.0)),
-    ])
+    // colors change fast at low temperatures, slowly at high ones
+    let temperatures = std::iter::once(0.0)
+        .chain((10..=80).map(|i| i as f64 * 50.0))
+        .chain((41..=400).map(|i| i as f64 * 100.0));
+
+    LookupTable::from_vec_sorted(temperatures.map(|t| (t, color::blackbody_rgb(t))).collect())
 }
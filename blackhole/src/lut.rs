@@ -1,5 +1,8 @@
+use crate::cache::Cacheable;
 use crate::math::Lerpable;
 
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
 pub struct LookupTable<T: Lerpable> {
     data: Vec<(f64, T)>,
 }
@@ -45,6 +48,25 @@ impl<T: Lerpable> LookupTable<T> {
     }
 }
 
+impl<T: Lerpable + Cacheable> Cacheable for LookupTable<T> {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        self.data.write_to(w)
+    }
+
+    fn read_from(r: &mut dyn Read) -> Result<Self> {
+        let data = Vec::read_from(r)?;
+
+        if data.len() <= 1 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "LUT needs at least two items",
+            ));
+        }
+
+        Ok(Self { data })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
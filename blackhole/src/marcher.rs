@@ -1,70 +1,728 @@
+use crate::framebuffer::Pixel;
+use crate::integrator::Integrator;
 use crate::material::MaterialResult;
-use crate::math::rand_unit;
-use crate::object::{Object, Shading};
+use crate::math::{orthonormal_basis, pixel_seed, rand_unit, seed_rng};
+use crate::object::{Distortion, Object, Shading};
 use crate::scene::Scene;
-use crate::{Ray, RenderMode};
+use crate::shader::{Footprint, ShadeContext};
+use crate::{Ray, RayKind, RenderMode};
 use cgmath::{Array, ElementWise, InnerSpace, Vector3, Zero};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+pub mod plugin;
+
+use plugin::MarchPlugin;
+
+/// Shortest step towards an event horizon, so rays close to it still get across.
+const HORIZON_STEP: f64 = 1e-4;
+
+#[derive(Clone)]
 pub struct RayMarcher {
     pub mode: RenderMode,
     pub samples: usize,
     pub max_steps: usize,
     pub max_depth: usize,
+    /// Added to random numbers deciding volume scattering, see [`crate::math::temporal_offset`]
+    pub temporal_offset: f64,
+    /// Record where traced rays first get NaN or infinite values into [`Scratch::non_finite`]
+    pub check_finite: bool,
+    /// Scheme bending rays inside distortions
+    pub integrator: Integrator,
+    /// Longest step inside distortions, trades accuracy of bending for speed
+    pub distortion_step: f64,
+    /// Longest step inside volumes, scattering is decided once per step
+    pub volume_step: f64,
+    /// Shortest step towards volumes, so rays grazing them don't stall
+    pub volume_min_step: f64,
+    /// Shade solids enclosing the camera with normals pointing inwards, towards the camera
+    pub flip_inside_normals: bool,
+    /// Angle between camera rays of neighbouring pixels, 0 to sample the background at points.
+    ///
+    /// When set, background is averaged over the patch each camera ray sees, found by tracing two
+    /// extra rays offset by this angle. Meant for perspective cameras.
+    pub pixel_angle: f64,
+    /// Trace an extra ray towards [`Scene::lights`] from scattering events in volumes, combined
+    /// with the scattered ray by multiple importance sampling. Solids don't sample lights
+    pub sample_lights: bool,
+    /// Find [`RayResult::surface`] of camera ray hits, needed only for render passes besides the
+    /// color
+    pub record_surfaces: bool,
+    /// Measure time spent marching and shading into [`MarchStats`], reading the clock around
+    /// every march and shading of a ray
+    pub profile: bool,
+    /// Custom physics called on every marched ray, in order of registration
+    pub plugins: Vec<Arc<dyn MarchPlugin>>,
+    /// Hold out the background and event horizons seen by camera rays for compositing, they see
+    /// nothing there and leave [`RayResult::alpha`] of what they passed through
+    pub transparent_background: bool,
+}
+
+/// Reusable per-thread state of the marcher, keeps allocations out of tracing of single rays.
+///
+/// Create one per rendering thread and pass it to every traced ray.
+#[derive(Default)]
+pub struct Scratch {
+    /// Indices of distortions the marched ray is inside of
+    active_distortions: Vec<usize>,
+    /// Indices of volumes integrated in deterministic mode
+    inside_volumes: Vec<usize>,
+    pub stats: MarchStats,
+    /// First non-finite value of the last traced ray, see [`RayMarcher::check_finite`]
+    pub non_finite: Option<NonFinite>,
+}
+
+impl Scratch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns counters collected since the last call and resets them.
+    pub fn take_stats(&mut self) -> MarchStats {
+        std::mem::take(&mut self.stats)
+    }
+}
+
+/// Counters of traced rays.
+#[derive(Copy, Clone, Debug)]
+pub struct MarchStats {
+    pub rays: u64,
+    /// Sum of [`RayResult::steps`] of all rays
+    pub steps: usize,
+    /// Most steps of a single ray
+    pub max_steps: usize,
+    /// Rays and their steps split by termination, indexed by [`Termination::index`]
+    pub terminations: [TerminationStats; Termination::ALL.len()],
+    /// Time spent marching rays, only measured with [`RayMarcher::profile`]
+    pub march_time: Duration,
+    /// Time spent in shaders of objects and the background, see [`MarchStats::march_time`]
+    pub shade_time: Duration,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TerminationStats {
+    pub rays: u64,
+    pub steps: usize,
+}
+
+impl MarchStats {
+    pub const fn new() -> Self {
+        Self {
+            rays: 0,
+            steps: 0,
+            max_steps: 0,
+            terminations: [TerminationStats { rays: 0, steps: 0 }; Termination::ALL.len()],
+            march_time: Duration::ZERO,
+            shade_time: Duration::ZERO,
+        }
+    }
+
+    fn record(&mut self, result: &RayResult) {
+        self.rays += 1;
+        self.steps += result.steps;
+        self.max_steps = self.max_steps.max(result.steps);
+
+        let termination = &mut self.terminations[result.termination.index()];
+        termination.rays += 1;
+        termination.steps += result.steps;
+    }
+
+    /// Adds counters of other stats, like ones collected by another thread.
+    pub fn merge(&mut self, other: &Self) {
+        self.rays += other.rays;
+        self.steps += other.steps;
+        self.max_steps = self.max_steps.max(other.max_steps);
+        self.march_time += other.march_time;
+        self.shade_time += other.shade_time;
+
+        for (a, b) in self.terminations.iter_mut().zip(&other.terminations) {
+            a.rays += b.rays;
+            a.steps += b.steps;
+        }
+    }
+}
+
+impl Default for MarchStats {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RayMarcher {
-    pub fn color_for_ray(&self, ray: Ray, scene: &Scene, max_step: f64, depth: usize) -> RayResult {
+    /// Replaces fields by the settings which are set. Samples are left to the renderer.
+    pub fn with_settings(mut self, settings: &RenderSettings) -> Self {
+        self.max_steps = settings.max_steps.unwrap_or(self.max_steps);
+        self.max_depth = settings.max_depth.unwrap_or(self.max_depth);
+        self.volume_step = settings.volume_step.unwrap_or(self.volume_step);
+        self.volume_min_step = settings.volume_min_step.unwrap_or(self.volume_min_step);
+        self.distortion_step = settings.distortion_step.unwrap_or(self.distortion_step);
+
+        self
+    }
+
+    /// Registers a plugin called after plugins registered before it.
+    pub fn with_plugin(mut self, plugin: impl MarchPlugin + 'static) -> Self {
+        self.plugins.push(Arc::new(plugin));
+
+        self
+    }
+
+    pub fn color_for_ray(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        max_step: f64,
+        depth: usize,
+        scratch: &mut Scratch,
+    ) -> RayResult {
+        let ray = self.sample_time(ray, scene);
+
+        let result = self.trace(ray, scene, max_step, depth, None, 1.0, scratch);
+        scratch.stats.record(&result);
+
+        let finite = is_finite(result.color);
+        self.check(scratch, finite, depth, ray.location, NonFiniteSource::Other);
+
+        result
+    }
+
+    /// Renders row `y` of a `row.len()` by `height` image, averaging `samples` rays per pixel.
+    ///
+    /// Samples are spread evenly over the pixels and scaled by the camera exposure. Rows don't
+    /// depend on each other, so they can be rendered on separate threads.
+    pub fn render_row(
+        &self,
+        scene: &Scene,
+        y: usize,
+        height: usize,
+        samples: usize,
+        row: &mut [Pixel],
+        scratch: &mut Scratch,
+    ) {
+        let width = row.len();
+        let max_step = scene.max_possible_step(scene.camera.location);
+        let exposure = scene.camera.exposure.multiplier();
+        let aspect_ratio = width as f64 / height as f64;
+
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let mut color = Vector3::zero();
+
+            for sample in 0..samples {
+                seed_rng(pixel_seed(0, sample, x, y));
+
+                // R2 sequence spreads the samples evenly over the pixel
+                let offset_x = (0.5 + sample as f64 * 0.754_877_666).fract();
+                let offset_y = (0.5 + sample as f64 * 0.569_840_291).fract();
+
+                let ray = scene.camera.cast_ray(
+                    (x as f64 + offset_x) / width as f64,
+                    (y as f64 + offset_y) / height as f64,
+                    aspect_ratio,
+                );
+
+                color += self.color_for_ray(ray, scene, max_step, 0, scratch).color;
+            }
+
+            let c = color * exposure / samples as f64;
+            *pixel = Pixel::new(c.x as f32, c.y as f32, c.z as f32, 1.0);
+        }
+    }
+
+    /// Same as [`RayMarcher::color_for_ray`], also splitting the color by light source.
+    ///
+    /// Light emitted by object with index `i` is added to `sources[i]`, the last item gets the
+    /// background and light from volumes integrated in deterministic mode. The slice must have
+    /// one item more than the scene has objects.
+    pub fn color_for_ray_by_source(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        max_step: f64,
+        sources: &mut [Vector3<f64>],
+        scratch: &mut Scratch,
+    ) -> RayResult {
+        let weight = Vector3::from_value(1.0);
+        let ray = self.sample_time(ray, scene);
+
+        let result = self.trace(
+            ray,
+            scene,
+            max_step,
+            0,
+            Some((sources, weight)),
+            1.0,
+            scratch,
+        );
+        scratch.stats.record(&result);
+
+        result
+    }
+
+    /// Picks random time within the shutter interval for rays of scenes with moving objects.
+    ///
+    /// Rays already bounced keep their time, so the whole path sees objects at the same place.
+    /// Moving cameras pick the time of their rays themselves, see [`crate::camera::Camera::motion`].
+    fn sample_time(&self, mut ray: Ray, scene: &Scene) -> Ray {
+        let blur = !matches!(self.mode, RenderMode::Deterministic)
+            && matches!(ray.kind, RayKind::Primary)
+            && scene.camera.motion.is_none()
+            && (scene.has_star_trails() || scene.objects.iter().any(|o| o.shape.is_moving()));
+
+        if blur {
+            ray.time = rand_unit();
+        }
+
+        ray
+    }
+
+    /// Traces the ray and rays scattered from it.
+    ///
+    /// Light of [`Scene::lights`] hit by the ray is scaled by `light_weight`, the rest of it was
+    /// already found by sampling the lights, see [`RayMarcher::sample_light`].
+    #[allow(clippy::too_many_arguments)]
+    fn trace(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        max_step: f64,
+        depth: usize,
+        mut sources: Option<(&mut [Vector3<f64>], Vector3<f64>)>,
+        light_weight: f64,
+        scratch: &mut Scratch,
+    ) -> RayResult {
         if depth >= self.max_depth {
             return RayResult {
                 steps: ray.steps_taken,
                 color: Vector3::zero(),
+                termination: Termination::MaxDepth,
+                distance: f64::INFINITY,
+                hit: None,
+                surface: None,
+                alpha: 1.0,
             };
         }
 
+        let start = ray;
         let mut ray = ray;
-        let obj = self.march_to_object(&mut ray, scene, max_step);
+        let mut absorption = Absorption::new();
+        let (obj, travelled) =
+            self.march_to_object(&mut ray, scene, max_step, &mut absorption, scratch, None);
+        let termination = obj.termination();
+        let distance = match obj {
+            MarchResult::Background(_) => f64::INFINITY,
+            _ => travelled,
+        };
+
+        let finite = is_finite(ray.direction);
+        self.check(
+            scratch,
+            finite,
+            depth,
+            ray.location,
+            NonFiniteSource::Direction,
+        );
+
+        // light which doesn't come from a single object is attributed to the background
+        let background = scene.objects.len();
+        let mut add_source = |index: usize, color: Vector3<f64>| {
+            if let Some((sources, weight)) = &mut sources {
+                sources[index] += weight.mul_element_wise(color);
+            }
+        };
 
-        let mat_res = match obj {
-            MarchResult::Object(obj) => {
-                let (mat, new_ray) = self.get_color(&ray, self.mode, obj);
+        if let RenderMode::Termination = self.mode {
+            add_source(background, termination.debug_color());
+
+            return RayResult {
+                steps: ray.steps_taken,
+                color: termination.debug_color(),
+                termination,
+                distance,
+                hit: None,
+                surface: None,
+                alpha: 1.0,
+            };
+        }
+
+        if let RenderMode::Depth = self.mode {
+            return RayResult {
+                steps: ray.steps_taken,
+                color: Vector3::from_value(distance),
+                termination,
+                distance,
+                hit: None,
+                surface: None,
+                alpha: 1.0,
+            };
+        }
+
+        add_source(background, absorption.emission);
+
+        // light found by sampling lights from scattering event, and weight of lights hit by the
+        // scattered ray
+        let mut direct = Vector3::zero();
+        let mut scattered_weight = 1.0;
+
+        let (mat_res, hit, surface) = match obj {
+            MarchResult::Object(obj, index) => {
+                let dist = obj.shape.dist_fn_at(ray.location, ray.time);
+
+                // camera rays starting inside a solid hit it right away
+                let inside = matches!(ray.kind, RayKind::Primary)
+                    && travelled == 0.0
+                    && matches!(obj.shading, Shading::Solid(_))
+                    && dist < 0.0;
+
+                // hits on the face left by the slice plane
+                let cut_normal = scene
+                    .slice
+                    .filter(|slice| slice.distance(ray.location) > dist)
+                    .map(|slice| slice.normal);
+
+                let start = self.start_timer();
+                let (mut mat, new_ray) = self.get_color(&ray, self.mode, obj, inside, cut_normal);
+                scratch.stats.shade_time += elapsed(start);
+
+                let surface = (depth == 0 && self.record_surfaces).then(|| Surface {
+                    normal: self.hit_normal(&ray, obj, inside, cut_normal),
+                    albedo: mat.albedo,
+                });
+
+                if scene.lights.contains(&index) {
+                    mat.emission *= light_weight;
+                }
+                add_source(index, mat.emission * absorption.transmittance);
+
+                let finite = is_finite(mat.emission)
+                    && is_finite(mat.albedo)
+                    && new_ray.is_none_or(|r| is_finite(r.direction));
+                let source = NonFiniteSource::Object {
+                    index,
+                    shader: obj.shader_name(),
+                };
+                self.check(scratch, finite, depth, ray.location, source);
 
                 match new_ray {
                     Some(new_ray) => {
+                        let sampled =
+                            self.sample_light(&ray, &new_ray, obj, scene, max_step, depth, scratch);
+
+                        if let Some((light, color, weight)) = sampled {
+                            direct = color;
+                            scattered_weight = weight;
+
+                            if let Some(light) = light {
+                                let color = mat.albedo.mul_element_wise(color);
+                                add_source(light, color * absorption.transmittance);
+                            }
+                        }
+
                         ray = new_ray;
                     }
                     None => {
                         return RayResult {
                             steps: ray.steps_taken,
-                            color: mat.emission,
+                            color: absorption.apply(mat.emission),
+                            termination,
+                            distance,
+                            hit: Some(index),
+                            surface,
+                            alpha: 1.0,
                         };
                     }
                 }
 
-                mat
+                (mat, Some(index), surface)
+            }
+            MarchResult::Background(_) if depth == 0 && self.transparent_background => {
+                return RayResult {
+                    steps: ray.steps_taken,
+                    color: absorption.apply(Vector3::zero()),
+                    termination,
+                    distance,
+                    hit: None,
+                    surface: None,
+                    alpha: 1.0 - absorption.transmittance,
+                };
             }
             MarchResult::Background(_direction) => {
+                let footprint = self.footprint(&start, &ray, scene, max_step, depth, scratch);
+                let start = self.start_timer();
+                let color = scene.background_emission(&ray, footprint.as_ref());
+                scratch.stats.shade_time += elapsed(start);
+                add_source(background, color * absorption.transmittance);
+
+                let source = NonFiniteSource::Background {
+                    shader: scene.background.type_name(),
+                };
+                self.check(scratch, is_finite(color), depth, ray.location, source);
+
                 // if background, end ray right away
                 return RayResult {
                     steps: ray.steps_taken,
-                    color: scene.background.emission_at(&ray),
+                    color: absorption.apply(color),
+                    termination,
+                    distance,
+                    hit: None,
+                    surface: None,
+                    alpha: 1.0,
+                };
+            }
+            MarchResult::Captured(distortion) => {
+                let color = match &distortion.capture_shader {
+                    Some(shader) => {
+                        let start = self.start_timer();
+                        let color = shader.emission_at(&ray);
+                        scratch.stats.shade_time += elapsed(start);
+
+                        let source = NonFiniteSource::Capture {
+                            shader: shader.type_name(),
+                        };
+                        self.check(scratch, is_finite(color), depth, ray.location, source);
+
+                        color
+                    }
+                    None => Vector3::zero(),
+                };
+                add_source(background, color * absorption.transmittance);
+
+                // rays falling into a horizon see nothing behind it
+                let held_out = distortion.capture_shader.is_none()
+                    && distortion.horizon_radius.is_some()
+                    && self.transparent_background;
+                let alpha = if held_out {
+                    1.0 - absorption.transmittance
+                } else {
+                    1.0
+                };
+
+                return RayResult {
+                    steps: ray.steps_taken,
+                    color: absorption.apply(color),
+                    termination,
+                    distance,
+                    hit: None,
+                    surface: None,
+                    alpha,
                 };
             }
-            MarchResult::None => {
+            MarchResult::Horizon => {
+                let alpha = if self.transparent_background {
+                    1.0 - absorption.transmittance
+                } else {
+                    1.0
+                };
+
                 return RayResult {
                     steps: ray.steps_taken,
-                    color: Vector3::zero(),
+                    color: absorption.apply(Vector3::zero()),
+                    termination,
+                    distance,
+                    hit: None,
+                    surface: None,
+                    alpha,
+                };
+            }
+            MarchResult::None(_) => {
+                return RayResult {
+                    steps: ray.steps_taken,
+                    color: absorption.apply(Vector3::zero()),
+                    termination,
+                    distance,
+                    hit: None,
+                    surface: None,
+                    alpha: 1.0,
                 };
             }
         };
 
-        let color_reflected = self.color_for_ray(ray, scene, max_step, depth + 1);
+        let sources = sources.map(|(sources, weight)| {
+            let throughput = mat_res.albedo * absorption.transmittance;
+
+            (sources, weight.mul_element_wise(throughput))
+        });
 
-        let color = mat_res.emission + mat_res.albedo.mul_element_wise(color_reflected.color);
+        let color_reflected = self.trace(
+            ray,
+            scene,
+            max_step,
+            depth + 1,
+            sources,
+            scattered_weight,
+            scratch,
+        );
+
+        let incoming = color_reflected.color + direct;
+        let color = mat_res.emission + mat_res.albedo.mul_element_wise(incoming);
 
         RayResult {
             steps: color_reflected.steps,
-            color,
+            color: absorption.apply(color),
+            termination,
+            distance,
+            hit,
+            surface,
+            alpha: 1.0,
+        }
+    }
+
+    /// Light reaching the volume scattering event of `ray` from [`Scene::lights`].
+    ///
+    /// Traces a ray in a direction picked by [`Scene::sample_light`], light it finds is dimmed by
+    /// volumes on the way and weighted by the power heuristic against finding it by the `scattered`
+    /// ray. Light emitted on the way by volumes and plugins isn't weighted, the scattered ray finds
+    /// it. Returns index of the hit light with the weighted light and weight for lights hit by the
+    /// scattered ray, or `None` when the event doesn't sample lights.
+    #[allow(clippy::too_many_arguments)]
+    fn sample_light(
+        &self,
+        ray: &Ray,
+        scattered: &Ray,
+        object: &Object,
+        scene: &Scene,
+        max_step: f64,
+        depth: usize,
+        scratch: &mut Scratch,
+    ) -> Option<(Option<usize>, Vector3<f64>, f64)> {
+        let enabled = self.sample_lights && matches!(self.mode, RenderMode::Shaded);
+
+        if !enabled || scene.lights.is_empty() || depth + 1 >= self.max_depth {
+            return None;
+        }
+
+        let phase_pdf = object.phase(ray, scattered, scattered.direction)?;
+        let scattered_weight = power_heuristic(
+            phase_pdf,
+            scene.light_pdf(scattered.location, scattered.direction),
+        );
+
+        let (direction, light_pdf) = scene.sample_light(scattered.location)?;
+        let phase = object.phase(ray, scattered, direction).unwrap_or(0.0);
+
+        let mut light_ray = Ray {
+            direction,
+            ..*scattered
+        };
+        let mut absorption = Absorption::new();
+        let (result, _) = self.march_to_object(
+            &mut light_ray,
+            scene,
+            max_step,
+            &mut absorption,
+            scratch,
+            None,
+        );
+
+        let (light, index) = match result {
+            MarchResult::Object(light, index) => (light, index),
+            _ => return Some((None, Vector3::zero(), scattered_weight)),
+        };
+
+        if !scene.lights.contains(&index) {
+            return Some((None, Vector3::zero(), scattered_weight));
+        }
+
+        let start = self.start_timer();
+        let (mat, _) = light.shade(&light_ray);
+        scratch.stats.shade_time += elapsed(start);
+        let weight = power_heuristic(light_pdf, phase) * phase / light_pdf;
+
+        Some((
+            Some(index),
+            mat.emission * absorption.transmittance * weight,
+            scattered_weight,
+        ))
+    }
+
+    /// Patch of background seen by the camera ray `start`, which escaped along `end`.
+    ///
+    /// Rays of neighbouring pixels are traced only if the ray was bent, all three have to escape.
+    fn footprint(
+        &self,
+        start: &Ray,
+        end: &Ray,
+        scene: &Scene,
+        max_step: f64,
+        depth: usize,
+        scratch: &mut Scratch,
+    ) -> Option<Footprint> {
+        if self.pixel_angle <= 0.0 || depth > 0 || !matches!(start.kind, RayKind::Primary) {
+            return None;
+        }
+
+        let (u, v) = orthonormal_basis(start.direction);
+        let bent = (end.direction - start.direction).magnitude2() > 1e-18;
+
+        let mut offset = |axis: Vector3<f64>| {
+            let mut neighbour = *start;
+            neighbour.direction = (start.direction + axis * self.pixel_angle).normalize();
+
+            if !bent {
+                return Some(neighbour.direction - end.direction);
+            }
+
+            let mut absorption = Absorption::new();
+            let (result, _) = self.march_to_object(
+                &mut neighbour,
+                scene,
+                max_step,
+                &mut absorption,
+                scratch,
+                None,
+            );
+
+            match result {
+                MarchResult::Background(direction) => Some(direction - end.direction),
+                _ => None,
+            }
+        };
+
+        Some(Footprint {
+            dx: offset(u)?,
+            dy: offset(v)?,
+        })
+    }
+
+    /// Marches the ray until it hits an object or escapes, returning its location after every step.
+    ///
+    /// Escaping rays end with a point `max_step` away in the escape direction.
+    pub fn trace_path(&self, ray: Ray, scene: &Scene, max_step: f64) -> Vec<Vector3<f64>> {
+        let mut path = Vec::new();
+        self.trace_path_into(ray, scene, max_step, &mut Scratch::new(), &mut path);
+
+        path
+    }
+
+    /// Same as [`RayMarcher::trace_path`], with the path replacing contents of `path`, returning
+    /// how marching ended.
+    pub fn trace_path_into(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        max_step: f64,
+        scratch: &mut Scratch,
+        path: &mut Vec<Vector3<f64>>,
+    ) -> Termination {
+        let mut ray = ray;
+        let mut absorption = Absorption::new();
+
+        path.clear();
+        path.push(ray.location);
+
+        let (res, _) = self.march_to_object(
+            &mut ray,
+            scene,
+            max_step,
+            &mut absorption,
+            scratch,
+            Some(path),
+        );
+
+        if let MarchResult::Background(direction) = res {
+            path.push(ray.location + direction * max_step);
         }
+
+        res.termination()
     }
 
     fn march_to_object<'r, 's>(
@@ -72,89 +730,250 @@ impl RayMarcher {
         ray: &'r mut Ray,
         scene: &'s Scene,
         max_step: f64,
-    ) -> MarchResult<'s> {
+        absorption: &mut Absorption,
+        scratch: &mut Scratch,
+        mut path: Option<&mut Vec<Vector3<f64>>>,
+    ) -> (MarchResult<'s>, f64) {
+        let start = self.start_timer();
         let mut i = 0;
-        let mut active_distortions = Vec::with_capacity(scene.distortions.len());
+        let mut travelled = 0.0;
+        let active_distortions = &mut scratch.active_distortions;
+        let inside_volumes = &mut scratch.inside_volumes;
+        let deterministic = matches!(self.mode, RenderMode::Deterministic);
+        // distortion the ray falls into, with radius under which no object can be hit anymore
+        let mut plunge: Option<(&Distortion, f64)> = None;
+        // camera clipping, measured along the whole path from the camera
+        let (clip_offset, clip_near, clip_far) = match ray.kind {
+            RayKind::Primary => {
+                let camera = &scene.camera;
+                (camera.clip_start, camera.clip_near, camera.clip_far)
+            }
+            RayKind::Secondary => (0.0, 0.0, f64::INFINITY),
+        };
 
-        loop {
+        let cut = |dist: f64, point: Vector3<f64>| match &scene.slice {
+            Some(slice) => slice.cut(dist, point),
+            None => dist,
+        };
+
+        let result = 'march: loop {
             let mut dst = f64::MAX;
 
+            if clip_offset + travelled > clip_far {
+                break 'march MarchResult::Background(ray.direction);
+            }
+
+            // captured rays keep marching only while there is something left to hit
+            if let Some((distortion, clear)) = plunge {
+                if (ray.location - distortion.shape.center()).magnitude() <= clear {
+                    break 'march MarchResult::Captured(distortion);
+                }
+            }
+
+            // rays march up to the near plane exactly, objects cut by it are hit right there
+            let before_near = clip_near - (clip_offset + travelled);
+            let clipped = before_near > 1e-9;
+
             active_distortions.clear();
-            for distortion in &scene.distortions {
+            inside_volumes.clear();
+            for (index, distortion) in scene.distortions.iter().enumerate() {
+                let horizon = distortion.horizon_dist(ray.location);
+                if horizon < 0.0 {
+                    break 'march MarchResult::Horizon;
+                }
                 if !distortion.can_ray_hit(ray) {
                     continue;
                 }
                 let dist = distortion.dist_fn(ray.location);
                 if dist <= 0.0 {
-                    active_distortions.push(distortion);
+                    active_distortions.push(index);
+                }
+                // rays step onto the horizon instead of over it
+                dst = dst
+                    .min(dist.max(self.distortion_step))
+                    .min(horizon.max(HORIZON_STEP));
+            }
+
+            if plunge.is_none() && !active_distortions.is_empty() {
+                plunge = active_distortions
+                    .iter()
+                    .map(|&index| &scene.distortions[index])
+                    .find(|d| d.captures(ray))
+                    .map(|d| (d, clear_radius(scene, d.shape.center(), ray.time)));
+
+                if let Some((distortion, clear)) = plunge {
+                    if (ray.location - distortion.shape.center()).magnitude() <= clear {
+                        break 'march MarchResult::Captured(distortion);
+                    }
                 }
-                dst = dst.min(dist.max(0.1));
             }
 
             let mut obj = None;
 
-            for object in &scene.objects {
+            if clipped {
+                dst = dst.min(before_near).min(max_step);
+            }
+
+            for (index, object) in scene.objects.iter().enumerate().filter(|_| !clipped) {
                 match &object.shading {
                     Shading::Solid(_) => {
                         if !object.shape.can_ray_hit(ray) && !active_distortions.is_empty() {
                             continue;
                         }
 
-                        let obj_dist = object.shape.dist_fn(ray.location);
+                        let obj_dist = object.shape.dist_fn_at(ray.location, ray.time);
+                        let obj_dist = cut(obj_dist, ray.location);
                         if obj_dist < dst {
                             dst = dst.min(obj_dist);
-                            obj = Some(object);
+                            obj = Some((object, index));
                         }
                     }
                     Shading::Volumetric(shader) => {
-                        let obj_dist = object.shape.dist_fn(ray.location);
+                        let obj_dist = object.shape.dist_fn_at(ray.location, ray.time);
+                        let obj_dist = cut(obj_dist, ray.location);
 
                         if obj_dist < 0.0 {
-                            dst = dst.min(0.01);
-                            let r = rand_unit();
-                            if (shader.density_at(ray.location) * dst) > r {
-                                return MarchResult::Object(object);
+                            dst = dst.min(self.volume_step);
+
+                            if deterministic {
+                                inside_volumes.push(index);
+                                continue;
+                            }
+
+                            let r = (rand_unit() + self.temporal_offset).fract();
+                            let local = object.local_point(ray.location, ray.time);
+
+                            if (shader.density_at(local, &object.context()) * dst) > r {
+                                break 'march MarchResult::Object(object, index);
                             }
                         } else if obj_dist < dst {
-                            dst = dst.min(obj_dist.max(0.002));
+                            dst = dst.min(obj_dist.max(self.volume_min_step));
                         }
                     }
                 }
             }
 
-            if let Some(obj) = obj {
+            if let Some((obj, index)) = obj {
                 if dst < 0.00001 {
-                    return MarchResult::Object(obj);
+                    break 'march MarchResult::Object(obj, index);
                 }
             }
 
-            for distortion in &active_distortions {
-                let strength = distortion.strength(ray.location);
+            let mut next_location = None;
 
-                if strength > 9.0 {
-                    return MarchResult::None;
+            if !active_distortions.is_empty() {
+                let too_strong = active_distortions
+                    .iter()
+                    .map(|&index| &scene.distortions[index])
+                    .any(|d| d.strength(ray.location) > 9.0);
+
+                if too_strong {
+                    break 'march stopped(plunge, Termination::Strength);
                 }
 
-                let force = (distortion.shape.center() - ray.location).normalize() * dst * strength;
+                let acceleration = |point: Vector3<f64>| {
+                    active_distortions
+                        .iter()
+                        .map(|&index| &scene.distortions[index])
+                        .filter(|d| d.dist_fn(point) <= 0.0)
+                        .map(|d| d.acceleration(point))
+                        .fold(Vector3::zero(), |sum, a| sum + a)
+                };
 
-                let new_dir = (ray.direction + force).normalize();
+                let (location, new_dir) =
+                    self.integrator
+                        .step(ray.location, ray.direction, dst, acceleration);
 
                 if ray.direction.dot(new_dir) < -0.0 {
-                    return MarchResult::None;
+                    break 'march stopped(plunge, Termination::Reversal);
                 }
                 ray.direction = new_dir;
+                next_location = Some(location);
+            }
+
+            if !self.plugins.is_empty() {
+                let mut direction = ray.direction;
+
+                for plugin in &self.plugins {
+                    plugin.on_bend(ray, &mut direction, dst);
+                }
+
+                ray.direction = direction.normalize();
             }
 
             if dst > max_step {
-                return MarchResult::Background(ray.direction);
+                break 'march MarchResult::Background(ray.direction);
             }
 
             if i >= self.max_steps {
-                return MarchResult::None;
+                break 'march stopped(plunge, Termination::MaxSteps);
             }
             i += 1;
 
-            ray.advance(dst);
+            for &index in inside_volumes.iter() {
+                absorption.integrate(&scene.objects[index], ray, dst);
+            }
+
+            if absorption.transmittance < 0.0001 {
+                break 'march MarchResult::None(Termination::Absorbed);
+            }
+
+            match next_location {
+                Some(location) => {
+                    ray.location = location;
+                    ray.steps_taken += 1;
+                }
+                None => ray.advance(dst),
+            }
+            travelled += dst;
+
+            if let Some(path) = &mut path {
+                path.push(ray.location);
+            }
+
+            let mut stop = false;
+            for plugin in &self.plugins {
+                let response = plugin.on_step(ray, dst);
+
+                absorption.emission += response.emission * absorption.transmittance;
+                stop |= response.stop;
+            }
+
+            if stop {
+                break 'march MarchResult::None(Termination::Plugin);
+            }
+        };
+
+        for plugin in &self.plugins {
+            absorption.emission +=
+                plugin.on_terminate(ray, result.termination()) * absorption.transmittance;
+        }
+
+        scratch.stats.march_time += elapsed(start);
+
+        (result, travelled)
+    }
+
+    /// Current time when profiling, see [`RayMarcher::profile`].
+    fn start_timer(&self) -> Option<Instant> {
+        self.profile.then(Instant::now)
+    }
+
+    /// Records the first non-finite value of the ray, if enabled.
+    fn check(
+        &self,
+        scratch: &mut Scratch,
+        finite: bool,
+        depth: usize,
+        location: Vector3<f64>,
+        source: NonFiniteSource,
+    ) {
+        if self.check_finite && !finite && scratch.non_finite.is_none() {
+            scratch.non_finite = Some(NonFinite {
+                depth,
+                location,
+                source,
+            });
         }
     }
 
@@ -163,15 +982,39 @@ impl RayMarcher {
         ray: &Ray,
         render_mode: RenderMode,
         object: &Object,
+        inside: bool,
+        cut_normal: Option<Vector3<f64>>,
     ) -> (MaterialResult, Option<Ray>) {
-        let (mat, new_ray) = object.shade(ray);
+        let flip = inside && self.flip_inside_normals;
+        let deterministic = matches!(render_mode, RenderMode::Deterministic);
+        let (mat, new_ray) = match cut_normal {
+            Some(normal) => object.shade_with_normal(ray, normal, deterministic),
+            None => object.shade_oriented(ray, flip, deterministic),
+        };
+
+        // light can't reach the inside of solids, only their emission is seen
+        let new_ray = new_ray.filter(|_| !inside);
+        let normal = || self.hit_normal(ray, object, inside, cut_normal);
 
         match render_mode {
             RenderMode::Shaded => (mat, new_ray),
+            RenderMode::Deterministic => {
+                let normal = normal();
+
+                // mirrored rays leave the camera clipping behind, like scattered ones
+                let new_ray = new_ray.map(|_| {
+                    let mut mirror = Ray {
+                        kind: RayKind::Secondary,
+                        ..ray.reflect(normal)
+                    };
+                    mirror.advance(0.01);
+                    mirror
+                });
+
+                (mat, new_ray)
+            }
             RenderMode::Normal => {
-                let eps = 0.00001;
-                let normal =
-                    object.shape.normal(ray.location, eps) * 0.5 + Vector3::from_value(0.5);
+                let normal = normal() * 0.5 + Vector3::from_value(0.5);
 
                 (
                     MaterialResult {
@@ -181,7 +1024,14 @@ impl RayMarcher {
                     new_ray,
                 )
             }
-            RenderMode::Samples => (
+            RenderMode::Albedo => (
+                MaterialResult {
+                    emission: mat.albedo,
+                    albedo: Vector3::zero(),
+                },
+                new_ray,
+            ),
+            RenderMode::Samples | RenderMode::Termination | RenderMode::Depth => (
                 MaterialResult {
                     emission: Vector3::zero(),
                     albedo: Vector3::zero(),
@@ -190,6 +1040,59 @@ impl RayMarcher {
             ),
         }
     }
+
+    /// Shading normal of the object at the ray location, facing like the normal used by
+    /// [`RayMarcher::get_color`].
+    fn hit_normal(
+        &self,
+        ray: &Ray,
+        object: &Object,
+        inside: bool,
+        cut_normal: Option<Vector3<f64>>,
+    ) -> Vector3<f64> {
+        let orientation = if inside && self.flip_inside_normals {
+            -1.0
+        } else {
+            1.0
+        };
+
+        let eps = 0.00001;
+        let normal = cut_normal
+            .unwrap_or_else(|| object.shape.normal_at(ray.location, eps, ray.time) * orientation);
+
+        object.shading_normal(ray, normal)
+    }
+}
+
+/// Quality settings which scenes can carry, unset ones are left to the frontend.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct RenderSettings {
+    pub samples: Option<usize>,
+    pub max_steps: Option<usize>,
+    pub max_depth: Option<usize>,
+    /// See [`RayMarcher::volume_step`]
+    pub volume_step: Option<f64>,
+    /// See [`RayMarcher::volume_min_step`]
+    pub volume_min_step: Option<f64>,
+    /// See [`RayMarcher::distortion_step`]
+    pub distortion_step: Option<f64>,
+    /// Render with lower priority, keeping the desktop responsive
+    pub background: Option<bool>,
+}
+
+impl RenderSettings {
+    /// Settings of `self`, with unset ones taken from `other`.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            samples: self.samples.or(other.samples),
+            max_steps: self.max_steps.or(other.max_steps),
+            max_depth: self.max_depth.or(other.max_depth),
+            volume_step: self.volume_step.or(other.volume_step),
+            volume_min_step: self.volume_min_step.or(other.volume_min_step),
+            distortion_step: self.distortion_step.or(other.distortion_step),
+            background: self.background.or(other.background),
+        }
+    }
 }
 
 impl Default for RayMarcher {
@@ -199,6 +1102,19 @@ impl Default for RayMarcher {
             samples: 128,
             max_steps: 2 << 16,
             max_depth: 16,
+            temporal_offset: 0.0,
+            check_finite: false,
+            integrator: Integrator::Euler,
+            distortion_step: 0.1,
+            volume_step: 0.01,
+            volume_min_step: 0.002,
+            flip_inside_normals: false,
+            pixel_angle: 0.0,
+            sample_lights: false,
+            record_surfaces: false,
+            profile: false,
+            plugins: Vec::new(),
+            transparent_background: false,
         }
     }
 }
@@ -206,10 +1122,217 @@ impl Default for RayMarcher {
 pub struct RayResult {
     pub steps: usize,
     pub color: Vector3<f64>,
+    /// Rule which ended marching of the primary ray
+    pub termination: Termination,
+    /// Length of the bent path of the primary ray to where it stopped, infinite for rays
+    /// escaping to the background
+    pub distance: f64,
+    /// Index in [`Scene::objects`] of the object the primary ray hit, `None` for rays which
+    /// escaped or were captured
+    pub hit: Option<usize>,
+    /// Surface of the object the primary ray hit, set only for rays traced from depth 0 with
+    /// [`RayMarcher::record_surfaces`]
+    pub surface: Option<Surface>,
+    /// Coverage of the ray, below 1 only for camera rays reaching the held out background or
+    /// falling into an event horizon, see [`RayMarcher::transparent_background`]
+    pub alpha: f64,
+}
+
+/// Properties of a ray hit used for render passes besides the color, see [`crate::aov::Aov`].
+#[derive(Copy, Clone, Debug)]
+pub struct Surface {
+    /// Shading normal in world space
+    pub normal: Vector3<f64>,
+    pub albedo: Vector3<f64>,
+}
+
+/// Where a traced ray first got NaN or infinite values.
+#[derive(Copy, Clone, Debug)]
+pub struct NonFinite {
+    /// Bounce of the ray, 0 for the primary ray
+    pub depth: usize,
+    /// Where the ray was at that bounce
+    pub location: Vector3<f64>,
+    pub source: NonFiniteSource,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NonFiniteSource {
+    /// Material or bounced ray of an object, with its index in [`Scene::objects`]
+    Object {
+        index: usize,
+        shader: &'static str,
+    },
+    Background {
+        shader: &'static str,
+    },
+    /// Shader of a distortion which captured the ray
+    Capture {
+        shader: &'static str,
+    },
+    /// Ray direction after bending by distortions
+    Direction,
+    /// Final color only, like light of volumes integrated in deterministic mode
+    Other,
+}
+
+fn is_finite(v: Vector3<f64>) -> bool {
+    v.x.is_finite() && v.y.is_finite() && v.z.is_finite()
+}
+
+/// Reason for marching of a ray to stop.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Termination {
+    Object,
+    Background,
+    /// Impact parameter below critical value of a distortion
+    Captured,
+    /// Crossed the event horizon of a distortion
+    Horizon,
+    /// Distortion strength over hard limit
+    Strength,
+    /// Ray direction turned around in a single step
+    Reversal,
+    MaxSteps,
+    MaxDepth,
+    /// Volumes in front absorbed almost all light, only in deterministic mode
+    Absorbed,
+    /// Stopped by a [`plugin::MarchPlugin`]
+    Plugin,
+}
+
+impl Termination {
+    pub const ALL: [Self; 10] = [
+        Self::Object,
+        Self::Background,
+        Self::Captured,
+        Self::Horizon,
+        Self::Strength,
+        Self::Reversal,
+        Self::MaxSteps,
+        Self::MaxDepth,
+        Self::Absorbed,
+        Self::Plugin,
+    ];
+
+    /// Position in [`Termination::ALL`].
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn debug_color(&self) -> Vector3<f64> {
+        match self {
+            Self::Object => Vector3::new(0.0, 1.0, 0.0),
+            Self::Background => Vector3::new(0.0, 0.0, 1.0),
+            Self::Captured => Vector3::new(1.0, 0.0, 0.0),
+            Self::Horizon => Vector3::new(0.5, 0.0, 0.0),
+            Self::Strength => Vector3::new(1.0, 0.5, 0.0),
+            Self::Reversal => Vector3::new(1.0, 0.0, 1.0),
+            Self::MaxSteps => Vector3::new(1.0, 1.0, 1.0),
+            Self::MaxDepth => Vector3::new(1.0, 1.0, 0.0),
+            Self::Absorbed => Vector3::new(0.5, 0.5, 0.5),
+            Self::Plugin => Vector3::new(0.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// Light emitted and absorbed by volumes along the ray, used by deterministic mode.
+struct Absorption {
+    emission: Vector3<f64>,
+    transmittance: f64,
+}
+
+impl Absorption {
+    fn new() -> Self {
+        Self {
+            emission: Vector3::zero(),
+            transmittance: 1.0,
+        }
+    }
+
+    fn integrate(&mut self, object: &Object, ray: &Ray, dist: f64) {
+        if let Shading::Volumetric(shader) = &object.shading {
+            let ctx = ShadeContext {
+                deterministic: true,
+                ..object.context()
+            };
+            let local = object.local_ray(ray);
+            let density = shader.density_at(local.location, &ctx).max(0.0);
+            let (mat, _) = shader.material_at(&local, &ctx);
+
+            let absorbed = 1.0 - (-density * dist).exp();
+
+            self.emission += mat.emission * absorbed * self.transmittance;
+            self.transmittance *= 1.0 - absorbed;
+        }
+    }
+
+    /// Adds absorbed and emitted light to color coming from behind the volumes.
+    fn apply(&self, color: Vector3<f64>) -> Vector3<f64> {
+        self.emission + color * self.transmittance
+    }
+}
+
+/// Time since the start of [`RayMarcher::start_timer`], zero without profiling.
+fn elapsed(start: Option<Instant>) -> Duration {
+    start.map_or(Duration::ZERO, |start| start.elapsed())
+}
+
+/// Weight of sample with density `pdf`, combined with a sample of another strategy of `other` density.
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other * other);
+
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+/// Radius of the ball around `center` which no object reaches into at given time.
+///
+/// Exact distance functions never overestimate, so rays inside the ball can't hit anything.
+/// Shapes with only estimated distances may reach anywhere, see
+/// [`Shape::is_exact`](crate::object::shape::Shape::is_exact).
+fn clear_radius(scene: &Scene, center: Vector3<f64>, time: f64) -> f64 {
+    scene
+        .objects
+        .iter()
+        .map(|o| {
+            if o.shape.is_exact() {
+                o.shape.dist_fn_at(center, time)
+            } else {
+                0.0
+            }
+        })
+        .fold(f64::MAX, f64::min)
+}
+
+/// Result of a ray stopped by a marching limit, rays already known to be captured stay captured.
+fn stopped(plunge: Option<(&Distortion, f64)>, termination: Termination) -> MarchResult<'_> {
+    match plunge {
+        Some((distortion, _)) => MarchResult::Captured(distortion),
+        None => MarchResult::None(termination),
+    }
 }
 
 enum MarchResult<'a> {
-    Object(&'a Object),
+    /// Hit object with its index in the scene
+    Object(&'a Object, usize),
     Background(Vector3<f64>),
-    None,
+    Captured(&'a Distortion),
+    Horizon,
+    None(Termination),
+}
+
+impl MarchResult<'_> {
+    fn termination(&self) -> Termination {
+        match self {
+            Self::Object(..) => Termination::Object,
+            Self::Background(_) => Termination::Background,
+            Self::Captured(_) => Termination::Captured,
+            Self::Horizon => Termination::Horizon,
+            Self::None(termination) => *termination,
+        }
+    }
 }
@@ -0,0 +1,70 @@
+//! Extension points for custom physics along marched rays, see [`MarchPlugin`].
+
+use cgmath::{Vector3, Zero};
+
+use super::Termination;
+use crate::Ray;
+
+/// Callbacks on marched rays, registered with [`super::RayMarcher::with_plugin`].
+///
+/// Plugins are called for every ray the marcher traces, including bounced ones and rays traced
+/// for light sampling and footprints. They are shared by all rendering threads.
+pub trait MarchPlugin: Send + Sync {
+    /// Called before every step with the direction the ray leaves it with, after distortions
+    /// bent it. Changing the direction bends the ray, `step` is the length of the step.
+    fn on_bend(&self, _ray: &Ray, _direction: &mut Vector3<f64>, _step: f64) {}
+
+    /// Called after every step of length `step`, with the ray at its end.
+    fn on_step(&self, _ray: &Ray, _step: f64) -> StepResponse {
+        StepResponse::CONTINUE
+    }
+
+    /// Called once marching of the ray ended, returns light added to the ray.
+    fn on_terminate(&self, _ray: &Ray, _termination: Termination) -> Vector3<f64> {
+        Vector3::zero()
+    }
+}
+
+/// What a plugin does with the ray after a step.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StepResponse {
+    /// Light added to the ray, dimmed by volumes in front of the step in deterministic mode
+    pub emission: Vector3<f64>,
+    /// Ends marching with [`Termination::Plugin`], the ray then sees nothing behind
+    pub stop: bool,
+}
+
+impl StepResponse {
+    pub const CONTINUE: Self = Self {
+        emission: Vector3::new(0.0, 0.0, 0.0),
+        stop: false,
+    };
+
+    pub const STOP: Self = Self {
+        emission: Vector3::new(0.0, 0.0, 0.0),
+        stop: true,
+    };
+
+    pub fn emit(emission: Vector3<f64>) -> Self {
+        Self {
+            emission,
+            stop: false,
+        }
+    }
+}
+
+/// Makes every step of marched rays glow, showing where the marcher spends its steps.
+///
+/// Rays crawling along surfaces and through distortions light up, which helps with tuning step
+/// sizes of scenes.
+#[derive(Copy, Clone, Debug)]
+pub struct StepDensity {
+    /// Light added by a single step
+    pub color: Vector3<f64>,
+}
+
+impl MarchPlugin for StepDensity {
+    fn on_step(&self, _ray: &Ray, _step: f64) -> StepResponse {
+        StepResponse::emit(self.color)
+    }
+}
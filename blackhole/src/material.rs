@@ -1,6 +1,11 @@
-use cgmath::{Vector3, Zero};
+use cgmath::{ElementWise, InnerSpace, Vector3, Zero};
+
+use crate::math::{rand_unit, rand_unit_vector};
+use crate::object::shape::Shape;
+use crate::{Ray, RayKind};
 
 pub struct MaterialResult {
+    /// Emitted radiance in W·sr⁻¹·m⁻², see [`crate::camera::Exposure`]
     pub emission: Vector3<f64>,
     pub albedo: Vector3<f64>,
 }
@@ -13,3 +18,146 @@ impl MaterialResult {
         }
     }
 }
+
+/// Subsurface scattering of a solid material.
+///
+/// Light entering the object does a random walk inside its shape, scattering after distances
+/// with mean of `radius` and being tinted by `color` on every scatter, until it leaves the shape
+/// again.
+#[derive(Copy, Clone, Debug)]
+pub struct Subsurface {
+    /// Mean free path inside the object
+    pub radius: f64,
+    /// Albedo of a single scattering event
+    pub color: Vector3<f64>,
+    /// Fraction of rays hitting the surface which are scattered below it
+    pub weight: f64,
+}
+
+impl Subsurface {
+    /// Walks are cut after this many scattering events and absorbed.
+    const MAX_BOUNCES: usize = 64;
+    const MAX_STEPS: usize = 256;
+
+    /// Walks the ray hitting the surface at its location through the shape.
+    ///
+    /// Returns throughput of the walk and ray leaving the shape, or `None` if the light was
+    /// absorbed.
+    pub fn random_walk(
+        &self,
+        shape: &dyn Shape,
+        ray: &Ray,
+        normal: Vector3<f64>,
+    ) -> (Vector3<f64>, Option<Ray>) {
+        let eps = 0.00001;
+
+        let mut location = ray.location - normal * eps * 10.0;
+        let mut direction = (rand_unit_vector() - normal).normalize();
+        let mut throughput = Vector3::new(1.0, 1.0, 1.0);
+        let mut steps_taken = ray.steps_taken;
+
+        for _ in 0..Self::MAX_BOUNCES {
+            // exponential free flight
+            let mut remaining = -(1.0 - rand_unit()).ln() * self.radius;
+
+            for _ in 0..Self::MAX_STEPS {
+                let inside = -shape.dist_fn_at(location, ray.time);
+
+                if inside < eps {
+                    let normal = shape.normal_at(location, eps, ray.time);
+
+                    let mut exit = Ray {
+                        location,
+                        direction: (normal + rand_unit_vector()).normalize(),
+                        steps_taken,
+                        kind: RayKind::Secondary,
+                        time: ray.time,
+                    };
+                    exit.location += normal * 0.01;
+
+                    return (throughput, Some(exit));
+                }
+
+                if remaining <= 0.0 {
+                    break;
+                }
+
+                let step = inside.min(remaining);
+                location += direction * step;
+                remaining -= step;
+                steps_taken += 1;
+            }
+
+            throughput = throughput.mul_element_wise(self.color);
+            direction = rand_unit_vector();
+        }
+
+        (Vector3::zero(), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::seed_rng;
+    use crate::object::shape::Sphere;
+
+    const WALKS: u64 = 256;
+
+    fn walk(subsurface: &Subsurface, seed: u64) -> (Vector3<f64>, Option<Ray>) {
+        seed_rng(seed);
+
+        let ray = Ray {
+            location: Vector3::new(0.0, 0.0, 1.0),
+            direction: Vector3::new(0.0, 0.0, -1.0),
+            steps_taken: 0,
+            kind: RayKind::Primary,
+            time: 0.5,
+        };
+
+        subsurface.random_walk(&Sphere::new(), &ray, Vector3::new(0.0, 0.0, 1.0))
+    }
+
+    fn mean_throughput(radius: f64) -> f64 {
+        let subsurface = Subsurface {
+            radius,
+            color: Vector3::new(0.8, 0.8, 0.8),
+            weight: 1.0,
+        };
+
+        (0..WALKS)
+            .map(|seed| walk(&subsurface, seed).0.x)
+            .sum::<f64>()
+            / WALKS as f64
+    }
+
+    #[test]
+    fn walks_leave_the_shape() {
+        let subsurface = Subsurface {
+            radius: 0.2,
+            color: Vector3::new(0.9, 0.5, 0.1),
+            weight: 1.0,
+        };
+
+        for seed in 0..WALKS {
+            let (throughput, exit) = walk(&subsurface, seed);
+
+            for channel in [throughput.x, throughput.y, throughput.z] {
+                assert!((0.0..=1.0).contains(&channel), "throughput {channel}");
+            }
+
+            if let Some(exit) = exit {
+                assert!(Sphere::new().dist_fn(exit.location) > 0.0);
+                assert!(matches!(exit.kind, RayKind::Secondary));
+            }
+        }
+    }
+
+    #[test]
+    fn throughput_drops_with_extinction() {
+        let thin = mean_throughput(0.5);
+        let dense = mean_throughput(0.05);
+
+        assert!(dense < thin, "dense {dense}, thin {thin}");
+    }
+}
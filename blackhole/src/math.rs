@@ -1,5 +1,5 @@
 use crate::GAUSS_LUT;
-use cgmath::{InnerSpace, Vector3, VectorSpace};
+use cgmath::{Deg, InnerSpace, Matrix3, Vector3, VectorSpace};
 use rand::{Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256StarStar;
 use std::cell::RefCell;
@@ -9,15 +9,16 @@ thread_local! {
 }
 
 pub fn rand_unit_vector() -> Vector3<f64> {
-    let nums = RNG.with(|r| {
-        let mut rng = r.borrow_mut();
+    RNG.with(|r| rand_unit_vector_with(&mut *r.borrow_mut()))
+}
 
-        (
-            GAUSS_LUT.lookup(rng.gen_range(0.0..1.0)),
-            GAUSS_LUT.lookup(rng.gen_range(0.0..1.0)),
-            GAUSS_LUT.lookup(rng.gen_range(0.0..1.0)),
-        )
-    });
+/// Random unit vector from given generator, for data which has to be the same on every run.
+pub fn rand_unit_vector_with<R: Rng>(rng: &mut R) -> Vector3<f64> {
+    let nums = (
+        GAUSS_LUT.lookup(rng.gen_range(0.0..1.0)),
+        GAUSS_LUT.lookup(rng.gen_range(0.0..1.0)),
+        GAUSS_LUT.lookup(rng.gen_range(0.0..1.0)),
+    );
 
     Vector3::new(nums.0, nums.1, nums.2).normalize()
 }
@@ -26,6 +27,118 @@ pub fn rand_unit() -> f64 {
     RNG.with(|r| r.borrow_mut().gen_range(0.0..1.0))
 }
 
+/// Reseeds random generator of the current thread.
+pub fn seed_rng(seed: u64) {
+    RNG.with(|r| *r.borrow_mut() = Xoshiro256StarStar::seed_from_u64(seed));
+}
+
+/// Derives new seed from a seed and a value, like frame or sample index.
+///
+/// Seeds derived from neighbouring values are uncorrelated.
+pub fn mix_seed(seed: u64, value: u64) -> u64 {
+    splitmix64(seed ^ splitmix64(value))
+}
+
+/// Seed for given sample of a pixel.
+///
+/// Reseeding before every pixel sample makes the noise independent of the order pixels are
+/// rendered in, so renders are reproducible regardless of thread count and scheduling.
+pub fn pixel_seed(seed: u64, sample: usize, x: usize, y: usize) -> u64 {
+    mix_seed(mix_seed(mix_seed(seed, sample as u64), y as u64), x as u64)
+}
+
+/// Seed for rendering given frame of a sequence.
+///
+/// With `lock_noise`, all frames share the base seed, so noise doesn't flicker between frames.
+pub fn frame_seed(base: u64, frame: u64, lock_noise: bool) -> u64 {
+    if lock_noise {
+        base
+    } else {
+        mix_seed(base, frame)
+    }
+}
+
+/// Offset in [0, 1) for given frame, successive frames are evenly spread over the interval.
+///
+/// Used together with locked noise, adding it to random numbers keeps the pattern stable but
+/// lets it converge over time.
+pub fn temporal_offset(frame: u64) -> f64 {
+    // golden ratio sequence
+    (frame as f64 * 0.618_033_988_749_894_9).fract()
+}
+
+pub fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// Direction for a point of an equirectangular image, with `u` and `v` from 0 to 1.
+///
+/// The image center looks along -Z, `u` goes around the Y axis and `v` from +Y down to -Y.
+pub fn equirect_direction(u: f64, v: f64) -> Vector3<f64> {
+    let longitude = (u - 0.5) * std::f64::consts::TAU;
+    let latitude = (0.5 - v) * std::f64::consts::PI;
+
+    Vector3::new(
+        longitude.sin() * latitude.cos(),
+        latitude.sin(),
+        -longitude.cos() * latitude.cos(),
+    )
+}
+
+/// Point of an equirectangular image seen in given direction, inverse of [`equirect_direction`].
+pub fn equirect_uv(direction: Vector3<f64>) -> (f64, f64) {
+    let direction = direction.normalize();
+    let longitude = direction.x.atan2(-direction.z);
+    let latitude = direction.y.clamp(-1.0, 1.0).asin();
+
+    (
+        longitude / std::f64::consts::TAU + 0.5,
+        0.5 - latitude / std::f64::consts::PI,
+    )
+}
+
+/// Two unit vectors perpendicular to each other and to the unit vector `n`.
+pub fn orthonormal_basis(n: Vector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
+    let helper = if n.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+
+    let u = n.cross(helper).normalize();
+
+    (u, n.cross(u))
+}
+
+/// Random direction inside the cone around unit `axis`, with cosine of its half angle `cos_max`.
+///
+/// Directions are spread evenly over the solid angle of the cone, with density of [`cone_pdf`].
+/// Cosine of -1 makes the cone cover the whole sphere.
+pub fn rand_cone_direction(axis: Vector3<f64>, cos_max: f64) -> Vector3<f64> {
+    let cos_theta = 1.0 - rand_unit() * (1.0 - cos_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = rand_unit() * std::f64::consts::TAU;
+
+    let (u, v) = orthonormal_basis(axis);
+
+    (u * phi.cos() + v * phi.sin()) * sin_theta + axis * cos_theta
+}
+
+/// Density per steradian of directions picked by [`rand_cone_direction`].
+pub fn cone_pdf(cos_max: f64) -> f64 {
+    1.0 / (std::f64::consts::TAU * (1.0 - cos_max))
+}
+
+/// Rotation by Euler angles in degrees, applied in Z, X, Y order.
+pub fn euler_rotation(angles: Vector3<f64>) -> Matrix3<f64> {
+    Matrix3::from_angle_y(Deg(angles.y))
+        * Matrix3::from_angle_x(Deg(angles.x))
+        * Matrix3::from_angle_z(Deg(angles.z))
+}
+
 pub fn sigmoid(x: f64, slope: f64, center: f64) -> f64 {
     1.0 / (1.0 + std::f64::consts::E.powf(-slope * (x - center)))
 }
@@ -1,21 +1,38 @@
 use crate::Ray;
+use cgmath::Vector3;
 use std::sync::Arc;
 
 mod aabb;
 mod distortion;
 pub mod shape;
 
+use crate::animation::{ObjectMotion, ObjectTrack, RigidPose};
 use crate::material::MaterialResult;
-use crate::shader::{SolidShader, VolumetricShader};
+use crate::math::rand_unit;
+use crate::shader::{Parameter, ShadeContext, SolidShader, VolumetricShader};
 
 pub use aabb::AABB;
 pub use distortion::Distortion;
-use shape::Shape;
+use shape::{Animated, Shape};
 
 #[derive(Clone)]
 pub struct Object {
     pub shape: Arc<dyn Shape>,
     pub shading: Shading,
+    pub instance_seed: u64,
+    /// Index of the render layer in [`Scene::layers`](crate::scene::Scene::layers)
+    pub layer: usize,
+    /// Movement over time, applied by [`Object::set_shutter`]
+    pub animation: Option<ObjectAnimation>,
+}
+
+/// Track of an animated object with its shape at rest.
+#[derive(Clone)]
+pub struct ObjectAnimation {
+    pub track: ObjectTrack,
+    pub rest_shape: Arc<dyn Shape>,
+    /// Placements over the current shutter interval, `None` before the object was posed
+    motion: Option<Arc<ObjectMotion>>,
 }
 
 impl Object {
@@ -23,6 +40,9 @@ impl Object {
         Self {
             shape,
             shading: Shading::Solid(shader),
+            instance_seed: 0,
+            layer: 0,
+            animation: None,
         }
     }
 
@@ -30,18 +50,265 @@ impl Object {
         Self {
             shape,
             shading: Shading::Volumetric(shader),
+            instance_seed: 0,
+            layer: 0,
+            animation: None,
+        }
+    }
+
+    /// Moves the object along the track, with the current shape as the one at time 0.
+    pub fn animate(mut self, track: ObjectTrack) -> Self {
+        self.animation = Some(ObjectAnimation {
+            track,
+            rest_shape: self.shape.clone(),
+            motion: None,
+        });
+
+        self
+    }
+
+    /// Poses animated objects over the shutter interval from time `open` to `close` in seconds.
+    pub fn set_shutter(&mut self, open: f64, close: f64) {
+        if let Some(animation) = &mut self.animation {
+            let motion = Arc::new(animation.track.motion(open, close));
+
+            self.shape = Arc::new(Animated::new(animation.rest_shape.clone(), motion.clone()));
+            animation.motion = Some(motion);
+        }
+    }
+
+    fn pose(&self, time: f64) -> Option<RigidPose> {
+        let motion = self.animation.as_ref()?.motion.as_ref()?;
+
+        Some(motion.pose_at(time))
+    }
+
+    /// Position of a point in the space of the object at rest, where shaders are evaluated.
+    pub fn local_point(&self, point: Vector3<f64>, time: f64) -> Vector3<f64> {
+        match self.pose(time) {
+            Some(pose) => pose.to_rest(point),
+            None => point,
+        }
+    }
+
+    /// Ray in the space of the object at rest, see [`Object::local_point`].
+    pub fn local_ray(&self, ray: &Ray) -> Ray {
+        match self.pose(ray.time) {
+            Some(pose) => Ray {
+                location: pose.to_rest(ray.location),
+                direction: pose.rotation.conjugate() * ray.direction,
+                ..*ray
+            },
+            None => *ray,
+        }
+    }
+
+    /// Inverse of [`Object::local_ray`].
+    fn world_ray(&self, ray: Ray) -> Ray {
+        match self.pose(ray.time) {
+            Some(pose) => Ray {
+                location: pose.apply(ray.location),
+                direction: pose.rotation * ray.direction,
+                ..ray
+            },
+            None => ray,
         }
     }
 
+    /// Direction in the space of the object at rest, see [`Object::local_point`].
+    fn local_direction(&self, direction: Vector3<f64>, time: f64) -> Vector3<f64> {
+        match self.pose(time) {
+            Some(pose) => pose.rotation.conjugate() * direction,
+            None => direction,
+        }
+    }
+
+    /// Inverse of [`Object::local_direction`].
+    fn world_direction(&self, direction: Vector3<f64>, time: f64) -> Vector3<f64> {
+        match self.pose(time) {
+            Some(pose) => pose.rotation * direction,
+            None => direction,
+        }
+    }
+
+    /// Shades volumes in the space of the object at rest.
+    fn shade_volume(
+        &self,
+        shader: &dyn VolumetricShader,
+        ray: &Ray,
+        deterministic: bool,
+    ) -> (MaterialResult, Option<Ray>) {
+        let ctx = ShadeContext {
+            deterministic,
+            ..self.context()
+        };
+        let (mat, new_ray) = shader.material_at(&self.local_ray(ray), &ctx);
+
+        (mat, new_ray.map(|r| self.world_ray(r)))
+    }
+
     pub fn shade(&self, ray: &Ray) -> (MaterialResult, Option<Ray>) {
+        self.shade_oriented(ray, false, false)
+    }
+
+    /// Shades the object, with normals of solids pointing into the shape when `flip` is set.
+    ///
+    /// With `deterministic` no random numbers are drawn, see [`ShadeContext::deterministic`].
+    pub fn shade_oriented(
+        &self,
+        ray: &Ray,
+        flip: bool,
+        deterministic: bool,
+    ) -> (MaterialResult, Option<Ray>) {
         match &self.shading {
-            Shading::Solid(s) => {
+            Shading::Solid(_) => {
                 let eps = 0.00001;
-                let normal = self.shape.normal(ray.location, eps);
+                let normal = self.shape.normal_at(ray.location, eps, ray.time);
+
+                let normal = if flip { -normal } else { normal };
+
+                self.shade_with_normal(ray, normal, deterministic)
+            }
+            Shading::Volumetric(v) => self.shade_volume(v.as_ref(), ray, deterministic),
+        }
+    }
+
+    /// Shades the object with given surface normal, which volumes don't use.
+    ///
+    /// Used for faces which don't belong to the shape, like ones cut by
+    /// [`Scene::slice`](crate::scene::Scene::slice).
+    pub fn shade_with_normal(
+        &self,
+        ray: &Ray,
+        normal: Vector3<f64>,
+        deterministic: bool,
+    ) -> (MaterialResult, Option<Ray>) {
+        match &self.shading {
+            Shading::Solid(s) => {
+                let ctx = ShadeContext {
+                    deterministic,
+                    ..self.surface_context(ray)
+                };
+                let local = self.local_ray(ray);
+
+                let normal = self.local_direction(normal, ray.time);
+                let normal = s.shading_normal(&local, normal, &ctx);
+                let (mat, new_ray) = s.material_at(&local, normal, &ctx);
+
+                let normal = self.world_direction(normal, ray.time);
+                let new_ray = new_ray.map(|r| self.world_ray(r));
+
+                match s.subsurface() {
+                    // the walk inside is random, deterministic shading stays on the surface
+                    Some(sss)
+                        if !deterministic && new_ray.is_some() && rand_unit() < sss.weight =>
+                    {
+                        let (throughput, new_ray) =
+                            sss.random_walk(self.shape.as_ref(), ray, normal);
+
+                        let mat = MaterialResult {
+                            emission: mat.emission,
+                            albedo: throughput,
+                        };
+
+                        (mat, new_ray)
+                    }
+                    _ => (mat, new_ray),
+                }
+            }
+            Shading::Volumetric(v) => self.shade_volume(v.as_ref(), ray, deterministic),
+        }
+    }
+
+    /// Normal of solids tilted by their shader, see [`SolidShader::shading_normal`].
+    pub fn shading_normal(&self, ray: &Ray, normal: Vector3<f64>) -> Vector3<f64> {
+        match &self.shading {
+            Shading::Solid(s) => {
+                let local = self.local_direction(normal, ray.time);
+                let shading =
+                    s.shading_normal(&self.local_ray(ray), local, &self.surface_context(ray));
+
+                self.world_direction(shading, ray.time)
+            }
+            Shading::Volumetric(_) => normal,
+        }
+    }
+
+    /// Phase function of volumes, see [`VolumetricShader::phase`].
+    pub fn phase(&self, ray: &Ray, scattered: &Ray, direction: Vector3<f64>) -> Option<f64> {
+        match &self.shading {
+            Shading::Solid(_) => None,
+            Shading::Volumetric(v) => v.phase(ray, scattered, direction),
+        }
+    }
+
+    /// See [`crate::shader::Shader::emits_light`].
+    pub fn emits_light(&self) -> bool {
+        match &self.shading {
+            Shading::Solid(s) => s.emits_light(),
+            Shading::Volumetric(v) => v.emits_light(),
+        }
+    }
+
+    /// Context of a point on the surface of the object.
+    fn surface_context(&self, ray: &Ray) -> ShadeContext {
+        ShadeContext {
+            uv: self.shape.uv_at(ray.location, ray.time),
+            ..self.context()
+        }
+    }
+
+    pub fn context(&self) -> ShadeContext {
+        ShadeContext {
+            instance_seed: self.instance_seed,
+            uv: None,
+            deterministic: false,
+        }
+    }
+
+    /// See [`crate::shader::Shader::type_name`].
+    pub fn shader_name(&self) -> &'static str {
+        match &self.shading {
+            Shading::Solid(s) => s.type_name(),
+            Shading::Volumetric(v) => v.type_name(),
+        }
+    }
+
+    /// See [`crate::shader::Shader::parameter`].
+    pub fn shader_parameter(&self, name: &str) -> Option<Parameter> {
+        match &self.shading {
+            Shading::Solid(s) => s.parameter(name),
+            Shading::Volumetric(v) => v.parameter(name),
+        }
+    }
+
+    /// See [`crate::shader::Shader::emission_scale`].
+    pub fn emission_scale(&self, name: &str, value: &Parameter) -> Option<Vector3<f64>> {
+        match &self.shading {
+            Shading::Solid(s) => s.emission_scale(name, value),
+            Shading::Volumetric(v) => v.emission_scale(name, value),
+        }
+    }
+
+    /// Changes parameter of the object's shader.
+    ///
+    /// Shader shared with other objects is cloned first, so they are not affected.
+    pub fn set_shader_parameter(&mut self, name: &str, value: Parameter) {
+        match &mut self.shading {
+            Shading::Solid(s) => {
+                if Arc::get_mut(s).is_none() {
+                    *s = s.clone_solid();
+                }
+
+                Arc::get_mut(s).unwrap().set_parameter(name, value);
+            }
+            Shading::Volumetric(v) => {
+                if Arc::get_mut(v).is_none() {
+                    *v = v.clone_volumetric();
+                }
 
-                s.material_at(ray, normal)
+                Arc::get_mut(v).unwrap().set_parameter(name, value);
             }
-            Shading::Volumetric(v) => v.material_at(ray),
         }
     }
 }
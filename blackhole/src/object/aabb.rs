@@ -1,5 +1,5 @@
 use crate::Ray;
-use cgmath::Vector3;
+use cgmath::{InnerSpace, Vector3};
 
 #[derive(Copy, Clone, Debug)]
 pub struct AABB {
@@ -31,6 +31,33 @@ impl AABB {
         Vector3::new(self.x_max, self.y_max, self.z_max)
     }
 
+    /// Smallest box containing all points, inverted (empty) box without any points.
+    pub fn around(points: impl IntoIterator<Item = Vector3<f64>>) -> Self {
+        let mut bb = Self {
+            x_min: f64::MAX,
+            x_max: f64::MIN,
+            y_min: f64::MAX,
+            y_max: f64::MIN,
+            z_min: f64::MAX,
+            z_max: f64::MIN,
+        };
+
+        for point in points {
+            bb.x_min = bb.x_min.min(point.x);
+            bb.x_max = bb.x_max.max(point.x);
+            bb.y_min = bb.y_min.min(point.y);
+            bb.y_max = bb.y_max.max(point.y);
+            bb.z_min = bb.z_min.min(point.z);
+            bb.z_max = bb.z_max.max(point.z);
+        }
+
+        bb
+    }
+
+    pub fn center(&self) -> Vector3<f64> {
+        (self.min() + self.max()) / 2.0
+    }
+
     pub fn ray_intersect(&self, ray: &Ray) -> bool {
         let (mut tmax, mut tmin) = (f64::MAX, f64::MIN);
         for a in 0..3 {
@@ -52,6 +79,17 @@ impl AABB {
 
         true
     }
+
+    /// Distance from the point to the nearest point of the box, 0 for points inside.
+    pub fn distance(&self, point: Vector3<f64>) -> f64 {
+        let outside = Vector3::new(
+            (self.x_min - point.x).max(point.x - self.x_max).max(0.0),
+            (self.y_min - point.y).max(point.y - self.y_max).max(0.0),
+            (self.z_min - point.z).max(point.z - self.z_max).max(0.0),
+        );
+
+        outside.magnitude()
+    }
 }
 
 impl Default for AABB {
@@ -1,11 +1,29 @@
 use crate::object::shape::{Shape, Sphere};
+use crate::shader::BackgroundShader;
 use crate::Ray;
-use cgmath::{Vector3, Zero};
+use cgmath::{InnerSpace, Vector3, Zero};
+
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct Distortion {
     pub strength: f64,
     pub shape: Sphere,
+    /// Shader for rays captured by the distortion, captured rays are black without one
+    pub capture_shader: Option<Arc<dyn BackgroundShader>>,
+    /// Rotation axis, disks generated around the distortion are perpendicular to it
+    pub spin_axis: Vector3<f64>,
+    /// Dimensionless spin from -1 to 1, negative for disks orbiting against the rotation
+    ///
+    /// Only affects orbits of matter around the distortion, not bending of rays.
+    pub spin: f64,
+    /// Radius of the event horizon, rays crossing it end black and are held out with
+    /// [`crate::marcher::RayMarcher::transparent_background`]
+    ///
+    /// Captured rays without a capture shader end the same way, as they would cross it later.
+    /// Rays still end once the distortion gets too strong, which only happens before crossing
+    /// horizons much smaller than the photon sphere.
+    pub horizon_radius: Option<f64>,
 }
 
 impl Distortion {
@@ -17,6 +35,10 @@ impl Distortion {
         Self {
             shape,
             strength: 0.3,
+            capture_shader: None,
+            spin_axis: Vector3::unit_y(),
+            spin: 0.0,
+            horizon_radius: None,
         }
     }
 
@@ -29,6 +51,93 @@ impl Distortion {
         self.strength / (x).powi(2)
     }
 
+    /// Rate of turning of ray direction towards the center, per unit of travelled distance.
+    pub fn acceleration(&self, point: Vector3<f64>) -> Vector3<f64> {
+        (self.shape.center() - point).normalize() * self.strength(point)
+    }
+
+    /// Radius of unstable circular orbit, equal to strength for inverse square falloff.
+    pub fn photon_sphere_radius(&self) -> f64 {
+        self.strength
+    }
+
+    /// Mass in scene units, chosen so the photon sphere is at `3M` like around a Schwarzschild
+    /// black hole.
+    pub fn gravitational_radius(&self) -> f64 {
+        self.photon_sphere_radius() / 3.0
+    }
+
+    /// Distance of the point from the event horizon, negative inside of it and infinite for
+    /// distortions without one.
+    pub fn horizon_dist(&self, point: Vector3<f64>) -> f64 {
+        match self.horizon_radius {
+            Some(r) => (point - self.shape.center()).magnitude() - r,
+            None => f64::INFINITY,
+        }
+    }
+
+    /// Radius of the innermost stable circular orbit, inside which disks can't hold matter.
+    ///
+    /// Computed for a Kerr black hole with the distortion mass and spin, giving `6M` without
+    /// spin and going from `1M` to `9M` between prograde and retrograde extremes.
+    pub fn isco_radius(&self) -> f64 {
+        let a = self.spin.clamp(-1.0, 1.0);
+        let a_abs = a.abs();
+
+        let z1 = 1.0 + (1.0 - a * a).cbrt() * ((1.0 + a_abs).cbrt() + (1.0 - a_abs).cbrt());
+        let z2 = (3.0 * a * a + z1 * z1).sqrt();
+
+        let root = ((3.0 - z1) * (3.0 + z1 + 2.0 * z2)).sqrt();
+
+        // prograde orbits get closer to the center
+        let r = if a >= 0.0 {
+            3.0 + z2 - root
+        } else {
+            3.0 + z2 + root
+        };
+
+        r * self.gravitational_radius()
+    }
+
+    /// Impact parameter below which incoming rays can't escape.
+    pub fn critical_impact(&self) -> f64 {
+        std::f64::consts::E * self.strength
+    }
+
+    /// Checks whether the ray is going to fall into the center of distortion.
+    ///
+    /// Bending only rotates the direction, so `|r × d| · e^(s/r)` stays constant along the path
+    /// and equals the impact parameter the ray would have far away. Rays heading inwards are
+    /// captured when it is below the critical value or when already inside the photon sphere.
+    /// Rays outside of the distortion go straight until they enter it, so the value is taken at
+    /// its boundary.
+    ///
+    /// Distance of captured rays from the center only decreases from then on.
+    pub fn captures(&self, ray: &Ray) -> bool {
+        let relative = ray.location - self.shape.center();
+
+        if relative.dot(ray.direction) >= 0.0 {
+            return false;
+        }
+
+        let r = relative.magnitude();
+        let radius = self.shape.radius();
+        let straight = relative.cross(ray.direction).magnitude();
+
+        if r > radius {
+            return straight < radius
+                && straight * (self.strength / radius).exp() < self.critical_impact();
+        }
+
+        if r < self.photon_sphere_radius() {
+            return true;
+        }
+
+        let impact = straight * (self.strength / r).exp();
+
+        impact < self.critical_impact()
+    }
+
     pub fn can_ray_hit(&self, ray: &Ray) -> bool {
         self.shape.can_ray_hit(ray)
     }
@@ -39,3 +148,41 @@ impl Default for Distortion {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_rays_from_outside() {
+        let distortion = Distortion::new();
+        let ray = |offset: f64| Ray {
+            location: Vector3::new(offset, 0.0, 50.0),
+            direction: Vector3::new(0.0, 0.0, -1.0),
+            steps_taken: 0,
+            kind: crate::RayKind::Primary,
+            time: 0.5,
+        };
+
+        // the boundary value of the impact parameter is higher than the straight one
+        let limit = distortion.critical_impact() * (-distortion.strength / 5.0).exp();
+
+        assert!(distortion.captures(&ray(limit * 0.99)));
+        assert!(!distortion.captures(&ray(limit * 1.01)));
+        assert!(!distortion.captures(&ray(6.0)));
+    }
+
+    #[test]
+    fn isco_spin_extremes() {
+        let mut distortion = Distortion::new();
+        distortion.strength = 3.0;
+
+        for (spin, isco) in [(0.0, 6.0), (1.0, 1.0), (-1.0, 9.0)] {
+            distortion.spin = spin;
+            assert!(
+                (distortion.isco_radius() - isco).abs() < 1e-9,
+                "spin {spin}"
+            );
+        }
+    }
+}
@@ -1,16 +1,33 @@
 use crate::object::AABB;
 use crate::Ray;
-use cgmath::{Array, InnerSpace, Vector3};
+use cgmath::{Array, InnerSpace, Vector2, Vector3};
 
+mod animated;
 mod composite;
 mod cube;
 mod cylinder;
+mod grid;
+mod mesh;
+mod moving;
+mod repeated;
+mod shell;
 mod sphere;
+mod transformed;
 
+pub use animated::Animated;
 pub use composite::Composite;
 pub use cube::Cube;
 pub use cylinder::Cylinder;
+pub use grid::GridShape;
+pub use mesh::{read_triangles, MeshShape};
+pub use moving::Moving;
+pub use repeated::Repeated;
+pub use shell::Shell;
 pub use sphere::Sphere;
+pub use transformed::Transformed;
+
+/// Points evaluated at once by shapes which need temporary buffers for batches.
+pub(crate) const BATCH_SIZE: usize = 64;
 
 pub trait Shape: Send + Sync {
     fn dist_fn(&self, point: Vector3<f64>) -> f64;
@@ -23,16 +40,65 @@ pub trait Shape: Send + Sync {
     }
 
     fn normal(&self, position: Vector3<f64>, epsilon: f64) -> Vector3<f64> {
+        self.normal_at(position, epsilon, 0.5)
+    }
+
+    /// Distance at given time of the shutter interval, see [`Ray::time`].
+    fn dist_fn_at(&self, point: Vector3<f64>, _time: f64) -> f64 {
+        self.dist_fn(point)
+    }
+
+    /// Distances of many points at given time, written to `out`.
+    ///
+    /// Shapes with vectorized distance functions override this, see [`crate::simd`].
+    fn dist_fn_batch(&self, points: &[Vector3<f64>], time: f64, out: &mut [f64]) {
+        for (point, out) in points.iter().zip(out) {
+            *out = self.dist_fn_at(*point, time);
+        }
+    }
+
+    fn normal_at(&self, position: Vector3<f64>, epsilon: f64, time: f64) -> Vector3<f64> {
         let eps = 0.00001;
 
-        let dist_x = self.dist_fn(position + Vector3::new(epsilon, 0.0, 0.0));
-        let dist_y = self.dist_fn(position + Vector3::new(0.0, epsilon, 0.0));
-        let dist_z = self.dist_fn(position + Vector3::new(0.0, 0.0, epsilon));
+        let points = [
+            position + Vector3::new(epsilon, 0.0, 0.0),
+            position + Vector3::new(0.0, epsilon, 0.0),
+            position + Vector3::new(0.0, 0.0, epsilon),
+            position,
+        ];
+        let mut dist = [0.0; 4];
+
+        self.dist_fn_batch(&points, time, &mut dist);
 
-        let normal = (Vector3::new(dist_x, dist_y, dist_z)
-            - Vector3::from_value(self.dist_fn(position)))
-            / eps;
+        let normal = (Vector3::new(dist[0], dist[1], dist[2]) - Vector3::from_value(dist[3])) / eps;
 
         normal.normalize()
     }
+
+    /// Surface coordinates of a point on the shape, for shapes with a natural mapping.
+    ///
+    /// Both coordinates are in the `0..1` range, with `v` going down the texture image.
+    fn uv_at(&self, _point: Vector3<f64>, _time: f64) -> Option<Vector2<f64>> {
+        None
+    }
+
+    /// Whether the shape changes during the shutter interval.
+    fn is_moving(&self) -> bool {
+        false
+    }
+
+    /// Whether the distance function never overestimates the distance to the surface.
+    ///
+    /// Shapes with only estimated distances can still be marched, but space around them can't
+    /// be assumed empty.
+    fn is_exact(&self) -> bool {
+        true
+    }
+
+    /// Samples the distance field into a grid, see [`GridShape`].
+    ///
+    /// Moving shapes are baked at the start of the shutter interval.
+    fn bake(&self, resolution: usize) -> GridShape {
+        GridShape::new(self, resolution)
+    }
 }
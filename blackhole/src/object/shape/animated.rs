@@ -0,0 +1,83 @@
+use super::{Shape, BATCH_SIZE};
+use crate::animation::ObjectMotion;
+use crate::object::AABB;
+use cgmath::{Vector2, Vector3, Zero};
+use std::sync::Arc;
+
+/// Shape placed by an object track, moving through its poses while the shutter is open.
+///
+/// Points are mapped back to where the wrapped shape is at rest.
+pub struct Animated {
+    shape: Arc<dyn Shape>,
+    motion: Arc<ObjectMotion>,
+    bounding_box: AABB,
+}
+
+impl Animated {
+    pub fn new(shape: Arc<dyn Shape>, motion: Arc<ObjectMotion>) -> Self {
+        let bb = shape.bounding_box();
+
+        let corners = motion.poses().iter().flat_map(|pose| {
+            (0..8).map(move |i| {
+                let corner = Vector3::new(
+                    if i & 1 == 0 { bb.x_min } else { bb.x_max },
+                    if i & 2 == 0 { bb.y_min } else { bb.y_max },
+                    if i & 4 == 0 { bb.z_min } else { bb.z_max },
+                );
+
+                pose.apply(corner)
+            })
+        });
+        let bounding_box = AABB::around(corners);
+
+        Self {
+            shape,
+            motion,
+            bounding_box,
+        }
+    }
+}
+
+impl Shape for Animated {
+    fn dist_fn(&self, point: Vector3<f64>) -> f64 {
+        self.dist_fn_at(point, 0.5)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bounding_box
+    }
+
+    fn dist_fn_at(&self, point: Vector3<f64>, time: f64) -> f64 {
+        let pose = self.motion.pose_at(time);
+
+        self.shape.dist_fn_at(pose.to_rest(point), time)
+    }
+
+    fn dist_fn_batch(&self, points: &[Vector3<f64>], time: f64, out: &mut [f64]) {
+        let pose = self.motion.pose_at(time);
+        let mut rest = [Vector3::zero(); BATCH_SIZE];
+
+        for (points, out) in points.chunks(BATCH_SIZE).zip(out.chunks_mut(BATCH_SIZE)) {
+            let rest = &mut rest[..points.len()];
+
+            for (rest, point) in rest.iter_mut().zip(points) {
+                *rest = pose.to_rest(*point);
+            }
+
+            self.shape.dist_fn_batch(rest, time, out);
+        }
+    }
+
+    fn uv_at(&self, point: Vector3<f64>, time: f64) -> Option<Vector2<f64>> {
+        self.shape
+            .uv_at(self.motion.pose_at(time).to_rest(point), time)
+    }
+
+    fn is_moving(&self) -> bool {
+        self.motion.is_moving() || self.shape.is_moving()
+    }
+
+    fn is_exact(&self) -> bool {
+        self.shape.is_exact()
+    }
+}
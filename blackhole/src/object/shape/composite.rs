@@ -1,4 +1,4 @@
-use super::Shape;
+use super::{Shape, BATCH_SIZE};
 use crate::object::AABB;
 use cgmath::Vector3;
 use std::sync::Arc;
@@ -14,6 +14,30 @@ pub enum BooleanOp {
     Difference,
     Intersection,
     Union,
+    /// Union with surfaces blended together within given radius
+    SmoothUnion(f64),
+    /// Difference with the cut edge rounded by given radius
+    SmoothDifference(f64),
+}
+
+impl BooleanOp {
+    fn apply(&self, a: f64, b: f64) -> f64 {
+        match *self {
+            BooleanOp::Difference => a.max(-b),
+            BooleanOp::Intersection => a.max(b),
+            BooleanOp::Union => a.min(b),
+            BooleanOp::SmoothUnion(k) => {
+                let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+
+                b + (a - b) * h - k * h * (1.0 - h)
+            }
+            BooleanOp::SmoothDifference(k) => {
+                let h = (0.5 - 0.5 * (a + b) / k).clamp(0.0, 1.0);
+
+                a + (-b - a) * h + k * h * (1.0 - h)
+            }
+        }
+    }
 }
 
 impl Composite {
@@ -50,37 +74,90 @@ impl Composite {
         composite
     }
 
+    /// Union blending the shapes within radius `k`, without a crease where they meet.
+    pub fn smooth_union(a: Arc<dyn Shape>, b: Arc<dyn Shape>, k: f64) -> Self {
+        let mut composite = Self {
+            a,
+            b,
+            op: BooleanOp::SmoothUnion(k),
+            bounding_box: AABB::new(),
+        };
+        composite.compute_bb();
+        composite
+    }
+
+    /// Difference with the edge of the cut rounded by radius `k`.
+    pub fn smooth_diff(a: Arc<dyn Shape>, b: Arc<dyn Shape>, k: f64) -> Self {
+        let mut composite = Self {
+            a,
+            b,
+            op: BooleanOp::SmoothDifference(k),
+            bounding_box: AABB::new(),
+        };
+        composite.compute_bb();
+        composite
+    }
+
     fn compute_bb(&mut self) {
         let abb = self.a.bounding_box();
         let bbb = self.b.bounding_box();
 
+        // blending bulges out of both shapes by at most a quarter of the radius
+        let grow = match self.op {
+            BooleanOp::SmoothUnion(k) => k / 4.0,
+            _ => 0.0,
+        };
+
         self.bounding_box = match self.op {
-            BooleanOp::Intersection | BooleanOp::Union => AABB {
-                x_min: abb.x_min.min(bbb.x_min),
-                x_max: abb.x_max.max(bbb.x_max),
-                y_min: abb.y_min.min(bbb.y_min),
-                y_max: abb.y_max.max(bbb.y_max),
-                z_min: abb.z_min.min(bbb.z_min),
-                z_max: abb.z_max.max(bbb.z_max),
+            BooleanOp::Intersection | BooleanOp::Union | BooleanOp::SmoothUnion(_) => AABB {
+                x_min: abb.x_min.min(bbb.x_min) - grow,
+                x_max: abb.x_max.max(bbb.x_max) + grow,
+                y_min: abb.y_min.min(bbb.y_min) - grow,
+                y_max: abb.y_max.max(bbb.y_max) + grow,
+                z_min: abb.z_min.min(bbb.z_min) - grow,
+                z_max: abb.z_max.max(bbb.z_max) + grow,
             },
-            BooleanOp::Difference => abb,
+            BooleanOp::Difference | BooleanOp::SmoothDifference(_) => abb,
         }
     }
 }
 
 impl Shape for Composite {
     fn dist_fn(&self, point: Vector3<f64>) -> f64 {
-        let a = self.a.dist_fn(point);
-        let b = self.b.dist_fn(point);
-
-        match self.op {
-            BooleanOp::Difference => (a).max(-b),
-            BooleanOp::Intersection => a.max(b),
-            BooleanOp::Union => a.min(b),
-        }
+        self.dist_fn_at(point, 0.5)
     }
 
     fn bounding_box(&self) -> AABB {
         self.bounding_box
     }
+
+    fn dist_fn_at(&self, point: Vector3<f64>, time: f64) -> f64 {
+        let a = self.a.dist_fn_at(point, time);
+        let b = self.b.dist_fn_at(point, time);
+
+        self.op.apply(a, b)
+    }
+
+    fn dist_fn_batch(&self, points: &[Vector3<f64>], time: f64, out: &mut [f64]) {
+        let mut dist_b = [0.0; BATCH_SIZE];
+
+        for (points, out) in points.chunks(BATCH_SIZE).zip(out.chunks_mut(BATCH_SIZE)) {
+            let dist_b = &mut dist_b[..points.len()];
+
+            self.a.dist_fn_batch(points, time, out);
+            self.b.dist_fn_batch(points, time, dist_b);
+
+            for (a, b) in out.iter_mut().zip(dist_b.iter()) {
+                *a = self.op.apply(*a, *b);
+            }
+        }
+    }
+
+    fn is_moving(&self) -> bool {
+        self.a.is_moving() || self.b.is_moving()
+    }
+
+    fn is_exact(&self) -> bool {
+        self.a.is_exact() && self.b.is_exact()
+    }
 }
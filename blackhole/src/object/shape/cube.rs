@@ -1,6 +1,6 @@
 use super::Shape;
 use crate::object::AABB;
-use cgmath::{Array, Vector3, Zero};
+use cgmath::{Array, ElementWise, Vector2, Vector3, Zero};
 
 pub struct Cube {
     center: Vector3<f64>,
@@ -58,6 +58,22 @@ impl Shape for Cube {
     fn bounding_box(&self) -> AABB {
         self.bounding_box
     }
+
+    /// Whole image on every face, upright on the side faces.
+    fn uv_at(&self, point: Vector3<f64>, _time: f64) -> Option<Vector2<f64>> {
+        let local = (point - self.center).div_element_wise(self.scales);
+        let abs = local.map(f64::abs);
+
+        let (u, v) = if abs.x >= abs.y && abs.x >= abs.z {
+            (-local.z * local.x.signum(), -local.y)
+        } else if abs.y >= abs.z {
+            (local.x, local.z * local.y.signum())
+        } else {
+            (local.x * local.z.signum(), -local.y)
+        };
+
+        Some(Vector2::new(u + 0.5, v + 0.5))
+    }
 }
 
 impl Default for Cube {
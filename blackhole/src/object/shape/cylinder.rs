@@ -1,11 +1,14 @@
 use super::Shape;
 use crate::object::AABB;
-use cgmath::{MetricSpace, Vector3, Zero};
+use cgmath::{InnerSpace, Matrix3, Quaternion, SquareMatrix, Vector3, Zero};
 
 pub struct Cylinder {
     center: Vector3<f64>,
     radius: f64,
     height: f64,
+    axis: Vector3<f64>,
+    /// Rotates the cylinder axis onto Y
+    rotation: Matrix3<f64>,
     bounding_box: AABB,
 }
 
@@ -15,6 +18,8 @@ impl Cylinder {
             center: Vector3::zero(),
             radius: 1.0,
             height: 1.0,
+            axis: Vector3::unit_y(),
+            rotation: Matrix3::identity(),
             bounding_box: AABB::new(),
         };
 
@@ -45,33 +50,54 @@ impl Cylinder {
         self.compute_bb();
     }
 
+    /// Sets direction of the cylinder axis, Y by default.
+    pub fn set_axis(&mut self, axis: Vector3<f64>) {
+        if axis.magnitude2() == 0.0 {
+            panic!("Cylinder axis must not be zero");
+        }
+
+        self.axis = axis.normalize();
+        self.rotation = Matrix3::from(Quaternion::from_arc(self.axis, Vector3::unit_y(), None));
+        self.compute_bb();
+    }
+
     fn compute_bb(&mut self) {
+        // extent of the caps and the axis along each world axis
+        let extent = |a: f64| self.radius * (1.0 - a * a).max(0.0).sqrt() + self.height * a.abs();
+
+        let x = extent(self.axis.x);
+        let y = extent(self.axis.y);
+        let z = extent(self.axis.z);
+
         self.bounding_box = AABB {
-            x_min: self.center.x - self.radius - 0.00,
-            x_max: self.center.x + self.radius + 0.00,
-            y_min: self.center.y - self.height - 0.00,
-            y_max: self.center.y + self.height + 0.00,
-            z_min: self.center.z - self.radius - 0.00,
-            z_max: self.center.z + self.radius + 0.00,
+            x_min: self.center.x - x,
+            x_max: self.center.x + x,
+            y_min: self.center.y - y,
+            y_max: self.center.y + y,
+            z_min: self.center.z - z,
+            z_max: self.center.z + z,
         };
     }
 }
 
 impl Shape for Cylinder {
     fn dist_fn(&self, point: Vector3<f64>) -> f64 {
-        let relative_point = point - self.center;
+        let relative_point = self.rotation * (point - self.center);
 
         if relative_point.y.abs() >= self.height {
-            if relative_point.xz().distance2(self.center.xz()) <= self.radius.powi(2) {
+            if relative_point.xz().magnitude2() <= self.radius.powi(2) {
                 relative_point.y.abs() - self.height
             } else {
-                let dist_to_center = relative_point.xz().distance(self.center.xz()) - self.radius;
+                let dist_to_center = relative_point.xz().magnitude() - self.radius;
                 let dist_to_side = relative_point.y.abs() - self.height;
 
                 (dist_to_center * dist_to_center + dist_to_side * dist_to_side).sqrt()
             }
         } else {
-            relative_point.xz().distance2(self.center.xz()) - self.radius.powi(2)
+            let dist_to_center = relative_point.xz().magnitude() - self.radius;
+            let dist_to_side = relative_point.y.abs() - self.height;
+
+            dist_to_center.max(dist_to_side)
         }
     }
 
@@ -85,3 +111,18 @@ impl Default for Cylinder {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_beside_side_are_exact() {
+        let mut cylinder = Cylinder::new();
+        cylinder.set_radius(2.0);
+
+        assert_eq!(cylinder.dist_fn(Vector3::new(5.0, 0.5, 0.0)), 3.0);
+        assert_eq!(cylinder.dist_fn(Vector3::new(0.0, 0.5, 0.0)), -0.5);
+        assert_eq!(cylinder.dist_fn(Vector3::new(0.0, 0.0, 1.5)), -0.5);
+    }
+}
@@ -0,0 +1,223 @@
+use super::{Shape, BATCH_SIZE};
+use crate::cache::{self, Cacheable};
+use crate::object::AABB;
+use cgmath::{InnerSpace, Vector3};
+use std::io::{Read, Result, Write};
+
+/// Cells of empty space kept around the baked shape, so the grid boundary is outside of it.
+const MARGIN: usize = 2;
+
+/// Distance field sampled on a regular grid, looked up with trilinear interpolation.
+///
+/// Usually created by [`Shape::bake`]. Lookups cost the same for any shape, but thin features smaller
+/// than a cell get rounded off.
+pub struct GridShape {
+    /// Position of the first sample
+    origin: Vector3<f64>,
+    cell: f64,
+    /// Samples along each axis
+    dims: [usize; 3],
+    /// Samples ordered by x, then y, then z
+    values: Vec<f32>,
+    bounding_box: AABB,
+}
+
+impl GridShape {
+    /// Samples the shape with `resolution` cells along the longest side of its bounding box.
+    pub fn new(shape: &(impl Shape + ?Sized), resolution: usize) -> Self {
+        let bb = shape.bounding_box();
+        let (origin, cell, dims) = Self::layout(bb, resolution);
+
+        Self {
+            origin,
+            cell,
+            dims,
+            values: Self::sample(shape, origin, cell, dims),
+            bounding_box: bb,
+        }
+    }
+
+    /// Same as [`GridShape::new`], with samples stored in the disk cache.
+    ///
+    /// Shapes can't be hashed, so `key` has to tell them apart, like a hash of the scene
+    /// description the shape was built from. Resolution and bounds are added to it.
+    pub fn cached(shape: &(impl Shape + ?Sized), resolution: usize, key: u64) -> Self {
+        let bb = shape.bounding_box();
+        let (origin, cell, dims) = Self::layout(bb, resolution);
+
+        let bounds = [bb.x_min, bb.x_max, bb.y_min, bb.y_max, bb.z_min, bb.z_max].map(f64::to_bits);
+        let key = cache::content_hash(&(key, bounds, resolution));
+
+        let values = cache::load_or_create("sdf_grid", key, || {
+            GridValues(Self::sample(shape, origin, cell, dims))
+        })
+        .0;
+
+        Self {
+            origin,
+            cell,
+            dims,
+            values,
+            bounding_box: bb,
+        }
+    }
+
+    /// Origin, cell size and sample counts of a grid over the bounds.
+    fn layout(bb: AABB, resolution: usize) -> (Vector3<f64>, f64, [usize; 3]) {
+        let resolution = resolution.max(1);
+
+        let size = Vector3::new(
+            bb.x_max - bb.x_min,
+            bb.y_max - bb.y_min,
+            bb.z_max - bb.z_min,
+        );
+        let cell = size.x.max(size.y).max(size.z).max(1e-9) / resolution as f64;
+
+        let dims = [size.x, size.y, size.z].map(|s| (s / cell).ceil() as usize + 1 + 2 * MARGIN);
+        let origin = Vector3::new(bb.x_min, bb.y_min, bb.z_min)
+            - Vector3::new(1.0, 1.0, 1.0) * (MARGIN as f64 * cell);
+
+        (origin, cell, dims)
+    }
+
+    fn sample(
+        shape: &(impl Shape + ?Sized),
+        origin: Vector3<f64>,
+        cell: f64,
+        dims: [usize; 3],
+    ) -> Vec<f32> {
+        let mut values = Vec::with_capacity(dims[0] * dims[1] * dims[2]);
+        let mut points = [Vector3::new(0.0, 0.0, 0.0); BATCH_SIZE];
+        let mut dist = [0.0; BATCH_SIZE];
+
+        for z in 0..dims[2] {
+            for y in 0..dims[1] {
+                for start in (0..dims[0]).step_by(BATCH_SIZE) {
+                    let len = (dims[0] - start).min(BATCH_SIZE);
+
+                    for (i, point) in points[..len].iter_mut().enumerate() {
+                        let index = Vector3::new(start + i, y, z).map(|i| i as f64);
+                        *point = origin + index * cell;
+                    }
+
+                    shape.dist_fn_batch(&points[..len], 0.0, &mut dist[..len]);
+                    values.extend(dist[..len].iter().map(|d| *d as f32));
+                }
+            }
+        }
+
+        values
+    }
+
+    /// Size of the grid in bytes.
+    pub fn memory_size(&self) -> usize {
+        self.values.len() * std::mem::size_of::<f32>()
+    }
+
+    fn value(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.values[x + self.dims[0] * (y + self.dims[1] * z)] as f64
+    }
+}
+
+impl Shape for GridShape {
+    fn dist_fn(&self, point: Vector3<f64>) -> f64 {
+        let local = (point - self.origin) / self.cell;
+
+        let mut index = [0; 3];
+        let mut t = [0.0; 3];
+        let mut clamped = [0.0; 3];
+
+        for a in 0..3 {
+            let last = (self.dims[a] - 1) as f64;
+            clamped[a] = local[a].clamp(0.0, last);
+
+            let i = clamped[a].floor().min(last - 1.0);
+            index[a] = i as usize;
+            t[a] = clamped[a] - i;
+        }
+
+        let [x, y, z] = index;
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+
+        let along_x = |y, z| lerp(self.value(x, y, z), self.value(x + 1, y, z), t[0]);
+        let along_y = |z| lerp(along_x(y, z), along_x(y + 1, z), t[1]);
+        let inside = lerp(along_y(z), along_y(z + 1), t[2]);
+
+        // both the distance to the grid and the distance at its boundary never overestimate
+        let outside = (local - Vector3::from(clamped)).magnitude() * self.cell;
+
+        if outside > 0.0 {
+            inside.max(outside)
+        } else {
+            inside
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bounding_box
+    }
+}
+
+/// Grid samples in the disk cache.
+struct GridValues(Vec<f32>);
+
+impl Cacheable for GridValues {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        (self.0.len() as u64).write_to(w)?;
+
+        for value in &self.0 {
+            w.write_all(&value.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn read_from(r: &mut dyn Read) -> Result<Self> {
+        let len = u64::read_from(r)? as usize;
+        let mut bytes = Vec::new();
+        r.take(len as u64 * 4).read_to_end(&mut bytes)?;
+
+        if bytes.len() != len * 4 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+
+        Ok(Self(
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::shape::Sphere;
+
+    #[test]
+    fn baked_sphere_keeps_distances() {
+        let sphere = Sphere::new();
+        let grid = sphere.bake(32);
+        let cell = 2.0 / 32.0;
+
+        for point in [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.3, -0.7, 0.5),
+            Vector3::new(1.01, 0.0, 0.0),
+        ] {
+            let exact = sphere.dist_fn(point);
+            let baked = grid.dist_fn(point);
+
+            assert!(
+                (exact - baked).abs() < cell,
+                "{point:?}: {exact} vs {baked}"
+            );
+        }
+
+        // outside of the grid distances are only underestimated
+        let far = Vector3::new(0.0, 5.0, 0.0);
+        assert!(grid.dist_fn(far) <= sphere.dist_fn(far));
+        assert!(grid.dist_fn(far) > sphere.dist_fn(far) - 4.0 * cell);
+    }
+}
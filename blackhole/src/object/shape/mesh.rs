@@ -0,0 +1,433 @@
+use super::Shape;
+use crate::object::AABB;
+use cgmath::{InnerSpace, Vector3, Zero};
+use std::collections::HashMap;
+use std::ops::Range;
+
+mod file;
+
+pub use file::read_triangles;
+
+/// Triangles in one leaf of the bounding volume hierarchy.
+const LEAF_SIZE: usize = 4;
+
+/// Nodes waiting to be visited when searching the hierarchy. Nodes are split in halves, so
+/// depth of the hierarchy and the stack grows with the logarithm of the triangle count.
+const STACK_SIZE: usize = 64;
+
+/// Closed triangle mesh, with distances found through a bounding volume hierarchy.
+///
+/// Sign of the distance comes from angle weighted pseudo-normals of the nearest face, edge or
+/// vertex, so the mesh should be closed and consistently wound with faces pointing out. Vertices
+/// at the same position are merged, so separate faces of STL files share edges.
+pub struct MeshShape {
+    vertices: Vec<Vector3<f64>>,
+    vertex_normals: Vec<Vector3<f64>>,
+    triangles: Vec<Triangle>,
+    nodes: Vec<Node>,
+}
+
+struct Triangle {
+    indices: [usize; 3],
+    normal: Vector3<f64>,
+    /// Pseudo-normals of edges from vertex `i` to vertex `i + 1`
+    edge_normals: [Vector3<f64>; 3],
+}
+
+struct Node {
+    bounds: AABB,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Leaf(Range<usize>),
+    /// Indices of child nodes
+    Inner(usize, usize),
+}
+
+/// Part of a triangle nearest to a point.
+#[derive(Copy, Clone)]
+enum Feature {
+    Face,
+    /// Edge starting at the vertex with given index within the triangle
+    Edge(usize),
+    Vertex(usize),
+}
+
+struct Nearest {
+    distance: f64,
+    point: Vector3<f64>,
+    triangle: usize,
+    feature: Feature,
+}
+
+impl MeshShape {
+    /// Creates mesh from triangles with counter-clockwise winding seen from outside.
+    ///
+    /// Triangles without area are skipped.
+    pub fn new(triangles: &[[Vector3<f64>; 3]]) -> Self {
+        let mut vertices = Vec::new();
+        let mut welded = HashMap::new();
+
+        let mut index_of = |v: Vector3<f64>| {
+            *welded
+                .entry([v.x.to_bits(), v.y.to_bits(), v.z.to_bits()])
+                .or_insert_with(|| {
+                    vertices.push(v);
+                    vertices.len() - 1
+                })
+        };
+
+        let faces = triangles
+            .iter()
+            .filter_map(|[a, b, c]| {
+                let normal = (b - a).cross(c - a);
+
+                (normal.magnitude2() > 0.0).then(|| {
+                    (
+                        [index_of(*a), index_of(*b), index_of(*c)],
+                        normal.normalize(),
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut vertex_normals = vec![Vector3::zero(); vertices.len()];
+        let mut edges: HashMap<(usize, usize), Vector3<f64>> = HashMap::new();
+
+        for (indices, normal) in &faces {
+            for i in 0..3 {
+                let v = indices[i];
+                let next = indices[(i + 1) % 3];
+                let prev = indices[(i + 2) % 3];
+
+                let to_next = (vertices[next] - vertices[v]).normalize();
+                let to_prev = (vertices[prev] - vertices[v]).normalize();
+                let angle = to_next.dot(to_prev).clamp(-1.0, 1.0).acos();
+
+                vertex_normals[v] += normal * angle;
+                *edges
+                    .entry((v.min(next), v.max(next)))
+                    .or_insert(Vector3::zero()) += *normal;
+            }
+        }
+
+        let triangles = faces
+            .into_iter()
+            .map(|(indices, normal)| {
+                let edge_normals = [0, 1, 2].map(|i| {
+                    let (a, b) = (indices[i], indices[(i + 1) % 3]);
+                    edges[&(a.min(b), a.max(b))]
+                });
+
+                Triangle {
+                    indices,
+                    normal,
+                    edge_normals,
+                }
+            })
+            .collect();
+
+        let mut mesh = Self {
+            vertices,
+            vertex_normals,
+            triangles,
+            nodes: Vec::new(),
+        };
+        mesh.build_hierarchy();
+
+        mesh
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn corners(&self, triangle: &Triangle) -> [Vector3<f64>; 3] {
+        triangle.indices.map(|i| self.vertices[i])
+    }
+
+    fn build_hierarchy(&mut self) {
+        let mut order = (0..self.triangles.len()).collect::<Vec<_>>();
+        let centroids = self
+            .triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = self.corners(t);
+                (a + b + c) / 3.0
+            })
+            .collect::<Vec<_>>();
+
+        let mut nodes = Vec::new();
+        self.build_node(&mut nodes, &mut order, 0, &centroids);
+
+        let mut triangles = std::mem::take(&mut self.triangles)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.triangles = order
+            .iter()
+            .map(|&i| triangles[i].take().unwrap())
+            .collect();
+        self.nodes = nodes;
+    }
+
+    /// Adds node for triangles in `order`, which start at `offset` in the final order.
+    fn build_node(
+        &self,
+        nodes: &mut Vec<Node>,
+        order: &mut [usize],
+        offset: usize,
+        centroids: &[Vector3<f64>],
+    ) -> usize {
+        let bounds = AABB::around(order.iter().flat_map(|&i| self.corners(&self.triangles[i])));
+
+        let index = nodes.len();
+        nodes.push(Node {
+            bounds,
+            kind: NodeKind::Leaf(offset..offset + order.len()),
+        });
+
+        if order.len() <= LEAF_SIZE {
+            return index;
+        }
+
+        // split in the middle of the longest axis of centroids
+        let spread = AABB::around(order.iter().map(|&i| centroids[i]));
+        let extent = [
+            spread.x_max - spread.x_min,
+            spread.y_max - spread.y_min,
+            spread.z_max - spread.z_min,
+        ];
+        let axis = (0..3).fold(0, |best, a| if extent[a] > extent[best] { a } else { best });
+
+        let half = order.len() / 2;
+        order.select_nth_unstable_by(half, |&a, &b| {
+            centroids[a][axis].total_cmp(&centroids[b][axis])
+        });
+
+        let (left, right) = order.split_at_mut(half);
+        let left = self.build_node(nodes, left, offset, centroids);
+        let right = self.build_node(nodes, right, offset + half, centroids);
+
+        nodes[index].kind = NodeKind::Inner(left, right);
+
+        index
+    }
+
+    fn nearest(&self, point: Vector3<f64>) -> Option<Nearest> {
+        let mut best: Option<Nearest> = None;
+        let mut stack = [0; STACK_SIZE];
+        let mut len = 1;
+
+        while len > 0 {
+            len -= 1;
+
+            let node = match self.nodes.get(stack[len]) {
+                Some(node) => node,
+                None => break,
+            };
+
+            let limit = best.as_ref().map_or(f64::MAX, |b| b.distance);
+            if node.bounds.distance(point) >= limit {
+                continue;
+            }
+
+            match &node.kind {
+                NodeKind::Leaf(range) => {
+                    for triangle in range.clone() {
+                        let [a, b, c] = self.corners(&self.triangles[triangle]);
+                        let (nearest, feature) = closest_on_triangle(point, a, b, c);
+                        let distance = (point - nearest).magnitude();
+
+                        if best.as_ref().is_none_or(|b| distance < b.distance) {
+                            best = Some(Nearest {
+                                distance,
+                                point: nearest,
+                                triangle,
+                                feature,
+                            });
+                        }
+                    }
+                }
+                NodeKind::Inner(left, right) => {
+                    let left_dist = self.nodes[*left].bounds.distance(point);
+                    let right_dist = self.nodes[*right].bounds.distance(point);
+
+                    // visit the closer child first, it's popped last
+                    let children = if left_dist < right_dist {
+                        [*right, *left]
+                    } else {
+                        [*left, *right]
+                    };
+
+                    stack[len..len + 2].copy_from_slice(&children);
+                    len += 2;
+                }
+            }
+        }
+
+        best
+    }
+
+    /// Outward pseudo-normal of the nearest feature, not normalized.
+    fn pseudo_normal(&self, nearest: &Nearest) -> Vector3<f64> {
+        let triangle = &self.triangles[nearest.triangle];
+
+        match nearest.feature {
+            Feature::Face => triangle.normal,
+            Feature::Edge(i) => triangle.edge_normals[i],
+            Feature::Vertex(i) => self.vertex_normals[triangle.indices[i]],
+        }
+    }
+}
+
+impl Shape for MeshShape {
+    fn dist_fn(&self, point: Vector3<f64>) -> f64 {
+        let nearest = match self.nearest(point) {
+            Some(nearest) => nearest,
+            None => return f64::MAX,
+        };
+
+        if (point - nearest.point).dot(self.pseudo_normal(&nearest)) < 0.0 {
+            -nearest.distance
+        } else {
+            nearest.distance
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        match self.nodes.first() {
+            Some(root) => root.bounds,
+            None => AABB::around([Vector3::zero()]),
+        }
+    }
+
+    fn normal_at(&self, position: Vector3<f64>, _epsilon: f64, _time: f64) -> Vector3<f64> {
+        match self.nearest(position) {
+            Some(nearest) => self.pseudo_normal(&nearest).normalize(),
+            None => Vector3::unit_y(),
+        }
+    }
+}
+
+/// Nearest point of the triangle and the part of it the point lies on.
+///
+/// Follows "Real-Time Collision Detection" by Christer Ericson.
+fn closest_on_triangle(
+    p: Vector3<f64>,
+    a: Vector3<f64>,
+    b: Vector3<f64>,
+    c: Vector3<f64>,
+) -> (Vector3<f64>, Feature) {
+    let ab = b - a;
+    let ac = c - a;
+
+    let ap = p - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return (a, Feature::Vertex(0));
+    }
+
+    let bp = p - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return (b, Feature::Vertex(1));
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return (a + ab * v, Feature::Edge(0));
+    }
+
+    let cp = p - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return (c, Feature::Vertex(2));
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return (a + ac * w, Feature::Edge(2));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return (b + (c - b) * w, Feature::Edge(1));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    let v = vb * denom;
+    let w = vc * denom;
+
+    (a + ab * v + ac * w, Feature::Face)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::Array;
+
+    /// Unit cube centered at the origin, faces pointing out.
+    fn cube() -> MeshShape {
+        let corner = |i: usize| {
+            Vector3::new(
+                (i & 1) as f64 - 0.5,
+                ((i >> 1) & 1) as f64 - 0.5,
+                ((i >> 2) & 1) as f64 - 0.5,
+            )
+        };
+
+        let quads = [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ];
+
+        let triangles = quads
+            .iter()
+            .flat_map(|q| {
+                [
+                    [corner(q[0]), corner(q[1]), corner(q[2])],
+                    [corner(q[0]), corner(q[2]), corner(q[3])],
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        MeshShape::new(&triangles)
+    }
+
+    #[test]
+    fn cube_mesh_matches_box_distance() {
+        let mesh = cube();
+        assert_eq!(mesh.triangle_count(), 12);
+
+        let points = [
+            (Vector3::new(0.0, 0.0, 0.0), -0.5),
+            (Vector3::new(0.2, 0.1, -0.3), -0.2),
+            (Vector3::new(2.0, 0.0, 0.0), 1.5),
+            // nearest to an edge and a corner, where face normals disagree
+            (Vector3::new(1.5, 1.5, 0.0), 2.0f64.sqrt()),
+            (Vector3::from_value(1.5), 3.0f64.sqrt()),
+            (Vector3::new(0.49, 0.49, 0.49), -0.01),
+        ];
+
+        for (point, expected) in points {
+            let dist = mesh.dist_fn(point);
+            assert!((dist - expected).abs() < 1e-9, "{point:?}: {dist}");
+        }
+
+        let normal = mesh.normal_at(Vector3::new(0.0, 0.7, 0.1), 1e-5, 0.5);
+        assert!((normal - Vector3::unit_y()).magnitude() < 1e-9);
+    }
+}
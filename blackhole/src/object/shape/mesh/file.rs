@@ -0,0 +1,202 @@
+use crate::cache;
+use cgmath::Vector3;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+/// Reads triangles of an OBJ or STL file, chosen by the file extension.
+///
+/// OBJ polygons are split into fans of triangles, STL files can be binary or ASCII. Parsed
+/// triangles are kept in the disk cache, one entry per file path, see [`crate::cache`].
+pub fn read_triangles<P: AsRef<Path>>(path: P) -> Result<Vec<[Vector3<f64>; 3]>> {
+    let path = path.as_ref();
+    let data = std::fs::read(path)?;
+
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+
+    let slot = cache::content_hash(&path.canonicalize().unwrap_or_else(|_| path.to_owned()));
+    let key = cache::content_hash(&(extension.as_deref(), data.as_slice()));
+
+    cache::try_load_or_replace("mesh", slot, key, || match extension.as_deref() {
+        Some("obj") => parse_obj(&String::from_utf8_lossy(&data)),
+        Some("stl") => parse_stl(&data),
+        _ => Err(invalid("unknown mesh format, expected .obj or .stl")),
+    })
+}
+
+fn parse_obj(text: &str) -> Result<Vec<[Vector3<f64>; 3]>> {
+    let mut vertices = Vec::new();
+    let mut triangles = Vec::new();
+
+    for line in text.lines() {
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("v") => vertices.push(parse_vec3(&mut tokens)?),
+            Some("f") => {
+                let corners = tokens
+                    .map(|t| obj_index(t, vertices.len()).map(|i| vertices[i]))
+                    .collect::<Result<Vec<_>>>()?;
+
+                if corners.len() < 3 {
+                    return Err(invalid("face with less than 3 vertices"));
+                }
+
+                for i in 1..corners.len() - 1 {
+                    triangles.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(triangles)
+}
+
+/// Index of a vertex in `v/vt/vn` face item, negative indices count from the last vertex.
+fn obj_index(item: &str, count: usize) -> Result<usize> {
+    let index = item
+        .split('/')
+        .next()
+        .and_then(|i| i.parse::<i64>().ok())
+        .ok_or_else(|| invalid("invalid face vertex"))?;
+
+    let index = match index {
+        i if i > 0 => i - 1,
+        i => count as i64 + i,
+    };
+
+    usize::try_from(index)
+        .ok()
+        .filter(|i| *i < count)
+        .ok_or_else(|| invalid("face vertex index out of range"))
+}
+
+fn parse_stl(data: &[u8]) -> Result<Vec<[Vector3<f64>; 3]>> {
+    // binary files may start with `solid` too, their size gives them away
+    let binary_size = data
+        .get(80..84)
+        .map(|count| 84 + 50 * u32::from_le_bytes(count.try_into().unwrap()) as usize);
+
+    if binary_size == Some(data.len()) || !data.starts_with(b"solid") {
+        return parse_binary_stl(data);
+    }
+
+    let text = String::from_utf8_lossy(data);
+    let mut corners = Vec::new();
+
+    for line in text.lines() {
+        let mut tokens = line.split_whitespace();
+
+        if tokens.next() == Some("vertex") {
+            corners.push(parse_vec3(&mut tokens)?);
+        }
+    }
+
+    if corners.len() % 3 != 0 {
+        return Err(invalid("facet without 3 vertices"));
+    }
+
+    Ok(corners.chunks(3).map(|c| [c[0], c[1], c[2]]).collect())
+}
+
+fn parse_binary_stl(data: &[u8]) -> Result<Vec<[Vector3<f64>; 3]>> {
+    let count = data
+        .get(80..84)
+        .map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize)
+        .ok_or_else(|| invalid("truncated STL header"))?;
+
+    let facets = data
+        .get(84..84 + count * 50)
+        .ok_or_else(|| invalid("truncated STL file"))?;
+
+    let float = |bytes: &[u8], i: usize| {
+        f32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()) as f64
+    };
+
+    Ok(facets
+        .chunks(50)
+        .map(|facet| {
+            // skip the normal, it's computed from the winding
+            let vertex = |v: usize| {
+                let i = 3 + v * 3;
+                Vector3::new(float(facet, i), float(facet, i + 1), float(facet, i + 2))
+            };
+
+            [vertex(0), vertex(1), vertex(2)]
+        })
+        .collect())
+}
+
+fn parse_vec3<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<Vector3<f64>> {
+    let mut values = [0.0; 3];
+
+    for value in &mut values {
+        *value = tokens
+            .next()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| invalid("invalid vertex coordinates"))?;
+    }
+
+    Ok(Vector3::from(values))
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn obj_polygons_become_fans() {
+        let obj = "# quad and triangle\n\
+            v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+            f 1/1/1 2/2/1 3/3/1 4/4/1\n\
+            f -4 -3 -1\n";
+
+        let triangles = parse_obj(obj).unwrap();
+        let v = |x, y| Vector3::new(x, y, 0.0);
+
+        assert_eq!(
+            triangles,
+            [
+                [v(0.0, 0.0), v(1.0, 0.0), v(1.0, 1.0)],
+                [v(0.0, 0.0), v(1.0, 1.0), v(0.0, 1.0)],
+                [v(0.0, 0.0), v(1.0, 0.0), v(0.0, 1.0)],
+            ]
+        );
+
+        assert!(parse_obj("v 0 0 0\nf 1 2 3\n").is_err());
+        assert!(parse_obj("v 0 0\n").is_err());
+    }
+
+    #[test]
+    fn ascii_and_binary_stl_match() {
+        let ascii = b"solid tri\nfacet normal 0 0 1\nouter loop\n\
+            vertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\n\
+            endloop\nendfacet\nendsolid tri\n";
+
+        // binary header starting with `solid` too, recognized by its size
+        let mut binary = b"solid binary".to_vec();
+        binary.resize(80, 0);
+        binary.extend(1_u32.to_le_bytes());
+        for value in [
+            0.0_f32, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+        ] {
+            binary.extend(value.to_le_bytes());
+        }
+        binary.extend([0, 0]);
+
+        let triangles = parse_stl(ascii).unwrap();
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(parse_stl(&binary).unwrap(), triangles);
+
+        // truncated binary files starting with `solid` would be read as ASCII
+        let mut truncated = binary[..100].to_vec();
+        truncated[..5].fill(0);
+        assert!(parse_stl(&truncated).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use super::{Shape, BATCH_SIZE};
+use crate::object::AABB;
+use cgmath::{InnerSpace, Vector2, Vector3, Zero};
+use std::sync::Arc;
+
+/// Shape moving along a straight line while the camera shutter is open.
+///
+/// The wrapped shape is at its own position in the middle of the shutter interval.
+pub struct Moving {
+    shape: Arc<dyn Shape>,
+    motion: Vector3<f64>,
+    bounding_box: AABB,
+}
+
+impl Moving {
+    /// Creates shape travelling `motion` during the whole shutter interval.
+    pub fn new(shape: Arc<dyn Shape>, motion: Vector3<f64>) -> Self {
+        let bb = shape.bounding_box();
+        let half = motion / 2.0;
+
+        let bounding_box = AABB {
+            x_min: bb.x_min - half.x.abs(),
+            x_max: bb.x_max + half.x.abs(),
+            y_min: bb.y_min - half.y.abs(),
+            y_max: bb.y_max + half.y.abs(),
+            z_min: bb.z_min - half.z.abs(),
+            z_max: bb.z_max + half.z.abs(),
+        };
+
+        Self {
+            shape,
+            motion,
+            bounding_box,
+        }
+    }
+
+    fn offset(&self, time: f64) -> Vector3<f64> {
+        self.motion * (time - 0.5)
+    }
+}
+
+impl Shape for Moving {
+    fn dist_fn(&self, point: Vector3<f64>) -> f64 {
+        self.shape.dist_fn(point)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bounding_box
+    }
+
+    fn dist_fn_at(&self, point: Vector3<f64>, time: f64) -> f64 {
+        self.shape.dist_fn_at(point - self.offset(time), time)
+    }
+
+    fn dist_fn_batch(&self, points: &[Vector3<f64>], time: f64, out: &mut [f64]) {
+        let offset = self.offset(time);
+        let mut shifted = [Vector3::zero(); BATCH_SIZE];
+
+        for (points, out) in points.chunks(BATCH_SIZE).zip(out.chunks_mut(BATCH_SIZE)) {
+            let shifted = &mut shifted[..points.len()];
+
+            for (shifted, point) in shifted.iter_mut().zip(points) {
+                *shifted = point - offset;
+            }
+
+            self.shape.dist_fn_batch(shifted, time, out);
+        }
+    }
+
+    fn uv_at(&self, point: Vector3<f64>, time: f64) -> Option<Vector2<f64>> {
+        self.shape.uv_at(point - self.offset(time), time)
+    }
+
+    fn is_moving(&self) -> bool {
+        self.motion.magnitude2() > 0.0 || self.shape.is_moving()
+    }
+
+    fn is_exact(&self) -> bool {
+        self.shape.is_exact()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::shape::Sphere;
+
+    fn sphere_at(center: Vector3<f64>) -> Sphere {
+        let mut sphere = Sphere::new();
+        sphere.set_center(center);
+        sphere
+    }
+
+    #[test]
+    fn shape_is_shifted_by_motion() {
+        let motion = Vector3::new(2.0, 0.0, -1.0);
+        let shape = Moving::new(Arc::new(Sphere::new()), motion);
+        let points = [
+            Vector3::new(0.5, 0.0, 0.0),
+            Vector3::new(-1.5, 0.3, 0.2),
+            Vector3::new(1.0, -0.4, -1.0),
+        ];
+
+        assert!(shape.is_moving());
+
+        for time in [0.0, 1.0] {
+            let shifted = sphere_at(motion * (time - 0.5));
+
+            for point in points {
+                let expected = shifted.dist_fn_at(point, time);
+                assert!((shape.dist_fn_at(point, time) - expected).abs() < 1e-12);
+
+                let expected = shifted.normal_at(point, 0.0001, time);
+                assert!((shape.normal_at(point, 0.0001, time) - expected).magnitude() < 1e-9);
+            }
+
+            let bb = shape.bounding_box();
+            let shifted = shifted.bounding_box();
+            assert!(bb.x_min <= shifted.x_min && shifted.x_max <= bb.x_max);
+            assert!(bb.y_min <= shifted.y_min && shifted.y_max <= bb.y_max);
+            assert!(bb.z_min <= shifted.z_min && shifted.z_max <= bb.z_max);
+        }
+
+        let bb = shape.bounding_box();
+        assert_eq!((bb.x_min, bb.x_max), (-2.0, 2.0));
+        assert_eq!((bb.z_min, bb.z_max), (-1.5, 1.5));
+    }
+}
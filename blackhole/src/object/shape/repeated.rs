@@ -0,0 +1,96 @@
+use super::{Shape, BATCH_SIZE};
+use crate::object::AABB;
+use cgmath::{ElementWise, Vector3, Zero};
+use std::sync::Arc;
+
+/// Shape copied on a regular 3D lattice, evaluated as a single shape.
+///
+/// Copies are placed `cell` apart around the original, the lattice is limited so rays can still
+/// escape the scene. Distances are exact only when the original fits inside one cell.
+pub struct Repeated {
+    shape: Arc<dyn Shape>,
+    origin: Vector3<f64>,
+    cell: Vector3<f64>,
+    copies: Vector3<f64>,
+    bounding_box: AABB,
+}
+
+impl Repeated {
+    /// Creates lattice with `copies` more shapes on each side of the original along each axis.
+    pub fn new(shape: Arc<dyn Shape>, cell: Vector3<f64>, copies: [u32; 3]) -> Self {
+        let bb = shape.bounding_box();
+        let copies = Vector3::new(copies[0] as f64, copies[1] as f64, copies[2] as f64);
+        let extent = cell.mul_element_wise(copies);
+
+        let bounding_box = AABB {
+            x_min: bb.x_min - extent.x,
+            x_max: bb.x_max + extent.x,
+            y_min: bb.y_min - extent.y,
+            y_max: bb.y_max + extent.y,
+            z_min: bb.z_min - extent.z,
+            z_max: bb.z_max + extent.z,
+        };
+
+        Self {
+            shape,
+            origin: bb.center(),
+            cell,
+            copies,
+            bounding_box,
+        }
+    }
+
+    /// Point moved into the cell of the original shape.
+    fn to_cell(&self, point: Vector3<f64>) -> Vector3<f64> {
+        let index = (point - self.origin).div_element_wise(self.cell);
+        let index = Vector3::new(
+            index.x.round().clamp(-self.copies.x, self.copies.x),
+            index.y.round().clamp(-self.copies.y, self.copies.y),
+            index.z.round().clamp(-self.copies.z, self.copies.z),
+        );
+
+        point - index.mul_element_wise(self.cell)
+    }
+}
+
+impl Shape for Repeated {
+    fn dist_fn(&self, point: Vector3<f64>) -> f64 {
+        self.shape.dist_fn(self.to_cell(point))
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bounding_box
+    }
+
+    fn dist_fn_at(&self, point: Vector3<f64>, time: f64) -> f64 {
+        self.shape.dist_fn_at(self.to_cell(point), time)
+    }
+
+    fn dist_fn_batch(&self, points: &[Vector3<f64>], time: f64, out: &mut [f64]) {
+        let mut local = [Vector3::zero(); BATCH_SIZE];
+
+        for (points, out) in points.chunks(BATCH_SIZE).zip(out.chunks_mut(BATCH_SIZE)) {
+            let local = &mut local[..points.len()];
+
+            for (local, point) in local.iter_mut().zip(points) {
+                *local = self.to_cell(*point);
+            }
+
+            self.shape.dist_fn_batch(local, time, out);
+        }
+    }
+
+    fn is_moving(&self) -> bool {
+        self.shape.is_moving()
+    }
+
+    /// Copies larger than the cell reach into neighbouring cells, where they are not evaluated.
+    fn is_exact(&self) -> bool {
+        let bb = self.shape.bounding_box();
+        let fits = bb.x_max - bb.x_min <= self.cell.x
+            && bb.y_max - bb.y_min <= self.cell.y
+            && bb.z_max - bb.z_min <= self.cell.z;
+
+        fits && self.shape.is_exact()
+    }
+}
@@ -0,0 +1,152 @@
+use super::Shape;
+use crate::math::equirect_uv;
+use crate::object::AABB;
+use crate::Ray;
+use cgmath::{InnerSpace, Vector2, Vector3, Zero};
+
+/// Hollow sphere with walls of given thickness, centered on the radius.
+#[derive(Clone)]
+pub struct Shell {
+    center: Vector3<f64>,
+    radius: f64,
+    thickness: f64,
+    bounding_box: AABB,
+}
+
+impl Shell {
+    pub fn new() -> Self {
+        let mut shell = Self {
+            center: Vector3::zero(),
+            radius: 1.0,
+            thickness: 0.1,
+            bounding_box: AABB::new(),
+        };
+
+        shell.compute_bb();
+        shell
+    }
+
+    pub fn set_center(&mut self, center: Vector3<f64>) {
+        self.center = center;
+        self.compute_bb();
+    }
+
+    pub fn set_radius(&mut self, radius: f64) {
+        if radius <= 0.0 {
+            panic!("Shell radius must be positive number, got {}", radius);
+        }
+
+        self.radius = radius;
+        self.compute_bb();
+    }
+
+    pub fn set_thickness(&mut self, thickness: f64) {
+        if thickness <= 0.0 {
+            panic!("Shell thickness must be positive number, got {}", thickness);
+        }
+
+        self.thickness = thickness;
+        self.compute_bb();
+    }
+
+    pub fn center(&self) -> Vector3<f64> {
+        self.center
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn thickness(&self) -> f64 {
+        self.thickness
+    }
+
+    fn outer_radius(&self) -> f64 {
+        self.radius + self.thickness / 2.0
+    }
+
+    fn compute_bb(&mut self) {
+        let r = self.outer_radius();
+
+        self.bounding_box = AABB {
+            x_min: self.center.x - r,
+            x_max: self.center.x + r,
+            y_min: self.center.y - r,
+            y_max: self.center.y + r,
+            z_min: self.center.z - r,
+            z_max: self.center.z + r,
+        };
+    }
+}
+
+impl Shape for Shell {
+    fn dist_fn(&self, point: Vector3<f64>) -> f64 {
+        ((point - self.center).magnitude() - self.radius).abs() - self.thickness / 2.0
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bounding_box
+    }
+
+    fn can_ray_hit(&self, ray: &Ray) -> bool {
+        let l = self.center - ray.location;
+        let tca = l.dot(ray.direction);
+        let d2 = l.dot(l) - tca * tca;
+
+        d2 <= self.outer_radius().powi(2)
+    }
+
+    /// Points away from the middle of the wall, outwards on the outer side.
+    ///
+    /// At the center every direction is as far from the wall, +Y is used there.
+    fn normal(&self, position: Vector3<f64>, _epsilon: f64) -> Vector3<f64> {
+        let relative = position - self.center;
+
+        if relative.magnitude2() == 0.0 {
+            Vector3::unit_y()
+        } else if relative.magnitude() < self.radius {
+            -relative.normalize()
+        } else {
+            relative.normalize()
+        }
+    }
+
+    /// Equirectangular mapping, with the middle of the image facing -Z.
+    fn uv_at(&self, point: Vector3<f64>, _time: f64) -> Option<Vector2<f64>> {
+        let (u, v) = equirect_uv(point - self.center);
+
+        Some(Vector2::new(u, v))
+    }
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normals_point_away_from_wall_middle() {
+        let mut shell = Shell::new();
+        shell.set_center(Vector3::new(1.0, 0.0, 0.0));
+        shell.set_radius(2.0);
+
+        assert_eq!(
+            shell.normal(Vector3::new(4.0, 0.0, 0.0), 0.0),
+            Vector3::unit_x()
+        );
+        assert_eq!(
+            shell.normal(Vector3::new(2.0, 0.0, 0.0), 0.0),
+            -Vector3::unit_x()
+        );
+        assert_eq!(
+            shell.normal(Vector3::new(1.0, 0.0, 0.0), 0.0),
+            Vector3::unit_y()
+        );
+        assert_eq!(shell.dist_fn(Vector3::new(1.0, 2.0, 0.0)), -0.05);
+    }
+}
@@ -1,7 +1,9 @@
 use super::Shape;
+use crate::math::equirect_uv;
 use crate::object::AABB;
+use crate::simd;
 use crate::Ray;
-use cgmath::{InnerSpace, Vector3, Zero};
+use cgmath::{InnerSpace, Vector2, Vector3, Zero};
 
 #[derive(Clone)]
 pub struct Sphere {
@@ -79,6 +81,17 @@ impl Shape for Sphere {
     fn normal(&self, position: Vector3<f64>, _epsilon: f64) -> Vector3<f64> {
         (position - self.center).normalize()
     }
+
+    fn dist_fn_batch(&self, points: &[Vector3<f64>], _time: f64, out: &mut [f64]) {
+        simd::sphere_dist(points, self.center, self.radius, out);
+    }
+
+    /// Equirectangular mapping, with the middle of the image facing -Z.
+    fn uv_at(&self, point: Vector3<f64>, _time: f64) -> Option<Vector2<f64>> {
+        let (u, v) = equirect_uv(point - self.center);
+
+        Some(Vector2::new(u, v))
+    }
 }
 
 impl Default for Sphere {
@@ -0,0 +1,162 @@
+use super::{Shape, BATCH_SIZE};
+use crate::math::euler_rotation;
+use crate::object::AABB;
+use cgmath::{Matrix, Matrix3, Vector2, Vector3, Zero};
+use std::sync::Arc;
+
+/// Shape rotated, uniformly scaled and moved as a whole.
+///
+/// Points are mapped back into the space of the wrapped shape, so any shape can be tilted.
+pub struct Transformed {
+    shape: Arc<dyn Shape>,
+    rotation: Matrix3<f64>,
+    scale: f64,
+    translation: Vector3<f64>,
+    bounding_box: AABB,
+}
+
+impl Transformed {
+    /// Creates shape placed at `rotation * scale * point + translation`.
+    ///
+    /// Panics with scale which isn't a positive finite number, distances couldn't be mapped back.
+    pub fn new(
+        shape: Arc<dyn Shape>,
+        rotation: Matrix3<f64>,
+        scale: f64,
+        translation: Vector3<f64>,
+    ) -> Self {
+        if !(scale > 0.0 && scale.is_finite()) {
+            panic!(
+                "Transformed scale must be positive finite number, got {}",
+                scale
+            );
+        }
+
+        let bb = shape.bounding_box();
+
+        let corners = (0..8).map(|i| {
+            let corner = Vector3::new(
+                if i & 1 == 0 { bb.x_min } else { bb.x_max },
+                if i & 2 == 0 { bb.y_min } else { bb.y_max },
+                if i & 4 == 0 { bb.z_min } else { bb.z_max },
+            );
+
+            rotation * corner * scale + translation
+        });
+        let bounding_box = AABB::around(corners);
+
+        Self {
+            shape,
+            rotation,
+            scale,
+            translation,
+            bounding_box,
+        }
+    }
+
+    /// Rotates by Euler angles in degrees and scales the shape around `pivot`.
+    ///
+    /// Angles are applied the same way as camera rotation.
+    pub fn around(
+        shape: Arc<dyn Shape>,
+        pivot: Vector3<f64>,
+        rotation: Vector3<f64>,
+        scale: f64,
+    ) -> Self {
+        let rotation = euler_rotation(rotation);
+        let translation = pivot - rotation * pivot * scale;
+
+        Self::new(shape, rotation, scale, translation)
+    }
+
+    fn to_local(&self, point: Vector3<f64>) -> Vector3<f64> {
+        // rotation matrices are orthonormal, transpose is the inverse
+        self.rotation.transpose() * (point - self.translation) / self.scale
+    }
+}
+
+impl Shape for Transformed {
+    fn dist_fn(&self, point: Vector3<f64>) -> f64 {
+        self.shape.dist_fn(self.to_local(point)) * self.scale
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bounding_box
+    }
+
+    fn dist_fn_at(&self, point: Vector3<f64>, time: f64) -> f64 {
+        self.shape.dist_fn_at(self.to_local(point), time) * self.scale
+    }
+
+    fn dist_fn_batch(&self, points: &[Vector3<f64>], time: f64, out: &mut [f64]) {
+        let mut local = [Vector3::zero(); BATCH_SIZE];
+
+        for (points, out) in points.chunks(BATCH_SIZE).zip(out.chunks_mut(BATCH_SIZE)) {
+            let local = &mut local[..points.len()];
+
+            for (local, point) in local.iter_mut().zip(points) {
+                *local = self.to_local(*point);
+            }
+
+            self.shape.dist_fn_batch(local, time, out);
+
+            for out in out.iter_mut() {
+                *out *= self.scale;
+            }
+        }
+    }
+
+    fn uv_at(&self, point: Vector3<f64>, time: f64) -> Option<Vector2<f64>> {
+        self.shape.uv_at(self.to_local(point), time)
+    }
+
+    fn is_moving(&self) -> bool {
+        self.shape.is_moving()
+    }
+
+    fn is_exact(&self) -> bool {
+        self.shape.is_exact()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::shape::Sphere;
+    use cgmath::SquareMatrix;
+
+    #[test]
+    fn distances_are_scaled_back() {
+        let shape = Transformed::new(
+            Arc::new(Sphere::new()),
+            Matrix3::identity(),
+            2.0,
+            Vector3::new(1.0, 0.0, 0.0),
+        );
+
+        assert_eq!(shape.dist_fn(Vector3::new(5.0, 0.0, 0.0)), 2.0);
+        assert_eq!(shape.bounding_box().x_max, 3.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_scale_is_rejected() {
+        Transformed::new(
+            Arc::new(Sphere::new()),
+            Matrix3::identity(),
+            0.0,
+            Vector3::zero(),
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn infinite_scale_is_rejected() {
+        Transformed::new(
+            Arc::new(Sphere::new()),
+            Matrix3::identity(),
+            f64::INFINITY,
+            Vector3::zero(),
+        );
+    }
+}
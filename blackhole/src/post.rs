@@ -0,0 +1,347 @@
+//! Pipeline of passes turning rendered radiance into a displayable image.
+//!
+//! Frontends build it from [`PostSettings`], so saved images look the same as the interactive
+//! preview, which runs the same passes on the GPU.
+
+use cgmath::{InnerSpace, Vector3, Zero};
+
+use crate::framebuffer::{FrameBuffer, Pixel};
+use crate::math::splitmix64;
+use crate::postprocess::{self, Tonemap};
+
+use std::sync::Arc;
+
+pub mod burn_in;
+mod cube;
+
+use burn_in::BurnIn;
+pub use cube::CubeLut;
+
+/// Display settings, plain values which frontends fill from their arguments or config files.
+///
+/// With the `serde` feature missing fields are read as their defaults.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PostSettings {
+    /// Exposure correction in EV, on top of camera exposure
+    pub exposure: f32,
+    /// Exposes radiance like a camera sensor, pixel value of 1.0 is the luminance saturating it.
+    /// Daylight scenes then look right around EV100 of 15, like photos
+    pub physical: bool,
+    /// Strength of bloom added to the image
+    pub bloom: f32,
+    /// Luminance above which pixels contribute to bloom
+    pub bloom_threshold: f32,
+    /// Darkening of image corners, 1.0 makes corners black
+    pub vignette: f32,
+    /// Tonemapping operator, overrides the one set in the scene
+    pub tonemap: Option<Tonemap>,
+    /// Gamma of the encoded image
+    pub gamma: f32,
+    /// Adds noise of one 8 bit step to the encoded image, hiding banding of smooth gradients
+    pub dither: bool,
+}
+
+impl PostSettings {
+    /// Passes applying the settings, the ones without any effect are left out.
+    pub fn pipeline(&self, scene_tonemap: Tonemap) -> Pipeline {
+        let mut passes = Vec::new();
+
+        if self.physical {
+            passes.push(Pass::Sensor);
+        }
+        if self.exposure != 0.0 {
+            passes.push(Pass::Exposure(self.exposure));
+        }
+        if self.bloom > 0.0 {
+            passes.push(Pass::Bloom {
+                strength: self.bloom,
+                threshold: self.bloom_threshold,
+            });
+        }
+
+        passes.push(Pass::Tonemap(self.tonemap.unwrap_or(scene_tonemap)));
+
+        if self.vignette > 0.0 {
+            passes.push(Pass::Vignette(self.vignette));
+        }
+
+        passes.push(Pass::Gamma(self.gamma));
+
+        if self.dither {
+            passes.push(Pass::Dither);
+        }
+
+        Pipeline { passes }
+    }
+}
+
+impl Default for PostSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            physical: false,
+            bloom: 0.0,
+            bloom_threshold: 1.0,
+            vignette: 0.0,
+            tonemap: None,
+            gamma: 2.2,
+            dither: false,
+        }
+    }
+}
+
+/// Single step of the pipeline working on straight colors, alpha of pixels is kept by all of them
+/// except the burn-in.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pass {
+    /// Scales radiance to the luminance saturating a sensor at EV100 of 0, by [`SENSOR_SCALE`]
+    Sensor,
+    /// Scales the image by 2 to the power of the value
+    Exposure(f32),
+    /// Adds blurred parts of the image brighter than `threshold`
+    Bloom {
+        strength: f32,
+        threshold: f32,
+    },
+    Tonemap(Tonemap),
+    /// Darkens corners, 1.0 makes them black
+    Vignette(f32),
+    /// Encodes linear values with the gamma
+    Gamma(f32),
+    /// Grades encoded colors
+    Lut(Arc<CubeLut>),
+    /// Adds noise of one 8 bit step
+    Dither,
+    /// Draws text over the finished image, see [`BurnIn::draw`]
+    BurnIn(BurnIn),
+}
+
+impl Pass {
+    pub fn apply(&self, fb: &mut FrameBuffer) {
+        match self {
+            Self::Sensor => map_colors(fb, |_, _, c| c * SENSOR_SCALE),
+            Self::Exposure(ev) => {
+                let scale = 2.0_f32.powf(*ev);
+
+                map_colors(fb, |_, _, c| c * scale);
+            }
+            Self::Bloom {
+                strength,
+                threshold,
+            } => bloom(fb, *strength, *threshold),
+            Self::Tonemap(tonemap) => postprocess::tonemap(fb, tonemap),
+            Self::Vignette(amount) => {
+                let (w, h) = (fb.width() as f32, fb.height() as f32);
+
+                map_colors(fb, |x, y, c| {
+                    // squared distance from center, 1.0 in corners
+                    let u = (x as f32 + 0.5) / w - 0.5;
+                    let v = (y as f32 + 0.5) / h - 0.5;
+                    let r2 = (u * u + v * v) * 2.0;
+
+                    c * (1.0 - r2 * *amount)
+                });
+            }
+            Self::Gamma(gamma) => postprocess::encode_gamma(fb, *gamma),
+            Self::Lut(lut) => map_colors(fb, |_, _, c| lut.apply(c)),
+            Self::Dither => {
+                let width = fb.width();
+
+                map_colors(fb, |x, y, c| {
+                    let mut state = splitmix64((x + y * width) as u64);
+                    let mut noise = || {
+                        state = splitmix64(state);
+                        ((state >> 40) as f32 / (1 << 24) as f32 - 0.5) / 255.0
+                    };
+
+                    Vector3::new(c.x + noise(), c.y + noise(), c.z + noise())
+                });
+            }
+            Self::BurnIn(burn_in) => burn_in.draw(fb),
+        }
+    }
+}
+
+/// Lumens per watt of 555 nm light, converts radiance to luminance.
+const LUMINOUS_EFFICACY: f32 = 683.0;
+
+/// Luminance saturating a sensor at EV100 of 0, from the saturation based ISO sensitivity with
+/// lens and vignetting factor of 0.65.
+const SATURATION_LUMINANCE: f32 = 78.0 / (100.0 * 0.65);
+
+/// Scale of [`Pass::Sensor`].
+pub const SENSOR_SCALE: f32 = LUMINOUS_EFFICACY / SATURATION_LUMINANCE;
+
+/// Passes run one after another.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pipeline {
+    passes: Vec<Pass>,
+}
+
+impl Pipeline {
+    pub fn new(passes: Vec<Pass>) -> Self {
+        Self { passes }
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    /// Adds grading by the table to encoded colors, before dithering.
+    pub fn with_lut(mut self, lut: Arc<CubeLut>) -> Self {
+        let index = match self.passes.last() {
+            Some(Pass::Dither) => self.passes.len() - 1,
+            _ => self.passes.len(),
+        };
+
+        self.passes.insert(index, Pass::Lut(lut));
+
+        self
+    }
+
+    pub fn run(&self, fb: &mut FrameBuffer) {
+        for pass in &self.passes {
+            pass.apply(fb);
+        }
+    }
+}
+
+fn map_colors(fb: &mut FrameBuffer, f: impl Fn(usize, usize, Vector3<f32>) -> Vector3<f32>) {
+    let width = fb.width();
+
+    for (i, pixel) in fb.buffer_mut().iter_mut().enumerate() {
+        let c = f(
+            i % width,
+            i / width,
+            Vector3::new(pixel.r, pixel.g, pixel.b),
+        );
+
+        *pixel = Pixel::new(c.x, c.y, c.z, pixel.a);
+    }
+}
+
+/// Image is shrunk by this for blurring, same as in the interactive preview.
+const BLOOM_DOWNSCALE: usize = 4;
+
+/// Gaussian weights of the blur taps, taken every other texel of the shrunk image.
+const BLOOM_WEIGHTS: [f32; 5] = [0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216];
+
+fn bloom(fb: &mut FrameBuffer, strength: f32, threshold: f32) {
+    let (width, height) = (fb.width(), fb.height());
+    let w = width.div_ceil(BLOOM_DOWNSCALE);
+    let h = height.div_ceil(BLOOM_DOWNSCALE);
+
+    // bright parts, averaged over blocks of pixels
+    let mut bright = fb.clone();
+    map_colors(&mut bright, |_, _, color| {
+        let luminance = color.dot(Vector3::new(0.2126, 0.7152, 0.0722));
+
+        color * ((luminance - threshold).max(0.0) / luminance.max(0.0001))
+    });
+
+    let bright = bright
+        .downscale(BLOOM_DOWNSCALE)
+        .buffer()
+        .iter()
+        .map(|p| Vector3::new(p.r, p.g, p.b))
+        .collect::<Vec<_>>();
+
+    let blurred = blur(&blur(&bright, w, h, (1, 0)), w, h, (0, 1));
+
+    // bilinear upscale, with pixel centers of both images aligned
+    let texel = |x: isize, y: isize| {
+        let x = x.clamp(0, w as isize - 1) as usize;
+        let y = y.clamp(0, h as isize - 1) as usize;
+
+        blurred[x + y * w]
+    };
+
+    map_colors(fb, |x, y, c| {
+        let u = (x as f32 + 0.5) / BLOOM_DOWNSCALE as f32 - 0.5;
+        let v = (y as f32 + 0.5) / BLOOM_DOWNSCALE as f32 - 0.5;
+        let (x0, y0) = (u.floor(), v.floor());
+        let (tx, ty) = (u - x0, v - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let top = texel(x0, y0) * (1.0 - tx) + texel(x0 + 1, y0) * tx;
+        let bottom = texel(x0, y0 + 1) * (1.0 - tx) + texel(x0 + 1, y0 + 1) * tx;
+
+        c + (top * (1.0 - ty) + bottom * ty) * strength
+    });
+}
+
+/// Separable gaussian blur along `axis`, edges are extended.
+fn blur(image: &[Vector3<f32>], w: usize, h: usize, axis: (usize, usize)) -> Vec<Vector3<f32>> {
+    let mut out = vec![Vector3::zero(); w * h];
+
+    for y in 0..h {
+        for x in 0..w {
+            let texel = |offset: isize| {
+                let x = (x as isize + offset * axis.0 as isize).clamp(0, w as isize - 1);
+                let y = (y as isize + offset * axis.1 as isize).clamp(0, h as isize - 1);
+
+                image[x as usize + y as usize * w]
+            };
+
+            let mut sum = texel(0) * BLOOM_WEIGHTS[0];
+
+            for (i, weight) in BLOOM_WEIGHTS.iter().enumerate().skip(1) {
+                let offset = 2 * i as isize;
+
+                sum += (texel(offset) + texel(-offset)) * *weight;
+            }
+
+            out[x + y * w] = sum;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_pipeline_matches_display_transform() {
+        let mut fb = FrameBuffer::new(4, 1);
+        for (i, pixel) in fb.buffer_mut().iter_mut().enumerate() {
+            *pixel = Pixel::new(i as f32, 0.5, 0.1, 1.0);
+        }
+        let mut expected = fb.clone();
+
+        PostSettings::default()
+            .pipeline(Tonemap::Reinhard)
+            .run(&mut fb);
+        postprocess::display_transform(&mut expected, &Tonemap::Reinhard);
+
+        for (a, b) in fb.buffer().iter().zip(expected.buffer()) {
+            assert!((a.r - b.r).abs() < 1e-6 && (a.g - b.g).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn sensor_saturates_at_its_luminance() {
+        let settings = PostSettings {
+            physical: true,
+            ..Default::default()
+        };
+        let pipeline = settings.pipeline(Tonemap::default());
+        assert_eq!(pipeline.passes()[0], Pass::Sensor);
+
+        // white radiance giving the saturation luminance, and one of a hundredth of it
+        let radiance = SATURATION_LUMINANCE / LUMINOUS_EFFICACY;
+        let mut fb = FrameBuffer::new(2, 1);
+        fb.buffer_mut()[0] = Pixel::new(radiance, radiance, radiance, 1.0);
+        fb.buffer_mut()[1] = Pixel::new(radiance, radiance, radiance, 1.0) * 0.01;
+
+        Pass::Sensor.apply(&mut fb);
+
+        let [full, dim] = [fb.buffer()[0], fb.buffer()[1]];
+        assert!((full.r - 1.0).abs() < 1e-5 && (full.b - 1.0).abs() < 1e-5);
+        assert!((dim.g - 0.01).abs() < 1e-6);
+        assert_eq!(full.a, 1.0);
+    }
+}
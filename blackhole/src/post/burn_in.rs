@@ -0,0 +1,301 @@
+//! Text burned into a corner of finished images, for labelling renders sent for review.
+
+use std::time::Duration;
+
+use crate::framebuffer::{FrameBuffer, Pixel};
+
+/// Size of glyphs of the font in font pixels, without spacing.
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+/// Brightness left of the image under the text box.
+const BOX_DIM: f32 = 0.3;
+
+/// Rows of 5x7 pixel glyphs sorted by character, highest bit on the left. Lowercase letters are
+/// drawn as uppercase and missing characters as `?`.
+#[rustfmt::skip]
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 65] = [
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('!', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100]),
+    ('"', [0b01010, 0b01010, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('#', [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010]),
+    ('$', [0b00100, 0b01111, 0b10100, 0b01110, 0b00101, 0b11110, 0b00100]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+    ('&', [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101]),
+    ('\'', [0b00100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+    ('*', [0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    (';', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b00100, 0b01000]),
+    ('<', [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010]),
+    ('=', [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000]),
+    ('>', [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+    ('@', [0b01110, 0b10001, 0b00001, 0b01101, 0b10101, 0b10101, 0b01110]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('[', [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110]),
+    ('\\', [0b00000, 0b10000, 0b01000, 0b00100, 0b00010, 0b00001, 0b00000]),
+    (']', [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110]),
+    ('_', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111]),
+    ('|', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('~', [0b00000, 0b00000, 0b01000, 0b10101, 0b00010, 0b00000, 0b00000]),
+];
+
+/// Corner of the image the text is placed in.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    #[default]
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "top_left" => Some(Self::TopLeft),
+            "top_right" => Some(Self::TopRight),
+            "bottom_left" => Some(Self::BottomLeft),
+            "bottom_right" => Some(Self::BottomRight),
+            _ => None,
+        }
+    }
+}
+
+/// Lines of text drawn over the image on a dimmed box.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BurnIn {
+    /// Text with `{scene}`, `{samples}`, `{time}`, `{frame}`, `{size}` and `{notes}` placeholders,
+    /// see [`BurnIn::filled`]
+    pub text: String,
+    pub corner: Corner,
+    /// Image pixels per font pixel
+    pub scale: usize,
+}
+
+impl Default for BurnIn {
+    fn default() -> Self {
+        Self {
+            text: "{scene}  frame {frame}  {samples} spp  {time}".into(),
+            corner: Corner::default(),
+            scale: 2,
+        }
+    }
+}
+
+/// Values of the placeholders of [`BurnIn::text`].
+#[derive(Copy, Clone, Debug)]
+pub struct BurnInFields<'a> {
+    /// Name of the scene, usually the file name without extension
+    pub scene: &'a str,
+    pub samples: usize,
+    /// Time the render took
+    pub render_time: Duration,
+    pub frame: u64,
+    /// Free text from the user
+    pub notes: &'a str,
+}
+
+impl BurnIn {
+    /// Copy with placeholders in the text replaced by values. Unknown placeholders are kept.
+    ///
+    /// `{size}` is filled by [`BurnIn::draw`], so it matches the image the text ends up in.
+    pub fn filled(&self, fields: &BurnInFields) -> Self {
+        let seconds = fields.render_time.as_secs();
+        let time = format!("{:02}:{:02}", seconds / 60, seconds % 60);
+
+        let text = [
+            ("{scene}", fields.scene.to_string()),
+            ("{samples}", fields.samples.to_string()),
+            ("{time}", time),
+            ("{frame}", fields.frame.to_string()),
+            ("{notes}", fields.notes.to_string()),
+        ]
+        .iter()
+        .fold(self.text.clone(), |text, (key, value)| {
+            text.replace(key, value)
+        });
+
+        Self {
+            text,
+            ..self.clone()
+        }
+    }
+
+    /// Draws the text in white on a dimmed box, opaque even over transparent parts of the image.
+    ///
+    /// Colors are straight, the box is the image composited over black and dimmed, so
+    /// transparent parts turn black. Lines are split by `\n`, text not fitting the image is cut
+    /// off.
+    pub fn draw(&self, fb: &mut FrameBuffer) {
+        let (width, height) = (fb.width(), fb.height());
+        let text = self.text.replace("{size}", &format!("{width}x{height}"));
+        let lines = text.lines().collect::<Vec<_>>();
+
+        let scale = self.scale.max(1);
+        let (advance, line_height) = ((GLYPH_WIDTH + 1) * scale, (GLYPH_HEIGHT + 2) * scale);
+        let columns = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+        if columns == 0 {
+            return;
+        }
+
+        // one font pixel of padding on each side of the text
+        let box_width = (columns * advance + scale).min(width);
+        let box_height = (lines.len() * line_height).min(height);
+
+        let left = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => 0,
+            Corner::TopRight | Corner::BottomRight => width - box_width,
+        };
+        let top = match self.corner {
+            Corner::TopLeft | Corner::TopRight => 0,
+            Corner::BottomLeft | Corner::BottomRight => height - box_height,
+        };
+
+        let buffer = fb.buffer_mut();
+
+        for y in top..top + box_height {
+            for x in left..left + box_width {
+                let p = &mut buffer[x + y * width];
+                let dim = p.a * BOX_DIM;
+                *p = Pixel::new(p.r * dim, p.g * dim, p.b * dim, 1.0);
+            }
+        }
+
+        for (row, line) in lines.iter().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                let rows = glyph(c);
+                let (glyph_x, glyph_y) = (
+                    left + scale + column * advance,
+                    top + scale + row * line_height,
+                );
+
+                for (gy, bits) in rows.iter().enumerate() {
+                    for gx in 0..GLYPH_WIDTH {
+                        if bits & (1 << (GLYPH_WIDTH - 1 - gx)) == 0 {
+                            continue;
+                        }
+
+                        for sy in 0..scale {
+                            for sx in 0..scale {
+                                let (x, y) = (glyph_x + gx * scale + sx, glyph_y + gy * scale + sy);
+
+                                if x < left + box_width && y < top + box_height {
+                                    buffer[x + y * width] = Pixel::new(1.0, 1.0, 1.0, 1.0);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    let find = |c: char| {
+        GLYPHS
+            .binary_search_by_key(&c, |(g, _)| *g)
+            .ok()
+            .map(|i| GLYPHS[i].1)
+    };
+
+    find(c.to_ascii_uppercase())
+        .or_else(|| find('?'))
+        .expect("font has question mark")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_filled_text_in_corner() {
+        let burn_in = BurnIn {
+            text: "{scene} {frame}\n{size}".into(),
+            corner: Corner::BottomRight,
+            scale: 1,
+        }
+        .filled(&BurnInFields {
+            scene: "disk",
+            samples: 16,
+            render_time: Duration::from_secs(75),
+            frame: 12,
+            notes: "",
+        });
+
+        assert_eq!(burn_in.text, "disk 12\n{size}");
+
+        let mut fb = FrameBuffer::new(80, 40);
+        for pixel in fb.buffer_mut() {
+            *pixel = Pixel::new(0.5, 0.5, 0.5, 0.0);
+        }
+
+        burn_in.draw(&mut fb);
+
+        // 7 columns of the longest line and 2 lines of text
+        let (box_width, box_height) = (7 * 6 + 1, 2 * 9);
+        let text = fb.buffer().iter().filter(|p| p.r == 1.0).count();
+        let covered = fb
+            .buffer()
+            .iter()
+            .filter(|p| p.a == 1.0 && p.r == 0.0)
+            .count();
+
+        for (i, pixel) in fb.buffer().iter().enumerate() {
+            let (x, y) = (i % 80, i / 80);
+            let inside = x >= 80 - box_width && y >= 40 - box_height;
+
+            assert_eq!(pixel.a == 1.0, inside, "{x} {y}");
+        }
+        assert!(text > 50, "{text}");
+        // transparent pixels under the box turn black, not into their straight colors
+        assert_eq!(text + covered, box_width * box_height);
+
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(glyph('^'), glyph('?'));
+    }
+}
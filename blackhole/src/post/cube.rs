@@ -0,0 +1,162 @@
+use cgmath::Vector3;
+
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+/// Color grade from an Adobe `.cube` file, a 3D table mapping display encoded colors.
+///
+/// Only 3D tables are supported. Colors outside of the table domain are clamped to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CubeLut {
+    /// Entries along each axis
+    size: usize,
+    domain_min: Vector3<f32>,
+    domain_max: Vector3<f32>,
+    /// Output colors with red changing fastest, then green, then blue
+    table: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    pub fn read(path: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = |msg: String| Error::new(ErrorKind::InvalidData, msg);
+        let numbers = |line: usize, values: &[&str]| -> Result<Vec<f32>> {
+            values
+                .iter()
+                .map(|v| {
+                    v.parse()
+                        .map_err(|_| invalid(format!("line {line}: invalid number '{v}'")))
+                })
+                .collect()
+        };
+        let triple = |line: usize, values: &[&str]| -> Result<[f32; 3]> {
+            numbers(line, values)?
+                .try_into()
+                .map_err(|_| invalid(format!("line {line}: expected 3 numbers")))
+        };
+
+        let mut size = None;
+        let mut domain_min = Vector3::new(0.0, 0.0, 0.0);
+        let mut domain_max = Vector3::new(1.0, 1.0, 1.0);
+        let mut table = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let words = line.split_whitespace().collect::<Vec<_>>();
+
+            let (keyword, values) = match words.split_first() {
+                Some((keyword, values)) if !keyword.starts_with('#') => (*keyword, values),
+                _ => continue,
+            };
+
+            match keyword {
+                "TITLE" => {}
+                "LUT_3D_SIZE" => {
+                    let n = values
+                        .first()
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .filter(|n| (2..=256).contains(n))
+                        .ok_or_else(|| invalid(format!("line {line_number}: invalid size")))?;
+
+                    size = Some(n);
+                }
+                "LUT_1D_SIZE" => return Err(invalid("1D tables are not supported".into())),
+                "DOMAIN_MIN" => domain_min = triple(line_number, values)?.into(),
+                "DOMAIN_MAX" => domain_max = triple(line_number, values)?.into(),
+                _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+                _ => table.push(triple(line_number, &words)?),
+            }
+        }
+
+        let size = size.ok_or_else(|| invalid("missing LUT_3D_SIZE".into()))?;
+
+        if table.len() != size.pow(3) {
+            return Err(invalid(format!(
+                "expected {} entries, found {}",
+                size.pow(3),
+                table.len()
+            )));
+        }
+
+        Ok(Self {
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    /// Entries along each axis of the table.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Input colors mapped to the first and last entries.
+    pub fn domain(&self) -> (Vector3<f32>, Vector3<f32>) {
+        (self.domain_min, self.domain_max)
+    }
+
+    /// Output colors with red index changing fastest, then green, then blue.
+    pub fn table(&self) -> &[[f32; 3]] {
+        &self.table
+    }
+
+    /// Color graded by the table, trilinearly interpolated between entries.
+    pub fn apply(&self, color: Vector3<f32>) -> Vector3<f32> {
+        let last = (self.size - 1) as f32;
+
+        let mut index = [0; 3];
+        let mut t = [0.0; 3];
+
+        for a in 0..3 {
+            let range = self.domain_max[a] - self.domain_min[a];
+            let pos = ((color[a] - self.domain_min[a]) / range).clamp(0.0, 1.0) * last;
+            let i = pos.floor().min(last - 1.0);
+
+            index[a] = i as usize;
+            t[a] = pos - i;
+        }
+
+        let entry = |r: usize, g: usize, b: usize| {
+            let i = index[0] + r + (index[1] + g) * self.size + (index[2] + b) * self.size.pow(2);
+
+            Vector3::from(self.table[i])
+        };
+
+        let lerp = |a: Vector3<f32>, b: Vector3<f32>, t: f32| a + (b - a) * t;
+        let along_r = |g, b| lerp(entry(0, g, b), entry(1, g, b), t[0]);
+        let along_g = |b| lerp(along_r(0, b), along_r(1, b), t[1]);
+
+        lerp(along_g(0), along_g(1), t[2])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_cube_keeps_colors() {
+        let mut text = String::from("TITLE \"identity\"\n# comment\nLUT_3D_SIZE 2\n\n");
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    text += &format!("{r} {g} {b}\n");
+                }
+            }
+        }
+
+        let lut = CubeLut::parse(&text).unwrap();
+        let color = Vector3::new(0.25, 0.5, 0.9);
+        let graded = lut.apply(color);
+
+        assert!((graded - color).map(f32::abs).x < 1e-6);
+        assert!((graded - color).map(f32::abs).y < 1e-6);
+        assert!((graded - color).map(f32::abs).z < 1e-6);
+
+        assert!(CubeLut::parse("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+    }
+}
@@ -0,0 +1,293 @@
+//! Conversion of rendered radiance into displayable images.
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::framebuffer::{FrameBuffer, Pixel};
+
+/// Maps linear HDR colors into the displayable 0 to 1 range, still linear.
+///
+/// Operators working on luminance may leave single channels of saturated colors above 1.
+pub trait Tonemapper {
+    fn map(&self, color: Vector3<f32>) -> Vector3<f32>;
+}
+
+/// Reinhard curve applied to luminance, keeps the hue of bright colors.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Reinhard;
+
+impl Tonemapper for Reinhard {
+    fn map(&self, color: Vector3<f32>) -> Vector3<f32> {
+        let luminance = color.dot(Vector3::new(0.2126, 0.7152, 0.0722));
+
+        // negative values from filter lobes go through the curve like the others
+        if luminance == 0.0 {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+
+        let new_luminance = luminance / (luminance + 1.0);
+
+        color * (new_luminance / luminance)
+    }
+}
+
+/// Krzysztof Narkowicz's fit of the ACES filmic curve, per channel.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AcesFilmic;
+
+impl Tonemapper for AcesFilmic {
+    fn map(&self, color: Vector3<f32>) -> Vector3<f32> {
+        color.map(|x| {
+            let x = x.max(0.0);
+
+            (x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+        })
+    }
+}
+
+/// John Hable's filmic curve from Uncharted 2, per channel.
+#[derive(Copy, Clone, Debug)]
+pub struct Uncharted2 {
+    /// Linear value mapped to white
+    pub white: f32,
+}
+
+impl Uncharted2 {
+    fn curve(x: f32) -> f32 {
+        const A: f32 = 0.15;
+        const B: f32 = 0.50;
+        const C: f32 = 0.10;
+        const D: f32 = 0.20;
+        const E: f32 = 0.02;
+        const F: f32 = 0.30;
+
+        ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+    }
+}
+
+impl Default for Uncharted2 {
+    fn default() -> Self {
+        Self { white: 11.2 }
+    }
+}
+
+impl Tonemapper for Uncharted2 {
+    fn map(&self, color: Vector3<f32>) -> Vector3<f32> {
+        // the curve is made for exposure bias of 2
+        let white_scale = 1.0 / Self::curve(self.white);
+
+        color.map(|x| (Self::curve(x.max(0.0) * 2.0) * white_scale).clamp(0.0, 1.0))
+    }
+}
+
+/// Clamps colors without any curve, for checking raw values.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LinearClamp;
+
+impl Tonemapper for LinearClamp {
+    fn map(&self, color: Vector3<f32>) -> Vector3<f32> {
+        color.map(|x| x.clamp(0.0, 1.0))
+    }
+}
+
+/// Built-in tonemapping operators, selectable by name in scene files and command line.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Tonemap {
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "reinhard"))]
+    Reinhard,
+    #[cfg_attr(feature = "serde", serde(rename = "aces"))]
+    AcesFilmic,
+    #[cfg_attr(feature = "serde", serde(rename = "uncharted2"))]
+    Uncharted2,
+    #[cfg_attr(feature = "serde", serde(rename = "linear"))]
+    LinearClamp,
+}
+
+impl Tonemap {
+    pub const ALL: [Self; 4] = [
+        Self::Reinhard,
+        Self::AcesFilmic,
+        Self::Uncharted2,
+        Self::LinearClamp,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Reinhard => "reinhard",
+            Self::AcesFilmic => "aces",
+            Self::Uncharted2 => "uncharted2",
+            Self::LinearClamp => "linear",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.name() == name)
+    }
+}
+
+impl Tonemapper for Tonemap {
+    fn map(&self, color: Vector3<f32>) -> Vector3<f32> {
+        match self {
+            Self::Reinhard => Reinhard.map(color),
+            Self::AcesFilmic => AcesFilmic.map(color),
+            Self::Uncharted2 => Uncharted2::default().map(color),
+            Self::LinearClamp => LinearClamp.map(color),
+        }
+    }
+}
+
+/// Tonemaps the framebuffer and encodes it with 2.2 gamma for display, alpha is kept.
+pub fn display_transform<T: Tonemapper + ?Sized>(fb: &mut FrameBuffer, tonemapper: &T) {
+    tonemap(fb, tonemapper);
+    encode_gamma(fb, 2.2);
+}
+
+/// Maps colors of the framebuffer with the operator, alpha is kept.
+pub fn tonemap<T: Tonemapper + ?Sized>(fb: &mut FrameBuffer, tonemapper: &T) {
+    for pixel in fb.buffer_mut() {
+        let mapped = tonemapper.map(Vector3::new(pixel.r, pixel.g, pixel.b));
+
+        *pixel = Pixel::new(mapped.x, mapped.y, mapped.z, pixel.a);
+    }
+}
+
+/// Encodes linear colors with the gamma, negative values become black, alpha is kept.
+pub fn encode_gamma(fb: &mut FrameBuffer, gamma: f32) {
+    for pixel in fb.buffer_mut() {
+        let [r, g, b] = [pixel.r, pixel.g, pixel.b].map(|c| c.max(0.0).powf(1.0 / gamma));
+
+        *pixel = Pixel::new(r, g, b, pixel.a);
+    }
+}
+
+/// Maps depth to brightness for display, closer is brighter, the farthest hit is at 0.1 and the
+/// background at infinity is black.
+pub fn depth_display(fb: &mut FrameBuffer) {
+    let far = fb
+        .buffer()
+        .iter()
+        .map(|p| p.r)
+        .filter(|d| d.is_finite())
+        .fold(0.0, f32::max);
+
+    for pixel in fb.buffer_mut() {
+        let value = if pixel.r.is_finite() && far > 0.0 {
+            1.0 - pixel.r / far * 0.9
+        } else {
+            0.0
+        };
+
+        *pixel = Pixel::new(value, value, value, pixel.a);
+    }
+}
+
+/// Distribution of step counts stored in the red channel, see [`steps_display`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StepLevels {
+    pub min: f32,
+    /// 1st percentile, shown as green
+    pub low: f32,
+    pub median: f32,
+    /// 99th percentile, shown as red
+    pub high: f32,
+    pub max: f32,
+}
+
+impl StepLevels {
+    pub fn measure(fb: &FrameBuffer) -> Self {
+        let mut steps = fb.buffer().iter().map(|p| p.r).collect::<Vec<_>>();
+        steps.sort_unstable_by(f32::total_cmp);
+
+        let percentile = |p: f32| match steps.len() {
+            0 => 0.0,
+            len => steps[((len - 1) as f32 * p).round() as usize],
+        };
+
+        Self {
+            min: percentile(0.0),
+            low: percentile(0.01),
+            median: percentile(0.5),
+            high: percentile(0.99),
+            max: percentile(1.0),
+        }
+    }
+}
+
+/// Colors step counts in the red channel from green at `levels.low` to red at `levels.high`.
+///
+/// Outliers outside of the percentiles are clamped, so a few very long rays don't wash out the
+/// rest of the image.
+pub fn steps_display(fb: &mut FrameBuffer, levels: &StepLevels) {
+    // counts are whole steps, keep at least one step between the ends
+    let range = (levels.high - levels.low).max(1.0);
+
+    for pixel in fb.buffer_mut() {
+        let value = ((pixel.r - levels.low) / range).clamp(0.0, 1.0);
+
+        *pixel = Pixel::new(value, 1.0 - value, 0.0, 1.0);
+    }
+}
+
+/// Divides colors by alpha for writers of straight alpha, fully transparent pixels become black.
+pub fn unpremultiply(fb: &mut FrameBuffer) {
+    for pixel in fb.buffer_mut() {
+        let a = pixel.a;
+
+        *pixel = if a > 0.0 {
+            Pixel::new(pixel.r / a, pixel.g / a, pixel.b / a, a)
+        } else {
+            Pixel::new(0.0, 0.0, 0.0, 0.0)
+        };
+    }
+}
+
+/// Multiplies colors by alpha, inverse of [`unpremultiply`].
+pub fn premultiply(fb: &mut FrameBuffer) {
+    for pixel in fb.buffer_mut() {
+        let a = pixel.a;
+
+        *pixel = Pixel::new(pixel.r * a, pixel.g * a, pixel.b * a, a);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_levels_skip_outliers() {
+        let mut fb = FrameBuffer::new(100, 2);
+        for (i, pixel) in fb.buffer_mut().iter_mut().enumerate() {
+            pixel.r = (i / 2) as f32;
+        }
+        fb.buffer_mut()[199].r = 1e6;
+
+        let levels = StepLevels::measure(&fb);
+        assert_eq!((levels.min, levels.median, levels.max), (0.0, 50.0, 1e6));
+        assert_eq!((levels.low, levels.high), (1.0, 98.0));
+
+        steps_display(&mut fb, &levels);
+        assert_eq!(fb.buffer()[0].r, 0.0);
+        assert_eq!(fb.buffer()[199].r, 1.0);
+    }
+
+    #[test]
+    fn operators_keep_gray_in_display_range() {
+        for tonemap in Tonemap::ALL {
+            for value in [0.0, 0.18, 1.0, 10.0, 1000.0] {
+                let mapped = tonemap.map(Vector3::new(value, value, value));
+
+                for c in [mapped.x, mapped.y, mapped.z] {
+                    assert!(
+                        (0.0..=1.0).contains(&c),
+                        "{} at {value}: {c}",
+                        tonemap.name()
+                    );
+                }
+            }
+
+            assert_eq!(Tonemap::from_name(tonemap.name()), Some(tonemap));
+        }
+    }
+}
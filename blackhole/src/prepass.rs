@@ -0,0 +1,230 @@
+//! Low resolution pre-pass spending samples where the image converges slowly.
+//!
+//! Few rays are traced on a coarse grid and pixels are classified by what the rays hit. Edges,
+//! like the border of the shadow or of the disk, get more samples, empty background fewer.
+
+use crate::frame::Frame;
+use crate::marcher::{RayMarcher, Scratch, Termination};
+use crate::math::{mix_seed, pixel_seed, seed_rng};
+use crate::scene::Scene;
+use crate::RenderMode;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PixelClass {
+    /// Only background or captured rays are nearby
+    Background,
+    /// Rays hit an object, usually the disk
+    Object,
+    /// Rays nearby end differently, like at the shadow border or object borders
+    Edge,
+}
+
+/// Amount of samples for every pixel of the frame.
+pub struct SampleMap {
+    width: usize,
+    stride: usize,
+    classes: Vec<PixelClass>,
+    /// Samples for background, object and edge pixels
+    class_samples: [usize; 3],
+}
+
+impl PixelClass {
+    /// Relative amount of samples pixels of the class get.
+    fn weight(&self) -> f64 {
+        match self {
+            Self::Background => 1.0,
+            Self::Object => 2.0,
+            Self::Edge => 4.0,
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Self::Background => 0,
+            Self::Object => 1,
+            Self::Edge => 2,
+        }
+    }
+}
+
+impl SampleMap {
+    /// Traces probe rays every `stride` pixels and classifies cells of the grid.
+    ///
+    /// Samples are split between the classes by their weight, so the average stays close to
+    /// `base_samples`. Rays are seeded from `seed`, so the map is the same for every run.
+    pub fn from_prepass(
+        scene: &Scene,
+        marcher: &RayMarcher,
+        frame: &Frame,
+        stride: usize,
+        base_samples: usize,
+        seed: u64,
+    ) -> Self {
+        let stride = stride.max(1);
+        let width = frame.width.div_ceil(stride);
+        let height = frame.height.div_ceil(stride);
+        // keep probe noise independent of the noise of the render itself
+        let seed = mix_seed(seed, u64::from_le_bytes(*b"prepass\0"));
+
+        // deterministic mode integrates volumes instead of scattering in them at random
+        let marcher = RayMarcher {
+            mode: RenderMode::Deterministic,
+            ..marcher.clone()
+        };
+        let max_step = scene.max_possible_step(scene.camera.location);
+
+        let mut probes = Vec::with_capacity(width * height);
+        let mut scratch = Scratch::new();
+        let mut path = Vec::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                seed_rng(pixel_seed(seed, 0, x, y));
+
+                let rel_x = ((x * stride) as f64 + stride as f64 / 2.0) / frame.width as f64;
+                let rel_y = ((y * stride) as f64 + stride as f64 / 2.0) / frame.height as f64;
+
+                // the path shows volumes the ray went through, which don't end marching
+                let ray = scene.camera.cast_ray(rel_x, rel_y, frame.aspect_ratio());
+                let termination =
+                    marcher.trace_path_into(ray, scene, max_step, &mut scratch, &mut path);
+
+                let object = matches!(termination, Termination::Object)
+                    || path.iter().any(|&p| scene.dist_fn(p) <= 0.0);
+
+                probes.push(Probe {
+                    object,
+                    shadow: !matches!(
+                        termination,
+                        Termination::Object | Termination::Background | Termination::Absorbed
+                    ),
+                });
+            }
+        }
+
+        let classes: Vec<_> = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as isize, (i / width) as isize);
+                let probe = probes[i];
+
+                let neighbors_differ = (-1..=1)
+                    .flat_map(|dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+                    .filter(|&(nx, ny)| {
+                        nx >= 0 && ny >= 0 && (nx as usize) < width && (ny as usize) < height
+                    })
+                    .any(|(nx, ny)| probes[nx as usize + ny as usize * width] != probe);
+
+                if neighbors_differ {
+                    PixelClass::Edge
+                } else if probe.object {
+                    PixelClass::Object
+                } else {
+                    PixelClass::Background
+                }
+            })
+            .collect();
+
+        let total_weight: f64 = classes.iter().map(PixelClass::weight).sum();
+        let scale = base_samples as f64 * classes.len() as f64 / total_weight;
+
+        let class_samples = [PixelClass::Background, PixelClass::Object, PixelClass::Edge]
+            .map(|c| ((c.weight() * scale).round() as usize).max(1));
+
+        Self {
+            width,
+            stride,
+            classes,
+            class_samples,
+        }
+    }
+
+    pub fn class(&self, x: usize, y: usize) -> PixelClass {
+        self.classes[x / self.stride + (y / self.stride) * self.width]
+    }
+
+    pub fn samples(&self, x: usize, y: usize) -> usize {
+        self.class_samples[self.class(x, y).index()]
+    }
+
+    pub fn max_samples(&self) -> usize {
+        self.classes
+            .iter()
+            .map(|c| self.class_samples[c.index()])
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Result of a single probe ray.
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct Probe {
+    /// Ray went through an object
+    object: bool,
+    /// Ray fell into a distortion or was stopped by it
+    shadow: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::Region;
+    use crate::material::MaterialResult;
+    use crate::object::shape::Sphere;
+    use crate::shader::{ShadeContext, Shader, SolidShader};
+    use crate::Ray;
+    use cgmath::{Vector3, Zero};
+
+    #[derive(Clone)]
+    struct Black;
+
+    impl Shader for Black {}
+
+    impl SolidShader for Black {
+        fn material_at(
+            &self,
+            _ray: &Ray,
+            _normal: Vector3<f64>,
+            _ctx: &ShadeContext,
+        ) -> (MaterialResult, Option<Ray>) {
+            (MaterialResult::black(), None)
+        }
+    }
+
+    #[test]
+    fn probes_classify_sphere() {
+        let mut scene = Scene::builder()
+            .look_from(Vector3::new(0.0, 0.0, -10.0), Vector3::zero())
+            .object(|o| o.shape(Sphere::new()).solid(Black))
+            .build();
+        scene.camera.hor_fov = 30.0;
+
+        let frame = Frame {
+            width: 32,
+            height: 32,
+            region: Region::Whole,
+            border: 0,
+        };
+        let marcher = RayMarcher::default();
+        let map = SampleMap::from_prepass(&scene, &marcher, &frame, 2, 8, 0);
+
+        assert_eq!(map.class(16, 16), PixelClass::Object);
+        assert_eq!(map.class(0, 0), PixelClass::Background);
+        assert_eq!(map.class(16, 8), PixelClass::Edge);
+        assert!(map.samples(16, 8) > map.samples(16, 16));
+        assert!(map.samples(16, 16) > map.samples(0, 0));
+
+        // the traced path ends the same way as the shaded ray
+        let max_step = scene.max_possible_step(scene.camera.location);
+        let mut scratch = Scratch::new();
+        let mut path = Vec::new();
+
+        for (x, y) in [(0.5, 0.5), (0.0, 0.0)] {
+            let ray = scene.camera.cast_ray(x, y, 1.0);
+            let termination =
+                marcher.trace_path_into(ray, &scene, max_step, &mut scratch, &mut path);
+            let res = marcher.color_for_ray(ray, &scene, max_step, 0, &mut scratch);
+
+            assert_eq!(termination, res.termination);
+        }
+    }
+}
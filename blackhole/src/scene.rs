@@ -1,10 +1,24 @@
 use std::sync::Arc;
 
-use cgmath::Vector3;
+use cgmath::{InnerSpace, Vector3};
 
+use crate::animation::Animation;
 use crate::camera::Camera;
+use crate::marcher::RenderSettings;
+use crate::math::{cone_pdf, rand_cone_direction, rand_unit};
+use crate::object::shape::{Shape, BATCH_SIZE};
 use crate::object::{Distortion, Object};
-use crate::shader::BackgroundShader;
+use crate::post::burn_in::BurnIn;
+use crate::postprocess::Tonemap;
+use crate::shader::{BackgroundShader, Footprint};
+use crate::Ray;
+
+mod builder;
+
+pub use builder::{DistortionBuilder, ObjectBuilder, SceneBuilder};
+
+/// Render layer of objects which weren't assigned one.
+pub const DEFAULT_LAYER: &str = "default";
 
 #[derive(Clone)]
 pub struct Scene {
@@ -12,6 +26,58 @@ pub struct Scene {
     pub distortions: Vec<Distortion>,
     pub background: Arc<dyn BackgroundShader>,
     pub camera: Camera,
+    pub animation: Animation,
+    /// Preferred tonemapping of displayed and saved images
+    pub tonemap: Tonemap,
+    /// Exposes displayed and saved images like a camera sensor, see
+    /// [`PostSettings::physical`](crate::post::PostSettings::physical)
+    pub physical_exposure: bool,
+    /// Time in seconds the scene is posed at, see [`Scene::set_time`]
+    pub time: f64,
+    /// Seconds of scene time after [`Scene::time`] every sample is spread over, for star trails
+    /// of the rotating sky. Objects and camera stay posed at the start
+    pub long_exposure: f64,
+    /// Names of render layers objects are assigned to, starting with `default`
+    pub layers: Vec<String>,
+    /// Plane cutting away objects on one side while marching, for looking inside of them
+    pub slice: Option<SlicePlane>,
+    /// Indices of objects emitting light, which scattering events sample directly.
+    ///
+    /// Kept up to date by [`Scene::push`], objects changed in other ways need
+    /// [`Scene::update_lights`]
+    pub lights: Vec<usize>,
+    /// Quality settings of the scene file, overridden by the frontend
+    pub render: RenderSettings,
+    /// Text drawn over saved images, overridden by the frontend
+    pub burn_in: Option<BurnIn>,
+}
+
+/// Plane removing everything on the side its normal points to.
+#[derive(Copy, Clone, Debug)]
+pub struct SlicePlane {
+    pub point: Vector3<f64>,
+    /// Unit normal pointing to the removed side
+    pub normal: Vector3<f64>,
+}
+
+impl SlicePlane {
+    /// Plane through `point`, the normal doesn't have to be normalized.
+    pub fn new(point: Vector3<f64>, normal: Vector3<f64>) -> Self {
+        Self {
+            point,
+            normal: normal.normalize(),
+        }
+    }
+
+    /// Signed distance from the plane, positive on the removed side.
+    pub fn distance(&self, point: Vector3<f64>) -> f64 {
+        (point - self.point).dot(self.normal)
+    }
+
+    /// Distance to an object with the removed side cut off.
+    pub fn cut(&self, dist: f64, point: Vector3<f64>) -> f64 {
+        dist.max(self.distance(point))
+    }
 }
 
 impl Scene {
@@ -21,21 +87,214 @@ impl Scene {
             distortions: Vec::new(),
             background,
             camera: Camera::new(),
+            animation: Animation::new(),
+            tonemap: Tonemap::default(),
+            physical_exposure: false,
+            time: 0.0,
+            long_exposure: 0.0,
+            layers: vec![DEFAULT_LAYER.into()],
+            slice: None,
+            lights: Vec::new(),
+            render: RenderSettings::default(),
+            burn_in: None,
         }
     }
 
+    /// Starts a scene constructed in code, see [`SceneBuilder`].
+    pub fn builder() -> SceneBuilder {
+        SceneBuilder::new()
+    }
+
     pub fn push(mut self, item: Object) -> Self {
+        if item.emits_light() {
+            self.lights.push(self.objects.len());
+        }
+
         self.objects.push(item);
 
         self
     }
 
+    /// Finds objects emitting light again, after their shaders changed.
+    pub fn update_lights(&mut self) {
+        self.lights = (0..self.objects.len())
+            .filter(|&i| self.objects[i].emits_light())
+            .collect();
+    }
+
+    /// Random direction from `point` towards one of the lights, with its density per steradian.
+    ///
+    /// Directions are spread over cones around bounding spheres of the lights, so a light may be
+    /// missed by rays bent on the way or by ones passing by its shape. The density is the one of
+    /// [`Scene::light_pdf`].
+    pub fn sample_light(&self, point: Vector3<f64>) -> Option<(Vector3<f64>, f64)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let pick = (rand_unit() * self.lights.len() as f64) as usize;
+        let (axis, cos_max) = self.light_cone(self.lights[pick.min(self.lights.len() - 1)], point);
+        let direction = rand_cone_direction(axis, cos_max);
+
+        Some((direction, self.light_pdf(point, direction)))
+    }
+
+    /// Density per steradian of picking `direction` from `point` by [`Scene::sample_light`].
+    ///
+    /// Cones of all lights add up, as any of them could have been picked.
+    pub fn light_pdf(&self, point: Vector3<f64>, direction: Vector3<f64>) -> f64 {
+        let sum: f64 = self
+            .lights
+            .iter()
+            .map(|&index| self.light_cone(index, point))
+            .filter(|(axis, cos_max)| direction.dot(*axis) >= *cos_max)
+            .map(|(_, cos_max)| cone_pdf(cos_max))
+            .sum();
+
+        sum / self.lights.len().max(1) as f64
+    }
+
+    /// Axis and cosine of half angle of the cone in which `point` sees bounding sphere of object.
+    ///
+    /// Points inside of the sphere get the whole sphere of directions.
+    fn light_cone(&self, index: usize, point: Vector3<f64>) -> (Vector3<f64>, f64) {
+        let bb = self.objects[index].shape.bounding_box();
+        let radius = Vector3::new(
+            bb.x_max - bb.x_min,
+            bb.y_max - bb.y_min,
+            bb.z_max - bb.z_min,
+        )
+        .magnitude()
+            / 2.0;
+
+        let to_center = bb.center() - point;
+        let dist = to_center.magnitude();
+
+        if dist <= radius {
+            return (Vector3::unit_y(), -1.0);
+        }
+
+        let sin_max = radius / dist;
+
+        (to_center / dist, (1.0 - sin_max * sin_max).sqrt())
+    }
+
+    /// Index of the render layer with given name, adding the layer if it doesn't exist yet.
+    pub fn layer_index(&mut self, name: &str) -> usize {
+        match self.layers.iter().position(|l| l == name) {
+            Some(index) => index,
+            None => {
+                self.layers.push(name.into());
+                self.layers.len() - 1
+            }
+        }
+    }
+
+    /// Poses animated parts of the scene at given time in seconds.
+    ///
+    /// With a shutter, animated cameras and objects also get their motion while it is open.
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+
+        let frame = 1.0 / self.animation.fps;
+        let (open, close) = match self.animation.shutter {
+            Some(shutter) => (time + shutter.open * frame, time + shutter.close * frame),
+            None => (time, time),
+        };
+
+        for object in &mut self.objects {
+            object.set_shutter(open, close);
+        }
+
+        if let Some(track) = &self.animation.camera {
+            track.apply(&mut self.camera, time);
+
+            self.camera.motion = self
+                .animation
+                .shutter
+                .map(|_| track.motion(&self.camera, open, close));
+        }
+    }
+
+    /// Light of the background coming along the ray, turned by the sky rotation at the ray time.
+    ///
+    /// With a footprint the light is averaged over it, see [`BackgroundShader::emission_over`].
+    pub fn background_emission(&self, ray: &Ray, footprint: Option<&Footprint>) -> Vector3<f64> {
+        let emission = |ray: &Ray, footprint: Option<&Footprint>| match footprint {
+            Some(footprint) => self.background.emission_over(ray, footprint),
+            None => self.background.emission_at(ray),
+        };
+
+        let sky = match &self.animation.sky {
+            Some(sky) => sky,
+            None => return emission(ray, footprint),
+        };
+
+        let time = self.time + ray.time * self.long_exposure;
+        let rotated = Ray {
+            direction: sky.direction_at(ray.direction, time),
+            ..*ray
+        };
+        let footprint = footprint.map(|f| Footprint {
+            dx: sky.direction_at(f.dx, time),
+            dy: sky.direction_at(f.dy, time),
+        });
+
+        emission(&rotated, footprint.as_ref())
+    }
+
+    /// Whether samples integrate the sky rotation over a time range, see [`Scene::long_exposure`].
+    pub fn has_star_trails(&self) -> bool {
+        self.animation.sky.is_some() && self.long_exposure > 0.0
+    }
+
+    /// Distance to the nearest object surface, negative inside objects.
+    pub fn dist_fn(&self, point: Vector3<f64>) -> f64 {
+        self.objects
+            .iter()
+            .map(|o| o.shape.dist_fn(point))
+            .fold(f64::MAX, f64::min)
+    }
+
+    /// Distances of many points at once, see [`Scene::dist_fn`].
+    pub fn dist_fn_batch(&self, points: &[Vector3<f64>], out: &mut [f64]) {
+        out.fill(f64::MAX);
+
+        let mut dist = [0.0; BATCH_SIZE];
+
+        for (points, out) in points.chunks(BATCH_SIZE).zip(out.chunks_mut(BATCH_SIZE)) {
+            let dist = &mut dist[..points.len()];
+
+            for object in &self.objects {
+                object.shape.dist_fn_batch(points, 0.5, dist);
+
+                for (out, dist) in out.iter_mut().zip(dist.iter()) {
+                    *out = out.min(*dist);
+                }
+            }
+        }
+    }
+
+    /// Sum of strengths of all distortions affecting given point.
+    pub fn distortion_strength(&self, point: Vector3<f64>) -> f64 {
+        self.distortions
+            .iter()
+            .filter(|d| d.dist_fn(point) <= 0.0)
+            .map(|d| d.strength(point))
+            .sum()
+    }
+
+    /// Length of the diagonal of the box around `origin`, all objects and all distortions.
+    ///
+    /// No step from `origin` needs to be longer to reach anything in the scene.
     pub fn max_possible_step(&self, origin: Vector3<f64>) -> f64 {
         let [mut min_x, mut max_x, mut min_y, mut max_y, mut min_z, mut max_z] =
             [origin.x, origin.x, origin.y, origin.y, origin.z, origin.z];
 
-        for object in &self.objects {
-            let bb = object.shape.bounding_box();
+        let objects = self.objects.iter().map(|o| o.shape.bounding_box());
+        let distortions = self.distortions.iter().map(|d| d.shape.bounding_box());
+
+        for bb in objects.chain(distortions) {
             min_x = min_x.min(bb.x_min);
             max_x = max_x.max(bb.x_max);
             min_y = min_y.min(bb.y_min);
@@ -45,9 +304,52 @@ impl Scene {
         }
         let delta_x = max_x - min_x;
         let delta_y = max_y - min_y;
-        let delta_z = max_z - min_y;
+        let delta_z = max_z - min_z;
 
         let delta_xy = (delta_x * delta_x + delta_y * delta_y).sqrt();
         (delta_xy * delta_xy + delta_z * delta_z).sqrt()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::MaterialResult;
+    use crate::object::shape::Sphere;
+    use crate::shader::{ShadeContext, Shader, SolidShader};
+    use crate::Ray;
+
+    #[derive(Clone)]
+    struct Black;
+
+    impl Shader for Black {}
+
+    impl SolidShader for Black {
+        fn material_at(
+            &self,
+            _ray: &Ray,
+            _normal: Vector3<f64>,
+            _ctx: &ShadeContext,
+        ) -> (MaterialResult, Option<Ray>) {
+            (MaterialResult::black(), None)
+        }
+    }
+
+    #[test]
+    fn max_step_reaches_everything() {
+        let mut sphere = Sphere::new();
+        sphere.set_center(Vector3::new(0.0, 10.0, 10.0));
+
+        // depth of the scene along z, with everything high above the ground
+        let scene = Scene::builder()
+            .object(|o| o.shape(sphere).solid(Black))
+            .build();
+        let origin = Vector3::new(0.0, 10.0, -10.0);
+        assert!(scene.max_possible_step(origin) >= 21.0);
+
+        // distortions bend rays passing by without any objects around
+        let scene = Scene::builder().distortion(|d| d.radius(15.0)).build();
+        let origin = Vector3::new(0.0, 0.0, -20.0);
+        assert!(scene.max_possible_step(origin) >= 35.0);
+    }
+}
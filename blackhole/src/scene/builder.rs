@@ -0,0 +1,332 @@
+//! Construction of scenes in code, without a scene file.
+//!
+//! ```
+//! use cgmath::{Vector3, Zero};
+//!
+//! use blackhole::animation::ObjectTrack;
+//! use blackhole::material::MaterialResult;
+//! use blackhole::object::shape::Sphere;
+//! use blackhole::scene::Scene;
+//! use blackhole::shader::{Parameter, ShadeContext, Shader, SolidShader};
+//! use blackhole::Ray;
+//!
+//! /// Surface glowing with a single color.
+//! #[derive(Clone)]
+//! struct Glow(Vector3<f64>);
+//!
+//! impl Shader for Glow {
+//!     fn set_parameter(&mut self, name: &str, value: Parameter) {
+//!         if let ("color", Parameter::Vec3(color)) = (name, value) {
+//!             self.0 = color;
+//!         }
+//!     }
+//!
+//!     fn emits_light(&self) -> bool {
+//!         true
+//!     }
+//! }
+//!
+//! impl SolidShader for Glow {
+//!     fn material_at(
+//!         &self,
+//!         _ray: &Ray,
+//!         _normal: Vector3<f64>,
+//!         _ctx: &ShadeContext,
+//!     ) -> (MaterialResult, Option<Ray>) {
+//!         let mat = MaterialResult {
+//!             emission: self.0,
+//!             ..MaterialResult::black()
+//!         };
+//!
+//!         (mat, None)
+//!     }
+//! }
+//!
+//! let mut moon = Sphere::new();
+//! moon.set_center(Vector3::new(6.0, 0.0, 0.0));
+//!
+//! let scene = Scene::builder()
+//!     .look_from(Vector3::new(0.0, 2.0, -20.0), Vector3::zero())
+//!     .distortion(|d| d.radius(10.0).strength(1.5))
+//!     .object(|o| {
+//!         o.shape(moon)
+//!             .solid(Glow(Vector3::zero()))
+//!             .parameter("color", Parameter::Vec3(Vector3::new(1.0, 0.8, 0.5)))
+//!             .layer("moons")
+//!             .animate(ObjectTrack::Orbit {
+//!                 center: Vector3::zero(),
+//!                 axis: Vector3::unit_y(),
+//!                 period: 10.0,
+//!             })
+//!     })
+//!     .build();
+//!
+//! assert_eq!(scene.layers, ["default", "moons"]);
+//! assert_eq!(scene.lights, [0]);
+//! ```
+
+use std::sync::Arc;
+
+use cgmath::{InnerSpace, Vector3, Zero};
+
+use crate::animation::ObjectTrack;
+use crate::camera::Camera;
+use crate::marcher::RenderSettings;
+use crate::object::shape::Shape;
+use crate::object::{Distortion, Object, Shading};
+use crate::postprocess::Tonemap;
+use crate::scene::Scene;
+use crate::shader::{BackgroundShader, Parameter, Shader, SolidShader, VolumetricShader};
+use crate::Ray;
+
+/// Fluent construction of a [`Scene`], started by [`Scene::builder`].
+///
+/// Scenes without a background get a black one.
+pub struct SceneBuilder {
+    scene: Scene,
+}
+
+impl SceneBuilder {
+    pub fn new() -> Self {
+        Self {
+            scene: Scene::new(Arc::new(Black)),
+        }
+    }
+
+    pub fn background(mut self, shader: impl BackgroundShader + 'static) -> Self {
+        self.scene.background = Arc::new(shader);
+        self
+    }
+
+    pub fn camera(mut self, camera: Camera) -> Self {
+        self.scene.camera = camera;
+        self
+    }
+
+    /// Moves the camera to `location`, turned towards `target`.
+    pub fn look_from(mut self, location: Vector3<f64>, target: Vector3<f64>) -> Self {
+        self.scene.camera.location = location;
+        self.scene.camera.look_at(target);
+        self
+    }
+
+    /// Adds an object set up by `build`, which needs to give it a shape and a shader.
+    ///
+    /// # Panics
+    ///
+    /// When the object has no shape or shader.
+    pub fn object(mut self, build: impl FnOnce(ObjectBuilder) -> ObjectBuilder) -> Self {
+        let builder = build(ObjectBuilder::new());
+
+        let shape = builder.shape.expect("object needs a shape");
+        let shading = builder.shading.expect("object needs a shader");
+
+        let mut object = match shading {
+            Shading::Solid(shader) => Object::solid(shape, shader),
+            Shading::Volumetric(shader) => Object::volumetric(shape, shader),
+        };
+
+        for (name, value) in builder.parameters {
+            object.set_shader_parameter(&name, value);
+        }
+
+        object.instance_seed = builder.instance_seed;
+
+        if let Some(layer) = &builder.layer {
+            object.layer = self.scene.layer_index(layer);
+        }
+
+        if let Some(track) = builder.track {
+            object = object.animate(track);
+        }
+
+        self.scene = self.scene.push(object);
+        self
+    }
+
+    /// Adds a distortion set up by `build`, starting from [`Distortion::new`].
+    pub fn distortion(
+        mut self,
+        build: impl FnOnce(DistortionBuilder) -> DistortionBuilder,
+    ) -> Self {
+        let builder = build(DistortionBuilder {
+            distortion: Distortion::new(),
+        });
+
+        self.scene.distortions.push(builder.distortion);
+        self
+    }
+
+    pub fn tonemap(mut self, tonemap: Tonemap) -> Self {
+        self.scene.tonemap = tonemap;
+        self
+    }
+
+    pub fn render_settings(mut self, render: RenderSettings) -> Self {
+        self.scene.render = render;
+        self
+    }
+
+    /// Finished scene, posed at time 0.
+    pub fn build(mut self) -> Scene {
+        self.scene.set_time(0.0);
+        self.scene
+    }
+}
+
+impl Default for SceneBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Object of a [`SceneBuilder`], see [`SceneBuilder::object`].
+pub struct ObjectBuilder {
+    shape: Option<Arc<dyn Shape>>,
+    shading: Option<Shading>,
+    /// Shader parameters, set on the shader once the object is complete
+    parameters: Vec<(String, Parameter)>,
+    instance_seed: u64,
+    layer: Option<String>,
+    track: Option<ObjectTrack>,
+}
+
+impl ObjectBuilder {
+    fn new() -> Self {
+        Self {
+            shape: None,
+            shading: None,
+            parameters: Vec::new(),
+            instance_seed: 0,
+            layer: None,
+            track: None,
+        }
+    }
+
+    pub fn shape(mut self, shape: impl Shape + 'static) -> Self {
+        self.shape = Some(Arc::new(shape));
+        self
+    }
+
+    /// Shape shared with other objects.
+    pub fn shared_shape(mut self, shape: Arc<dyn Shape>) -> Self {
+        self.shape = Some(shape);
+        self
+    }
+
+    pub fn solid(mut self, shader: impl SolidShader + 'static) -> Self {
+        self.shading = Some(Shading::Solid(Arc::new(shader)));
+        self
+    }
+
+    pub fn volumetric(mut self, shader: impl VolumetricShader + 'static) -> Self {
+        self.shading = Some(Shading::Volumetric(Arc::new(shader)));
+        self
+    }
+
+    /// Shader shared with other objects, parameters set on the object don't change the others.
+    pub fn shading(mut self, shading: Shading) -> Self {
+        self.shading = Some(shading);
+        self
+    }
+
+    /// Sets a parameter of the shader, like the scene loader does with ones of scene files.
+    pub fn parameter(mut self, name: &str, value: Parameter) -> Self {
+        self.parameters.push((name.into(), value));
+        self
+    }
+
+    /// See [`Object::instance_seed`].
+    pub fn instance_seed(mut self, seed: u64) -> Self {
+        self.instance_seed = seed;
+        self
+    }
+
+    /// Render layer with given name, added to the scene if it's not there yet.
+    pub fn layer(mut self, name: &str) -> Self {
+        self.layer = Some(name.into());
+        self
+    }
+
+    /// Moves the object along the track, see [`Object::animate`].
+    pub fn animate(mut self, track: ObjectTrack) -> Self {
+        self.track = Some(track);
+        self
+    }
+}
+
+/// Distortion of a [`SceneBuilder`], see [`SceneBuilder::distortion`].
+pub struct DistortionBuilder {
+    distortion: Distortion,
+}
+
+impl DistortionBuilder {
+    pub fn center(mut self, center: Vector3<f64>) -> Self {
+        self.distortion.shape.set_center(center);
+        self
+    }
+
+    /// Radius of the sphere rays are bent in.
+    pub fn radius(mut self, radius: f64) -> Self {
+        self.distortion.shape.set_radius(radius);
+        self
+    }
+
+    pub fn strength(mut self, strength: f64) -> Self {
+        self.distortion.strength = strength;
+        self
+    }
+
+    /// Rotation of the distortion, see [`Distortion::spin`].
+    ///
+    /// # Panics
+    ///
+    /// When the spin is not between -1 and 1 or the axis has zero length.
+    pub fn spin(mut self, axis: Vector3<f64>, spin: f64) -> Self {
+        assert!(
+            (-1.0..=1.0).contains(&spin),
+            "distortion spin must be between -1 and 1, got {spin}"
+        );
+        assert!(
+            axis.magnitude2() > 0.0,
+            "distortion spin axis has zero length"
+        );
+
+        self.distortion.spin_axis = axis.normalize();
+        self.distortion.spin = spin;
+        self
+    }
+
+    /// See [`Distortion::horizon_radius`].
+    ///
+    /// # Panics
+    ///
+    /// When the radius is not positive.
+    pub fn horizon(mut self, radius: f64) -> Self {
+        assert!(
+            radius > 0.0,
+            "horizon radius must be positive, got {radius}"
+        );
+
+        self.distortion.horizon_radius = Some(radius);
+        self
+    }
+
+    /// Shader of rays captured by the distortion, see [`Distortion::capture_shader`].
+    pub fn capture_shader(mut self, shader: impl BackgroundShader + 'static) -> Self {
+        self.distortion.capture_shader = Some(Arc::new(shader));
+        self
+    }
+}
+
+/// Background of scenes which didn't set one.
+#[derive(Clone)]
+struct Black;
+
+impl Shader for Black {}
+
+impl BackgroundShader for Black {
+    fn emission_at(&self, _ray: &Ray) -> Vector3<f64> {
+        Vector3::zero()
+    }
+}
@@ -0,0 +1,189 @@
+use cgmath::{InnerSpace, Vector3};
+use std::fmt::{Display, Formatter};
+
+use crate::scene::Scene;
+
+/// Most samples of a plane section, about 1 GB of them.
+pub const MAX_PLANE_SAMPLES: usize = 1 << 25;
+
+/// Reasons a plane section can't be sampled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SectionError {
+    /// Start and end of the line are the same point, or aren't finite
+    EmptyLine,
+    /// Vertical extent is zero or isn't finite
+    EmptyUp,
+    /// Plane would have more than [`MAX_PLANE_SAMPLES`]
+    TooLarge { width: usize, height: usize },
+}
+
+impl Display for SectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyLine => write!(f, "start and end of the section must be different points"),
+            Self::EmptyUp => write!(f, "vertical extent of the section must not be zero"),
+            Self::TooLarge { width, height } => write!(
+                f,
+                "section of {width}x{height} samples is larger than {MAX_PLANE_SAMPLES}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SectionError {}
+
+/// Values of scene fields at one point of a cross-section.
+#[derive(Copy, Clone, Debug)]
+pub struct SectionSample {
+    pub position: Vector3<f64>,
+    /// Distance to the nearest object, see [`Scene::dist_fn`]
+    pub distance: f64,
+    /// See [`Scene::distortion_strength`]
+    pub strength: f64,
+}
+
+impl SectionSample {
+    pub fn at(scene: &Scene, position: Vector3<f64>) -> Self {
+        Self {
+            position,
+            distance: scene.dist_fn(position),
+            strength: scene.distortion_strength(position),
+        }
+    }
+}
+
+pub struct PlaneSection {
+    pub width: usize,
+    pub height: usize,
+    /// Samples in row-major order, first row is at `start + up / 2`
+    pub samples: Vec<SectionSample>,
+}
+
+/// Samples scene fields on `samples` evenly spaced points from `start` to `end`, both included.
+pub fn line(
+    scene: &Scene,
+    start: Vector3<f64>,
+    end: Vector3<f64>,
+    samples: usize,
+) -> Vec<SectionSample> {
+    let divisor = (samples.max(2) - 1) as f64;
+
+    let positions: Vec<_> = (0..samples)
+        .map(|i| start + (end - start) * (i as f64 / divisor))
+        .collect();
+
+    let mut distances = vec![0.0; samples];
+    scene.dist_fn_batch(&positions, &mut distances);
+
+    positions
+        .into_iter()
+        .zip(distances)
+        .map(|(position, distance)| SectionSample {
+            position,
+            distance,
+            strength: scene.distortion_strength(position),
+        })
+        .collect()
+}
+
+/// Samples scene fields on a rectangle spanned by line from `start` to `end` horizontally and
+/// vector `up` vertically, centered on the line.
+///
+/// Rows get as many samples as `width` gives for the length of the line, with square cells.
+pub fn plane(
+    scene: &Scene,
+    start: Vector3<f64>,
+    end: Vector3<f64>,
+    up: Vector3<f64>,
+    width: usize,
+) -> Result<PlaneSection, SectionError> {
+    let length = (end - start).magnitude();
+    let extent = up.magnitude();
+
+    if !(length > 0.0 && length.is_finite()) {
+        return Err(SectionError::EmptyLine);
+    }
+    if !(extent > 0.0 && extent.is_finite()) {
+        return Err(SectionError::EmptyUp);
+    }
+
+    let width = width.max(1);
+    let height = (width as f64 * extent / length).round().max(1.0);
+
+    if width as f64 * height > MAX_PLANE_SAMPLES as f64 {
+        return Err(SectionError::TooLarge {
+            width,
+            height: height as usize,
+        });
+    }
+
+    let height = height as usize;
+    let mut samples = Vec::with_capacity(width * height);
+    let mut positions = Vec::with_capacity(width);
+    let mut distances = vec![0.0; width];
+
+    for y in 0..height {
+        let rel_y = 0.5 - (y as f64 + 0.5) / height as f64;
+
+        positions.clear();
+        positions.extend((0..width).map(|x| {
+            let rel_x = (x as f64 + 0.5) / width as f64;
+
+            start + (end - start) * rel_x + up * rel_y
+        }));
+
+        scene.dist_fn_batch(&positions, &mut distances);
+
+        samples.extend(
+            positions
+                .iter()
+                .zip(&distances)
+                .map(|(&position, &distance)| SectionSample {
+                    position,
+                    distance,
+                    strength: scene.distortion_strength(position),
+                }),
+        );
+    }
+
+    Ok(PlaneSection {
+        width,
+        height,
+        samples,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Zero;
+
+    #[test]
+    fn degenerate_planes_are_rejected() {
+        let scene = Scene::builder().build();
+        let (start, end) = (Vector3::zero(), Vector3::unit_x());
+        let plane = |start, end, up, width| plane(&scene, start, end, up, width).map(|p| p.height);
+
+        assert_eq!(
+            plane(start, start, Vector3::unit_y(), 8),
+            Err(SectionError::EmptyLine)
+        );
+        assert_eq!(
+            plane(start, end * f64::INFINITY, Vector3::unit_y(), 8),
+            Err(SectionError::EmptyLine)
+        );
+        assert_eq!(
+            plane(start, end, Vector3::zero(), 8),
+            Err(SectionError::EmptyUp)
+        );
+        assert_eq!(
+            plane(start, end, Vector3::new(f64::NAN, 0.0, 0.0), 8),
+            Err(SectionError::EmptyUp)
+        );
+        assert!(matches!(
+            plane(start, end * 1e-9, Vector3::unit_y(), 8),
+            Err(SectionError::TooLarge { width: 8, .. })
+        ));
+        assert_eq!(plane(start, end, Vector3::unit_y() * 0.5, 8), Ok(4));
+    }
+}
@@ -1,28 +1,182 @@
-use crate::material::MaterialResult;
+use crate::material::{MaterialResult, Subsurface};
+use crate::math::splitmix64;
 use crate::Ray;
-use cgmath::Vector3;
+use cgmath::{Vector2, Vector3};
+use std::path::PathBuf;
+use std::sync::Arc;
 
+#[derive(Clone, Debug)]
 pub enum Parameter {
     Usize(usize),
     Float(f64),
     Vec3(Vector3<f64>),
+    /// Path to an asset file, already resolved by the loader
+    Path(PathBuf),
+    /// Name of an option, like the kind of noise
+    Text(String),
 }
 
 pub trait Shader: Send + Sync {
     #[allow(unused_variables)]
     /// Method for changing shader parameters. Used in loader.
     fn set_parameter(&mut self, name: &str, value: Parameter) {}
+
+    #[allow(unused_variables)]
+    /// Current value of a parameter, for editors changing it relative to where it is.
+    ///
+    /// Only parameters which editors change need to be reported.
+    fn parameter(&self, name: &str) -> Option<Parameter> {
+        None
+    }
+
+    /// Name of the implementing type without its module path, for diagnostics.
+    fn type_name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    /// Whether the shader can emit any light.
+    ///
+    /// Objects with emitting shaders are sampled directly as lights, see [`Scene::lights`].
+    ///
+    /// [`Scene::lights`]: crate::scene::Scene::lights
+    fn emits_light(&self) -> bool {
+        false
+    }
+
+    #[allow(unused_variables)]
+    /// Per channel factor by which setting the parameter scales emitted light.
+    ///
+    /// Returns `None` unless the parameter only scales emission, which lets renderers rescale
+    /// accumulated light instead of starting over.
+    fn emission_scale(&self, name: &str, value: &Parameter) -> Option<Vector3<f64>> {
+        None
+    }
+}
+
+pub trait SolidShader: Shader + SolidShaderClone {
+    fn material_at(
+        &self,
+        ray: &Ray,
+        normal: Vector3<f64>,
+        ctx: &ShadeContext,
+    ) -> (MaterialResult, Option<Ray>);
+
+    /// Scattering below the surface, used instead of [`SolidShader::material_at`] rays for
+    /// [`Subsurface::weight`] of hits.
+    fn subsurface(&self) -> Option<Subsurface> {
+        None
+    }
+
+    #[allow(unused_variables)]
+    /// Normal used for shading instead of the one of the shape, for bumps smaller than the shape.
+    fn shading_normal(&self, ray: &Ray, normal: Vector3<f64>, ctx: &ShadeContext) -> Vector3<f64> {
+        normal
+    }
 }
 
-pub trait SolidShader: Shader {
-    fn material_at(&self, ray: &Ray, normal: Vector3<f64>) -> (MaterialResult, Option<Ray>);
+pub trait VolumetricShader: Shader + VolumetricShaderClone {
+    fn density_at(&self, position: Vector3<f64>, ctx: &ShadeContext) -> f64;
+    fn material_at(&self, ray: &Ray, ctx: &ShadeContext) -> (MaterialResult, Option<Ray>);
+
+    #[allow(unused_variables)]
+    /// Phase function of the event which scattered `ray` into `scattered`, for light coming from
+    /// `direction`, per steradian.
+    ///
+    /// Shaders returning it must pick directions of scattered rays with the same density and
+    /// return albedo not depending on them. Their scattering events then also sample lights
+    /// directly. `None` turns light sampling off, like for rays passing straight through.
+    fn phase(&self, ray: &Ray, scattered: &Ray, direction: Vector3<f64>) -> Option<f64> {
+        None
+    }
 }
 
-pub trait VolumetricShader: Shader {
-    fn density_at(&self, position: Vector3<f64>) -> f64;
-    fn material_at(&self, ray: &Ray) -> (MaterialResult, Option<Ray>);
+/// Information about the shaded object instance.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ShadeContext {
+    /// Seed of the instance, objects sharing one shader use it to look different
+    pub instance_seed: u64,
+    /// Surface coordinates of the shaded point, if the shape has them
+    pub uv: Option<Vector2<f64>>,
+    /// Shading without randomness, see [`RenderMode::Deterministic`](crate::RenderMode).
+    /// Shaders don't draw random numbers then, solids return their mirror reflection
+    pub deterministic: bool,
 }
 
-pub trait BackgroundShader: Shader {
+impl ShadeContext {
+    /// Offset to add to texture lookup coordinates, unique for every instance seed.
+    ///
+    /// Seed of 0 has no offset.
+    pub fn texture_offset(&self) -> Vector3<f64> {
+        if self.instance_seed == 0 {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+
+        // splitmix64 steps, spread over the 256 unit period of noise textures
+        let mut state = self.instance_seed;
+        let mut next = || {
+            let z = splitmix64(state);
+            state = state.wrapping_add(0x9E3779B97F4A7C15);
+
+            (z >> 11) as f64 / (1_u64 << 53) as f64 * 256.0
+        };
+
+        Vector3::new(next(), next(), next())
+    }
+}
+
+pub trait BackgroundShader: Shader + BackgroundShaderClone {
     fn emission_at(&self, ray: &Ray) -> Vector3<f64>;
+
+    #[allow(unused_variables)]
+    /// Light averaged over the patch of background seen by a pixel.
+    ///
+    /// Backgrounds with details smaller than pixels override this, so lensing which stretches
+    /// or squeezes the patch doesn't make the details pop in and out.
+    fn emission_over(&self, ray: &Ray, footprint: &Footprint) -> Vector3<f64> {
+        self.emission_at(ray)
+    }
+}
+
+/// Change of the ray direction towards rays of neighbouring pixels, along both image axes.
+#[derive(Copy, Clone, Debug)]
+pub struct Footprint {
+    pub dx: Vector3<f64>,
+    pub dy: Vector3<f64>,
+}
+
+/// Clone of shader behind trait object, implemented for every `Clone` shader.
+///
+/// Shaders with large data (tables, stars) should keep it behind `Arc`, so the clone stays cheap.
+pub trait SolidShaderClone {
+    fn clone_solid(&self) -> Arc<dyn SolidShader>;
+}
+
+impl<T: SolidShader + Clone + 'static> SolidShaderClone for T {
+    fn clone_solid(&self) -> Arc<dyn SolidShader> {
+        Arc::new(self.clone())
+    }
+}
+
+/// See [`SolidShaderClone`].
+pub trait VolumetricShaderClone {
+    fn clone_volumetric(&self) -> Arc<dyn VolumetricShader>;
+}
+
+impl<T: VolumetricShader + Clone + 'static> VolumetricShaderClone for T {
+    fn clone_volumetric(&self) -> Arc<dyn VolumetricShader> {
+        Arc::new(self.clone())
+    }
+}
+
+/// See [`SolidShaderClone`].
+pub trait BackgroundShaderClone {
+    fn clone_background(&self) -> Arc<dyn BackgroundShader>;
+}
+
+impl<T: BackgroundShader + Clone + 'static> BackgroundShaderClone for T {
+    fn clone_background(&self) -> Arc<dyn BackgroundShader> {
+        Arc::new(self.clone())
+    }
 }
@@ -0,0 +1,395 @@
+//! Vectorized kernels for the hottest math, selected at runtime by CPU features.
+//!
+//! Every kernel has a fallback in [`scalar`] which gives bit-identical results, so renders don't
+//! depend on the CPU they were made on. Setting `BLACKHOLE_SIMD=0` forces the fallbacks.
+
+use cgmath::Vector3;
+use once_cell::sync::Lazy;
+
+use crate::framebuffer::Pixel;
+
+/// Instruction set used by the kernels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Level {
+    Scalar,
+    Avx2,
+    Neon,
+}
+
+static LEVEL: Lazy<Level> = Lazy::new(detect);
+
+/// Instruction set detected on this CPU.
+pub fn level() -> Level {
+    *LEVEL
+}
+
+fn detect() -> Level {
+    if std::env::var_os("BLACKHOLE_SIMD").is_some_and(|v| v == "0") {
+        return Level::Scalar;
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        return Level::Avx2;
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        return Level::Neon;
+    }
+
+    Level::Scalar
+}
+
+/// Blends pixels as `dst * keep + src * add`, used for accumulating samples.
+pub fn blend_pixels(dst: &mut [Pixel], src: &[Pixel], keep: f32, add: f32) {
+    assert_eq!(dst.len(), src.len());
+
+    match level() {
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx2 => unsafe { x86::blend_pixels(dst, src, keep, add) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => unsafe { neon::blend_pixels(dst, src, keep, add) },
+        _ => scalar::blend_pixels(dst, src, keep, add),
+    }
+}
+
+/// Distances of points to surface of a sphere.
+pub fn sphere_dist(points: &[Vector3<f64>], center: Vector3<f64>, radius: f64, out: &mut [f64]) {
+    assert_eq!(points.len(), out.len());
+
+    match level() {
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx2 => unsafe { x86::sphere_dist(points, center, radius, out) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => unsafe { neon::sphere_dist(points, center, radius, out) },
+        _ => scalar::sphere_dist(points, center, radius, out),
+    }
+}
+
+/// Interpolates gradients at corners of a unit cell of Perlin noise.
+///
+/// Corner with offset `(x, y, z)` has index `x * 4 + y * 2 + z`, `u`, `v` and `w` are the
+/// position inside the cell.
+pub fn perlin_filter(gradients: &[Vector3<f64>; 8], u: f64, v: f64, w: f64) -> f64 {
+    match level() {
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx2 => unsafe { x86::perlin_filter(gradients, u, v, w) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => unsafe { neon::perlin_filter(gradients, u, v, w) },
+        _ => scalar::perlin_filter(gradients, u, v, w),
+    }
+}
+
+/// Smoothstep weights of the cell corners along one axis.
+fn fade(t: f64) -> (f64, f64) {
+    let tt = t * t * (3.0 - 2.0 * t);
+
+    (1.0 - tt, tt)
+}
+
+pub mod scalar {
+    use cgmath::{InnerSpace, Vector3};
+
+    use crate::framebuffer::Pixel;
+
+    pub fn blend_pixels(dst: &mut [Pixel], src: &[Pixel], keep: f32, add: f32) {
+        for (dst, src) in dst.iter_mut().zip(src) {
+            *dst = *dst * keep + *src * add;
+        }
+    }
+
+    pub fn sphere_dist(
+        points: &[Vector3<f64>],
+        center: Vector3<f64>,
+        radius: f64,
+        out: &mut [f64],
+    ) {
+        for (point, out) in points.iter().zip(out) {
+            *out = (point - center).magnitude() - radius;
+        }
+    }
+
+    pub fn perlin_filter(gradients: &[Vector3<f64>; 8], u: f64, v: f64, w: f64) -> f64 {
+        let fx = super::fade(u);
+        let fy = super::fade(v);
+        let fz = super::fade(w);
+
+        let mut acc = 0.0;
+
+        for (i, gradient) in gradients.iter().enumerate() {
+            let (x, y, z) = (i >> 2, (i >> 1) & 1, i & 1);
+
+            let offset = Vector3::new(u - x as f64, v - y as f64, w - z as f64);
+            let factor = [fx.0, fx.1][x] * [fy.0, fy.1][y] * [fz.0, fz.1][z];
+
+            acc += factor * gradient.dot(offset);
+        }
+
+        acc
+    }
+}
+
+// The kernels do the same operations in the same order as the scalar versions, without fused
+// multiply-add, so the results match exactly.
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use cgmath::Vector3;
+
+    use crate::framebuffer::Pixel;
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn blend_pixels(dst: &mut [Pixel], src: &[Pixel], keep: f32, add: f32) {
+        let split = dst.len() / 2 * 2;
+
+        let keep_v = _mm256_set1_ps(keep);
+        let add_v = _mm256_set1_ps(add);
+
+        let dst_ptr = dst.as_mut_ptr() as *mut f32;
+        let src_ptr = src.as_ptr() as *const f32;
+
+        // two pixels per register
+        for i in (0..split * 4).step_by(8) {
+            let d = _mm256_loadu_ps(dst_ptr.add(i));
+            let s = _mm256_loadu_ps(src_ptr.add(i));
+
+            let res = _mm256_add_ps(_mm256_mul_ps(d, keep_v), _mm256_mul_ps(s, add_v));
+            _mm256_storeu_ps(dst_ptr.add(i), res);
+        }
+
+        super::scalar::blend_pixels(&mut dst[split..], &src[split..], keep, add);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sphere_dist(
+        points: &[Vector3<f64>],
+        center: Vector3<f64>,
+        radius: f64,
+        out: &mut [f64],
+    ) {
+        let split = points.len() / 4 * 4;
+
+        let cx = _mm256_set1_pd(center.x);
+        let cy = _mm256_set1_pd(center.y);
+        let cz = _mm256_set1_pd(center.z);
+        let r = _mm256_set1_pd(radius);
+
+        for (chunk, out) in points[..split]
+            .chunks_exact(4)
+            .zip(out[..split].chunks_exact_mut(4))
+        {
+            let [p0, p1, p2, p3] = [chunk[0], chunk[1], chunk[2], chunk[3]];
+
+            let dx = _mm256_sub_pd(_mm256_set_pd(p3.x, p2.x, p1.x, p0.x), cx);
+            let dy = _mm256_sub_pd(_mm256_set_pd(p3.y, p2.y, p1.y, p0.y), cy);
+            let dz = _mm256_sub_pd(_mm256_set_pd(p3.z, p2.z, p1.z, p0.z), cz);
+
+            let len2 = _mm256_add_pd(
+                _mm256_add_pd(_mm256_mul_pd(dx, dx), _mm256_mul_pd(dy, dy)),
+                _mm256_mul_pd(dz, dz),
+            );
+
+            let dist = _mm256_sub_pd(_mm256_sqrt_pd(len2), r);
+            _mm256_storeu_pd(out.as_mut_ptr(), dist);
+        }
+
+        super::scalar::sphere_dist(&points[split..], center, radius, &mut out[split..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn perlin_filter(gradients: &[Vector3<f64>; 8], u: f64, v: f64, w: f64) -> f64 {
+        let fx = super::fade(u);
+        let fy = super::fade(v);
+        let fz = super::fade(w);
+
+        // lanes are corners with the same x offset, y and z offsets are 00, 01, 10, 11
+        let wy = _mm256_set_pd(v - 1.0, v - 1.0, v, v);
+        let wz = _mm256_set_pd(w - 1.0, w, w - 1.0, w);
+        let fy_v = _mm256_set_pd(fy.1, fy.1, fy.0, fy.0);
+        let fz_v = _mm256_set_pd(fz.1, fz.0, fz.1, fz.0);
+
+        let mut products = [0.0; 8];
+
+        for (x, (wx, fx)) in [(u, fx.0), (u - 1.0, fx.1)].into_iter().enumerate() {
+            let g = &gradients[x * 4..x * 4 + 4];
+
+            let gx = _mm256_set_pd(g[3].x, g[2].x, g[1].x, g[0].x);
+            let gy = _mm256_set_pd(g[3].y, g[2].y, g[1].y, g[0].y);
+            let gz = _mm256_set_pd(g[3].z, g[2].z, g[1].z, g[0].z);
+
+            let dot = _mm256_add_pd(
+                _mm256_add_pd(_mm256_mul_pd(gx, _mm256_set1_pd(wx)), _mm256_mul_pd(gy, wy)),
+                _mm256_mul_pd(gz, wz),
+            );
+
+            // factor is multiplied as (fx * fy) * fz like in the scalar version
+            let factor = _mm256_mul_pd(_mm256_mul_pd(_mm256_set1_pd(fx), fy_v), fz_v);
+
+            _mm256_storeu_pd(products[x * 4..].as_mut_ptr(), _mm256_mul_pd(factor, dot));
+        }
+
+        products.iter().fold(0.0, |acc, p| acc + p)
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use cgmath::Vector3;
+
+    use crate::framebuffer::Pixel;
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn blend_pixels(dst: &mut [Pixel], src: &[Pixel], keep: f32, add: f32) {
+        let keep_v = vdupq_n_f32(keep);
+        let add_v = vdupq_n_f32(add);
+
+        // one pixel per register
+        for (dst, src) in dst.iter_mut().zip(src) {
+            let dst_ptr = dst as *mut Pixel as *mut f32;
+
+            let d = vld1q_f32(dst_ptr);
+            let s = vld1q_f32(src as *const Pixel as *const f32);
+
+            vst1q_f32(
+                dst_ptr,
+                vaddq_f32(vmulq_f32(d, keep_v), vmulq_f32(s, add_v)),
+            );
+        }
+    }
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn sphere_dist(
+        points: &[Vector3<f64>],
+        center: Vector3<f64>,
+        radius: f64,
+        out: &mut [f64],
+    ) {
+        let split = points.len() / 2 * 2;
+
+        let cx = vdupq_n_f64(center.x);
+        let cy = vdupq_n_f64(center.y);
+        let cz = vdupq_n_f64(center.z);
+        let r = vdupq_n_f64(radius);
+
+        for (chunk, out) in points[..split]
+            .chunks_exact(2)
+            .zip(out[..split].chunks_exact_mut(2))
+        {
+            let lanes = |a: f64, b: f64| vld1q_f64([a, b].as_ptr());
+
+            let dx = vsubq_f64(lanes(chunk[0].x, chunk[1].x), cx);
+            let dy = vsubq_f64(lanes(chunk[0].y, chunk[1].y), cy);
+            let dz = vsubq_f64(lanes(chunk[0].z, chunk[1].z), cz);
+
+            let len2 = vaddq_f64(
+                vaddq_f64(vmulq_f64(dx, dx), vmulq_f64(dy, dy)),
+                vmulq_f64(dz, dz),
+            );
+
+            vst1q_f64(out.as_mut_ptr(), vsubq_f64(vsqrtq_f64(len2), r));
+        }
+
+        super::scalar::sphere_dist(&points[split..], center, radius, &mut out[split..]);
+    }
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn perlin_filter(gradients: &[Vector3<f64>; 8], u: f64, v: f64, w: f64) -> f64 {
+        let fx = super::fade(u);
+        let fy = super::fade(v);
+        let fz = super::fade(w);
+
+        let fz_v = vld1q_f64([fz.0, fz.1].as_ptr());
+        let wz = vld1q_f64([w, w - 1.0].as_ptr());
+
+        let mut products = [0.0; 8];
+
+        // lanes are pairs of corners differing in the z offset
+        for (pair, g) in gradients.chunks_exact(2).enumerate() {
+            let (x, y) = (pair >> 1, pair & 1);
+
+            let wx = [u, u - 1.0][x];
+            let wy = [v, v - 1.0][y];
+
+            let gx = vld1q_f64([g[0].x, g[1].x].as_ptr());
+            let gy = vld1q_f64([g[0].y, g[1].y].as_ptr());
+            let gz = vld1q_f64([g[0].z, g[1].z].as_ptr());
+
+            let dot = vaddq_f64(
+                vaddq_f64(
+                    vmulq_f64(gx, vdupq_n_f64(wx)),
+                    vmulq_f64(gy, vdupq_n_f64(wy)),
+                ),
+                vmulq_f64(gz, wz),
+            );
+
+            let fxy = [fx.0, fx.1][x] * [fy.0, fy.1][y];
+            let factor = vmulq_f64(vdupq_n_f64(fxy), fz_v);
+
+            vst1q_f64(products[pair * 2..].as_mut_ptr(), vmulq_f64(factor, dot));
+        }
+
+        products.iter().fold(0.0, |acc, p| acc + p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    fn random_points(count: usize) -> Vec<Vector3<f64>> {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(7);
+
+        (0..count)
+            .map(|_| {
+                Vector3::new(
+                    rng.gen_range(-5.0..5.0),
+                    rng.gen(),
+                    rng.gen_range(-1.0..1.0),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn kernels_match_scalar() {
+        let points = random_points(103);
+
+        let mut fast = vec![0.0; points.len()];
+        let mut slow = vec![0.0; points.len()];
+        let center = Vector3::new(0.5, -0.25, 1.0);
+
+        sphere_dist(&points, center, 1.5, &mut fast);
+        scalar::sphere_dist(&points, center, 1.5, &mut slow);
+        assert_eq!(fast, slow);
+
+        for cell in points.chunks_exact(9) {
+            let gradients = std::array::from_fn(|i| cell[i]);
+            let p = cell[8];
+
+            assert_eq!(
+                perlin_filter(&gradients, p.x.fract().abs(), p.y, p.z.abs()).to_bits(),
+                scalar::perlin_filter(&gradients, p.x.fract().abs(), p.y, p.z.abs()).to_bits()
+            );
+        }
+
+        let to_pixel = |p: &Vector3<f64>| Pixel::new(p.x as f32, p.y as f32, p.z as f32, 1.0);
+        let src: Vec<_> = points.iter().map(to_pixel).collect();
+        let mut fast: Vec<_> = points.iter().rev().map(to_pixel).collect();
+        let mut slow = fast.clone();
+
+        blend_pixels(&mut fast, &src, 0.75, 0.25);
+        scalar::blend_pixels(&mut slow, &src, 0.75, 0.25);
+
+        for (a, b) in fast.iter().zip(&slow) {
+            assert_eq!([a.r, a.g, a.b, a.a], [b.r, b.g, b.b, b.a]);
+        }
+    }
+}
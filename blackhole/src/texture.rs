@@ -1,13 +1,233 @@
-use cgmath::Vector3;
+use cgmath::{InnerSpace, Vector2, Vector3};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
 
+use crate::object::AABB;
+
+mod baked;
+mod curl;
+mod image;
 mod perlin;
+mod triplanar;
 mod worley;
 
+pub use baked::BakedTexture3D;
+pub use curl::CurlNoiseTexture3D;
+pub use image::ImageTexture2D;
 pub use perlin::NoiseTexture3D;
+pub use triplanar::Triplanar;
 pub use worley::WorleyTexture3D;
 
+/// Fractal sum of noise octaves, each with frequency multiplied by `lacunarity` and amplitude
+/// by `gain` from the previous one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fbm {
+    pub octaves: u8,
+    pub lacunarity: f64,
+    pub gain: f64,
+}
+
+impl Fbm {
+    /// Octaves doubling in frequency and halving in amplitude.
+    pub fn new(octaves: u8) -> Self {
+        Self {
+            octaves,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    /// Sum of `sample` over the octaves at given position.
+    #[inline]
+    pub fn sum(&self, position: Vector3<f64>, sample: impl Fn(Vector3<f64>) -> f64) -> f64 {
+        (0..self.octaves)
+            .map(|i| {
+                let i = i as i32;
+
+                sample(position * self.lacunarity.powi(i)) * self.gain.powi(i)
+            })
+            .sum()
+    }
+}
+
 pub trait Texture3D: Send + Sync {
     type Output;
 
     fn color_at(&self, position: Vector3<f64>) -> Self::Output;
 }
+
+/// Texture looked up by surface coordinates, see [`crate::object::shape::Shape::uv_at`].
+pub trait Texture2D: Send + Sync {
+    type Output;
+
+    fn color_at(&self, uv: Vector2<f64>) -> Self::Output;
+}
+
+/// Scalar texture with static dispatch for the built-in textures.
+///
+/// Shaders sample textures on every marching step, matching on the kind lets the built-ins be
+/// inlined, unlike calls through a trait object. Other implementations go to `Custom`.
+#[derive(Clone)]
+pub enum TextureKind {
+    Perlin(NoiseTexture3D),
+    Worley(WorleyTexture3D),
+    /// Curl noise sampled as the length of its vectors, strongest in the middle of swirls
+    Curl(CurlNoiseTexture3D),
+    Baked(BakedTexture3D),
+    Custom(Arc<dyn Texture3D<Output = f64>>),
+}
+
+impl TextureKind {
+    /// Built-in noise by name, `perlin`, `worley` or `curl`.
+    pub fn noise(name: &str, scale: f64, seed: u64, fbm: Fbm) -> Option<Self> {
+        let kind = match name {
+            "perlin" => Self::Perlin(NoiseTexture3D::new(scale, seed, fbm.octaves).with_fbm(fbm)),
+            "worley" => Self::Worley(WorleyTexture3D::new(scale, seed).with_fbm(fbm)),
+            "curl" => Self::Curl(CurlNoiseTexture3D::new(scale, seed, fbm.octaves).with_fbm(fbm)),
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+
+    /// Samples the texture into a grid over `bounds`, see [`BakedTexture3D`].
+    ///
+    /// Baked textures are baked again from their original.
+    pub fn bake(&self, bounds: AABB, resolution: usize) -> Self {
+        Self::Baked(BakedTexture3D::new(
+            self.unbaked().clone(),
+            bounds,
+            resolution,
+        ))
+    }
+
+    /// Same texture from another seed, custom textures stay the same.
+    pub fn with_seed(&self, seed: u64) -> Self {
+        match self {
+            Self::Perlin(texture) => Self::Perlin(texture.with_seed(seed)),
+            Self::Worley(texture) => Self::Worley(texture.with_seed(seed)),
+            Self::Curl(texture) => Self::Curl(texture.with_seed(seed)),
+            Self::Baked(texture) => {
+                Self::Baked(texture.with_source(texture.source().with_seed(seed)))
+            }
+            Self::Custom(_) => self.clone(),
+        }
+    }
+
+    /// Same texture with features scaled by `1 / scale`, custom textures stay the same.
+    pub fn with_scale(&self, scale: f64) -> Self {
+        match self {
+            Self::Perlin(texture) => Self::Perlin(texture.with_scale(scale)),
+            Self::Worley(texture) => Self::Worley(texture.with_scale(scale)),
+            Self::Curl(texture) => Self::Curl(texture.with_scale(scale)),
+            Self::Baked(texture) => {
+                Self::Baked(texture.with_source(texture.source().with_scale(scale)))
+            }
+            Self::Custom(_) => self.clone(),
+        }
+    }
+
+    /// Same texture with other octaves, custom textures stay the same.
+    pub fn with_fbm(&self, fbm: Fbm) -> Self {
+        match self {
+            Self::Perlin(texture) => Self::Perlin(texture.with_fbm(fbm)),
+            Self::Worley(texture) => Self::Worley(texture.with_fbm(fbm)),
+            Self::Curl(texture) => Self::Curl(texture.with_fbm(fbm)),
+            Self::Baked(texture) => {
+                Self::Baked(texture.with_source(texture.source().with_fbm(fbm)))
+            }
+            Self::Custom(_) => self.clone(),
+        }
+    }
+
+    /// Scale of the built-in textures, 1 for custom ones.
+    pub fn scale(&self) -> f64 {
+        match self.unbaked() {
+            Self::Perlin(texture) => texture.scale(),
+            Self::Worley(texture) => texture.scale(),
+            Self::Curl(texture) => texture.scale(),
+            _ => 1.0,
+        }
+    }
+
+    /// Seed of the built-in textures, 0 for custom ones.
+    pub fn seed(&self) -> u64 {
+        match self.unbaked() {
+            Self::Perlin(texture) => texture.seed(),
+            Self::Worley(texture) => texture.seed(),
+            Self::Curl(texture) => texture.seed(),
+            _ => 0,
+        }
+    }
+
+    /// Octaves of the built-in textures, a single one for custom ones.
+    pub fn fbm(&self) -> Fbm {
+        match self.unbaked() {
+            Self::Perlin(texture) => texture.fbm(),
+            Self::Worley(texture) => texture.fbm(),
+            Self::Curl(texture) => texture.fbm(),
+            _ => Fbm::new(1),
+        }
+    }
+
+    /// The original of baked textures, other textures as they are.
+    fn unbaked(&self) -> &Self {
+        match self {
+            Self::Baked(texture) => texture.source(),
+            _ => self,
+        }
+    }
+}
+
+impl Texture3D for TextureKind {
+    type Output = f64;
+
+    #[inline]
+    fn color_at(&self, position: Vector3<f64>) -> Self::Output {
+        match self {
+            Self::Perlin(texture) => texture.color_at(position),
+            Self::Worley(texture) => texture.color_at(position),
+            Self::Curl(texture) => texture.color_at(position).magnitude(),
+            Self::Baked(texture) => texture.color_at(position),
+            Self::Custom(texture) => texture.color_at(position),
+        }
+    }
+}
+
+impl Debug for TextureKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Perlin(_) => "Perlin",
+            Self::Worley(_) => "Worley",
+            Self::Curl(_) => "Curl",
+            Self::Baked(_) => "Baked",
+            Self::Custom(_) => "Custom",
+        };
+
+        f.debug_tuple(name).finish_non_exhaustive()
+    }
+}
+
+impl From<NoiseTexture3D> for TextureKind {
+    fn from(texture: NoiseTexture3D) -> Self {
+        Self::Perlin(texture)
+    }
+}
+
+impl From<WorleyTexture3D> for TextureKind {
+    fn from(texture: WorleyTexture3D) -> Self {
+        Self::Worley(texture)
+    }
+}
+
+impl From<CurlNoiseTexture3D> for TextureKind {
+    fn from(texture: CurlNoiseTexture3D) -> Self {
+        Self::Curl(texture)
+    }
+}
+
+impl From<BakedTexture3D> for TextureKind {
+    fn from(texture: BakedTexture3D) -> Self {
+        Self::Baked(texture)
+    }
+}
@@ -0,0 +1,148 @@
+use cgmath::Vector3;
+use std::sync::Arc;
+
+use super::{Texture3D, TextureKind};
+use crate::object::AABB;
+
+/// Scalar texture sampled on a regular grid over a box, looked up with trilinear interpolation.
+///
+/// Usually created by [`TextureKind::bake`]. Lookups cost the same for any texture, which pays off
+/// for noise with several octaves or derivatives. Positions outside of the box are looked up in
+/// the original texture.
+#[derive(Clone)]
+pub struct BakedTexture3D {
+    source: Arc<TextureKind>,
+    bounds: AABB,
+    resolution: usize,
+    /// Position of the first sample
+    origin: Vector3<f64>,
+    cell: f64,
+    /// Samples along each axis
+    dims: [usize; 3],
+    /// Samples ordered by x, then y, then z
+    values: Arc<[f32]>,
+}
+
+impl BakedTexture3D {
+    /// Samples the texture with `resolution` cells along the longest side of `bounds`.
+    pub fn new(source: TextureKind, bounds: AABB, resolution: usize) -> Self {
+        let resolution = resolution.max(1);
+        let origin = Vector3::new(bounds.x_min, bounds.y_min, bounds.z_min);
+        let size = Vector3::new(
+            bounds.x_max - bounds.x_min,
+            bounds.y_max - bounds.y_min,
+            bounds.z_max - bounds.z_min,
+        );
+        let cell = size.x.max(size.y).max(size.z).max(1e-9) / resolution as f64;
+
+        let dims = [size.x, size.y, size.z].map(|s| (s / cell).ceil() as usize + 1);
+        let mut values = Vec::with_capacity(dims[0] * dims[1] * dims[2]);
+
+        for z in 0..dims[2] {
+            for y in 0..dims[1] {
+                for x in 0..dims[0] {
+                    let index = Vector3::new(x, y, z).map(|i| i as f64);
+                    values.push(source.color_at(origin + index * cell) as f32);
+                }
+            }
+        }
+
+        Self {
+            source: Arc::new(source),
+            bounds,
+            resolution,
+            origin,
+            cell,
+            dims,
+            values: values.into(),
+        }
+    }
+
+    /// Texture the samples were taken from.
+    pub fn source(&self) -> &TextureKind {
+        &self.source
+    }
+
+    /// Same grid with samples of another texture.
+    pub fn with_source(&self, source: TextureKind) -> Self {
+        Self::new(source, self.bounds, self.resolution)
+    }
+
+    pub fn bounds(&self) -> AABB {
+        self.bounds
+    }
+
+    pub fn resolution(&self) -> usize {
+        self.resolution
+    }
+
+    fn value(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.values[x + self.dims[0] * (y + self.dims[1] * z)] as f64
+    }
+}
+
+impl Texture3D for BakedTexture3D {
+    type Output = f64;
+
+    #[inline]
+    fn color_at(&self, position: Vector3<f64>) -> Self::Output {
+        let local = (position - self.origin) / self.cell;
+
+        let mut index = [0; 3];
+        let mut t = [0.0; 3];
+
+        for a in 0..3 {
+            let last = (self.dims[a] - 1) as f64;
+
+            if !(0.0..=last).contains(&local[a]) {
+                return self.source.color_at(position);
+            }
+
+            let i = local[a].floor().min(last - 1.0).max(0.0);
+            index[a] = i as usize;
+            t[a] = local[a] - i;
+        }
+
+        // flat grids have a single sample along some axis
+        let next = |a: usize, i: usize| (i + 1).min(self.dims[a] - 1);
+
+        let [x, y, z] = index;
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+
+        let along_x = |y, z| lerp(self.value(x, y, z), self.value(next(0, x), y, z), t[0]);
+        let along_y = |z| lerp(along_x(y, z), along_x(next(1, y), z), t[1]);
+
+        lerp(along_y(z), along_y(next(2, z)), t[2])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::NoiseTexture3D;
+
+    #[test]
+    fn baked_noise_matches_original() {
+        let noise = TextureKind::from(NoiseTexture3D::new(2.0, 3, 1));
+        let bounds = AABB {
+            x_min: -1.0,
+            x_max: 1.0,
+            y_min: 0.0,
+            y_max: 0.5,
+            z_min: -1.0,
+            z_max: 1.0,
+        };
+        let baked = BakedTexture3D::new(noise.clone(), bounds, 128);
+
+        for i in 0..100 {
+            let t = i as f64 / 100.0;
+            let inside = Vector3::new(2.0 * t - 1.0, 0.5 * t, 1.0 - 2.0 * t);
+
+            assert!((baked.color_at(inside) - noise.color_at(inside)).abs() < 0.02);
+        }
+
+        // outside of the grid the original is used
+        let outside = Vector3::new(1.5, 0.2, 0.0);
+        assert_eq!(baked.color_at(outside), noise.color_at(outside));
+    }
+}
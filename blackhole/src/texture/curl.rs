@@ -0,0 +1,171 @@
+use cgmath::Vector3;
+
+use super::{Fbm, NoiseTexture3D, Texture3D};
+use crate::math::splitmix64;
+
+/// Step of the finite differences, in noise space.
+const EPSILON: f64 = 1e-4;
+
+/// Divergence-free vector noise, the curl of a vector potential made of three Perlin noises.
+///
+/// Moving points along it swirls them around without bunching them together, like flow of an
+/// incompressible fluid. Derivatives are taken in noise space, so vectors have the same length
+/// at any scale.
+#[derive(Clone)]
+pub struct CurlNoiseTexture3D {
+    seed: u64,
+    potential: [NoiseTexture3D; 3],
+}
+
+impl CurlNoiseTexture3D {
+    pub fn new(scale: f64, seed: u64, octaves: u8) -> Self {
+        Self {
+            seed,
+            potential: std::array::from_fn(|i| {
+                NoiseTexture3D::new(scale, Self::component_seed(seed, i), octaves)
+            }),
+        }
+    }
+
+    /// Seeds of the potential components, so they are not correlated.
+    fn component_seed(seed: u64, component: usize) -> u64 {
+        splitmix64(seed.wrapping_mul(3).wrapping_add(component as u64))
+    }
+
+    /// Same texture with the potential generated from another seed.
+    pub fn with_seed(&self, seed: u64) -> Self {
+        Self {
+            seed,
+            potential: std::array::from_fn(|i| {
+                self.potential[i].with_seed(Self::component_seed(seed, i))
+            }),
+        }
+    }
+
+    /// Same texture with swirls scaled by `1 / scale`.
+    pub fn with_scale(&self, scale: f64) -> Self {
+        Self {
+            seed: self.seed,
+            potential: self.potential.clone().map(|p| p.with_scale(scale)),
+        }
+    }
+
+    /// Same texture with other octaves of the potential.
+    pub fn with_fbm(&self, fbm: Fbm) -> Self {
+        Self {
+            seed: self.seed,
+            potential: self.potential.clone().map(|p| p.with_fbm(fbm)),
+        }
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.potential[0].scale()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn fbm(&self) -> Fbm {
+        self.potential[0].fbm()
+    }
+}
+
+impl Texture3D for CurlNoiseTexture3D {
+    type Output = Vector3<f64>;
+
+    #[inline]
+    fn color_at(&self, position: Vector3<f64>) -> Self::Output {
+        let step = EPSILON / self.scale();
+
+        // derivative of a potential component along an axis
+        let d = |component: usize, axis: Vector3<f64>| {
+            let potential = &self.potential[component];
+
+            (potential.color_at(position + axis * step)
+                - potential.color_at(position - axis * step))
+                / (2.0 * EPSILON)
+        };
+
+        let (x, y, z) = (Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z());
+
+        Vector3::new(d(2, y) - d(1, z), d(0, z) - d(2, x), d(1, x) - d(0, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    /// Positions spread over a few noise cells.
+    fn positions() -> impl Iterator<Item = Vector3<f64>> {
+        (0..20).map(|i| {
+            let i = i as f64;
+            Vector3::new(i * 0.37 + 0.05, i * -0.21 + 1.03, i * 0.13 + 0.07)
+        })
+    }
+
+    #[test]
+    fn curl_matches_finite_differences_of_potential() {
+        let curl = CurlNoiseTexture3D::new(2.0, 5, 2);
+        // much longer step than the texture takes, in world space
+        let h = 1e-3;
+
+        for position in positions() {
+            let d = |component: usize, axis: Vector3<f64>| {
+                let potential = &curl.potential[component];
+
+                (potential.color_at(position + axis * h) - potential.color_at(position - axis * h))
+                    / (2.0 * h)
+            };
+            let (x, y, z) = (Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z());
+
+            // the texture differentiates in noise space, `scale` times longer than world space
+            let expected = Vector3::new(d(2, y) - d(1, z), d(0, z) - d(2, x), d(1, x) - d(0, y))
+                / curl.scale();
+            let actual = curl.color_at(position);
+
+            assert!(actual.magnitude() > 0.01, "{actual:?}");
+            assert!(
+                (actual - expected).magnitude() < 0.02 * actual.magnitude(),
+                "{actual:?} {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn curl_noise_has_no_divergence() {
+        let curl = CurlNoiseTexture3D::new(2.0, 5, 2);
+        let h = 1e-3;
+
+        for position in positions() {
+            let axes = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+
+            let divergence: f64 = axes
+                .iter()
+                .enumerate()
+                .map(|(i, axis)| {
+                    let ahead = curl.color_at(position + axis * h)[i];
+                    let behind = curl.color_at(position - axis * h)[i];
+
+                    (ahead - behind) / (2.0 * h)
+                })
+                .sum();
+            // a field which isn't divergence-free has divergence comparable to its derivatives
+            let gradient: f64 = axes
+                .iter()
+                .map(|axis| {
+                    ((curl.color_at(position + axis * h) - curl.color_at(position - axis * h))
+                        / (2.0 * h))
+                        .magnitude()
+                })
+                .sum();
+
+            assert!(
+                divergence.abs() < 0.01 * gradient,
+                "{divergence} at {position:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use super::Texture2D;
+use crate::framebuffer::{FrameBuffer, Pixel};
+use cgmath::{Vector2, Vector3};
+use std::sync::Arc;
+
+/// Image sampled with bilinear filtering, repeating in both directions.
+///
+/// Coordinates of `(0, 0)` are in the top left corner of the image, `(1, 1)` in the bottom right.
+#[derive(Clone)]
+pub struct ImageTexture2D {
+    image: Arc<FrameBuffer>,
+}
+
+impl ImageTexture2D {
+    /// Panics on image without any pixels.
+    pub fn new(image: FrameBuffer) -> Self {
+        assert!(
+            image.width() > 0 && image.height() > 0,
+            "texture image must not be empty"
+        );
+
+        Self {
+            image: Arc::new(image),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.image.height()
+    }
+
+    fn texel(&self, x: usize, y: usize) -> Vector3<f64> {
+        let Pixel { r, g, b, .. } = self.image.buffer()[x + y * self.image.width()];
+
+        Vector3::new(r as f64, g as f64, b as f64)
+    }
+}
+
+impl Texture2D for ImageTexture2D {
+    type Output = Vector3<f64>;
+
+    fn color_at(&self, uv: Vector2<f64>) -> Self::Output {
+        let (w, h) = (self.width(), self.height());
+
+        // filtering between pixel centers
+        let x = uv.x * w as f64 - 0.5;
+        let y = uv.y * h as f64 - 0.5;
+
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let x0 = (x0 as isize).rem_euclid(w as isize) as usize;
+        let y0 = (y0 as isize).rem_euclid(h as isize) as usize;
+        let x1 = (x0 + 1) % w;
+        let y1 = (y0 + 1) % h;
+
+        let top = self.texel(x0, y0) * (1.0 - tx) + self.texel(x1, y0) * tx;
+        let bottom = self.texel(x0, y1) * (1.0 - tx) + self.texel(x1, y1) * tx;
+
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_texture_repeats() {
+        let mut image = FrameBuffer::new(2, 1);
+        image.buffer_mut()[1] = Pixel::new(1.0, 1.0, 1.0, 1.0);
+
+        let texture = ImageTexture2D::new(image);
+
+        assert_eq!(texture.color_at(Vector2::new(0.75, 0.5)).x, 1.0);
+        assert_eq!(texture.color_at(Vector2::new(-0.25, 0.5)).x, 1.0);
+        // halfway between the pixel centers, across the edge of the image
+        assert_eq!(texture.color_at(Vector2::new(1.0, 0.5)).x, 0.5);
+    }
+}
@@ -1,17 +1,20 @@
-use cgmath::{InnerSpace, Vector3, Zero};
+use cgmath::Vector3;
+use std::sync::Arc;
 
 use rand::{Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256StarStar;
 
-use super::Texture3D;
-use crate::math::rand_unit_vector;
+use super::{Fbm, Texture3D};
+use crate::math::rand_unit_vector_with;
+use crate::simd;
 
 #[derive(Clone)]
 pub struct NoiseTexture3D {
     scale: f64,
-    octaves: u8,
-    randoms: Vec<Vector3<f64>>,
-    permutations: [Vec<usize>; 3],
+    fbm: Fbm,
+    seed: u64,
+    randoms: Arc<Vec<Vector3<f64>>>,
+    permutations: Arc<[Vec<usize>; 3]>,
 }
 
 impl NoiseTexture3D {
@@ -25,7 +28,7 @@ impl NoiseTexture3D {
         let mut rng = Xoshiro256StarStar::seed_from_u64(seed);
 
         for i in 0..256 {
-            randoms.push(rand_unit_vector());
+            randoms.push(rand_unit_vector_with(&mut rng));
             permutations[0].push(i);
             permutations[1].push(i);
             permutations[2].push(i);
@@ -41,12 +44,49 @@ impl NoiseTexture3D {
 
         Self {
             scale,
-            octaves,
-            randoms,
-            permutations,
+            fbm: Fbm::new(octaves),
+            seed,
+            randoms: Arc::new(randoms),
+            permutations: Arc::new(permutations),
         }
     }
 
+    /// Same texture with gradients generated from another seed.
+    pub fn with_seed(&self, seed: u64) -> Self {
+        Self {
+            fbm: self.fbm,
+            ..Self::new(self.scale, seed, self.fbm.octaves)
+        }
+    }
+
+    /// Same texture with features scaled by `1 / scale`.
+    pub fn with_scale(&self, scale: f64) -> Self {
+        Self {
+            scale,
+            ..self.clone()
+        }
+    }
+
+    /// Same texture with other octaves.
+    pub fn with_fbm(&self, fbm: Fbm) -> Self {
+        Self {
+            fbm,
+            ..self.clone()
+        }
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn fbm(&self) -> Fbm {
+        self.fbm
+    }
+
     fn sample(&self, position: Vector3<f64>) -> f64 {
         let position = position * self.scale;
 
@@ -58,59 +98,53 @@ impl NoiseTexture3D {
         let y = (position.y).floor() as isize;
         let z = (position.z).floor() as isize;
 
-        let mut values = [[[Vector3::zero(); 2]; 2]; 2];
+        let gradients = std::array::from_fn(|i| {
+            let (dx, dy, dz) = ((i >> 2) as isize, ((i >> 1) & 1) as isize, (i & 1) as isize);
 
-        for dx in 0..2 {
-            for dy in 0..2 {
-                for dz in 0..2 {
-                    values[dx][dy][dz] = self.randoms[self.permutations[0]
-                        [((x + dx as isize) & 255) as usize]
-                        ^ self.permutations[1][((y + dy as isize) & 255) as usize]
-                        ^ self.permutations[2][((z + dz as isize) & 255) as usize]];
-                }
-            }
-        }
+            self.randoms[self.permutations[0][((x + dx) & 255) as usize]
+                ^ self.permutations[1][((y + dy) & 255) as usize]
+                ^ self.permutations[2][((z + dz) & 255) as usize]]
+        });
 
-        Self::perlin_filter(values, u, v, w) * 0.5 + 0.5
-    }
-
-    fn perlin_filter(inputs: [[[Vector3<f64>; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
-        let uu = u * u * (3.0 - 2.0 * u);
-        let vv = v * v * (3.0 - 2.0 * v);
-        let ww = w * w * (3.0 - 2.0 * w);
-
-        let mut acc = 0.0;
-        for x in 0..2 {
-            let xf = x as f64;
-            for y in 0..2 {
-                let yf = y as f64;
-                for z in 0..2 {
-                    let zf = z as f64;
-                    let weight = Vector3::new(u - xf, v - yf, w - zf);
-                    acc += (xf * uu + (1.0 - xf) * (1.0 - uu))
-                        * (yf * vv + (1.0 - yf) * (1.0 - vv))
-                        * (zf * ww + (1.0 - zf) * (1.0 - ww))
-                        * inputs[x][y][z].dot(weight);
-                }
-            }
-        }
-
-        acc
+        simd::perlin_filter(&gradients, u, v, w) * 0.5 + 0.5
     }
 }
 
 impl Texture3D for NoiseTexture3D {
     type Output = f64;
 
+    #[inline]
     fn color_at(&self, position: Vector3<f64>) -> Self::Output {
-        let mut sum = 0.0;
-
-        for x in 0..self.octaves {
-            let pow = 1.0 / (2.0_f64).powi(x as i32);
-
-            sum += (self.sample(position * (2.0_f64.powi(x as i32))) - 0.5) * pow;
-        }
+        self.fbm.sum(position, |p| self.sample(p) - 0.5) + 0.5
+    }
+}
 
-        sum + 0.5
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn octaves_follow_fbm_settings() {
+        let fbm = Fbm {
+            octaves: 3,
+            lacunarity: 3.0,
+            gain: 0.25,
+        };
+        let noise = NoiseTexture3D::new(2.0, 1, 1).with_fbm(fbm);
+        let position = Vector3::new(0.3, 1.7, -4.1);
+
+        let octaves: f64 = [(1.0, 1.0), (3.0, 0.25), (9.0, 0.0625)]
+            .iter()
+            .map(|(frequency, amplitude)| (noise.sample(position * *frequency) - 0.5) * amplitude)
+            .sum();
+
+        assert!((noise.color_at(position) - (octaves + 0.5)).abs() < 1e-12);
+
+        // changing the seed or scale keeps the other settings
+        let changed = noise.with_seed(7).with_scale(4.0);
+        assert_eq!(
+            (changed.fbm(), changed.seed(), changed.scale()),
+            (fbm, 7, 4.0)
+        );
     }
 }
@@ -0,0 +1,150 @@
+use super::Texture2D;
+use cgmath::{InnerSpace, Vector2, Vector3};
+
+/// Projects a 2D texture along the three axes and blends the projections by the surface normal.
+///
+/// Works on any shape, as it needs only the position and normal of the shaded point. Faces
+/// looking along an axis get a single projection, tilted ones a mix of two or three.
+#[derive(Clone)]
+pub struct Triplanar<T> {
+    texture: T,
+    /// Repetitions of the texture per unit of length
+    scale: f64,
+    /// Higher values make narrower transitions between the projections
+    sharpness: f64,
+}
+
+impl<T: Texture2D<Output = Vector3<f64>>> Triplanar<T> {
+    pub fn new(texture: T, scale: f64) -> Self {
+        Self {
+            texture,
+            scale,
+            sharpness: 4.0,
+        }
+    }
+
+    pub fn set_scale(&mut self, scale: f64) {
+        self.scale = scale;
+    }
+
+    pub fn set_sharpness(&mut self, sharpness: f64) {
+        self.sharpness = sharpness.max(1.0);
+    }
+
+    pub fn texture(&self) -> &T {
+        &self.texture
+    }
+
+    /// Color at `position` on surface with unit `normal`.
+    pub fn color_at(&self, position: Vector3<f64>, normal: Vector3<f64>) -> Vector3<f64> {
+        self.projections(position, normal)
+            .map(|(_, weight, uv)| self.texture.color_at(uv) * weight)
+            .sum()
+    }
+
+    /// Unit `normal` tilted by a tangent space normal map, with the tilt scaled by `strength`.
+    ///
+    /// The texture has to be linear, with the image up direction in the green channel.
+    pub fn normal_at(
+        &self,
+        position: Vector3<f64>,
+        normal: Vector3<f64>,
+        strength: f64,
+    ) -> Vector3<f64> {
+        let tilt: Vector3<f64> = self
+            .projections(position, normal)
+            .map(|(axis, weight, uv)| {
+                let t = self.texture.color_at(uv) * 2.0 - Vector3::new(1.0, 1.0, 1.0);
+
+                // right and up directions of the image in each projection
+                let tilt = match axis {
+                    0 => Vector3::new(0.0, t.y, t.x),
+                    1 => Vector3::new(t.x, 0.0, -t.y),
+                    _ => Vector3::new(t.x, t.y, 0.0),
+                };
+
+                tilt * weight
+            })
+            .sum();
+
+        (normal + tilt * strength).normalize()
+    }
+
+    /// Axis, weight and texture coordinates of projections seen from the normal.
+    ///
+    /// Zero normals see all three projections equally.
+    fn projections(
+        &self,
+        position: Vector3<f64>,
+        normal: Vector3<f64>,
+    ) -> impl Iterator<Item = (usize, f64, Vector2<f64>)> {
+        let weights = normal.map(|n| n.abs().powf(self.sharpness));
+        let sum = weights.x + weights.y + weights.z;
+        let weights = if sum > 0.0 {
+            weights / sum
+        } else {
+            Vector3::new(1.0, 1.0, 1.0) / 3.0
+        };
+
+        let p = position * self.scale;
+        // images are upright on the side faces, v goes down
+        let projections = [
+            (0, weights.x, Vector2::new(p.z, -p.y)),
+            (1, weights.y, Vector2::new(p.x, p.z)),
+            (2, weights.z, Vector2::new(p.x, -p.y)),
+        ];
+
+        projections
+            .into_iter()
+            .filter(|(_, weight, _)| *weight > 1e-4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Same color everywhere.
+    struct Flat(Vector3<f64>);
+
+    impl Texture2D for Flat {
+        type Output = Vector3<f64>;
+
+        fn color_at(&self, _uv: Vector2<f64>) -> Self::Output {
+            self.0
+        }
+    }
+
+    #[test]
+    fn projection_weights_sum_to_one() {
+        let color = Vector3::new(0.2, 0.4, 0.8);
+        let triplanar = Triplanar::new(Flat(color), 1.0);
+        let position = Vector3::new(0.3, -1.2, 2.5);
+
+        let tilted = Vector3::new(1.0, 2.0, -0.5).normalize();
+
+        for normal in [Vector3::unit_x(), tilted, Vector3::new(0.0, 0.0, 0.0)] {
+            let diff = triplanar.color_at(position, normal) - color;
+
+            assert!(diff.magnitude() < 1e-12, "{normal:?}");
+        }
+    }
+
+    #[test]
+    fn normal_maps_tilt_normals() {
+        let position = Vector3::new(0.3, -1.2, 2.5);
+
+        let straight = Triplanar::new(Flat(Vector3::new(0.5, 0.5, 1.0)), 1.0);
+        assert_eq!(
+            straight.normal_at(position, Vector3::unit_z(), 1.0),
+            Vector3::unit_z()
+        );
+
+        // image right on the +Z face is +X
+        let right = Triplanar::new(Flat(Vector3::new(1.0, 0.5, 1.0)), 1.0);
+        let tilted = right.normal_at(position, Vector3::unit_z(), 1.0);
+        let expected = Vector3::new(1.0, 0.0, 1.0).normalize();
+
+        assert!((tilted - expected).magnitude() < 1e-12);
+    }
+}
@@ -1,21 +1,71 @@
-use super::Texture3D;
-use crate::math::rand_unit_vector;
+use super::{Fbm, Texture3D};
+use crate::math::rand_unit_vector_with;
 use cgmath::{Array, ElementWise, MetricSpace, Vector3};
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256StarStar;
+use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct WorleyTexture3D {
     scale: f64,
-    randoms: Vec<Vector3<f64>>,
+    fbm: Fbm,
+    seed: u64,
+    randoms: Arc<Vec<Vector3<f64>>>,
 }
 
 impl WorleyTexture3D {
-    pub fn new(scale: f64) -> Self {
+    pub fn new(scale: f64, seed: u64) -> Self {
         let mut randoms = Vec::new();
+        let mut rng = Xoshiro256StarStar::seed_from_u64(seed);
 
         for _ in 0..256 {
-            randoms.push((rand_unit_vector() * 0.5).add_element_wise(Vector3::from_value(0.5)))
+            randoms.push(
+                (rand_unit_vector_with(&mut rng) * 0.5).add_element_wise(Vector3::from_value(0.5)),
+            )
         }
 
-        Self { scale, randoms }
+        Self {
+            scale,
+            fbm: Fbm::new(1),
+            seed,
+            randoms: Arc::new(randoms),
+        }
+    }
+
+    /// Same texture with cells placed from another seed.
+    pub fn with_seed(&self, seed: u64) -> Self {
+        Self {
+            fbm: self.fbm,
+            ..Self::new(self.scale, seed)
+        }
+    }
+
+    /// Same texture with cells scaled by `1 / scale`.
+    pub fn with_scale(&self, scale: f64) -> Self {
+        Self {
+            scale,
+            ..self.clone()
+        }
+    }
+
+    /// Same texture with distances to cells of several sizes added up, one octave by default.
+    pub fn with_fbm(&self, fbm: Fbm) -> Self {
+        Self {
+            fbm,
+            ..self.clone()
+        }
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn fbm(&self) -> Fbm {
+        self.fbm
     }
 
     fn sample(&self, position: Vector3<f64>) -> f64 {
@@ -56,7 +106,8 @@ impl WorleyTexture3D {
 impl Texture3D for WorleyTexture3D {
     type Output = f64;
 
+    #[inline]
     fn color_at(&self, position: Vector3<f64>) -> Self::Output {
-        self.sample(position)
+        self.fbm.sum(position, |p| self.sample(p))
     }
 }
@@ -0,0 +1,140 @@
+//! Conversion of physical lengths to scene units.
+//!
+//! Scenes are scaled by the Schwarzschild radius of the black hole, which ties physical distances
+//! to distortion strength, see [`crate::object::Distortion::gravitational_radius`].
+
+/// Gravitational radius `GM/c²` of one solar mass, in meters.
+pub const SOLAR_GRAVITATIONAL_RADIUS: f64 = 1_476.625;
+/// Astronomical unit in meters.
+pub const ASTRONOMICAL_UNIT: f64 = 1.495_978_707e11;
+/// Light year in meters.
+pub const LIGHT_YEAR: f64 = 9.460_730_472_580_8e15;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LengthUnit {
+    /// Plain scene units
+    Scene,
+    /// Gravitational radius `GM/c²`, half of the Schwarzschild radius
+    GravitationalRadius,
+    SchwarzschildRadius,
+    Meter,
+    Kilometer,
+    AstronomicalUnit,
+    LightYear,
+}
+
+impl LengthUnit {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "" => Some(Self::Scene),
+            "M" | "rg" => Some(Self::GravitationalRadius),
+            "rs" => Some(Self::SchwarzschildRadius),
+            "m" => Some(Self::Meter),
+            "km" => Some(Self::Kilometer),
+            "au" | "AU" => Some(Self::AstronomicalUnit),
+            "ly" => Some(Self::LightYear),
+            _ => None,
+        }
+    }
+
+    /// Length of the unit in meters, for units not given by the black hole.
+    fn meters(&self) -> Option<f64> {
+        match self {
+            Self::Meter => Some(1.0),
+            Self::Kilometer => Some(1e3),
+            Self::AstronomicalUnit => Some(ASTRONOMICAL_UNIT),
+            Self::LightYear => Some(LIGHT_YEAR),
+            Self::Scene | Self::GravitationalRadius | Self::SchwarzschildRadius => None,
+        }
+    }
+}
+
+/// Splits lengths like `6 rs` or `0.5au` into the value and unit. Plain numbers are in scene units.
+pub fn parse_length(text: &str) -> Option<(f64, LengthUnit)> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| c.is_ascii_alphabetic() && c != 'e' && c != 'E')
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(split);
+
+    let value = value.trim().parse::<f64>().ok()?;
+    let unit = LengthUnit::from_name(unit.trim())?;
+
+    Some((value, unit))
+}
+
+/// Scale of the scene, given by the black hole all distances are measured against.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Units {
+    /// Scene units per Schwarzschild radius
+    pub schwarzschild_radius: f64,
+    /// Mass of the black hole in solar masses, needed for lengths in meters and larger units
+    pub mass: Option<f64>,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self {
+            schwarzschild_radius: 1.0,
+            mass: None,
+        }
+    }
+}
+
+impl Units {
+    /// Strength of distortions with the mass of the black hole.
+    ///
+    /// Distortions have their photon sphere at strength, which is `1.5` Schwarzschild radii.
+    pub fn distortion_strength(&self) -> f64 {
+        1.5 * self.schwarzschild_radius
+    }
+
+    /// Scene units per meter, if the mass is known.
+    pub fn per_meter(&self) -> Option<f64> {
+        self.mass
+            .map(|mass| self.schwarzschild_radius / (2.0 * SOLAR_GRAVITATIONAL_RADIUS * mass))
+    }
+
+    /// Length in scene units, `None` for physical units without known mass.
+    pub fn to_scene(&self, value: f64, unit: LengthUnit) -> Option<f64> {
+        match unit {
+            LengthUnit::Scene => Some(value),
+            LengthUnit::GravitationalRadius => Some(value * self.schwarzschild_radius / 2.0),
+            LengthUnit::SchwarzschildRadius => Some(value * self.schwarzschild_radius),
+            _ => Some(value * unit.meters()? * self.per_meter()?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_lengths() {
+        let units = Units {
+            schwarzschild_radius: 2.0,
+            mass: Some(4.3e6),
+        };
+
+        let length = |text| {
+            let (value, unit) = parse_length(text).unwrap();
+            units.to_scene(value, unit).unwrap()
+        };
+
+        assert_eq!(length("3"), 3.0);
+        assert_eq!(length("6 rs"), 12.0);
+        assert_eq!(length("6M"), 6.0);
+
+        // Schwarzschild radius of Sagittarius A* is about 0.085 au
+        let au = length("1 au");
+        assert!((au / 2.0 - 1.0 / 0.0849).abs() < 0.1, "{au}");
+        assert!((length("1e3 m") - length("1 km")).abs() < 1e-12);
+
+        assert_eq!(parse_length("2 parsecs"), None);
+        assert_eq!(
+            Units::default().to_scene(1.0, LengthUnit::AstronomicalUnit),
+            None
+        );
+    }
+}
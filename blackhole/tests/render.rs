@@ -0,0 +1,377 @@
+//! Renders small scenes built in code and checks statistics of the images, so regressions of the
+//! core are caught without the renderers of the other crates.
+
+use cgmath::{InnerSpace, Vector3, Zero};
+
+use blackhole::cache;
+use blackhole::framebuffer::{FrameBuffer, Pixel};
+use blackhole::marcher::{RayMarcher, Scratch, Termination};
+use blackhole::material::MaterialResult;
+use blackhole::math::rand_unit_vector;
+use blackhole::object::shape::Sphere;
+use blackhole::scene::Scene;
+use blackhole::shader::{BackgroundShader, ShadeContext, Shader, SolidShader, VolumetricShader};
+use blackhole::{Ray, RayKind, RenderMode};
+
+/// Solid emitting the same light everywhere, without reflections.
+#[derive(Clone)]
+struct Glow(f64);
+
+impl Shader for Glow {
+    fn emits_light(&self) -> bool {
+        true
+    }
+}
+
+impl SolidShader for Glow {
+    fn material_at(
+        &self,
+        _ray: &Ray,
+        _normal: Vector3<f64>,
+        _ctx: &ShadeContext,
+    ) -> (MaterialResult, Option<Ray>) {
+        let mat = MaterialResult {
+            emission: Vector3::new(self.0, self.0, self.0),
+            ..MaterialResult::black()
+        };
+
+        (mat, None)
+    }
+}
+
+/// Solid reflecting all light, like a mirror in the deterministic mode.
+#[derive(Clone)]
+struct Mirror;
+
+impl Shader for Mirror {}
+
+impl SolidShader for Mirror {
+    fn material_at(
+        &self,
+        ray: &Ray,
+        normal: Vector3<f64>,
+        _ctx: &ShadeContext,
+    ) -> (MaterialResult, Option<Ray>) {
+        let mat = MaterialResult {
+            albedo: Vector3::new(1.0, 1.0, 1.0),
+            ..MaterialResult::black()
+        };
+
+        (mat, Some(ray.reflect(normal)))
+    }
+}
+
+/// Volume scattering light into uniformly random directions.
+#[derive(Clone)]
+struct Fog {
+    density: f64,
+}
+
+impl Shader for Fog {}
+
+impl VolumetricShader for Fog {
+    fn density_at(&self, _position: Vector3<f64>, _ctx: &ShadeContext) -> f64 {
+        self.density
+    }
+
+    fn material_at(&self, ray: &Ray, _ctx: &ShadeContext) -> (MaterialResult, Option<Ray>) {
+        let mat = MaterialResult {
+            albedo: Vector3::new(0.8, 0.8, 0.8),
+            ..MaterialResult::black()
+        };
+        let scattered = Ray {
+            direction: rand_unit_vector(),
+            kind: RayKind::Secondary,
+            ..*ray
+        };
+
+        (mat, Some(scattered))
+    }
+
+    fn phase(&self, _ray: &Ray, _scattered: &Ray, _direction: Vector3<f64>) -> Option<f64> {
+        Some(1.0 / (4.0 * std::f64::consts::PI))
+    }
+}
+
+/// Background lit only from directions within the cone around `direction`.
+#[derive(Clone)]
+struct Spot {
+    direction: Vector3<f64>,
+    cos_angle: f64,
+}
+
+impl Spot {
+    fn everywhere() -> Self {
+        Self {
+            direction: Vector3::unit_z(),
+            cos_angle: -1.0,
+        }
+    }
+}
+
+impl Shader for Spot {}
+
+impl BackgroundShader for Spot {
+    fn emission_at(&self, ray: &Ray) -> Vector3<f64> {
+        if ray.direction.normalize().dot(self.direction) >= self.cos_angle {
+            Vector3::new(1.0, 1.0, 1.0)
+        } else {
+            Vector3::zero()
+        }
+    }
+}
+
+/// Keeps lookup tables the tests generate out of the user cache directory.
+fn temporary_cache() {
+    cache::set_dir(Some(cache::temporary_dir()));
+}
+
+/// Renders the scene like the command line renderer, one row after another.
+fn render(scene: &Scene, marcher: &RayMarcher, size: usize, samples: usize) -> FrameBuffer {
+    temporary_cache();
+
+    let mut scratch = Scratch::new();
+    let mut fb = FrameBuffer::new(size, size);
+
+    for (y, row) in fb.buffer_mut().chunks_mut(size).enumerate() {
+        marcher.render_row(scene, y, size, samples, row, &mut scratch);
+    }
+
+    fb
+}
+
+fn luminance(pixel: &Pixel) -> f64 {
+    (0.2126 * pixel.r + 0.7152 * pixel.g + 0.0722 * pixel.b) as f64
+}
+
+fn mean_luminance(fb: &FrameBuffer) -> f64 {
+    fb.buffer().iter().map(luminance).sum::<f64>() / fb.buffer().len() as f64
+}
+
+/// Mean luminance of rings around the image center, each `width` pixels wide.
+fn radial_profile(fb: &FrameBuffer, width: f64) -> Vec<f64> {
+    let center = fb.width() as f64 / 2.0;
+    let rings = (center / width) as usize;
+    let mut sums = vec![(0.0, 0); rings];
+
+    for (i, pixel) in fb.buffer().iter().enumerate() {
+        let (x, y) = ((i % fb.width()) as f64 + 0.5, (i / fb.width()) as f64 + 0.5);
+        let ring = ((x - center).hypot(y - center) / width) as usize;
+
+        if let Some((sum, count)) = sums.get_mut(ring) {
+            *sum += luminance(pixel);
+            *count += 1;
+        }
+    }
+
+    sums.iter()
+        .map(|(sum, count)| sum / *count as f64)
+        .collect()
+}
+
+#[test]
+fn emitting_sphere_covers_its_pixels() {
+    let mut scene = Scene::builder()
+        .look_from(Vector3::new(0.0, 0.0, -10.0), Vector3::zero())
+        .object(|o| o.shape(Sphere::new()).solid(Glow(2.0)))
+        .build();
+    scene.camera.hor_fov = 30.0;
+
+    let fb = render(&scene, &RayMarcher::default(), 32, 4);
+
+    // sphere of radius 1 seen from 10 units covers a disk of tangent of its angular radius
+    let sphere = (1.0_f64 / 10.0).asin().tan();
+    let frame = (15.0_f64).to_radians().tan();
+    let coverage = std::f64::consts::PI * sphere * sphere / (4.0 * frame * frame);
+
+    let mean = mean_luminance(&fb);
+    assert!((mean / (2.0 * coverage) - 1.0).abs() < 0.1, "{mean}");
+
+    let profile = radial_profile(&fb, 2.0);
+    assert!(profile[0] > 1.99 && profile[7] < 0.01, "{profile:?}");
+}
+
+#[test]
+fn distortion_casts_shadow() {
+    let mut scene = Scene::builder()
+        .background(Spot::everywhere())
+        .look_from(Vector3::new(0.0, 0.0, -20.0), Vector3::zero())
+        .distortion(|d| d.radius(15.0).strength(1.0))
+        .build();
+    scene.camera.hor_fov = 20.0;
+
+    let fb = render(&scene, &RayMarcher::default(), 32, 2);
+    let profile = radial_profile(&fb, 2.0);
+
+    // captured rays are black, ones passing by still see the uniform background
+    assert!(profile[0] < 0.01, "{profile:?}");
+    assert!(profile[profile.len() - 1] > 0.99, "{profile:?}");
+
+    let mean = mean_luminance(&fb);
+    assert!((0.4..0.8).contains(&mean), "{mean}");
+}
+
+#[test]
+fn photon_ring_shows_light_behind_camera() {
+    // only rays bent back towards the camera side see light, around the shadow
+    let mut scene = Scene::builder()
+        .background(Spot {
+            direction: -Vector3::unit_z(),
+            cos_angle: 0.5,
+        })
+        .look_from(Vector3::new(0.0, 0.0, -20.0), Vector3::zero())
+        .distortion(|d| d.radius(15.0).strength(1.0))
+        .build();
+    scene.camera.hor_fov = 20.0;
+
+    let fb = render(&scene, &RayMarcher::default(), 48, 2);
+    let profile = radial_profile(&fb, 1.0);
+
+    let (peak, &brightest) = profile
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .unwrap();
+
+    assert!(brightest > 0.2, "{profile:?}");
+    assert!(profile[0] < 0.01, "{profile:?}");
+    assert!(profile[profile.len() - 1] < 0.01, "{profile:?}");
+    assert!((2..profile.len() - 2).contains(&peak), "{profile:?}");
+}
+
+#[test]
+fn horizon_hides_what_is_inside() {
+    let mut sphere = Sphere::new();
+    sphere.set_radius(0.1);
+
+    // captured rays keep marching towards the glowing sphere, until they cross the horizon
+    let scene = Scene::builder()
+        .background(Spot::everywhere())
+        .look_from(Vector3::new(0.0, 0.0, -20.0), Vector3::zero())
+        .distortion(|d| d.radius(15.0).strength(1.0).horizon(2.0 / 3.0))
+        .object(|o| o.shape(sphere).solid(Glow(1.0)))
+        .build();
+    temporary_cache();
+
+    let max_step = scene.max_possible_step(scene.camera.location);
+    let trace = |marcher: RayMarcher| {
+        let ray = scene.camera.cast_ray(0.5, 0.5, 1.0);
+        marcher.color_for_ray(ray, &scene, max_step, 0, &mut Scratch::new())
+    };
+
+    let result = trace(RayMarcher::default());
+    assert_eq!(result.termination, Termination::Horizon);
+    assert_eq!(result.alpha, 1.0);
+    assert_eq!(result.color, Vector3::zero());
+
+    // only held out with transparent background
+    let result = trace(RayMarcher {
+        transparent_background: true,
+        ..Default::default()
+    });
+    assert_eq!(result.alpha, 0.0);
+}
+
+#[test]
+fn small_horizon_is_not_stepped_over() {
+    let scene = Scene::builder()
+        .background(Spot::everywhere())
+        .look_from(Vector3::new(0.0, 0.0, -20.0), Vector3::zero())
+        .distortion(|d| d.radius(15.05).strength(0.001).horizon(0.02))
+        .build();
+    temporary_cache();
+
+    // ray passing too far from the center to be captured, but crossing the horizon, while the
+    // steps inside the distortion land 0.05 before and after the center
+    let max_step = scene.max_possible_step(scene.camera.location);
+    let mut ray = scene.camera.cast_ray(0.5, 0.5, 1.0);
+    ray.location.x += 0.01;
+    let result = RayMarcher::default().color_for_ray(ray, &scene, max_step, 0, &mut Scratch::new());
+
+    assert_eq!(result.termination, Termination::Horizon);
+}
+
+#[test]
+fn captured_ray_ends_without_marching_on() {
+    let mut sphere = Sphere::new();
+    sphere.set_center(Vector3::new(0.0, 18.0, 0.0));
+
+    let scene = Scene::builder()
+        .background(Spot::everywhere())
+        .look_from(Vector3::new(0.0, 0.0, -20.0), Vector3::zero())
+        .distortion(|d| d.radius(15.0).strength(1.0))
+        .object(|o| o.shape(sphere).solid(Glow(1.0)))
+        .build();
+    temporary_cache();
+
+    let max_step = scene.max_possible_step(scene.camera.location);
+    let ray = scene.camera.cast_ray(0.5, 0.5, 1.0);
+    let result = RayMarcher::default().color_for_ray(ray, &scene, max_step, 0, &mut Scratch::new());
+
+    // the sphere is out of reach of rays inside the distortion, so the ray ends on entering it
+    assert_eq!(result.termination, Termination::Captured);
+    assert!(result.steps < 5, "{}", result.steps);
+}
+
+#[test]
+fn light_sampling_keeps_mean_radiance() {
+    let mut fog = Sphere::new();
+    fog.set_radius(1.5);
+
+    let mut light = Sphere::new();
+    light.set_center(Vector3::new(0.0, 2.5, 0.0));
+    light.set_radius(1.0);
+
+    // part of the fog shadows the light from the rest of it
+    let mut scene = Scene::builder()
+        .background(Spot {
+            direction: Vector3::unit_z(),
+            cos_angle: 2.0,
+        })
+        .look_from(Vector3::new(0.0, 0.0, -8.0), Vector3::zero())
+        .object(|o| o.shape(fog).volumetric(Fog { density: 1.0 }))
+        .object(|o| o.shape(light).solid(Glow(4.0)))
+        .build();
+    scene.camera.hor_fov = 30.0;
+
+    let mean = |sample_lights: bool| {
+        let marcher = RayMarcher {
+            sample_lights,
+            ..Default::default()
+        };
+
+        mean_luminance(&render(&scene, &marcher, 24, 32))
+    };
+
+    let (sampled, scattered) = (mean(true), mean(false));
+    assert!(
+        (sampled / scattered - 1.0).abs() < 0.05,
+        "{sampled} {scattered}"
+    );
+}
+
+#[test]
+fn mirrored_rays_are_not_clipped_by_camera() {
+    let mut light = Sphere::new();
+    light.set_center(Vector3::new(0.0, 0.0, -30.0));
+    light.set_radius(2.0);
+
+    // light behind the camera is seen only in the mirror, further than the far clip
+    let mut scene = Scene::builder()
+        .look_from(Vector3::new(0.0, 0.0, -10.0), Vector3::zero())
+        .object(|o| o.shape(Sphere::new()).solid(Mirror))
+        .object(|o| o.shape(light).solid(Glow(1.0)))
+        .build();
+    scene.camera.clip_far = 15.0;
+    temporary_cache();
+
+    let marcher = RayMarcher {
+        mode: RenderMode::Deterministic,
+        ..Default::default()
+    };
+    let max_step = scene.max_possible_step(scene.camera.location);
+    let ray = scene.camera.cast_ray(0.5, 0.5, 1.0);
+    let result = marcher.color_for_ray(ray, &scene, max_step, 0, &mut Scratch::new());
+
+    assert!(result.color.x > 0.9, "{:?}", result.color);
+}
@@ -0,0 +1,61 @@
+//! Renders a scene constructed in code with [`Scene::builder`], without a scene file.
+//!
+//! `cargo run --release -p blackhole-cli --example builder -- out.exr`
+
+use cgmath::{Vector3, Zero};
+use rayon::prelude::*;
+
+use blackhole::framebuffer::FrameBuffer;
+use blackhole::marcher::{RayMarcher, Scratch};
+use blackhole::object::shape::Sphere;
+use blackhole::scene::Scene;
+use blackhole::shader::Parameter;
+
+use blackhole_common::shaders::{BasicSolidShader, SolidColorBackgroundShader};
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 180;
+const SAMPLES: usize = 16;
+
+fn main() {
+    let output = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "builder.exr".into());
+
+    let sphere = |center: Vector3<f64>, radius: f64| {
+        let mut sphere = Sphere::new();
+        sphere.set_center(center);
+        sphere.set_radius(radius);
+        sphere
+    };
+
+    let scene = Scene::builder()
+        .background(SolidColorBackgroundShader::new())
+        .look_from(Vector3::new(0.0, 3.0, -20.0), Vector3::zero())
+        .distortion(|d| d.radius(8.0).strength(1.5))
+        .object(|o| {
+            o.shape(sphere(Vector3::new(4.0, 0.0, 2.0), 1.0))
+                .solid(BasicSolidShader::default())
+                .parameter("albedo", Parameter::Vec3(Vector3::new(0.8, 0.3, 0.1)))
+        })
+        .object(|o| {
+            o.shape(sphere(Vector3::new(-3.0, 6.0, -4.0), 1.5))
+                .solid(BasicSolidShader::default())
+                .parameter("emission", Parameter::Vec3(Vector3::new(40.0, 36.0, 30.0)))
+        })
+        .build();
+
+    let marcher = RayMarcher::default();
+    let mut fb = FrameBuffer::new(WIDTH, HEIGHT);
+    fb.buffer_mut()
+        .par_chunks_mut(WIDTH)
+        .enumerate()
+        .for_each_init(Scratch::new, |scratch, (y, row)| {
+            marcher.render_row(&scene, y, HEIGHT, SAMPLES, row, scratch);
+        });
+
+    if let Err(e) = fb.write_exr(&output) {
+        eprintln!("Could not write {output}: {e}");
+        std::process::exit(-1);
+    }
+}
@@ -0,0 +1,58 @@
+//! Renders how many march steps rays take through a scene, using a marcher plugin.
+//!
+//! `cargo run --release -p blackhole-cli --example step_density -- scenes/blackhole.json5 out.exr`
+
+use cgmath::Vector3;
+use rayon::prelude::*;
+
+use blackhole::framebuffer::FrameBuffer;
+use blackhole::marcher::plugin::StepDensity;
+use blackhole::marcher::{RayMarcher, Scratch};
+use blackhole::RenderMode;
+
+use blackhole_common::scene_loader::SceneLoader;
+
+const WIDTH: usize = 640;
+const HEIGHT: usize = 360;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let (scene, output) = match (args.next(), args.next()) {
+        (Some(scene), output) => (scene, output.unwrap_or("step_density.exr".into())),
+        _ => {
+            eprintln!("Usage: step_density <SCENE> [OUTPUT.exr]");
+            std::process::exit(-1);
+        }
+    };
+
+    let scene = match SceneLoader::load_from_path(&scene) {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("Could not read scene: {e}");
+            std::process::exit(-1);
+        }
+    };
+
+    // camera rays only, so the image shows steps of the primary ray paths
+    let marcher = RayMarcher {
+        mode: RenderMode::Deterministic,
+        max_depth: 1,
+        ..Default::default()
+    }
+    .with_plugin(StepDensity {
+        color: Vector3::new(0.002, 0.001, 0.0005),
+    });
+
+    let mut fb = FrameBuffer::new(WIDTH, HEIGHT);
+    fb.buffer_mut()
+        .par_chunks_mut(WIDTH)
+        .enumerate()
+        .for_each_init(Scratch::new, |scratch, (y, row)| {
+            marcher.render_row(&scene, y, HEIGHT, 1, row, scratch);
+        });
+
+    if let Err(e) = fb.write_exr(&output) {
+        eprintln!("Could not write {output}: {e}");
+        std::process::exit(-1);
+    }
+}
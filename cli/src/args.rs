@@ -1,38 +1,260 @@
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 
+use blackhole::aov::Aov;
+use blackhole::frame::Region;
+use blackhole::integrator::Integrator;
+use blackhole::postprocess::Tonemap;
 use blackhole::RenderMode;
-use std::path::PathBuf;
+use cgmath::Vector3;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
     /// Path to scene JSON file
-    #[arg()]
-    pub scene: PathBuf,
-    /// Width of the output image
+    #[arg(required = true)]
+    pub scene: Option<PathBuf>,
+    /// Width of the output image, before preset resolution scale
     #[arg(long, default_value_t = 1280)]
     pub width: usize,
-    /// Height of the output image
+    /// Height of the output image, before preset resolution scale
     #[arg(long, default_value_t = 720)]
     pub height: usize,
     /// Render setting, used for debugging
     #[arg(value_enum, default_value_t = RenderModeArg::Shaded)]
     pub mode: RenderModeArg,
-    /// Amount of samples to render
-    #[arg(short, long, default_value_t = 128)]
-    pub samples: usize,
-    /// Threads to use for rendering (0 for automatic setting)
+    /// Amount of samples to render [default: 128, or from preset or scene]
+    #[arg(short, long)]
+    pub samples: Option<usize>,
+    /// Maximum ray bounces [default: 16, or from preset or scene]
+    #[arg(long)]
+    pub max_depth: Option<usize>,
+    /// Scheme bending rays inside distortions. `rk4` keeps longer steps accurate, `rkf45` also
+    /// splits steps where bending changes quickly, for close orbits around the photon sphere
+    #[arg(long, value_enum, default_value_t = IntegratorArg::Euler)]
+    pub integrator: IntegratorArg,
+    /// Shade solids around the camera facing inwards, instead of seeing their outer side from
+    /// within
+    #[arg(long)]
+    pub flip_inside_normals: bool,
+    /// Average the background over the lensed patch each pixel sees, tracing two extra rays for
+    /// camera rays bent by distortions. Keeps stars stretched into arcs from flickering
+    #[arg(long)]
+    pub background_footprints: bool,
+    /// Trace extra rays towards emitting objects from volume scattering events, instead of only
+    /// finding light by the randomly scattered rays
+    #[arg(long)]
+    pub light_sampling: bool,
+    /// Longest step inside distortions. RK4 stays accurate with steps several times longer
+    /// [default: 0.1, or from scene]
+    #[arg(long)]
+    pub distortion_step: Option<f64>,
+    /// Bundle of render settings, built-in `draft`, `preview` and `final`, or defined in the user
+    /// config file. Other flags override preset values, which override the scene `render` settings
+    #[arg(long)]
+    pub preset: Option<String>,
+    /// Threads to use for rendering (0 for one per physical core)
     #[arg(short, long, default_value_t = 0)]
     pub threads: usize,
+    /// With `--threads 0`, use one thread per logical core including hyper-threads
+    #[arg(long)]
+    pub logical_cores: bool,
+    /// Render with lower priority, keeping the desktop responsive
+    #[arg(long)]
+    pub background: bool,
+    /// Parse the scene file again instead of loading its parsed snapshot from the cache
+    #[arg(long)]
+    pub no_cache: bool,
     /// Path to save render to
     #[arg(short, long, default_value_os_t = PathBuf::from("out.png"))]
     pub output: PathBuf,
+    /// Never overwrite existing images, number the output as `out-1.png`, `out-2.png`... instead.
+    /// Applies to frames of sequences, render layers and passes too
+    #[arg(long, conflicts_with = "resume")]
+    pub no_clobber: bool,
+    /// Format of the saved image, detected from the output extension if not set.
+    /// EXR keeps linear HDR values without tonemapping
+    #[arg(long, value_enum)]
+    pub output_format: Option<OutputFormat>,
+    /// Tonemapping operator of PNG output, overrides the scene setting
+    #[arg(long, value_enum)]
+    pub tonemap: Option<TonemapArg>,
+    /// Exposure correction of PNG output in EV, on top of camera exposure
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    pub exposure: f32,
+    /// Strength of bloom in PNG output
+    #[arg(long, default_value_t = 0.0)]
+    pub bloom: f32,
+    /// Luminance above which pixels bloom
+    #[arg(long, default_value_t = 1.0)]
+    pub bloom_threshold: f32,
+    /// Darkening of PNG output corners, from 0 to 1
+    #[arg(long, default_value_t = 0.0)]
+    pub vignette: f32,
+    /// Add noise of one 8 bit step to PNG output, hiding banding of smooth gradients
+    #[arg(long)]
+    pub dither: bool,
+    /// Grade PNG output with a 3D `.cube` table, applied to gamma encoded colors
+    #[arg(long)]
+    pub lut: Option<PathBuf>,
+    /// Index of the frame when rendering a sequence, gives each frame different noise
+    #[arg(long, default_value_t = 0)]
+    pub frame: u64,
+    /// Render a sequence of this many frames of the scene animation, starting at `--frame`.
+    /// Frame numbers are appended to the output file name
+    #[arg(long)]
+    pub frames: Option<u64>,
+    /// Seed of random numbers, renders with the same seed and settings are identical
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// Use the same noise pattern in every frame to reduce flicker
+    #[arg(long)]
+    pub lock_noise: bool,
+    /// Trace a low resolution pass first and give more samples to edges, fewer to empty background
+    #[arg(long)]
+    pub prepass: bool,
+    /// Save the unfinished render every this many seconds next to the output, as
+    /// `<output>.checkpoint`
+    #[arg(long, value_name = "SECONDS", conflicts_with = "frames")]
+    pub checkpoint_interval: Option<u64>,
+    /// Continue accumulating samples from the checkpoint of the output. Use the same scene and
+    /// settings as the interrupted render
+    #[arg(long, conflicts_with = "frames")]
+    pub resume: bool,
+    /// Save the unfinished image to the output every this many samples
+    #[arg(long, value_name = "SAMPLES", conflicts_with_all = ["frames", "stereo"])]
+    pub preview_every: Option<usize>,
+    /// Save the unfinished image to the output every this many seconds
+    #[arg(long, value_name = "SECONDS", conflicts_with_all = ["frames", "stereo", "preview_every"])]
+    pub preview_interval: Option<u64>,
+    /// Check every sample for NaN and infinite values. Such samples are dropped from the image,
+    /// and where they came from is reported after the render
+    #[arg(long)]
+    pub check_nan: bool,
+    /// Spread every sample over this many seconds of the sky rotation from the scene animation,
+    /// drawing lensed star trails. Objects and camera stay at their pose at the frame time
+    #[arg(long, value_name = "SECONDS")]
+    pub star_trails: Option<f64>,
+    /// Render left and right eye side by side, this distance apart. Width is per eye
+    #[arg(long, value_name = "SEPARATION", conflicts_with_all = ["frames", "checkpoint_interval", "resume"])]
+    pub stereo: Option<f64>,
+    /// Render only pixels in this window, as `x_min,y_min,x_max,y_max` with max excluded. The rest
+    /// of the image is left transparent. Can be repeated to render several windows
+    #[arg(long, value_parser = parse_region, group = "regions", conflicts_with = "stereo")]
+    pub region: Vec<Region>,
+    /// Render only pixels which are white in this image of the render size, PNG or EXR
+    #[arg(
+        long,
+        value_name = "IMAGE",
+        group = "regions",
+        conflicts_with = "stereo"
+    )]
+    pub region_mask: Option<PathBuf>,
+    /// Save only the bounds of the rendered region instead of a full size image
+    #[arg(long, requires = "regions")]
+    pub crop: bool,
+    /// Also render this many pixels around the region, so denoising has context at its edges.
+    /// They are not saved
+    #[arg(long, value_name = "PIXELS", default_value_t = 0, requires = "regions")]
+    pub region_border: usize,
+    /// Keep the image in temporary files in this directory instead of memory, for renders
+    /// larger than the available memory
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["frames", "stereo", "resume"])]
+    pub spill_dir: Option<PathBuf>,
+    /// Also save each render layer of the scene as `<output>_<layer>.exr`, with objects of other
+    /// layers held out. Background is saved as the `background` layer
+    #[arg(long, conflicts_with_all = ["frames", "stereo", "checkpoint_interval", "resume"])]
+    pub layers: bool,
+    /// Remove noise from the finished image, guided by quickly rendered normals and albedo. Works
+    /// only in the shaded mode
+    #[arg(long, conflicts_with = "stereo")]
+    pub denoise: bool,
+    /// Also save these passes of camera rays as `<output>.<pass>.exr`, collected in the same
+    /// render
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["frames", "stereo", "checkpoint_interval", "resume"])]
+    pub aovs: Vec<AovArg>,
+    /// Draw text over the saved image, with the template of the scene or the given one. Template
+    /// placeholders are `{scene}`, `{samples}`, `{time}`, `{frame}`, `{size}` and `{notes}`
+    #[arg(long, value_name = "TEMPLATE")]
+    pub burn_in: Option<Option<String>>,
+    /// Text for the `{notes}` placeholder of the burn-in
+    #[arg(long, default_value = "")]
+    pub notes: String,
+    /// Hold out the background for compositing, camera rays reaching it leave the pixel
+    /// transparent. Light of volumes in front of it is kept
+    #[arg(long)]
+    pub transparent: bool,
+    /// Write colors multiplied by alpha instead of straight ones. Keeps light of glowing volumes
+    /// over transparent background, which straight alpha can't hold
+    #[arg(long)]
+    pub premultiplied: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Png,
+    Exr,
+}
+
+impl OutputFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("exr") => Self::Exr,
+            _ => Self::Png,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum TonemapArg {
+    Reinhard,
+    Aces,
+    Uncharted2,
+    Linear,
+}
+
+impl From<TonemapArg> for Tonemap {
+    fn from(t: TonemapArg) -> Self {
+        match t {
+            TonemapArg::Reinhard => Self::Reinhard,
+            TonemapArg::Aces => Self::AcesFilmic,
+            TonemapArg::Uncharted2 => Self::Uncharted2,
+            TonemapArg::Linear => Self::LinearClamp,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum IntegratorArg {
+    Euler,
+    Rk4,
+    Rkf45,
+}
+
+impl From<IntegratorArg> for Integrator {
+    fn from(i: IntegratorArg) -> Self {
+        match i {
+            IntegratorArg::Euler => Self::Euler,
+            IntegratorArg::Rk4 => Self::Rk4,
+            IntegratorArg::Rkf45 => Self::Rkf45,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
 pub enum RenderModeArg {
     Samples,
     Normal,
+    /// Color of surfaces and volumes seen by camera rays, without lighting
+    Albedo,
     Shaded,
+    Deterministic,
+    Termination,
+    /// Length of the bent ray path to the first hit, infinite for background. Written
+    /// unchanged to EXR, PNG shows closer pixels brighter
+    Depth,
 }
 
 impl From<RenderModeArg> for RenderMode {
@@ -40,7 +262,243 @@ impl From<RenderModeArg> for RenderMode {
         match r {
             RenderModeArg::Samples => Self::Samples,
             RenderModeArg::Normal => Self::Normal,
+            RenderModeArg::Albedo => Self::Albedo,
             RenderModeArg::Shaded => Self::Shaded,
+            RenderModeArg::Deterministic => Self::Deterministic,
+            RenderModeArg::Termination => Self::Termination,
+            RenderModeArg::Depth => Self::Depth,
         }
     }
 }
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum AovArg {
+    /// Shading normal of the first hit, from -1 to 1
+    Normal,
+    /// Length of the bent ray path to the first hit, nearest of all samples
+    Depth,
+    /// Color of the first hit without lighting
+    Albedo,
+    /// Average march steps of the pixel samples
+    Steps,
+}
+
+impl From<AovArg> for Aov {
+    fn from(a: AovArg) -> Self {
+        match a {
+            AovArg::Normal => Self::Normal,
+            AovArg::Depth => Self::Depth,
+            AovArg::Albedo => Self::Albedo,
+            AovArg::Steps => Self::Steps,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Evaluate scene distance field and distortion strength along a line or on a plane
+    Section(SectionArgs),
+    /// Copy scene and all assets it references into a directory or a `.zip` archive
+    Pack(PackArgs),
+    /// Load scene and report likely mistakes without rendering
+    Validate(ValidateArgs),
+    /// Compare two renders and save false color image of their relative difference
+    Diff(DiffArgs),
+    /// Render small quick previews of scene files, in parallel
+    Thumbnail(ThumbnailArgs),
+    /// Render only the background shader of a scene as an equirectangular panorama, without
+    /// marching any rays
+    PreviewBackground(PreviewBackgroundArgs),
+    /// Check a directory of rendered frames for glitches, save a contact sheet and JSON report
+    SequenceReport(SequenceReportArgs),
+    /// Render regions of an earlier render again with its settings and merge them into its image
+    Patch(PatchArgs),
+}
+
+#[derive(Debug, clap::Args)]
+pub struct SectionArgs {
+    /// Path to scene JSON file
+    pub scene: PathBuf,
+    /// Start of the line, as `x,y,z`
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub from: Vector3<f64>,
+    /// End of the line, as `x,y,z`
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub to: Vector3<f64>,
+    /// Vertical extent of the plane centered on the line, as `x,y,z`. Samples only the line if not set
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub up: Option<Vector3<f64>>,
+    /// Samples along the line, or width of the plane image
+    #[arg(long, default_value_t = 512)]
+    pub resolution: usize,
+    /// Path to save samples to, `.csv` for table, PNG image otherwise (plane only)
+    #[arg(short, long, default_value_os_t = PathBuf::from("section.csv"))]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct PackArgs {
+    /// Path to scene JSON file
+    pub scene: PathBuf,
+    /// Directory to copy scene into, or archive path ending with `.zip`
+    #[arg(short, long)]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ValidateArgs {
+    /// Path to scene JSON file
+    pub scene: PathBuf,
+    /// Exit with an error when any warnings are found
+    #[arg(long)]
+    pub deny_warnings: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct DiffArgs {
+    /// First image, EXR or PNG
+    pub a: PathBuf,
+    /// Second image, EXR or PNG
+    pub b: PathBuf,
+    /// Path to save false color PNG to, only statistics are printed if not set
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Relative error shown as white in the false color image
+    #[arg(long, default_value_t = 1.0)]
+    pub scale: f32,
+    /// Use logarithmic false color scale covering three orders of magnitude below `--scale`
+    #[arg(long)]
+    pub log: bool,
+    /// Exit with an error when mean relative error exceeds this value
+    #[arg(long)]
+    pub fail_above: Option<f32>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct PatchArgs {
+    /// Image saved by a render, PNG or EXR. Render layers and passes are not patched
+    pub image: PathBuf,
+    /// Window to render again, as `x_min,y_min,x_max,y_max` with max excluded. Can be repeated
+    #[arg(long, value_parser = parse_region, required_unless_present = "region_mask")]
+    pub region: Vec<Region>,
+    /// Render again pixels which are white in this image of the render size
+    #[arg(long, value_name = "IMAGE", conflicts_with = "region")]
+    pub region_mask: Option<PathBuf>,
+    /// Also render this many pixels around the region for denoising, they are not merged
+    #[arg(long, value_name = "PIXELS", default_value_t = 0)]
+    pub region_border: usize,
+    /// Samples per pixel of the patch, the ones of the render by default
+    #[arg(short, long)]
+    pub samples: Option<usize>,
+    /// Seed of the patch, the one of the render by default
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Patch even when the scene file changed since the render
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ThumbnailArgs {
+    /// Paths to scene JSON files
+    #[arg(required = true)]
+    pub scenes: Vec<PathBuf>,
+    /// Directory to save thumbnails to, they are saved next to scenes as `<name>.thumb.png` if not set
+    #[arg(short, long)]
+    pub output_dir: Option<PathBuf>,
+    /// Width and height of thumbnails
+    #[arg(long, default_value_t = 256)]
+    pub size: usize,
+    /// Amount of samples to render
+    #[arg(short, long, default_value_t = 16)]
+    pub samples: usize,
+    /// Threads to use, each renders one scene at a time (0 for one per physical core)
+    #[arg(short, long, default_value_t = 0)]
+    pub threads: usize,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct PreviewBackgroundArgs {
+    /// Path to scene JSON file
+    pub scene: PathBuf,
+    /// Width of the panorama, height is half of it
+    #[arg(long, default_value_t = 2048)]
+    pub width: usize,
+    /// Random directions per pixel, single stars are smaller than a pixel
+    #[arg(short, long, default_value_t = 4)]
+    pub samples: usize,
+    /// Tonemapping operator, overrides the scene setting
+    #[arg(long, value_enum)]
+    pub tonemap: Option<TonemapArg>,
+    /// Path to save the panorama to
+    #[arg(short, long, default_value_os_t = PathBuf::from("background.png"))]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct SequenceReportArgs {
+    /// Directory with EXR or PNG frames, ordered by file name
+    pub dir: PathBuf,
+    /// Path to save the JSON report to
+    #[arg(short, long, default_value_os_t = PathBuf::from("sequence-report.json"))]
+    pub output: PathBuf,
+    /// Path to save the contact sheet PNG to, flagged frames have red border
+    #[arg(long, default_value_os_t = PathBuf::from("contact-sheet.png"))]
+    pub sheet: PathBuf,
+    /// Frames in a row of the contact sheet
+    #[arg(long, default_value_t = 8)]
+    pub columns: usize,
+    /// Largest width of frames in the contact sheet, frames are shrunk by whole factors
+    #[arg(long, default_value_t = 160)]
+    pub thumb_width: usize,
+    /// Frames on each side a frame is compared to
+    #[arg(long, default_value_t = 2)]
+    pub window: usize,
+    /// Relative deviation of average luminance from neighbors above which frames are flagged
+    #[arg(long, default_value_t = 0.1)]
+    pub luminance_tolerance: f32,
+    /// Relative deviation of noise from neighbors above which frames are flagged
+    #[arg(long, default_value_t = 0.3)]
+    pub noise_tolerance: f32,
+    /// Exit with an error when any frame is flagged
+    #[arg(long)]
+    pub deny_flagged: bool,
+}
+
+pub fn parse_vec3(value: &str) -> Result<Vector3<f64>, String> {
+    let parts = value
+        .split(',')
+        .map(|p| p.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    match parts.as_slice() {
+        [x, y, z] => Ok(Vector3::new(*x, *y, *z)),
+        _ => Err(format!(
+            "expected 3 comma separated numbers, got {}",
+            parts.len()
+        )),
+    }
+}
+
+pub fn parse_region(value: &str) -> Result<Region, String> {
+    let parts = value
+        .split(',')
+        .map(|p| p.trim().parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    match *parts.as_slice() {
+        [x_min, y_min, x_max, y_max] if x_min < x_max && y_min < y_max => Ok(Region::Window {
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+        }),
+        [_, _, _, _] => Err("region minimum must be less than maximum".into()),
+        _ => Err(format!(
+            "expected 4 comma separated numbers, got {}",
+            parts.len()
+        )),
+    }
+}
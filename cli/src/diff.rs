@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::path::Path;
+
+use blackhole::diff;
+use blackhole::framebuffer::{FrameBuffer, Pixel};
+
+use crate::args::{DiffArgs, OutputFormat};
+use crate::write_out;
+
+pub fn run(args: DiffArgs) {
+    let a = read_image(&args.a);
+    let b = read_image(&args.b);
+
+    let difference = match diff::relative(&a, &b) {
+        Some(d) => d,
+        None => {
+            eprintln!(
+                "Image sizes do not match: {}x{} and {}x{}",
+                a.width(),
+                a.height(),
+                b.width(),
+                b.height()
+            );
+            std::process::exit(-1);
+        }
+    };
+
+    let stats = difference.stats();
+
+    println!("Relative error {stats}");
+
+    if let Some(output) = &args.output {
+        write_out(&difference.false_color(args.scale, args.log), output, None);
+    }
+
+    if let Some(limit) = args.fail_above {
+        if stats.mean > limit {
+            eprintln!("Mean relative error {} is above {limit}", stats.mean);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reads linear values from EXR files, PNG values are kept as stored.
+pub fn try_read_image(path: &Path) -> Result<FrameBuffer, String> {
+    match OutputFormat::from_path(path) {
+        OutputFormat::Exr => FrameBuffer::read_exr(path).map_err(|e| e.to_string()),
+        OutputFormat::Png => read_png(path).map_err(|e| e.to_string()),
+    }
+}
+
+fn read_image(path: &Path) -> FrameBuffer {
+    match try_read_image(path) {
+        Ok(fb) => fb,
+        Err(e) => {
+            eprintln!("Could not read {}: {e}", path.display());
+            std::process::exit(-1);
+        }
+    }
+}
+
+fn read_png(path: &Path) -> Result<FrameBuffer, png::DecodingError> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+
+    let channels = info.color_type.samples();
+    let mut fb = FrameBuffer::new(info.width as usize, info.height as usize);
+
+    for (pixel, data) in fb.buffer_mut().iter_mut().zip(buf.chunks(channels)) {
+        let values = data.iter().map(|&v| v as f32 / 255.0).collect::<Vec<_>>();
+
+        *pixel = match *values.as_slice() {
+            [l] => Pixel::new(l, l, l, 1.0),
+            [l, a] => Pixel::new(l, l, l, a),
+            [r, g, b] => Pixel::new(r, g, b, 1.0),
+            [r, g, b, a] => Pixel::new(r, g, b, a),
+            _ => unreachable!("expanded PNG has up to 4 channels"),
+        };
+    }
+
+    Ok(fb)
+}
@@ -1,106 +1,773 @@
 use std::fs::File;
-use std::io::BufWriter;
-use std::path::PathBuf;
-
-use cgmath::{InnerSpace, Vector3};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use clap::Parser;
 
-use blackhole::frame::{Frame, Region};
+use blackhole::aov::Aov;
+use blackhole::camera::Projection;
+use blackhole::denoise::{Denoiser, Guides};
+use blackhole::frame::{Frame, Mask, Region};
 use blackhole::framebuffer::{FrameBuffer, Pixel};
-use blackhole::marcher::RayMarcher;
+use blackhole::marcher::{RayMarcher, RenderSettings};
+use blackhole::post::burn_in::{BurnIn, BurnInFields};
+use blackhole::post::{CubeLut, Pass, Pipeline, PostSettings};
+use blackhole::postprocess::{self, Tonemap};
+use blackhole::scene::Scene;
 use blackhole::RenderMode;
 
+use blackhole_common::config::{Preset, UserConfig};
 use blackhole_common::scene_loader::SceneLoader;
+use blackhole_common::threads::{self, ThreadCount};
 
 mod args;
+mod diff;
+mod output;
+mod pack;
+mod patch;
+mod preview_background;
 mod renderer;
+mod section;
+mod sequence_report;
+mod thumbnail;
+mod validate;
+
+use args::{Args, Command, OutputFormat};
+use patch::Record;
+use renderer::{Checkpoints, CliRenderer, PreviewInterval, Previews};
 
-use args::Args;
-use renderer::CliRenderer;
+/// Samples per pixel of the normal and albedo passes guiding the denoiser, at most the samples
+/// of the render.
+const DENOISE_GUIDE_SAMPLES: usize = 8;
 
 fn main() {
     // clion needs help in trait annotation
     let args = <Args as Parser>::parse();
 
-    let mut fb = FrameBuffer::new(args.width, args.height);
+    match args.command {
+        Some(Command::Section(section_args)) => section::run(section_args),
+        Some(Command::Pack(pack_args)) => pack::run(pack_args),
+        Some(Command::Validate(validate_args)) => validate::run(validate_args),
+        Some(Command::Diff(diff_args)) => diff::run(diff_args),
+        Some(Command::Thumbnail(thumbnail_args)) => thumbnail::run(thumbnail_args),
+        Some(Command::PreviewBackground(preview_args)) => preview_background::run(preview_args),
+        Some(Command::SequenceReport(report_args)) => sequence_report::run(report_args),
+        Some(Command::Patch(patch_args)) => patch::run(patch_args),
+        None => render(args, None),
+    }
+}
 
-    let scene = SceneLoader::load_from_path(args.scene);
+/// Renders the scene of the arguments, writing `record` into the images or a new one if missing.
+fn render(mut args: Args, record: Option<Record>) {
+    SceneLoader::set_snapshots(!args.no_cache);
 
-    let scene = match scene {
-        Ok(v) => v,
-        Err(e) => {
-            eprintln!("Could not read scene description: {e}");
+    let mut scene = load_scene(args.scene.as_ref().expect("scene path is required"));
+    let preset = load_preset(args.preset.as_deref());
+
+    let width = scaled_size(args.width, preset.resolution_scale);
+    let height = scaled_size(args.height, preset.resolution_scale);
+    let region = load_region(&args.region, args.region_mask.as_deref(), width, height);
+
+    if let Some(seconds) = args.star_trails {
+        if scene.animation.sky.is_none() {
+            eprintln!("Star trails need `animation.sky` rotation in the scene");
+            std::process::exit(-1);
+        }
+
+        scene.long_exposure = seconds;
+    }
+
+    if args.no_clobber {
+        let output = output::numbered_path(&args.output, |path| {
+            output_paths(path, &args, &scene).iter().any(|p| p.exists())
+        });
+
+        if output != args.output {
+            println!(
+                "{} exists, saving to {}",
+                args.output.display(),
+                output.display()
+            );
+            args.output = output;
+        }
+    }
+
+    // patched images keep the record of their original render
+    let record = record.or_else(|| Record::of_render(&args));
+
+    let pixel_angle = match (args.background_footprints, scene.camera.projection) {
+        (false, _) => 0.0,
+        (true, Projection::Perspective | Projection::Fisheye) => {
+            scene.camera.hor_fov.to_radians() / width as f64
+        }
+        (true, Projection::Equirectangular | Projection::Stereo360 { .. }) => {
+            std::f64::consts::TAU / width as f64
+        }
+        (true, Projection::Parallel { .. }) => {
+            eprintln!(
+                "Background footprints need a camera with perspective or panoramic projection"
+            );
             std::process::exit(-1);
         }
     };
 
+    // flags override a chosen preset, which overrides settings of the scene
+    let preset_settings = args.preset.is_some().then_some(RenderSettings {
+        samples: Some(preset.samples),
+        max_depth: Some(preset.max_depth),
+        ..Default::default()
+    });
+    let settings = RenderSettings {
+        samples: args.samples,
+        max_depth: args.max_depth,
+        distortion_step: args.distortion_step,
+        background: args.background.then_some(true),
+        ..Default::default()
+    }
+    .or(preset_settings.unwrap_or_default())
+    .or(scene.render);
+
+    let background = settings.background.unwrap_or(false);
+    let threads = pick_threads(args.threads, args.logical_cores, background);
+
     let mut renderer = CliRenderer {
         ray_marcher: RayMarcher {
             mode: args.mode.into(),
+            max_depth: preset.max_depth,
+            check_finite: args.check_nan,
+            integrator: args.integrator.into(),
+            flip_inside_normals: args.flip_inside_normals,
+            pixel_angle,
+            sample_lights: args.light_sampling,
+            transparent_background: args.transparent,
             ..Default::default()
-        },
-        samples: args.samples,
-        threads: args.threads,
+        }
+        .with_settings(&settings),
+        samples: settings.samples.unwrap_or(preset.samples),
+        threads,
         frame: Frame {
-            width: args.width,
-            height: args.height,
-            region: Region::Whole,
+            width,
+            height,
+            region: region.clone(),
+            border: args.region_border,
         },
+        filter: preset.pixel_filter(),
+        seed: args.seed,
+        frame_index: args.frame,
+        lock_noise: args.lock_noise,
+        prepass: args.prepass,
+        checkpoint: args.checkpoint_interval.map(|seconds| Checkpoints {
+            path: checkpoint_path(&args.output),
+            interval: Duration::from_secs(seconds),
+        }),
         ..Default::default()
     };
 
-    renderer.render(&scene, &mut fb);
+    let mode: RenderMode = args.mode.into();
+
+    let mut denoise_guides = match (args.denoise, mode) {
+        (false, _) => None,
+        (true, RenderMode::Shaded) => Some(CliRenderer {
+            ray_marcher: renderer.ray_marcher.clone(),
+            samples: DENOISE_GUIDE_SAMPLES.min(renderer.samples),
+            threads,
+            frame: Frame {
+                width,
+                height,
+                region: region.clone(),
+                border: args.region_border,
+            },
+            seed: args.seed,
+            quiet: true,
+            ..Default::default()
+        }),
+        (true, _) => {
+            eprintln!("Denoising works only in the shaded mode, rendering without it");
+            None
+        }
+    };
+    let denoiser = Denoiser::default();
+    let format = args
+        .output_format
+        .unwrap_or_else(|| OutputFormat::from_path(&args.output));
+    let post = PostSettings {
+        exposure: args.exposure,
+        physical: scene.physical_exposure,
+        bloom: args.bloom,
+        bloom_threshold: args.bloom_threshold,
+        vignette: args.vignette,
+        tonemap: args.tonemap.map(Tonemap::from),
+        dither: args.dither,
+        ..Default::default()
+    }
+    .pipeline(scene.tonemap);
+
+    let post = match &args.lut {
+        Some(path) => match CubeLut::read(path) {
+            Ok(lut) => post.with_lut(Arc::new(lut)),
+            Err(e) => {
+                eprintln!("Could not read LUT {}: {e}", path.display());
+                std::process::exit(-1);
+            }
+        },
+        None => post,
+    };
+    let saving = Saving { mode, post, record };
+
+    let preview_every = match (args.preview_every, args.preview_interval) {
+        (Some(samples), _) => Some(PreviewInterval::Samples(samples)),
+        (_, Some(seconds)) => Some(PreviewInterval::Time(Duration::from_secs(seconds))),
+        _ => None,
+    };
+
+    if let Some(every) = preview_every {
+        let (output, crop, saving) = (args.output.clone(), args.crop, saving.clone());
+        let region = region.clone();
+
+        renderer.preview = Some(Previews {
+            every,
+            write: Box::new(move |fb| {
+                let mut fb = apply_region(fb.clone(), &region, crop);
+                save(&mut fb, &output, format, &saving, args.premultiplied, None);
+            }),
+        });
+    }
+
+    let burn_in = match &args.burn_in {
+        Some(text) => {
+            let burn_in = scene.burn_in.clone().unwrap_or_default();
+
+            Some(BurnIn {
+                text: text.clone().unwrap_or(burn_in.text),
+                ..burn_in
+            })
+        }
+        None => scene.burn_in.clone(),
+    };
+    let scene_name = args
+        .scene
+        .as_ref()
+        .and_then(|p| p.file_stem())
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let samples = renderer.samples;
+    let fill_burn_in = |frame: u64, render_time: Duration| {
+        burn_in.as_ref().map(|burn_in| {
+            burn_in.filled(&BurnInFields {
+                scene: &scene_name,
+                samples,
+                render_time,
+                frame,
+                notes: &args.notes,
+            })
+        })
+    };
+
+    if let Some(frames) = args.frames {
+        let mut frame_start = Instant::now();
+
+        renderer.render_sequence(&scene, args.frame..args.frame + frames, |frame, mut fb| {
+            let path = sequence_path(&args.output, frame);
+
+            if let Some(guides) = &mut denoise_guides {
+                let mut frame_scene = scene.clone();
+                frame_scene.set_time(scene.animation.frame_time(frame));
+
+                denoise(guides, &frame_scene, &mut fb, &denoiser);
+            }
+
+            let mut fb = apply_region(fb, &region, args.crop);
+            let burn_in = fill_burn_in(frame, frame_start.elapsed());
+
+            save(
+                &mut fb,
+                &path,
+                format,
+                &saving,
+                args.premultiplied,
+                burn_in.as_ref(),
+            );
+
+            frame_start = Instant::now();
+        });
+
+        return;
+    }
+
+    scene.set_time(scene.animation.frame_time(args.frame));
+
+    let start = Instant::now();
+
+    if let Some(separation) = args.stereo {
+        let mut fb = render_stereo(&mut renderer, &scene, separation);
+        let burn_in = fill_burn_in(args.frame, start.elapsed());
+
+        save(
+            &mut fb,
+            &args.output,
+            format,
+            &saving,
+            args.premultiplied,
+            burn_in.as_ref(),
+        );
+
+        return;
+    }
+
+    let new_fb = || match &args.spill_dir {
+        Some(dir) => FrameBuffer::new_mapped(width, height, dir).unwrap_or_else(|e| {
+            eprintln!("Could not create framebuffer in {}: {e}", dir.display());
+            std::process::exit(-1);
+        }),
+        None => FrameBuffer::new(width, height),
+    };
+
+    let mut fb = if args.resume {
+        let (fb, samples) = load_checkpoint(&checkpoint_path(&args.output), width, height);
+        renderer.start_sample = samples;
+
+        fb
+    } else {
+        new_fb()
+    };
+
+    let mut layers = if args.layers {
+        (0..=scene.layers.len()).map(|_| new_fb()).collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut aovs = args
+        .aovs
+        .iter()
+        .map(|aov| (Aov::from(*aov), new_fb()))
+        .collect::<Vec<_>>();
+
+    renderer.render_passes(&scene, &mut fb, &mut layers, &mut aovs);
+
+    if let Some(guides) = &mut denoise_guides {
+        denoise(guides, &scene, &mut fb, &denoiser);
+    }
+
+    let mut fb = apply_region(fb, &region, args.crop);
+    let burn_in = fill_burn_in(args.frame, start.elapsed());
+
+    save(
+        &mut fb,
+        &args.output,
+        format,
+        &saving,
+        args.premultiplied,
+        burn_in.as_ref(),
+    );
+
+    // layers and passes stay premultiplied, so they add up to the full image
+    let names = scene
+        .layers
+        .iter()
+        .map(String::as_str)
+        .chain(["background"]);
 
-    post_process(&mut fb, &args.mode.into());
+    for (name, layer) in names.zip(layers) {
+        let mut layer = apply_region(layer, &region, args.crop);
+        let path = layer_path(&args.output, name);
 
-    write_out(fb, &args.output, args.width as u32, args.height as u32);
+        save(&mut layer, &path, OutputFormat::Exr, &saving, true, None);
+    }
+
+    for (aov, pass) in aovs {
+        let mut pass = apply_region(pass, &region, args.crop);
+        let path = aov_path(&args.output, aov);
+
+        save(&mut pass, &path, OutputFormat::Exr, &saving, true, None);
+    }
+
+    if args.resume || args.checkpoint_interval.is_some() {
+        match std::fs::remove_file(checkpoint_path(&args.output)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                eprintln!("Could not remove checkpoint: {e}");
+            }
+            _ => {}
+        }
+    }
 }
 
-fn post_process(fb: &mut FrameBuffer, mode: &RenderMode) {
-    let luminance_base = Vector3::new(0.2126, 0.7152, 0.0722);
+/// Renders normal and albedo passes with the guide renderer and filters the image with them.
+fn denoise(guides: &mut CliRenderer, scene: &Scene, fb: &mut FrameBuffer, denoiser: &Denoiser) {
+    println!("Denoising");
 
-    match mode {
-        RenderMode::Shaded => {
-            for pixel in fb.buffer_mut() {
-                let luminance = Vector3::new(pixel.r, pixel.g, pixel.b).dot(luminance_base);
+    let (width, height) = (fb.width(), fb.height());
+    let mut pass = |mode| {
+        let mut pass = FrameBuffer::new(width, height);
+        guides.ray_marcher.mode = mode;
+        guides.render(scene, &mut pass);
+
+        pass
+    };
 
-                let new_luminance = luminance / (luminance + 1.0);
+    let normal = pass(RenderMode::Normal);
+    let albedo = pass(RenderMode::Albedo);
 
-                let tonemapped = Pixel::new(
-                    pixel.r * (new_luminance / luminance),
-                    pixel.g * (new_luminance / luminance),
-                    pixel.b * (new_luminance / luminance),
-                    pixel.a,
-                );
+    denoiser.run(
+        fb,
+        Guides {
+            normal: Some(&normal),
+            albedo: Some(&albedo),
+        },
+    );
+}
+
+/// Makes pixels outside of the rendered region transparent, cropping the image to its bounds if
+/// asked.
+fn apply_region(mut fb: FrameBuffer, region: &Region, crop: bool) -> FrameBuffer {
+    let width = fb.width();
+
+    for (i, pixel) in fb.buffer_mut().iter_mut().enumerate() {
+        if !region.contains(i % width, i / width) {
+            *pixel = Pixel::new(0.0, 0.0, 0.0, 0.0);
+        }
+    }
+
+    if crop {
+        fb.crop(region)
+    } else {
+        fb
+    }
+}
+
+/// Region given by `--region` windows or `--region-mask`, exits when it doesn't fit the image.
+fn load_region(windows: &[Region], mask: Option<&Path>, width: usize, height: usize) -> Region {
+    for window in windows {
+        let (_, _, x_max, y_max) = window.bounds(width, height);
+
+        if x_max > width || y_max > height {
+            eprintln!("Region does not fit into the {width}x{height} image");
+            std::process::exit(-1);
+        }
+    }
 
-                let new_pixel = Pixel::new(
-                    tonemapped.r.powf(1.0 / 2.2),
-                    tonemapped.g.powf(1.0 / 2.2),
-                    tonemapped.b.powf(1.0 / 2.2),
-                    pixel.a,
+    let mask = match (mask, windows) {
+        (None, []) => return Region::Whole,
+        (None, [window]) => return window.clone(),
+        (None, windows) => Mask::from_windows(width, height, windows),
+        (Some(path), _) => {
+            let fb = match diff::try_read_image(path) {
+                Ok(fb) => fb,
+                Err(e) => {
+                    eprintln!("Could not read region mask {}: {e}", path.display());
+                    std::process::exit(-1);
+                }
+            };
+
+            if fb.width() != width || fb.height() != height {
+                eprintln!(
+                    "Region mask is {}x{}, but the image is {width}x{height}",
+                    fb.width(),
+                    fb.height()
                 );
+                std::process::exit(-1);
+            }
+
+            Mask::from_framebuffer(&fb)
+        }
+    };
+
+    if mask.is_empty() {
+        eprintln!("Region mask has no pixels to render");
+        std::process::exit(-1);
+    }
 
-                *pixel = new_pixel;
+    Region::Mask(Arc::new(mask))
+}
+
+/// Renders both eyes with the same noise pattern and puts them side by side, left eye first.
+fn render_stereo(renderer: &mut CliRenderer, scene: &Scene, separation: f64) -> FrameBuffer {
+    let (width, height) = (renderer.frame.width, renderer.frame.height);
+    let mut stereo = FrameBuffer::new(width * 2, height);
+
+    for (i, camera) in scene.camera.stereo_eyes(separation).into_iter().enumerate() {
+        let mut eye_scene = scene.clone();
+        eye_scene.camera = camera;
+
+        renderer.filter.reset();
+
+        let mut fb = FrameBuffer::new(width, height);
+        renderer.render(&eye_scene, &mut fb);
+
+        for (y, row) in fb.buffer().chunks(width).enumerate() {
+            let start = y * width * 2 + i * width;
+            stereo.buffer_mut()[start..start + width].copy_from_slice(row);
+        }
+    }
+
+    stereo
+}
+
+/// Sidecar file with the unfinished render, `out.png` has `out.png.checkpoint`.
+fn checkpoint_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".checkpoint");
+
+    PathBuf::from(path)
+}
+
+fn load_checkpoint(path: &Path, width: usize, height: usize) -> (FrameBuffer, usize) {
+    let (fb, samples) = match FrameBuffer::read_checkpoint(path) {
+        Ok(checkpoint) => checkpoint,
+        Err(e) => {
+            eprintln!("Could not read checkpoint {}: {e}", path.display());
+            std::process::exit(-1);
+        }
+    };
+
+    if (fb.width(), fb.height()) != (width, height) {
+        eprintln!(
+            "Checkpoint is {}x{}, but the render is {width}x{height}",
+            fb.width(),
+            fb.height()
+        );
+        std::process::exit(-1);
+    }
+
+    println!("Resuming after {samples} samples");
+
+    (fb, samples)
+}
+
+/// Settings shared by every image a render saves.
+#[derive(Clone)]
+struct Saving {
+    mode: RenderMode,
+    post: Pipeline,
+    /// Written into the metadata of the images
+    record: Option<Record>,
+}
+
+/// Writes the image with colors of straight alpha, or `premultiplied` by it as rendered.
+///
+/// The burn-in pass is drawn over the finished image, after post processing, on straight colors
+/// in both formats.
+fn save(
+    fb: &mut FrameBuffer,
+    path: &Path,
+    format: OutputFormat,
+    saving: &Saving,
+    premultiplied: bool,
+    burn_in: Option<&BurnIn>,
+) {
+    let record = saving.record.as_ref();
+    let burn_in = burn_in.map(|burn_in| Pass::BurnIn(burn_in.clone()));
+
+    match format {
+        OutputFormat::Png => {
+            // post processing works on straight colors
+            postprocess::unpremultiply(fb);
+            post_process(fb, &saving.mode, &saving.post);
+
+            if let Some(burn_in) = &burn_in {
+                burn_in.apply(fb);
+            }
+
+            if premultiplied {
+                postprocess::premultiply(fb);
+            }
+
+            write_out(fb, path, record);
+        }
+        OutputFormat::Exr => {
+            if let Some(burn_in) = &burn_in {
+                postprocess::unpremultiply(fb);
+                burn_in.apply(fb);
+
+                if premultiplied {
+                    postprocess::premultiply(fb);
+                }
+            } else if !premultiplied {
+                postprocess::unpremultiply(fb);
             }
+
+            write_exr(fb, path, record);
         }
-        RenderMode::Samples | RenderMode::Normal => {}
     }
 }
 
-fn write_out(fb: FrameBuffer, name: &PathBuf, width: u32, height: u32) {
-    let buf = unsafe {
-        assert_eq!(std::mem::size_of::<Pixel>(), 4 * std::mem::size_of::<f32>());
+/// Every image the render saves with this output path.
+fn output_paths(output: &Path, args: &Args, scene: &Scene) -> Vec<PathBuf> {
+    if let Some(frames) = args.frames {
+        return (args.frame..args.frame + frames)
+            .map(|frame| sequence_path(output, frame))
+            .collect();
+    }
+
+    let layers = scene
+        .layers
+        .iter()
+        .map(String::as_str)
+        .chain(["background"])
+        .filter(|_| args.layers)
+        .map(|name| layer_path(output, name));
+    let aovs = args
+        .aovs
+        .iter()
+        .map(|aov| aov_path(output, Aov::from(*aov)));
+
+    std::iter::once(output.to_owned())
+        .chain(layers)
+        .chain(aovs)
+        .collect()
+}
+
+/// Appends zero padded frame number to the file name, `out.png` becomes `out_0012.png`.
+fn sequence_path(path: &Path, frame: u64) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
 
-        fb.as_f32_slice()
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}_{frame:04}.{}", ext.to_string_lossy()),
+        None => format!("{stem}_{frame:04}"),
     };
 
-    let mapped = buf.iter().map(|e| (e * 255.0) as u8).collect::<Vec<_>>();
+    path.with_file_name(name)
+}
+
+/// Render layer next to the output, `out.png` has layer `ship` in `out_ship.exr`.
+fn layer_path(path: &Path, layer: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    path.with_file_name(format!("{stem}_{layer}.exr"))
+}
+
+/// Path of an AOV next to the output, `out.png` becomes `out.normal.exr`.
+fn aov_path(path: &Path, aov: Aov) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
 
-    let file = File::create(name).unwrap();
-    let writer = BufWriter::new(file);
-    let mut encoder = png::Encoder::new(writer, width, height);
-    encoder.set_color(png::ColorType::Rgba);
-    let mut writer = encoder.write_header().unwrap();
-    writer.write_image_data(&mapped).unwrap();
+    path.with_file_name(format!("{stem}.{}.exr", aov.name()))
+}
+
+fn load_scene(path: impl AsRef<Path>) -> Scene {
+    match SceneLoader::load_with_lints(path) {
+        Ok((scene, lints)) => {
+            for lint in lints {
+                eprintln!("warning: {lint}");
+            }
+
+            scene
+        }
+        Err(e) => {
+            eprintln!("Could not read scene description: {e}");
+            std::process::exit(-1);
+        }
+    }
+}
+
+/// Resolves automatic thread count and lowers priority in background mode, reporting both.
+fn pick_threads(requested: usize, logical: bool, background: bool) -> ThreadCount {
+    let count = ThreadCount::pick(requested, logical);
+
+    if count.oversubscribed() {
+        eprintln!(
+            "warning: {} threads are more than {} logical cores, rendering won't be faster",
+            count.threads, count.logical_cores
+        );
+    }
+
+    if background {
+        match threads::lower_priority() {
+            Ok(()) => println!("Running with lowered priority"),
+            Err(e) => eprintln!("warning: could not lower priority: {e}"),
+        }
+    }
+
+    count
+}
+
+/// Finds preset selected on command line, or default settings without one.
+fn load_preset(name: Option<&str>) -> Preset {
+    let name = match name {
+        Some(name) => name,
+        None => return Preset::default(),
+    };
+
+    let config = match UserConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Could not read user config: {e}");
+            std::process::exit(-1);
+        }
+    };
+
+    match config.preset(name) {
+        Some(preset) => preset,
+        None => {
+            eprintln!(
+                "Unknown preset '{name}', available: {}",
+                config.preset_names().join(", ")
+            );
+            std::process::exit(-1);
+        }
+    }
+}
+
+fn scaled_size(size: usize, scale: f64) -> usize {
+    ((size as f64 * scale).round() as usize).max(1)
+}
+
+fn post_process(fb: &mut FrameBuffer, mode: &RenderMode, post: &Pipeline) {
+    match mode {
+        RenderMode::Shaded | RenderMode::Deterministic => post.run(fb),
+        RenderMode::Depth => postprocess::depth_display(fb),
+        RenderMode::Samples | RenderMode::Normal | RenderMode::Termination | RenderMode::Albedo => {
+        }
+    }
+}
+
+fn write_exr(fb: &FrameBuffer, name: &Path, record: Option<&Record>) {
+    let text = output::image_text(record);
+    let text = text
+        .iter()
+        .map(|(k, v)| (*k, v.as_str()))
+        .collect::<Vec<_>>();
+
+    if let Err(e) = output::write_atomic(name, |path| fb.write_exr_with_text(path, &text)) {
+        eprintln!("Could not write {}: {e}", name.display());
+        std::process::exit(-1);
+    }
+}
+
+fn write_out(fb: &FrameBuffer, name: &Path, record: Option<&Record>) {
+    let (width, height) = (fb.width() as u32, fb.height() as u32);
+
+    let res = output::write_atomic(name, |path| {
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+
+        for (key, text) in output::image_text(record) {
+            encoder.add_itxt_chunk(key.into(), text)?;
+        }
+
+        // encoded row by row, so images spilled to disk don't need another copy in memory
+        let mut stream = encoder.write_header()?.into_stream_writer()?;
+        let mut bytes = Vec::with_capacity(fb.width() * 4);
+
+        for y in 0..fb.height() {
+            bytes.clear();
+            bytes.extend(
+                fb.row(y)
+                    .iter()
+                    .flat_map(|p| [p.r, p.g, p.b, p.a].map(|c| (c * 255.0) as u8)),
+            );
+
+            stream.write_all(&bytes)?;
+        }
+
+        stream.finish()
+    });
+
+    if let Err(e) = res {
+        eprintln!("Could not write {}: {e}", name.display());
+        std::process::exit(-1);
+    }
 }
@@ -0,0 +1,84 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::patch::Record;
+
+/// Key of the render record in PNG text chunks and EXR header attributes.
+pub const RECORD_KEY: &str = "blackhole:render";
+
+/// Metadata attached to saved images, with the `record` of the render that made them.
+pub fn image_text(record: Option<&Record>) -> Vec<(&'static str, String)> {
+    record
+        .map(|record| (RECORD_KEY, record.to_json()))
+        .into_iter()
+        .collect()
+}
+
+/// Writes a file through a temporary file next to it, renamed over the target when complete.
+///
+/// An interrupted or failed write leaves any previous file at the path untouched. The temporary
+/// file is in the same directory, so the rename stays on one filesystem and is atomic.
+pub fn write_atomic<E: From<std::io::Error>>(
+    path: &Path,
+    write: impl FnOnce(&Path) -> Result<(), E>,
+) -> Result<(), E> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let partial = path.with_file_name(format!(".{name}.{}.partial", std::process::id()));
+
+    let res = write(&partial)
+        .and_then(|_| Ok(File::open(&partial)?.sync_all()?))
+        .and_then(|_| Ok(std::fs::rename(&partial, path)?));
+
+    if res.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+
+    res
+}
+
+/// First of `out.png`, `out-1.png`, `out-2.png`... for which `taken` is false.
+///
+/// `taken` gets the numbered output path and should also check files derived from it.
+pub fn numbered_path(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    if !taken(path) {
+        return path.to_owned();
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    (1..)
+        .map(|n| match path.extension() {
+            Some(ext) => path.with_file_name(format!("{stem}-{n}.{}", ext.to_string_lossy())),
+            None => path.with_file_name(format!("{stem}-{n}")),
+        })
+        .find(|path| !taken(path))
+        .expect("some number is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_write_keeps_previous_file() {
+        let dir = std::env::temp_dir().join(format!("blackhole-output-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.txt");
+
+        write_atomic(&path, |p| std::fs::write(p, "good")).unwrap();
+
+        let res = write_atomic(&path, |p| {
+            std::fs::write(p, "corrupt")?;
+            Err(std::io::Error::other("crashed"))
+        });
+
+        assert!(res.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "good");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        let free = numbered_path(&path, |p| p.exists() || p.ends_with("out-1.txt"));
+        assert_eq!(free, dir.join("out-2.txt"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
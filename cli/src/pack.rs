@@ -0,0 +1,85 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use blackhole_common::assets::PackedScene;
+use blackhole_common::scene_loader::SceneLoader;
+
+use crate::args::PackArgs;
+
+pub fn run(args: PackArgs) {
+    let packed = match SceneLoader::pack(&args.scene) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Could not pack scene: {e}");
+            std::process::exit(-1);
+        }
+    };
+
+    let scene_name = args
+        .scene
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "scene.json5".to_owned());
+
+    let is_zip = args
+        .output
+        .extension()
+        .map(|e| e.eq_ignore_ascii_case("zip"))
+        .unwrap_or(false);
+
+    let res = if is_zip {
+        write_zip(&packed, &scene_name, &args.output)
+    } else {
+        write_dir(&packed, &scene_name, &args.output)
+    };
+
+    match res {
+        Ok(()) => println!(
+            "Packed scene with {} assets into {}",
+            packed.assets.len(),
+            args.output.display()
+        ),
+        Err(e) => {
+            eprintln!("Could not write packed scene: {e}");
+            std::process::exit(-1);
+        }
+    }
+}
+
+fn write_dir(packed: &PackedScene, scene_name: &str, dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(scene_name), &packed.scene)?;
+
+    for asset in &packed.assets {
+        let target = dir.join(&asset.name);
+
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::copy(&asset.source, target)?;
+    }
+
+    Ok(())
+}
+
+fn write_zip(packed: &PackedScene, scene_name: &str, path: &Path) -> std::io::Result<()> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = FileOptions::default();
+
+    zip.start_file(scene_name, options)?;
+    zip.write_all(packed.scene.as_bytes())?;
+
+    for asset in &packed.assets {
+        zip.start_file(asset.name.as_str(), options)?;
+        zip.write_all(&std::fs::read(&asset.source)?)?;
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
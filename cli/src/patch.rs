@@ -0,0 +1,296 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use serde_json::{json, Value};
+
+use blackhole::framebuffer::FrameBuffer;
+
+use blackhole_common::scene_loader::SceneLoader;
+
+use crate::args::{Args, OutputFormat, PatchArgs};
+use crate::diff::try_read_image;
+use crate::{load_region, output, render, sequence_path, write_exr, write_out};
+
+/// Settings of a render saved into its images, so parts of them can be rendered again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    /// Hash of the contents of the scene file and every file the render reads, see [`input_hash`]
+    pub scene_hash: String,
+    /// Working directory of the render, relative paths in its arguments start there
+    pub cwd: PathBuf,
+    /// Output path after `--no-clobber` numbering
+    pub output: PathBuf,
+    /// Command line arguments without the program name
+    pub args: Vec<String>,
+}
+
+impl Record {
+    /// Record of the render started by this process, `None` if some of its inputs can't be read.
+    pub fn of_render(args: &Args) -> Option<Self> {
+        Some(Self {
+            scene_hash: input_hash(args)?,
+            cwd: std::env::current_dir().ok()?,
+            output: args.output.clone(),
+            args: std::env::args_os()
+                .skip(1)
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        json!({
+            "scene_hash": self.scene_hash,
+            "cwd": self.cwd,
+            "output": self.output,
+            "args": self.args,
+        })
+        .to_string()
+    }
+
+    pub fn from_json(text: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(text).ok()?;
+        let string = |key: &str| value.get(key)?.as_str().map(str::to_owned);
+
+        Some(Self {
+            scene_hash: string("scene_hash")?,
+            cwd: string("cwd")?.into(),
+            output: string("output")?.into(),
+            args: value
+                .get("args")?
+                .as_array()?
+                .iter()
+                .map(|arg| arg.as_str().map(str::to_owned))
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// Reads the record from metadata of a PNG or EXR image.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let text = match OutputFormat::from_path(path) {
+            OutputFormat::Png => {
+                let decoder = png::Decoder::new(File::open(path).map_err(|e| e.to_string())?);
+                let reader = decoder.read_info().map_err(|e| e.to_string())?;
+
+                reader
+                    .info()
+                    .utf8_text
+                    .iter()
+                    .find(|chunk| chunk.keyword == output::RECORD_KEY)
+                    .map(|chunk| chunk.get_text())
+                    .transpose()
+                    .map_err(|e| e.to_string())?
+            }
+            OutputFormat::Exr => FrameBuffer::read_exr_text(path)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .find(|(key, _)| key == output::RECORD_KEY)
+                .map(|(_, text)| text),
+        };
+
+        let text = text.ok_or("image has no record of its render")?;
+
+        Self::from_json(&text).ok_or_else(|| "render record is damaged".into())
+    }
+}
+
+/// FNV-1a hash of the scene file, assets it references and the LUT of the arguments, in hex.
+///
+/// Assets are found the same way the loader does, so a moved or edited texture or mesh changes
+/// the hash as well as edits of the scene itself.
+fn input_hash(args: &Args) -> Option<String> {
+    let scene = args.scene.as_ref()?;
+    let assets = SceneLoader::pack(scene).ok()?.assets;
+
+    let inputs = std::iter::once(scene.clone())
+        .chain(assets.into_iter().map(|asset| asset.source))
+        .chain(args.lut.clone());
+
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+
+    for input in inputs {
+        // paths keep moved files apart, contents catch edits
+        let path = input.to_string_lossy().into_owned().into_bytes();
+
+        for byte in path.into_iter().chain(std::fs::read(&input).ok()?) {
+            hash = (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    Some(format!("{hash:016x}"))
+}
+
+pub fn run(patch: PatchArgs) {
+    let fail = |message: String| -> ! {
+        eprintln!("{message}");
+        std::process::exit(-1);
+    };
+    let absolute = |path: &Path| {
+        std::path::absolute(path)
+            .unwrap_or_else(|e| fail(format!("Could not find {}: {e}", path.display())))
+    };
+
+    let image = absolute(&patch.image);
+    let region_mask = patch.region_mask.as_deref().map(absolute);
+
+    let record = Record::read(&image)
+        .unwrap_or_else(|e| fail(format!("Could not read {}: {e}", image.display())));
+
+    // paths in the arguments are relative to the directory the render started in
+    if let Err(e) = std::env::set_current_dir(&record.cwd) {
+        fail(format!("Could not enter {}: {e}", record.cwd.display()));
+    }
+
+    let mut args = Args::try_parse_from(["blackhole-cli".into()].iter().chain(&record.args))
+        .unwrap_or_else(|e| fail(format!("Could not read render arguments: {e}")));
+
+    if !patch.force && input_hash(&args).as_ref() != Some(&record.scene_hash) {
+        let scene = args.scene.as_deref().unwrap_or(Path::new(""));
+
+        fail(format!(
+            "{} or its assets changed since the render, use --force to patch anyway",
+            scene.display()
+        ));
+    }
+
+    if args.stereo.is_some() {
+        fail("Stereo renders can't be patched".into());
+    }
+
+    if let Some(frames) = args.frames.take() {
+        let is_image =
+            |frame: &u64| sequence_path(&record.output, *frame).file_name() == image.file_name();
+
+        match (args.frame..args.frame + frames).find(is_image) {
+            Some(frame) => args.frame = frame,
+            None => fail(format!("{} is not a frame of the render", image.display())),
+        }
+    }
+
+    let name = image.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = image.extension().unwrap_or_default().to_string_lossy();
+    let patched = image.with_file_name(format!(".{name}.patch.{ext}"));
+
+    args.output = patched.clone();
+    args.region = patch.region;
+    args.region_mask = region_mask;
+    args.region_border = patch.region_border;
+    args.samples = patch.samples.or(args.samples);
+    args.seed = patch.seed.unwrap_or(args.seed);
+    args.crop = false;
+    args.no_clobber = false;
+    args.resume = false;
+    args.checkpoint_interval = None;
+    args.preview_every = None;
+    args.preview_interval = None;
+    args.layers = false;
+    args.aovs.clear();
+
+    let region_args = (args.region.clone(), args.region_mask.clone());
+
+    render(args, Some(record.clone()));
+
+    let read = |path: &Path| {
+        try_read_image(path)
+            .unwrap_or_else(|e| fail(format!("Could not read {}: {e}", path.display())))
+    };
+    let mut fb = read(&image);
+    let rendered = read(&patched);
+    let _ = std::fs::remove_file(&patched);
+
+    if (fb.width(), fb.height()) != (rendered.width(), rendered.height()) {
+        fail(format!(
+            "{} does not have the size of the render",
+            image.display()
+        ));
+    }
+
+    let (region, region_mask) = region_args;
+    let region = load_region(&region, region_mask.as_deref(), fb.width(), fb.height());
+    let width = fb.width();
+
+    for (i, (pixel, new)) in fb
+        .buffer_mut()
+        .iter_mut()
+        .zip(rendered.buffer())
+        .enumerate()
+    {
+        if region.contains(i % width, i / width) {
+            *pixel = *new;
+        }
+    }
+
+    match OutputFormat::from_path(&image) {
+        OutputFormat::Png => write_out(&fb, &image, Some(&record)),
+        OutputFormat::Exr => write_exr(&fb, &image, Some(&record)),
+    }
+
+    println!("Patched {}", image.display());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_is_read_from_images() {
+        let record = Record {
+            scene_hash: "0123456789abcdef".into(),
+            cwd: "/renders".into(),
+            output: "shot.png".into(),
+            args: vec!["scene.json5".into(), "--samples".into(), "64".into()],
+        };
+
+        let dir = std::env::temp_dir().join(format!("blackhole-patch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for name in ["shot.png", "shot.exr"] {
+            let path = dir.join(name);
+            let fb = FrameBuffer::new(4, 2);
+
+            match OutputFormat::from_path(&path) {
+                OutputFormat::Png => write_out(&fb, &path, Some(&record)),
+                OutputFormat::Exr => write_exr(&fb, &path, Some(&record)),
+            }
+
+            assert_eq!(Record::read(&path).unwrap(), record);
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hash_covers_assets() {
+        blackhole::cache::set_dir(Some(blackhole::cache::temporary_dir()));
+
+        let dir = std::env::temp_dir().join(format!("blackhole-hash-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let scene = dir.join("scene.json5");
+        let sky = r#"{
+            shaders: {
+                sky: { class: "HdriBackgroundShader", kind: "background", parameters: { path: "sky.hdr" } },
+            },
+            objects: [],
+            distortions: [],
+            background: "sky",
+            camera: { location: [0, 0, -5], hor_fov: 40 },
+        }"#;
+        std::fs::write(&scene, sky).unwrap();
+        std::fs::write(dir.join("sky.hdr"), "first").unwrap();
+
+        let args = Args::try_parse_from(["blackhole-cli".as_ref(), scene.as_os_str()]).unwrap();
+        let hash = input_hash(&args).unwrap();
+        assert_eq!(input_hash(&args).unwrap(), hash);
+
+        std::fs::write(dir.join("sky.hdr"), "second").unwrap();
+        assert_ne!(input_hash(&args).unwrap(), hash);
+
+        // missing assets fail the render, so there is nothing to record
+        std::fs::remove_file(dir.join("sky.hdr")).unwrap();
+        assert_eq!(input_hash(&args), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
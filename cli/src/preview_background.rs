@@ -0,0 +1,67 @@
+use rayon::prelude::*;
+
+use blackhole::framebuffer::{FrameBuffer, Pixel};
+use blackhole::math::{equirect_direction, pixel_seed, rand_unit, seed_rng};
+use blackhole::post::PostSettings;
+use blackhole::postprocess::Tonemap;
+use blackhole::{Ray, RayKind};
+
+use cgmath::{Vector3, Zero};
+
+use crate::args::PreviewBackgroundArgs;
+use crate::{load_scene, write_out};
+
+pub fn run(args: PreviewBackgroundArgs) {
+    let scene = load_scene(&args.scene);
+
+    let width = args.width.max(2);
+    let height = width / 2;
+    let samples = args.samples.max(1);
+    let exposure = scene.camera.exposure.multiplier();
+
+    let start = std::time::Instant::now();
+
+    let mut fb = FrameBuffer::new(width, height);
+
+    fb.buffer_mut()
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let mut color = Vector3::zero();
+
+                for sample in 0..samples {
+                    seed_rng(pixel_seed(0, sample, x, y));
+
+                    let u = (x as f64 + rand_unit()) / width as f64;
+                    let v = (y as f64 + rand_unit()) / height as f64;
+
+                    let ray = Ray {
+                        location: scene.camera.location,
+                        direction: equirect_direction(u, v),
+                        steps_taken: 0,
+                        kind: RayKind::Primary,
+                        time: 0.5,
+                    };
+
+                    color += scene.background.emission_at(&ray);
+                }
+
+                *pixel = Pixel::from(color * (exposure / samples as f64));
+            }
+        });
+
+    let post = PostSettings {
+        tonemap: args.tonemap.map(Tonemap::from),
+        physical: scene.physical_exposure,
+        ..Default::default()
+    };
+    post.pipeline(scene.tonemap).run(&mut fb);
+
+    write_out(&fb, &args.output, None);
+
+    println!(
+        "Background took {:.02} seconds",
+        start.elapsed().as_secs_f64()
+    );
+}
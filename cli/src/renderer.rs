@@ -1,8 +1,38 @@
-use std::sync::atomic::AtomicUsize;
+use blackhole::marcher::NonFinite;
 
-static TOTAL_STEPS: AtomicUsize = AtomicUsize::new(0);
-static MAX_STEPS_PER_SAMPLE: AtomicUsize = AtomicUsize::new(0);
+/// Most non-finite samples kept for the report, the rest is only counted.
+const MAX_NON_FINITE: usize = 100_000;
+
+/// Samples of a render which got non-finite values, up to [`MAX_NON_FINITE`].
+struct NonFiniteLog {
+    count: usize,
+    samples: Vec<NonFiniteSample>,
+}
+
+impl NonFiniteLog {
+    const fn new() -> Self {
+        Self {
+            count: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, sample: NonFiniteSample) {
+        self.count += 1;
+
+        if self.samples.len() < MAX_NON_FINITE {
+            self.samples.push(sample);
+        }
+    }
+}
+
+struct NonFiniteSample {
+    x: usize,
+    y: usize,
+    sample: usize,
+    found: NonFinite,
+}
 
 mod cli;
 
-pub use cli::CliRenderer;
+pub use cli::{Checkpoints, CliRenderer, PreviewInterval, Previews};
@@ -1,66 +1,242 @@
+use blackhole::aov::Aov;
 use blackhole::filter::{BlackmanHarrisFilter, PixelFilter};
-use blackhole::frame::{Frame, Region};
+use blackhole::frame::{Frame, Mask, Region};
 use blackhole::framebuffer::{FrameBuffer, Pixel};
-use blackhole::marcher::RayMarcher;
+use blackhole::marcher::{MarchStats, NonFiniteSource, RayMarcher, Scratch, Termination};
+use blackhole::math::{frame_seed, pixel_seed, seed_rng, temporal_offset};
+use blackhole::postprocess::{self, StepLevels};
+use blackhole::prepass::SampleMap;
 use blackhole::scene::Scene;
+use blackhole::simd;
 use blackhole::RenderMode;
+use blackhole_common::threads::ThreadCount;
+
+use cgmath::{Vector3, Zero};
 
 use std::io::Write;
+use std::ops::Range;
+use std::path::PathBuf;
 use std::slice::ChunksMut;
-use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use rayon::prelude::*;
 
-use crate::renderer::{MAX_STEPS_PER_SAMPLE, TOTAL_STEPS};
+use crate::renderer::{NonFiniteLog, NonFiniteSample};
 
 pub struct CliRenderer {
     pub ray_marcher: RayMarcher,
     pub samples: usize,
-    pub threads: usize,
+    /// Threads rendering the samples, printed with the statistics
+    pub threads: ThreadCount,
     pub frame: Frame,
     pub filter: Box<dyn PixelFilter>,
+    pub seed: u64,
+    /// Index of the rendered frame in a sequence, changes the noise pattern
+    pub frame_index: u64,
+    /// Keep the same noise pattern in every frame, offsetting volume scattering over time
+    pub lock_noise: bool,
+    /// Spend samples by a low resolution pre-pass, see [`SampleMap`]
+    pub prepass: bool,
+    /// Don't print progress and statistics
+    pub quiet: bool,
+    /// Samples already accumulated in the framebuffer, rendering continues with the next one
+    pub start_sample: usize,
+    /// Periodically save progress to continue an interrupted render
+    pub checkpoint: Option<Checkpoints>,
+    /// Periodically pass the unfinished image to a callback
+    pub preview: Option<Previews>,
+}
+
+/// Callback for unfinished images.
+pub type PreviewFn = Box<dyn FnMut(&FrameBuffer) + Send + Sync>;
+
+pub struct Previews {
+    pub every: PreviewInterval,
+    pub write: PreviewFn,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum PreviewInterval {
+    Samples(usize),
+    Time(Duration),
+}
+
+/// Where and how often to save the framebuffer during a render.
+pub struct Checkpoints {
+    pub path: PathBuf,
+    pub interval: Duration,
+}
+
+/// Pixels between probe rays of the pre-pass.
+const PREPASS_STRIDE: usize = 8;
+
+/// Settings and counters shared by all pixels in one sample pass.
+struct Pass<'a> {
+    index: usize,
+    offset: (f64, f64),
+    seed: u64,
+    sample_map: Option<&'a SampleMap>,
+    /// Pixels to trace when the region is a mask, the rows cover only its bounds
+    mask: Option<&'a Mask>,
+    stats: &'a Mutex<MarchStats>,
+    non_finite: &'a Mutex<NonFiniteLog>,
+}
+
+/// Rows of render layers and AOVs matching the rendered row of the image.
+struct PassRows<'fb> {
+    layers: Vec<FrameBufferSlice<'fb>>,
+    aovs: Vec<(Aov, FrameBufferSlice<'fb>)>,
 }
 
 impl CliRenderer {
     pub fn render(&mut self, scene: &Scene, fb: &mut FrameBuffer) {
+        self.render_passes(scene, fb, &mut [], &mut []);
+    }
+
+    /// Renders the scene, also splitting every sample into render layers by the object it hit
+    /// and collecting AOVs of camera rays.
+    ///
+    /// `layers` are either empty, or one framebuffer for each of [`Scene::layers`] followed by
+    /// one for the background. Each layer keeps alpha of its coverage, samples hitting other
+    /// layers are transparent, so the layers sum up to the full image.
+    pub fn render_passes(
+        &mut self,
+        scene: &Scene,
+        fb: &mut FrameBuffer,
+        layers: &mut [FrameBuffer],
+        aovs: &mut [(Aov, FrameBuffer)],
+    ) {
+        assert!(layers.is_empty() || layers.len() == scene.layers.len() + 1);
+
         let pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(self.threads)
+            .num_threads(self.threads.threads)
             .build()
             .expect("Failed to build rendering threadpool");
 
-        let start = std::time::Instant::now();
+        let start = Instant::now();
+        let mut last_checkpoint = start;
+        let mut last_preview = start;
 
         let max_step = scene.max_possible_step(scene.camera.location);
 
         let mut max_step_count = 0;
 
-        TOTAL_STEPS.store(0, Ordering::SeqCst);
+        // counted for this render only, so renders running side by side don't mix them
+        let stats = Mutex::new(MarchStats::new());
+        let non_finite = Mutex::new(NonFiniteLog::new());
 
-        for i in 0..self.samples {
-            let offset = self.filter.next().unwrap();
-            let fbi = FrameBufferIterator::from_framebuffer(fb, self.frame.region);
+        let seed = frame_seed(self.seed, self.frame_index, self.lock_noise);
+        if self.lock_noise {
+            self.ray_marcher.temporal_offset = temporal_offset(self.frame_index);
+        }
+        self.ray_marcher.record_surfaces = !aovs.is_empty();
 
-            if self.threads == 1 {
-                for slice in fbi {
-                    self.scanline(scene, max_step, slice, i, offset);
+        let sample_map = self.prepass.then(|| {
+            SampleMap::from_prepass(
+                scene,
+                &self.ray_marcher,
+                &self.frame,
+                PREPASS_STRIDE,
+                self.samples,
+                seed,
+            )
+        });
+        let passes = sample_map
+            .as_ref()
+            .map_or(self.samples, SampleMap::max_samples);
+
+        let mut scratch = Scratch::new();
+        let region = self.frame.traced_region();
+
+        // keep sub pixel offsets of the skipped samples, so a resumed render matches
+        // an uninterrupted one
+        for _ in 0..self.start_sample {
+            self.filter.next();
+        }
+
+        for i in self.start_sample..passes {
+            let pass = Pass {
+                index: i,
+                offset: self.filter.next().unwrap(),
+                seed,
+                sample_map: sample_map.as_ref(),
+                mask: match &region {
+                    Region::Mask(mask) => Some(mask),
+                    _ => None,
+                },
+                stats: &stats,
+                non_finite: &non_finite,
+            };
+            let mut layer_rows = layers
+                .iter_mut()
+                .map(|fb| FrameBufferIterator::from_framebuffer(fb, &region))
+                .collect::<Vec<_>>();
+            let mut aov_rows = aovs
+                .iter_mut()
+                .map(|(aov, fb)| (*aov, FrameBufferIterator::from_framebuffer(fb, &region)))
+                .collect::<Vec<_>>();
+            let fbi = FrameBufferIterator::from_framebuffer(fb, &region).map(|slice| {
+                let rows = PassRows {
+                    layers: layer_rows.iter_mut().filter_map(Iterator::next).collect(),
+                    aovs: aov_rows
+                        .iter_mut()
+                        .filter_map(|(aov, rows)| rows.next().map(|row| (*aov, row)))
+                        .collect(),
+                };
+
+                (slice, rows)
+            });
+
+            if self.threads.threads == 1 {
+                for (slice, rows) in fbi {
+                    self.scanline(scene, max_step, slice, rows, &pass, &mut scratch);
                 }
             } else {
                 pool.install(|| {
                     fbi.par_bridge()
-                        .for_each(|slice| self.scanline(scene, max_step, slice, i, offset));
+                        .for_each_init(Scratch::new, |scratch, (slice, rows)| {
+                            self.scanline(scene, max_step, slice, rows, &pass, scratch)
+                        });
                 });
             }
 
-            max_step_count += MAX_STEPS_PER_SAMPLE.load(Ordering::SeqCst);
+            max_step_count += stats.lock().unwrap().max_steps;
+
+            if let Some(checkpoint) = &self.checkpoint {
+                if i + 1 < passes && last_checkpoint.elapsed() >= checkpoint.interval {
+                    if let Err(e) = fb.write_checkpoint(&checkpoint.path, i + 1) {
+                        eprintln!("\nCould not write checkpoint: {e}");
+                    }
+
+                    last_checkpoint = Instant::now();
+                }
+            }
 
-            let sample_end = std::time::Instant::now();
-            let remaining_part = self.samples as f32 / (i as f32 + 1.0) - 1.0;
+            if let Some(preview) = &mut self.preview {
+                let due = match preview.every {
+                    PreviewInterval::Samples(n) => (i + 1) % n.max(1) == 0,
+                    PreviewInterval::Time(interval) => last_preview.elapsed() >= interval,
+                };
+
+                if i + 1 < passes && due {
+                    (preview.write)(fb);
+                    last_preview = Instant::now();
+                }
+            }
+
+            if self.quiet {
+                continue;
+            }
+
+            let sample_end = Instant::now();
+            let remaining_part = (passes - i - 1) as f32 / (i + 1 - self.start_sample) as f32;
             let time = sample_end - start;
             let remaining_time = time.mul_f32(remaining_part);
             print!(
                 "\rSample {}/{}, time: {:02}:{:02}, remaining: {:02}:{:02}",
                 i + 1,
-                self.samples,
+                passes,
                 time.as_secs() / 60,
                 time.as_secs() % 60,
                 remaining_time.as_secs() / 60,
@@ -69,7 +245,9 @@ impl CliRenderer {
             std::io::stdout().flush().expect("Failed to flush stdout");
         }
 
-        println!();
+        if !self.quiet {
+            println!();
+        }
 
         if let RenderMode::Samples = self.ray_marcher.mode {
             for y in 0..self.frame.height {
@@ -77,23 +255,88 @@ impl CliRenderer {
                     let pixel = fb.pixel_mut(x, y).unwrap();
 
                     let sample_count = pixel.r;
+                    let samples = match &sample_map {
+                        Some(map) => map.samples(x, y),
+                        None => self.samples,
+                    };
 
-                    let value = sample_count / 256.0 / self.samples as f32;
-
-                    *pixel = Pixel::new(value, 1.0 - value, 0.0, 1.0);
+                    pixel.r = sample_count / samples as f32;
                 }
             }
+
+            let levels = StepLevels::measure(fb);
+            postprocess::steps_display(fb, &levels);
+
+            if !self.quiet {
+                println!(
+                    "Steps per pixel: min {}, median {}, max {} (colored from {} to {})",
+                    levels.min, levels.median, levels.max, levels.low, levels.high
+                );
+            }
         }
 
-        let end = std::time::Instant::now();
+        if self.ray_marcher.check_finite {
+            print_non_finite(&non_finite.lock().unwrap());
+        }
 
-        println!("Render took {:.02} seconds", (end - start).as_secs_f64());
+        if self.quiet {
+            return;
+        }
+
+        let end = Instant::now();
+
+        let stats = stats.into_inner().unwrap();
+
+        println!(
+            "Render took {:.02} seconds with {}",
+            (end - start).as_secs_f64(),
+            self.threads
+        );
         println!("Max steps: {max_step_count}");
         println!(
             "Avg steps per pixel: {}",
-            TOTAL_STEPS.load(Ordering::SeqCst) as f64
-                / (self.frame.width * self.frame.height) as f64
+            stats.steps as f64 / (self.frame.width * self.frame.height) as f64
         );
+
+        if let RenderMode::Samples | RenderMode::Termination = self.ray_marcher.mode {
+            print_terminations(&stats);
+        }
+    }
+
+    /// Renders given frames of the scene animation, passing each finished frame to `on_frame`.
+    ///
+    /// Frames are rendered the same as when rendered alone with their `frame_index`.
+    pub fn render_sequence<F>(&mut self, scene: &Scene, frames: Range<u64>, mut on_frame: F)
+    where
+        F: FnMut(u64, FrameBuffer),
+    {
+        let start = Instant::now();
+        let count = frames.end.saturating_sub(frames.start);
+
+        for (i, frame) in frames.enumerate() {
+            println!("Frame {}/{count} (#{frame})", i + 1);
+
+            let mut frame_scene = scene.clone();
+            frame_scene.set_time(scene.animation.frame_time(frame));
+
+            self.frame_index = frame;
+            self.filter.reset();
+
+            let mut fb = FrameBuffer::new(self.frame.width, self.frame.height);
+            self.render(&frame_scene, &mut fb);
+
+            on_frame(frame, fb);
+
+            let time = start.elapsed();
+            let remaining_time = time.mul_f64(count as f64 / (i as f64 + 1.0) - 1.0);
+            println!(
+                "Sequence time: {:02}:{:02}, remaining: {:02}:{:02}",
+                time.as_secs() / 60,
+                time.as_secs() % 60,
+                remaining_time.as_secs() / 60,
+                remaining_time.as_secs() % 60
+            );
+        }
     }
 
     fn scanline<'fb>(
@@ -101,35 +344,185 @@ impl CliRenderer {
         scene: &Scene,
         max_step: f64,
         slice: FrameBufferSlice<'fb>,
-        sample: usize,
-        offset: (f64, f64),
+        mut rows: PassRows<'fb>,
+        pass: &Pass,
+        scratch: &mut Scratch,
     ) {
-        let rel_y = (slice.y as f64 + offset.1) / (self.frame.height as f64);
-        for (x, pixel) in slice.slice.iter_mut().enumerate() {
-            let rel_x = ((x + slice.x_start) as f64 + offset.0) / (self.frame.width as f64);
+        let sample = pass.index;
+        let rel_y = (slice.y as f64 + pass.offset.1) / (self.frame.height as f64);
+        let exposure = scene.camera.exposure.multiplier();
+        let (keep, add) = (
+            sample as f32 / (sample as f32 + 1.0),
+            1.0 / (sample as f32 + 1.0),
+        );
+
+        // colors of the current run of traced pixels, blended into the slice together
+        let mut colors = Vec::with_capacity(slice.slice.len());
+        let mut run_start = 0;
+
+        for x in 0..slice.slice.len() {
+            let (image_x, y) = (x + slice.x_start, slice.y);
+            let skipped = pass
+                .sample_map
+                .is_some_and(|map| sample >= map.samples(image_x, y))
+                || pass.mask.is_some_and(|mask| !mask.contains(image_x, y));
 
-            let sample_info = self.ray_marcher.color_for_ray(
+            if skipped {
+                simd::blend_pixels(&mut slice.slice[run_start..x], &colors, keep, add);
+                colors.clear();
+                run_start = x + 1;
+
+                continue;
+            }
+
+            let rel_x = ((x + slice.x_start) as f64 + pass.offset.0) / (self.frame.width as f64);
+
+            seed_rng(pixel_seed(pass.seed, sample, x + slice.x_start, slice.y));
+
+            let mut sample_info = self.ray_marcher.color_for_ray(
                 scene
                     .camera
                     .cast_ray(rel_x, rel_y, self.frame.aspect_ratio()),
                 scene,
                 max_step,
                 0,
+                scratch,
             );
 
-            MAX_STEPS_PER_SAMPLE.fetch_max(sample_info.steps, Ordering::SeqCst);
-            TOTAL_STEPS.fetch_add(sample_info.steps, Ordering::SeqCst);
+            // checked samples are dropped, so they don't spread over the image
+            if let Some(found) = scratch.non_finite.take() {
+                pass.non_finite.lock().unwrap().push(NonFiniteSample {
+                    x: x + slice.x_start,
+                    y: slice.y,
+                    sample,
+                    found,
+                });
+                sample_info.color = Vector3::zero();
+            }
+
+            for (aov, row) in &mut rows.aovs {
+                aov.accumulate(&mut row.slice[x], &sample_info, sample);
+            }
+
             if let RenderMode::Samples = self.ray_marcher.mode {
-                *pixel += Pixel::new(sample_info.steps as f32, 0.0, 0.0, 0.0);
+                slice.slice[x] += Pixel::new(sample_info.steps as f32, 0.0, 0.0, 0.0);
+                run_start = x + 1;
+            } else if let RenderMode::Depth = self.ray_marcher.mode {
+                // nearest hit of all samples, averaging would push edges to infinity
+                let depth = sample_info.distance as f32;
+                let pixel = &mut slice.slice[x];
+
+                if sample == 0 || depth < pixel.r {
+                    *pixel = Pixel::new(depth, depth, depth, 1.0);
+                }
+                run_start = x + 1;
             } else {
-                let base = *pixel;
-
-                let color = Pixel::from(sample_info.color);
+                let mut color = Pixel::from(sample_info.color * exposure);
+                color.a = sample_info.alpha as f32;
+
+                let hit_layer = match sample_info.hit {
+                    Some(index) => scene.objects[index].layer,
+                    None => scene.layers.len(),
+                };
+
+                for (i, layer) in rows.layers.iter_mut().enumerate() {
+                    let value = if i == hit_layer {
+                        color
+                    } else {
+                        Pixel::new(0.0, 0.0, 0.0, 0.0)
+                    };
+
+                    let pixel = &mut layer.slice[x];
+                    *pixel = *pixel * keep + value * add;
+                }
 
-                *pixel = base * (sample as f32 / (sample as f32 + 1.0))
-                    + color * (1.0 / (sample as f32 + 1.0));
+                colors.push(color);
             }
         }
+
+        simd::blend_pixels(&mut slice.slice[run_start..], &colors, keep, add);
+
+        pass.stats.lock().unwrap().merge(&scratch.take_stats());
+    }
+}
+
+/// Prints how many rays ended by each termination rule and how many steps they took.
+fn print_terminations(stats: &MarchStats) {
+    println!("Termination       rays     share  avg steps");
+
+    for termination in Termination::ALL {
+        let counts = stats.terminations[termination.index()];
+
+        if counts.rays == 0 {
+            continue;
+        }
+
+        println!(
+            "{:<12} {:>10} {:>8.2}% {:>10.1}",
+            format!("{termination:?}"),
+            counts.rays,
+            counts.rays as f64 / stats.rays as f64 * 100.0,
+            counts.steps as f64 / counts.rays as f64
+        );
+    }
+}
+
+/// Prints how many samples got non-finite values by where they came from, with first few pixels.
+fn print_non_finite(log: &NonFiniteLog) {
+    if log.count == 0 {
+        println!("No non-finite samples");
+        return;
+    }
+
+    println!("Non-finite samples: {}", log.count);
+
+    let mut groups: Vec<((NonFiniteSource, usize), usize)> = Vec::new();
+
+    for sample in &log.samples {
+        let key = (sample.found.source, sample.found.depth);
+
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, count)) => *count += 1,
+            None => groups.push((key, 1)),
+        }
+    }
+
+    groups.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    for ((source, depth), count) in groups {
+        println!("{count:>10} {} at bounce {depth}", describe_source(source));
+    }
+
+    if log.count > log.samples.len() {
+        println!("(grouped first {} samples)", log.samples.len());
+    }
+
+    println!("First samples:");
+
+    for sample in log.samples.iter().take(10) {
+        let location = sample.found.location;
+
+        println!(
+            "  pixel {}x{} sample {}: {} at bounce {}, ray at [{:.4}, {:.4}, {:.4}]",
+            sample.x,
+            sample.y,
+            sample.sample,
+            describe_source(sample.found.source),
+            sample.found.depth,
+            location.x,
+            location.y,
+            location.z
+        );
+    }
+}
+
+fn describe_source(source: NonFiniteSource) -> String {
+    match source {
+        NonFiniteSource::Object { index, shader } => format!("objects[{index}] ({shader})"),
+        NonFiniteSource::Background { shader } => format!("background ({shader})"),
+        NonFiniteSource::Capture { shader } => format!("capture shader ({shader})"),
+        NonFiniteSource::Direction => "ray direction bent by distortions".into(),
+        NonFiniteSource::Other => "final color".into(),
     }
 }
 
@@ -138,13 +531,22 @@ impl Default for CliRenderer {
         Self {
             ray_marcher: RayMarcher::default(),
             samples: 128,
-            threads: 0,
+            threads: ThreadCount::pick(0, true),
             frame: Frame {
                 width: 1280,
                 height: 720,
                 region: Region::Whole,
+                border: 0,
             },
             filter: Box::new(BlackmanHarrisFilter::new(1.5)),
+            seed: 0,
+            frame_index: 0,
+            lock_noise: false,
+            prepass: false,
+            quiet: false,
+            start_sample: 0,
+            checkpoint: None,
+            preview: None,
         }
     }
 }
@@ -163,26 +565,15 @@ struct FrameBufferIterator<'fb> {
 }
 
 impl<'fb> FrameBufferIterator<'fb> {
-    pub fn from_framebuffer(fb: &'fb mut FrameBuffer, region: Region) -> Self {
+    pub fn from_framebuffer(fb: &'fb mut FrameBuffer, region: &Region) -> Self {
         let width = fb.width();
-        match region {
-            Region::Whole => Self {
-                start: 0,
-                end: fb.width(),
-                line: 0,
-                chunks: fb.buffer_mut().chunks_mut(width),
-            },
-            Region::Window {
-                x_min,
-                x_max,
-                y_min,
-                y_max,
-            } => Self {
-                start: x_min,
-                end: x_max - x_min,
-                line: y_min,
-                chunks: fb.buffer_mut()[y_min * width..y_max * width].chunks_mut(width),
-            },
+        let (x_min, y_min, x_max, y_max) = region.bounds(width, fb.height());
+
+        Self {
+            start: x_min,
+            end: x_max - x_min,
+            line: y_min,
+            chunks: fb.buffer_mut()[y_min * width..y_max * width].chunks_mut(width),
         }
     }
 }
@@ -206,3 +597,304 @@ impl<'fb> Iterator for FrameBufferIterator<'fb> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use blackhole::cache;
+    use blackhole::marcher::plugin::{MarchPlugin, StepResponse};
+    use blackhole::Ray;
+    use blackhole_common::scene_loader::SceneLoader;
+
+    /// Loads the test scene, keeping its cache entries out of the user cache directory.
+    fn load_scene() -> Scene {
+        cache::set_dir(Some(cache::temporary_dir()));
+
+        SceneLoader::load_from_path("../scenes/blackhole.json5").unwrap()
+    }
+
+    fn render(threads: usize, region: Region) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(32, 18);
+        render_samples(threads, region, 0..2, &mut fb);
+
+        fb
+    }
+
+    fn render_samples(threads: usize, region: Region, samples: Range<usize>, fb: &mut FrameBuffer) {
+        let scene = load_scene();
+
+        let mut renderer = CliRenderer {
+            samples: samples.end,
+            start_sample: samples.start,
+            threads: ThreadCount::pick(threads, false),
+            frame: Frame {
+                width: 32,
+                height: 18,
+                region,
+                border: 0,
+            },
+            quiet: true,
+            ..Default::default()
+        };
+        renderer.render(&scene, fb);
+    }
+
+    fn bits(pixel: &Pixel) -> [u32; 4] {
+        [pixel.r, pixel.g, pixel.b, pixel.a].map(f32::to_bits)
+    }
+
+    #[test]
+    fn render_is_reproducible() {
+        let single = render(1, Region::Whole);
+        let multi = render(4, Region::Whole);
+
+        for (a, b) in single.buffer().iter().zip(multi.buffer()) {
+            assert_eq!(bits(a), bits(b));
+        }
+
+        let region = Region::Window {
+            x_min: 8,
+            y_min: 4,
+            x_max: 20,
+            y_max: 12,
+        };
+        let mut window = render(4, region);
+
+        for y in 4..12 {
+            for x in 8..20 {
+                let a = *window.pixel_mut(x, y).unwrap();
+                let b = single.buffer()[x + y * 32];
+
+                assert_eq!(bits(&a), bits(&b));
+            }
+        }
+    }
+
+    #[test]
+    fn border_is_traced_around_region() {
+        let scene = load_scene();
+        let mut fb = FrameBuffer::new(32, 18);
+        fb.buffer_mut().fill(Pixel::new(0.0, 0.0, 0.0, 0.0));
+
+        let mut renderer = CliRenderer {
+            samples: 1,
+            frame: Frame {
+                width: 32,
+                height: 18,
+                region: Region::Window {
+                    x_min: 1,
+                    y_min: 4,
+                    x_max: 20,
+                    y_max: 12,
+                },
+                border: 2,
+            },
+            quiet: true,
+            ..Default::default()
+        };
+        renderer.render(&scene, &mut fb);
+
+        for y in 0..18 {
+            for x in 0..32 {
+                let traced = (0..22).contains(&x) && (2..14).contains(&y);
+                let pixel = *fb.pixel_mut(x, y).unwrap();
+
+                assert_eq!(pixel.a > 0.0, traced, "{x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn mask_renders_windows() {
+        let window = |x_min, y_min| Region::Window {
+            x_min,
+            y_min,
+            x_max: x_min + 4,
+            y_max: y_min + 3,
+        };
+        let windows = [window(2, 2), window(20, 10)];
+        let region = Region::Mask(Arc::new(Mask::from_windows(32, 18, &windows)));
+
+        let whole = render(1, Region::Whole);
+        let masked = render(4, region);
+        let empty = FrameBuffer::new(32, 18);
+
+        for (i, pixel) in masked.buffer().iter().enumerate() {
+            let (x, y) = (i % 32, i / 32);
+            let expected = if windows.iter().any(|w| w.contains(x, y)) {
+                whole.buffer()[i]
+            } else {
+                empty.buffer()[i]
+            };
+
+            assert_eq!(bits(pixel), bits(&expected), "{x}, {y}");
+        }
+    }
+
+    #[test]
+    fn seed_changes_noise() {
+        let scene = load_scene();
+        let render_seeded = |seed| {
+            let mut fb = FrameBuffer::new(32, 18);
+            let mut renderer = CliRenderer {
+                samples: 2,
+                seed,
+                frame: Frame {
+                    width: 32,
+                    height: 18,
+                    region: Region::Whole,
+                    border: 0,
+                },
+                quiet: true,
+                ..Default::default()
+            };
+            renderer.render(&scene, &mut fb);
+
+            fb
+        };
+
+        let a = render_seeded(7);
+        let b = render_seeded(7);
+        let other = render_seeded(8);
+
+        for (a, b) in a.buffer().iter().zip(b.buffer()) {
+            assert_eq!(bits(a), bits(b));
+        }
+
+        assert!(a
+            .buffer()
+            .iter()
+            .zip(other.buffer())
+            .any(|(a, b)| bits(a) != bits(b)));
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted() {
+        let whole = render(1, Region::Whole);
+
+        let mut resumed = FrameBuffer::new(32, 18);
+        render_samples(1, Region::Whole, 0..1, &mut resumed);
+        render_samples(1, Region::Whole, 1..2, &mut resumed);
+
+        for (a, b) in whole.buffer().iter().zip(resumed.buffer()) {
+            assert_eq!(bits(a), bits(b));
+        }
+    }
+
+    #[test]
+    fn layers_sum_to_full_image() {
+        let mut scene = load_scene();
+        scene.objects[0].layer = scene.layer_index("disk");
+
+        let mut renderer = CliRenderer {
+            samples: 2,
+            threads: ThreadCount::pick(1, false),
+            frame: Frame {
+                width: 32,
+                height: 18,
+                region: Region::Whole,
+                border: 0,
+            },
+            quiet: true,
+            ..Default::default()
+        };
+
+        let mut fb = FrameBuffer::new(32, 18);
+        let mut layers = vec![FrameBuffer::new(32, 18); 3];
+        renderer.render_passes(&scene, &mut fb, &mut layers, &mut []);
+
+        for (i, pixel) in fb.buffer().iter().enumerate() {
+            let sum = layers
+                .iter()
+                .fold(Pixel::new(0.0, 0.0, 0.0, 0.0), |sum, l| sum + l.buffer()[i]);
+
+            assert!((sum.r - pixel.r).abs() <= 1e-4 * pixel.r.max(1.0));
+            assert!((sum.a - 1.0).abs() <= 1e-5);
+        }
+    }
+
+    #[test]
+    fn plugins_stop_rays() {
+        struct StopAfter(usize);
+
+        impl MarchPlugin for StopAfter {
+            fn on_step(&self, ray: &Ray, _step: f64) -> StepResponse {
+                if ray.steps_taken >= self.0 {
+                    StepResponse::STOP
+                } else {
+                    StepResponse::CONTINUE
+                }
+            }
+        }
+
+        let scene = load_scene();
+        let marcher = RayMarcher::default().with_plugin(StopAfter(3));
+
+        let ray = scene.camera.cast_ray(0.5, 0.5, 16.0 / 9.0);
+        let max_step = scene.max_possible_step(scene.camera.location);
+        let result = marcher.color_for_ray(ray, &scene, max_step, 0, &mut Scratch::new());
+
+        assert_eq!(result.termination, Termination::Plugin);
+        assert_eq!(result.steps, 3);
+    }
+
+    #[test]
+    fn depth_aov_matches_depth_mode() {
+        let scene = load_scene();
+        let mut renderer = CliRenderer {
+            samples: 2,
+            threads: ThreadCount::pick(1, false),
+            frame: Frame {
+                width: 32,
+                height: 18,
+                region: Region::Whole,
+                border: 0,
+            },
+            quiet: true,
+            ..Default::default()
+        };
+
+        let mut fb = FrameBuffer::new(32, 18);
+        let mut aovs = [(Aov::Depth, FrameBuffer::new(32, 18))];
+        renderer.render_passes(&scene, &mut fb, &mut [], &mut aovs);
+
+        renderer.ray_marcher.mode = RenderMode::Depth;
+        renderer.filter.reset();
+        let mut depth = FrameBuffer::new(32, 18);
+        renderer.render(&scene, &mut depth);
+
+        for (a, b) in aovs[0].1.buffer().iter().zip(depth.buffer()) {
+            assert_eq!(bits(a), bits(b));
+        }
+    }
+
+    #[test]
+    fn surfaces_are_recorded_only_for_aovs() {
+        let scene = load_scene();
+        let mut renderer = CliRenderer {
+            samples: 1,
+            threads: ThreadCount::pick(1, false),
+            frame: Frame {
+                width: 32,
+                height: 18,
+                region: Region::Whole,
+                border: 0,
+            },
+            quiet: true,
+            ..Default::default()
+        };
+
+        let mut fb = FrameBuffer::new(32, 18);
+        let mut aovs = [(Aov::Normal, FrameBuffer::new(32, 18))];
+        renderer.render_passes(&scene, &mut fb, &mut [], &mut aovs);
+
+        assert!(renderer.ray_marcher.record_surfaces);
+        assert!(aovs[0].1.buffer().iter().any(|p| p.a > 0.0));
+
+        renderer.render(&scene, &mut fb);
+        assert!(!renderer.ray_marcher.record_surfaces);
+    }
+}
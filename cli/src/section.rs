@@ -0,0 +1,104 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use blackhole::framebuffer::{FrameBuffer, Pixel};
+use blackhole::section::{self, SectionSample};
+
+use crate::args::SectionArgs;
+use crate::{load_scene, write_out};
+
+pub fn run(args: SectionArgs) {
+    let scene = load_scene(&args.scene);
+
+    let is_csv = args
+        .output
+        .extension()
+        .map(|e| e.eq_ignore_ascii_case("csv"))
+        .unwrap_or(false);
+
+    match args.up {
+        None => {
+            if !is_csv {
+                eprintln!("Line section can only be written as CSV");
+                std::process::exit(-1);
+            }
+
+            let samples = section::line(&scene, args.from, args.to, args.resolution);
+
+            write_csv(&samples, &args);
+        }
+        Some(up) => {
+            let plane = match section::plane(&scene, args.from, args.to, up, args.resolution) {
+                Ok(plane) => plane,
+                Err(e) => {
+                    eprintln!("Could not sample section: {e}");
+                    std::process::exit(-1);
+                }
+            };
+
+            if is_csv {
+                write_csv(&plane.samples, &args);
+            } else {
+                let mut fb = FrameBuffer::new(plane.width, plane.height);
+
+                for (pixel, sample) in fb.buffer_mut().iter_mut().zip(&plane.samples) {
+                    *pixel = sample_color(sample);
+                }
+
+                write_out(&fb, &args.output, None);
+            }
+        }
+    }
+}
+
+fn write_csv(samples: &[SectionSample], args: &SectionArgs) {
+    let file = match File::create(&args.output) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Could not create output file: {e}");
+            std::process::exit(-1);
+        }
+    };
+
+    if let Err(e) = write_samples(BufWriter::new(file), samples) {
+        eprintln!("Could not write output file: {e}");
+        std::process::exit(-1);
+    }
+}
+
+fn write_samples(mut writer: impl Write, samples: &[SectionSample]) -> std::io::Result<()> {
+    writeln!(writer, "x,y,z,distance,strength")?;
+
+    for s in samples {
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            s.position.x, s.position.y, s.position.z, s.distance, s.strength
+        )?;
+    }
+
+    writer.flush()
+}
+
+/// Grayscale distance with contour lines every 0.25 units, objects in blue, distortion in red.
+fn sample_color(sample: &SectionSample) -> Pixel {
+    let strength = (sample.strength / (sample.strength + 1.0)) as f32;
+
+    if sample.distance < 0.0 {
+        return Pixel::new(strength, 0.2, 0.8, 1.0);
+    }
+
+    let gray = (1.0 - (-sample.distance * 0.5).exp()) as f32;
+    let contour = if (sample.distance * 4.0).fract() < 0.05 {
+        0.5
+    } else {
+        1.0
+    };
+
+    Pixel::new(
+        (gray * contour).max(strength),
+        gray * contour,
+        gray * contour,
+        1.0,
+    )
+}
@@ -0,0 +1,354 @@
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use serde_json::json;
+
+use blackhole::framebuffer::{FrameBuffer, Pixel};
+use blackhole::post::PostSettings;
+use blackhole::postprocess::Tonemap;
+
+use crate::args::{OutputFormat, SequenceReportArgs};
+use crate::diff::try_read_image;
+use crate::write_out;
+
+/// Width of the border around flagged frames in the contact sheet, in pixels.
+const FLAG_BORDER: usize = 2;
+
+/// Measurements of a single frame.
+struct FrameStats {
+    width: usize,
+    height: usize,
+    /// Mean luminance, of linear values for EXR and of stored values for PNG
+    luminance: f32,
+    /// Mean difference of pixel luminance from its four neighbors, relative to mean luminance
+    noise: f32,
+    /// Downscaled and display encoded frame for the contact sheet
+    thumb: FrameBuffer,
+}
+
+pub fn run(args: SequenceReportArgs) {
+    let paths = match frame_paths(&args.dir, &args.sheet) {
+        Ok(paths) if !paths.is_empty() => paths,
+        Ok(_) => {
+            eprintln!("No EXR or PNG frames in {}", args.dir.display());
+            std::process::exit(-1);
+        }
+        Err(e) => {
+            eprintln!("Could not read {}: {e}", args.dir.display());
+            std::process::exit(-1);
+        }
+    };
+
+    let thumb_width = args.thumb_width.max(1);
+    let frames = paths
+        .par_iter()
+        .map(|path| measure(path, thumb_width))
+        .collect::<Vec<_>>();
+
+    // frames are expected to have the size of the first readable one
+    let size = frames.iter().flatten().map(|f| (f.width, f.height)).next();
+
+    let luminance = deviations(&frame_values(&frames, |f| f.luminance), args.window);
+    let noise = deviations(&frame_values(&frames, |f| f.noise), args.window);
+
+    let mut reports = Vec::new();
+    let mut flagged = Vec::new();
+
+    for (i, (path, frame)) in paths.iter().zip(&frames).enumerate() {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let mut flags = Vec::new();
+
+        let report = match frame {
+            Ok(frame) => {
+                if Some((frame.width, frame.height)) != size {
+                    flags.push("size");
+                }
+                if luminance[i] > args.luminance_tolerance {
+                    flags.push("luminance");
+                }
+                if noise[i] > args.noise_tolerance {
+                    flags.push("noise");
+                }
+
+                json!({
+                    "file": name,
+                    "width": frame.width,
+                    "height": frame.height,
+                    "luminance": frame.luminance,
+                    "noise": frame.noise,
+                    "luminance_deviation": luminance[i],
+                    "noise_deviation": noise[i],
+                    "flags": flags,
+                })
+            }
+            Err(e) => {
+                flags.push("unreadable");
+
+                json!({
+                    "file": name,
+                    "error": e,
+                    "flags": flags,
+                })
+            }
+        };
+
+        if !flags.is_empty() {
+            println!("{name}: {}", flags.join(", "));
+            flagged.push(i);
+        }
+
+        reports.push(report);
+    }
+
+    let report = json!({
+        "directory": args.dir,
+        "frames": reports,
+        "flagged": flagged
+            .iter()
+            .map(|i| paths[*i].file_name().unwrap_or_default().to_string_lossy())
+            .collect::<Vec<_>>(),
+    });
+
+    let text = serde_json::to_string_pretty(&report).expect("report has only plain values");
+    if let Err(e) = std::fs::write(&args.output, text) {
+        eprintln!("Could not write report {}: {e}", args.output.display());
+        std::process::exit(-1);
+    }
+
+    write_out(
+        &contact_sheet(&frames, &flagged, args.columns.max(1), thumb_width),
+        &args.sheet,
+        None,
+    );
+
+    println!(
+        "{} frames, {} flagged, report saved to {}, contact sheet to {}",
+        paths.len(),
+        flagged.len(),
+        args.output.display(),
+        args.sheet.display()
+    );
+
+    if args.deny_flagged && !flagged.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// EXR and PNG files of the directory sorted by name, without the contact sheet of an earlier run.
+fn frame_paths(dir: &Path, sheet: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let sheet = std::fs::canonicalize(sheet).ok();
+
+    let mut paths = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let image = path.extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("exr") || ext.eq_ignore_ascii_case("png")
+            });
+
+            image && path.is_file() && std::fs::canonicalize(path).ok() != sheet
+        })
+        .collect::<Vec<_>>();
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+fn measure(path: &Path, thumb_width: usize) -> Result<FrameStats, String> {
+    let fb = try_read_image(path)?;
+    let (width, height) = (fb.width(), fb.height());
+
+    let lum = fb
+        .buffer()
+        .iter()
+        .map(|p| 0.2126 * p.r + 0.7152 * p.g + 0.0722 * p.b)
+        .collect::<Vec<_>>();
+    let luminance = lum.iter().sum::<f32>() / lum.len().max(1) as f32;
+
+    let mut laplacian = 0.0;
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let i = x + y * width;
+            let around = (lum[i - 1] + lum[i + 1] + lum[i - width] + lum[i + width]) / 4.0;
+
+            laplacian += (lum[i] - around).abs();
+        }
+    }
+    let inner = width.saturating_sub(2) * height.saturating_sub(2);
+    let noise = laplacian / inner.max(1) as f32 / luminance.max(1e-6);
+
+    let mut thumb = fb.downscale(width.div_ceil(thumb_width));
+    if let OutputFormat::Exr = OutputFormat::from_path(path) {
+        PostSettings::default()
+            .pipeline(Tonemap::default())
+            .run(&mut thumb);
+    }
+
+    Ok(FrameStats {
+        width,
+        height,
+        luminance,
+        noise,
+        thumb,
+    })
+}
+
+fn frame_values(
+    frames: &[Result<FrameStats, String>],
+    value: impl Fn(&FrameStats) -> f32,
+) -> Vec<Option<f32>> {
+    frames.iter().map(|f| f.as_ref().ok().map(&value)).collect()
+}
+
+/// Relative deviation of values from the median of `window` values on each side.
+///
+/// The median keeps single glitched frames from flagging their neighbors too. Missing values
+/// have no deviation and are left out of medians.
+fn deviations(values: &[Option<f32>], window: usize) -> Vec<f32> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let value = match value {
+                Some(v) => *v,
+                None => return 0.0,
+            };
+
+            // at the ends of the sequence the window is shifted to keep its size
+            let size = (2 * window + 1).min(values.len());
+            let start = i.saturating_sub(window).min(values.len() - size);
+            let end = start + size;
+
+            let mut neighbors = (start..end)
+                .filter(|j| *j != i)
+                .filter_map(|j| values[j])
+                .collect::<Vec<_>>();
+
+            if neighbors.is_empty() {
+                return 0.0;
+            }
+
+            neighbors.sort_by(f32::total_cmp);
+            let mid = neighbors.len() / 2;
+            let median = if neighbors.len() % 2 == 0 {
+                (neighbors[mid - 1] + neighbors[mid]) / 2.0
+            } else {
+                neighbors[mid]
+            };
+
+            (value - median).abs() / median.abs().max(1e-6)
+        })
+        .collect()
+}
+
+/// Thumbnails in rows, in cells of the first readable frame size. Unreadable frames stay black.
+fn contact_sheet(
+    frames: &[Result<FrameStats, String>],
+    flagged: &[usize],
+    columns: usize,
+    thumb_width: usize,
+) -> FrameBuffer {
+    let cell_height = frames
+        .iter()
+        .flatten()
+        .map(|f| f.thumb.height())
+        .next()
+        .unwrap_or(1);
+    let cell_width = frames
+        .iter()
+        .flatten()
+        .map(|f| f.thumb.width())
+        .next()
+        .unwrap_or(thumb_width);
+
+    let columns = columns.min(frames.len());
+    let rows = frames.len().div_ceil(columns);
+    let width = columns * cell_width;
+
+    let mut sheet = FrameBuffer::new(width, rows * cell_height);
+    for pixel in sheet.buffer_mut() {
+        *pixel = Pixel::new(0.0, 0.0, 0.0, 1.0);
+    }
+
+    for (i, frame) in frames.iter().enumerate() {
+        let (left, top) = ((i % columns) * cell_width, (i / columns) * cell_height);
+
+        if let Ok(frame) = frame {
+            let thumb = &frame.thumb;
+
+            for y in 0..thumb.height().min(cell_height) {
+                for x in 0..thumb.width().min(cell_width) {
+                    let p = thumb.buffer()[x + y * thumb.width()];
+
+                    sheet.buffer_mut()[left + x + (top + y) * width] =
+                        Pixel::new(p.r, p.g, p.b, 1.0);
+                }
+            }
+        }
+
+        if flagged.contains(&i) {
+            for y in 0..cell_height {
+                for x in 0..cell_width {
+                    let edge = x < FLAG_BORDER
+                        || y < FLAG_BORDER
+                        || x + FLAG_BORDER >= cell_width
+                        || y + FLAG_BORDER >= cell_height;
+
+                    if edge {
+                        sheet.buffer_mut()[left + x + (top + y) * width] =
+                            Pixel::new(1.0, 0.0, 0.0, 1.0);
+                    }
+                }
+            }
+        }
+    }
+
+    sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glitched_frame_stands_out() {
+        let values = [1.0, 1.1, 0.9, 5.0, 1.0, 1.05, 0.95].map(Some);
+        let deviations = deviations(&values, 2);
+
+        assert!(deviations[3] > 3.0);
+        assert!(deviations
+            .iter()
+            .enumerate()
+            .all(|(i, d)| i == 3 || *d < 0.2));
+    }
+
+    #[test]
+    fn flagged_frames_get_a_border() {
+        let frame = |value: f32| {
+            let mut fb = FrameBuffer::new(32, 16);
+            for pixel in fb.buffer_mut() {
+                *pixel = Pixel::new(value, value, value, 1.0);
+            }
+
+            Ok(FrameStats {
+                width: 32,
+                height: 16,
+                luminance: value,
+                noise: 0.0,
+                thumb: fb.downscale(2),
+            })
+        };
+
+        let frames = [frame(0.5), frame(0.5), Err("unreadable".into())];
+        let sheet = contact_sheet(&frames, &[1], 2, 16);
+
+        assert_eq!((sheet.width(), sheet.height()), (32, 16));
+
+        let red = |x: usize, y: usize| sheet.buffer()[x + y * 32].r;
+        assert_eq!(red(0, 0), 0.5);
+        assert_eq!(red(16, 0), 1.0);
+        assert_eq!(red(24, 4), 0.5);
+        // unreadable frame stays black
+        assert_eq!(red(0, 12), 0.0);
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use blackhole::filter::BoxFilter;
+use blackhole::frame::{Frame, Region};
+use blackhole::framebuffer::FrameBuffer;
+use blackhole::marcher::RayMarcher;
+use blackhole::post::PostSettings;
+
+use blackhole_common::scene_loader::SceneLoader;
+use blackhole_common::threads::ThreadCount;
+
+use crate::args::ThumbnailArgs;
+use crate::renderer::CliRenderer;
+use crate::write_out;
+
+pub fn run(args: ThumbnailArgs) {
+    if let Some(dir) = &args.output_dir {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("Could not create output directory: {e}");
+            std::process::exit(-1);
+        }
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(ThreadCount::pick(args.threads, false).threads)
+        .build()
+        .expect("Failed to build thumbnail threadpool");
+
+    let failed = pool.install(|| {
+        args.scenes
+            .par_iter()
+            .filter(|scene| {
+                let output = output_path(scene, args.output_dir.as_deref());
+
+                match thumbnail(scene, &output, &args) {
+                    Ok(()) => {
+                        println!("{} -> {}", scene.display(), output.display());
+                        false
+                    }
+                    Err(e) => {
+                        eprintln!("Could not create thumbnail of {}: {e}", scene.display());
+                        true
+                    }
+                }
+            })
+            .count()
+    });
+
+    if failed > 0 {
+        std::process::exit(-1);
+    }
+}
+
+fn thumbnail(scene_path: &Path, output: &Path, args: &ThumbnailArgs) -> Result<(), String> {
+    let mut scene = SceneLoader::load_from_path(scene_path).map_err(|e| e.to_string())?;
+    scene.set_time(scene.animation.frame_time(0));
+
+    let size = args.size.max(1);
+
+    let mut renderer = CliRenderer {
+        ray_marcher: RayMarcher {
+            max_depth: 4,
+            ..Default::default()
+        },
+        samples: args.samples,
+        // scenes are already rendered in parallel
+        threads: ThreadCount::pick(1, false),
+        frame: Frame {
+            width: size,
+            height: size,
+            region: Region::Whole,
+            border: 0,
+        },
+        filter: Box::new(BoxFilter::new(1.0)),
+        quiet: true,
+        ..Default::default()
+    };
+
+    let mut fb = FrameBuffer::new(size, size);
+    renderer.render(&scene, &mut fb);
+
+    let post = PostSettings {
+        physical: scene.physical_exposure,
+        ..Default::default()
+    };
+    post.pipeline(scene.tonemap).run(&mut fb);
+
+    write_out(&fb, output, None);
+
+    Ok(())
+}
+
+fn output_path(scene: &Path, output_dir: Option<&Path>) -> PathBuf {
+    let mut name = scene.file_stem().unwrap_or_default().to_owned();
+    name.push(".thumb.png");
+
+    match output_dir {
+        Some(dir) => dir.join(name),
+        None => scene.with_file_name(name),
+    }
+}
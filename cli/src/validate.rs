@@ -0,0 +1,23 @@
+use blackhole_common::scene_loader::SceneLoader;
+
+use crate::args::ValidateArgs;
+
+pub fn run(args: ValidateArgs) {
+    let lints = match SceneLoader::load_with_lints(&args.scene) {
+        Ok((_, lints)) => lints,
+        Err(e) => {
+            eprintln!("Could not read scene description: {e}");
+            std::process::exit(-1);
+        }
+    };
+
+    for lint in &lints {
+        println!("warning: {lint}");
+    }
+
+    if lints.is_empty() {
+        println!("No problems found in {}", args.scene.display());
+    } else if args.deny_warnings {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,21 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use blackhole::shader::{Parameter, Shader};
+use blackhole_common::scene_loader::SceneLoader;
+use blackhole_common::shaders::StarSkyShader;
+
+pub fn scene_clone(c: &mut Criterion) {
+    let scene = SceneLoader::load_from_path("../scenes/blackhole.json5").unwrap();
+
+    c.bench_function("scene_clone", |b| b.iter(|| scene.clone()));
+}
+
+pub fn star_sky_clone(c: &mut Criterion) {
+    let mut shader = StarSkyShader::new();
+    shader.set_parameter("star_count", Parameter::Usize(42_000));
+
+    c.bench_function("star_sky_clone", |b| b.iter(|| shader.clone()));
+}
+
+criterion_group!(benches, scene_clone, star_sky_clone);
+criterion_main!(benches);
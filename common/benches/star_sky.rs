@@ -5,6 +5,26 @@ use blackhole::shader::{BackgroundShader, Parameter, Shader};
 use blackhole::{Ray, RayKind};
 use blackhole_common::shaders::StarSkyShader;
 
+fn rays() -> Vec<Ray> {
+    let directions = [
+        Vector3::new(0.5, 0.5, 0.5),
+        Vector3::new(1.0, 0.01, 0.0),
+        Vector3::new(-0.3, -0.1, -1.0),
+        Vector3::new(0.0, 0.9, 0.1),
+    ];
+
+    directions
+        .iter()
+        .map(|d| Ray {
+            location: Vector3::zero(),
+            direction: d.normalize(),
+            steps_taken: 5,
+            kind: RayKind::Primary,
+            time: 0.5,
+        })
+        .collect()
+}
+
 pub fn star_sky(c: &mut Criterion) {
     let mut shader = StarSkyShader::new();
     shader.set_parameter("star_count", Parameter::Usize(42_000));
@@ -14,10 +34,42 @@ pub fn star_sky(c: &mut Criterion) {
         direction: Vector3::new(0.5, 0.5, 0.5).normalize(),
         steps_taken: 5,
         kind: RayKind::Primary,
+        time: 0.5,
     };
 
     c.bench_function("star_sky", |b| b.iter(|| shader.emission_at(&ray)));
 }
 
-criterion_group!(benches, star_sky);
+pub fn star_sky_million(c: &mut Criterion) {
+    let rays = rays();
+
+    for count in [1_000_000, 4_000_000] {
+        let mut shader = StarSkyShader::new();
+        shader.set_parameter("star_count", Parameter::Usize(count));
+
+        c.bench_function(&format!("star_sky_{count}"), |b| {
+            b.iter(|| {
+                rays.iter()
+                    .map(|r| shader.emission_at(r))
+                    .sum::<Vector3<f64>>()
+            })
+        });
+
+        // 90 degree field of view on a 320 pixel wide preview
+        shader.set_parameter(
+            "cull_pixel_angle",
+            Parameter::Float(std::f64::consts::FRAC_PI_2 / 320.0),
+        );
+
+        c.bench_function(&format!("star_sky_{count}_culled"), |b| {
+            b.iter(|| {
+                rays.iter()
+                    .map(|r| shader.emission_at(r))
+                    .sum::<Vector3<f64>>()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, star_sky, star_sky_million);
 criterion_main!(benches);
@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+
+use crate::scene_loader::LoaderError;
+
+/// Environment variable with additional asset directories, separated like `PATH`.
+pub const ASSET_PATH_VAR: &str = "BLACKHOLE_ASSET_PATH";
+
+/// Finds files referenced by a scene.
+///
+/// Relative paths are looked up next to the scene file first, then in the search paths in order
+/// they were added.
+pub struct AssetResolver {
+    base: PathBuf,
+    search_paths: Vec<PathBuf>,
+}
+
+impl AssetResolver {
+    pub fn new(scene_path: impl AsRef<Path>) -> Self {
+        let base = scene_path
+            .as_ref()
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        Self {
+            base,
+            search_paths: Vec::new(),
+        }
+    }
+
+    /// Adds directory to search in, relative directories are taken from the scene directory.
+    pub fn add_search_path(&mut self, path: impl AsRef<Path>) {
+        let path = self.base.join(path);
+        self.search_paths.push(path);
+    }
+
+    /// Adds directories listed in [`ASSET_PATH_VAR`].
+    pub fn add_env_search_paths(&mut self) {
+        if let Some(paths) = std::env::var_os(ASSET_PATH_VAR) {
+            for path in std::env::split_paths(&paths) {
+                self.add_search_path(path);
+            }
+        }
+    }
+
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, LoaderError> {
+        std::iter::once(&self.base)
+            .chain(&self.search_paths)
+            .map(|dir| dir.join(path))
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| LoaderError::MissingAsset(path.to_owned()))
+    }
+}
+
+/// Scene description rewritten to reference assets copied next to it.
+pub struct PackedScene {
+    pub scene: String,
+    pub assets: Vec<PackedAsset>,
+}
+
+pub struct PackedAsset {
+    pub source: PathBuf,
+    /// Path relative to the packed scene file
+    pub name: String,
+}
+
+impl PackedScene {
+    /// Returns packed name for the asset, reusing it if the same file was already added.
+    pub(crate) fn add_asset(&mut self, source: PathBuf) -> String {
+        if let Some(asset) = self.assets.iter().find(|a| a.source == source) {
+            return asset.name.clone();
+        }
+
+        let file_name = source
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "asset".to_owned());
+
+        let mut name = format!("assets/{file_name}");
+        let mut i = 1;
+        while self.assets.iter().any(|a| a.name == name) {
+            name = format!("assets/{i}_{file_name}");
+            i += 1;
+        }
+
+        self.assets.push(PackedAsset {
+            source,
+            name: name.clone(),
+        });
+
+        name
+    }
+}
@@ -0,0 +1,162 @@
+//! User configuration file.
+//!
+//! Read from `blackhole/config.json5` in the platform config directory, or from the path in
+//! `BLACKHOLE_CONFIG`. Missing file is the same as an empty one.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use blackhole::cache::{self, UserDir};
+use blackhole::filter::{BlackmanHarrisFilter, BoxFilter, PixelFilter};
+
+use crate::scene_loader::LoaderError;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UserConfig {
+    /// Render presets by name, see [`Preset`]
+    #[serde(default)]
+    pub presets: HashMap<String, PresetStub>,
+}
+
+/// Preset as written in the config file, missing fields are taken from the built-in preset of
+/// the same name, or from default settings.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PresetStub {
+    pub samples: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub filter: Option<FilterKind>,
+    pub filter_size: Option<f64>,
+    pub resolution_scale: Option<f64>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    Box,
+    BlackmanHarris,
+}
+
+/// Bundle of renderer settings for common workflows.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Preset {
+    pub samples: usize,
+    pub max_depth: usize,
+    pub filter: FilterKind,
+    pub filter_size: f64,
+    /// Multiplier of output width and height
+    pub resolution_scale: f64,
+}
+
+impl Preset {
+    pub const BUILTIN: [&'static str; 3] = ["draft", "preview", "final"];
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        let preset = match name {
+            "draft" => Self {
+                samples: 8,
+                max_depth: 4,
+                filter: FilterKind::Box,
+                filter_size: 1.0,
+                resolution_scale: 0.5,
+            },
+            "preview" => Self {
+                samples: 32,
+                max_depth: 8,
+                ..Default::default()
+            },
+            "final" => Self {
+                samples: 256,
+                ..Default::default()
+            },
+            _ => return None,
+        };
+
+        Some(preset)
+    }
+
+    pub fn pixel_filter(&self) -> Box<dyn PixelFilter> {
+        match self.filter {
+            FilterKind::Box => Box::new(BoxFilter::new(self.filter_size)),
+            FilterKind::BlackmanHarris => Box::new(BlackmanHarrisFilter::new(self.filter_size)),
+        }
+    }
+
+    fn with(self, stub: &PresetStub) -> Self {
+        Self {
+            samples: stub.samples.unwrap_or(self.samples),
+            max_depth: stub.max_depth.unwrap_or(self.max_depth),
+            filter: stub.filter.unwrap_or(self.filter),
+            filter_size: stub.filter_size.unwrap_or(self.filter_size),
+            resolution_scale: stub.resolution_scale.unwrap_or(self.resolution_scale),
+        }
+    }
+}
+
+/// Settings used without any preset.
+impl Default for Preset {
+    fn default() -> Self {
+        Self {
+            samples: 128,
+            max_depth: 16,
+            filter: FilterKind::BlackmanHarris,
+            filter_size: 1.5,
+            resolution_scale: 1.0,
+        }
+    }
+}
+
+impl UserConfig {
+    pub fn load() -> Result<Self, LoaderError> {
+        let path = match std::env::var_os("BLACKHOLE_CONFIG") {
+            Some(path) => PathBuf::from(path),
+            None => match default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let config_str = std::fs::read_to_string(path).map_err(LoaderError::InputError)?;
+        let config: Self = json5::from_str(&config_str).map_err(LoaderError::FormatError)?;
+
+        for name in config.presets.keys() {
+            let preset = config.preset(name).unwrap();
+
+            if preset.samples == 0 || preset.filter_size <= 0.0 || preset.resolution_scale <= 0.0 {
+                return Err(LoaderError::Other(format!(
+                    "preset '{name}' needs positive samples, filter_size and resolution_scale"
+                )));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Finds preset by name, user presets override fields of built-in ones.
+    pub fn preset(&self, name: &str) -> Option<Preset> {
+        let builtin = Preset::builtin(name);
+
+        match self.presets.get(name) {
+            Some(stub) => Some(builtin.unwrap_or_default().with(stub)),
+            None => builtin,
+        }
+    }
+
+    /// Names of built-in and user presets, sorted.
+    pub fn preset_names(&self) -> Vec<&str> {
+        let mut names = Preset::BUILTIN
+            .into_iter()
+            .chain(self.presets.keys().map(String::as_str))
+            .collect::<Vec<_>>();
+
+        names.sort_unstable();
+        names.dedup();
+
+        names
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    Some(cache::user_dir(UserDir::Config)?.join("config.json5"))
+}
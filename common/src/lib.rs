@@ -1,2 +1,8 @@
+#[cfg(feature = "loader")]
+pub mod assets;
+#[cfg(feature = "loader")]
+pub mod config;
+#[cfg(feature = "loader")]
 pub mod scene_loader;
 pub mod shaders;
+pub mod threads;
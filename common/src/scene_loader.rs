@@ -1,24 +1,45 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use blackhole::scene::Scene;
+use blackhole::cache;
+use blackhole::scene::{Scene, SlicePlane};
 use blackhole::shader::{BackgroundShader, Parameter, Shader, SolidShader, VolumetricShader};
 
-use cgmath::Vector3;
-
-use serde::{Deserialize, Serialize};
-
-use blackhole::camera::Camera;
+use cgmath::{Deg, InnerSpace, Vector3, Zero};
+
+use blackhole::animation::{
+    Animation, CameraKeyframe, CameraTrack, ObjectTrack, Orbit, Orientation, Shutter, SkyRotation,
+};
+use blackhole::camera::{Camera, Exposure, Projection};
+use blackhole::marcher::RenderSettings;
+use blackhole::post::burn_in::{BurnIn, Corner};
+use blackhole::post::SENSOR_SCALE;
+use blackhole::postprocess::Tonemap;
+use blackhole::units::{self, Units};
 use serde_json::{Map, Value};
 
-use blackhole::object::shape::{Composite, Cube, Cylinder, Shape, Sphere};
+use blackhole::object::shape::{
+    read_triangles, Composite, Cube, Cylinder, GridShape, MeshShape, Moving, Repeated, Shape,
+    Shell, Sphere, Transformed,
+};
 use blackhole::object::{Distortion, Object};
 
+use crate::assets::{AssetResolver, PackedScene};
 use crate::shaders::*;
 
+mod file;
+mod lint;
+mod writer;
+
+use file::*;
+
+pub use lint::Lint;
+pub use writer::SceneWriter;
+
 macro_rules! extract_vec3 {
     ($stub:ident, $shape:ident, $method:path, $name:literal) => {
         if let Some(item) = $stub.get($name) {
@@ -47,13 +68,51 @@ macro_rules! extract_float {
     };
 }
 
+/// Keeps baked grids of a single shape under a gigabyte.
+const MAX_BAKE_RESOLUTION: u32 = 512;
+
+/// Keeps baked noise of a disk shader under 32 MB.
+const MAX_NOISE_BAKE_RESOLUTION: u32 = 256;
+
+/// Source of the scene file structures, snapshots saved by loaders with different ones are not
+/// read.
+const FILE_FORMAT: &str = include_str!("scene_loader/file.rs");
+
+static SNAPSHOTS: AtomicBool = AtomicBool::new(true);
+
 pub struct SceneLoader {}
 
 impl SceneLoader {
+    /// Whether parsed scene files are kept as binary snapshots in the disk cache, see
+    /// [`blackhole::cache`]. Enabled by default.
+    ///
+    /// Every scene file path keeps a single snapshot, replaced when the contents of the file
+    /// change. Large scenes then skip the slow json5 parsing when loaded again, but only that, the
+    /// built scene is not cached. Shapes and shaders are still built from the snapshot, costly
+    /// parts like meshes and baked grids have their own cache entries.
+    pub fn set_snapshots(enabled: bool) {
+        SNAPSHOTS.store(enabled, Ordering::Relaxed);
+    }
+
     pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Scene, LoaderError> {
-        let scene_str = std::fs::read_to_string(path).map_err(LoaderError::InputError)?;
+        Self::load_with_lints(path).map(|(scene, _)| scene)
+    }
 
-        let json: SceneFile = json5::from_str(&scene_str).map_err(LoaderError::FormatError)?;
+    /// Loads scene and checks it for likely mistakes, see [`Lint`].
+    pub fn load_with_lints<P: AsRef<Path>>(path: P) -> Result<(Scene, Vec<Lint>), LoaderError> {
+        let mut json = read_scene_file(&path)?;
+        let assets = asset_resolver(&path, &json);
+
+        // shaders without their own seed follow the scene one
+        if let Some(seed) = json.seed {
+            for shader in json.shaders.values_mut() {
+                shader
+                    .parameters
+                    .get_or_insert_with(HashMap::new)
+                    .entry("seed".into())
+                    .or_insert(ParameterValue::U64(seed));
+            }
+        }
 
         let mut shaders_solid: HashMap<String, Arc<dyn SolidShader>> = HashMap::new();
         let mut shaders_volumetric: HashMap<String, Arc<dyn VolumetricShader>> = HashMap::new();
@@ -61,24 +120,45 @@ impl SceneLoader {
 
         let mut shader_types: HashMap<String, ShaderType> = HashMap::new();
 
+        let units = json.units.as_ref().map(load_units).transpose()?;
+        let units = units.as_ref();
+
+        let mut camera = load_camera(&json.camera, units)?;
+        let physical_exposure = json
+            .camera
+            .exposure
+            .as_ref()
+            .and_then(|e| e.physical)
+            .unwrap_or(false);
+
         for (name, shader) in &json.shaders {
             let params = shader.parameters.as_ref();
 
             match shader.kind.as_str() {
                 "background" => {
-                    let shader = build_background_shader(shader.class.as_str(), params)?;
+                    let mut shader =
+                        build_background_shader(shader.class.as_str(), params, &assets)?;
+
+                    // lets backgrounds follow exposure set for the rest of the scene
+                    if let Some(shader) = Arc::get_mut(&mut shader) {
+                        let mut ev100 = camera.exposure.ev100;
+                        if physical_exposure {
+                            ev100 -= (SENSOR_SCALE as f64).log2();
+                        }
+                        shader.set_parameter("camera_ev100", Parameter::Float(ev100));
+                    }
 
                     shaders_background.insert(name.clone(), shader);
                     shader_types.insert(name.clone(), ShaderType::Background);
                 }
                 "volumetric" => {
-                    let shader = build_volumetric_shader(shader.class.as_str(), params)?;
+                    let shader = build_volumetric_shader(shader.class.as_str(), params, &assets)?;
 
                     shaders_volumetric.insert(name.clone(), shader);
                     shader_types.insert(name.clone(), ShaderType::Volumetric);
                 }
                 "solid" => {
-                    let shader = build_solid_shader(shader.class.as_str(), params)?;
+                    let shader = build_solid_shader(shader.class.as_str(), params, &assets)?;
 
                     shaders_solid.insert(name.clone(), shader);
                     shader_types.insert(name.clone(), ShaderType::Solid);
@@ -87,10 +167,9 @@ impl SceneLoader {
             }
         }
 
-        let bg_name = json.background;
-        let bg = shaders_background
-            .get(&bg_name)
-            .ok_or(LoaderError::IndexError(bg_name, "background shaders"))?;
+        let bg = shaders_background.get(&json.background).ok_or_else(|| {
+            LoaderError::IndexError(json.background.clone(), "background shaders")
+        })?;
 
         let mut scene = Scene::new(Arc::clone(bg));
 
@@ -100,42 +179,279 @@ impl SceneLoader {
                 None => return Err(LoaderError::IndexError(stub.shader.clone(), "shaders")),
             };
 
-            let shape = build_shape(&stub.shape)?;
+            let mut shape = build_shape(&stub.shape, &assets)?;
+
+            if let Some(resolution) = stub.bake {
+                if !(2..=MAX_BAKE_RESOLUTION).contains(&resolution) {
+                    return Err(LoaderError::Other(format!(
+                        "bake resolution must be between 2 and {MAX_BAKE_RESOLUTION}"
+                    )));
+                }
+
+                let key = shape_key(&stub.shape, &assets)?;
+                shape = Arc::new(GridShape::cached(&*shape, resolution as usize, key));
+            }
+
+            if let Some(motion) = stub.motion {
+                shape = Arc::new(Moving::new(shape, Vector3::from(motion)));
+            }
+
+            // objects without overrides share the shader instance
+            let overridden = stub.parameters.as_ref().map(|overrides| {
+                let shader = &json.shaders[&stub.shader];
 
-            let object = match st {
+                let mut params = shader.parameters.clone().unwrap_or_default();
+                params.extend(overrides.clone());
+
+                (shader.class.as_str(), params)
+            });
+
+            let mut object = match st {
                 ShaderType::Solid => {
-                    let shader = shaders_solid.get(&stub.shader).unwrap().clone();
+                    let shader = match &overridden {
+                        Some((class, params)) => build_solid_shader(class, Some(params), &assets)?,
+                        None => shaders_solid.get(&stub.shader).unwrap().clone(),
+                    };
 
                     Object::solid(shape, shader)
                 }
                 ShaderType::Volumetric => {
-                    let shader = shaders_volumetric.get(&stub.shader).unwrap().clone();
+                    let shader = match &overridden {
+                        Some((class, params)) => {
+                            build_volumetric_shader(class, Some(params), &assets)?
+                        }
+                        None => shaders_volumetric.get(&stub.shader).unwrap().clone(),
+                    };
 
                     Object::volumetric(shape, shader)
                 }
                 _ => return Err(LoaderError::Other("invalid shader type".into())),
             };
 
+            object.instance_seed = stub.seed.unwrap_or(0);
+            if let Some(layer) = &stub.layer {
+                object.layer = scene.layer_index(layer);
+            }
+            if let Some(track) = &stub.animation {
+                object = object.animate(load_object_track(track, units)?);
+            }
+
             scene = scene.push(object);
         }
 
-        scene.distortions = load_distortions(&json.distortions);
-        scene.camera = load_camera(&json.camera);
+        scene.distortions = load_distortions(&json.distortions, &shaders_background, units)?;
+
+        for (i, stub) in json.distortions.iter().enumerate() {
+            if let Some(disk) = &stub.with_disk {
+                let objects = build_disk(&scene.distortions[i], disk, json.seed, units)?;
+                let layer = match &disk.layer {
+                    Some(layer) => scene.layer_index(layer),
+                    None => 0,
+                };
+
+                for mut object in objects {
+                    object.layer = layer;
+                    scene = scene.push(object);
+                }
+            }
+
+            if let Some(ring) = &stub.with_photon_ring {
+                let mut object = build_photon_ring(&scene.distortions[i], ring)?;
+
+                if let Some(layer) = &ring.layer {
+                    object.layer = scene.layer_index(layer);
+                }
+
+                scene = scene.push(object);
+            }
+        }
+
+        if let Some(frame) = &json.camera.frame {
+            let (center, radius) =
+                framed_sphere(frame, &json.distortions, &scene.distortions, units)?;
 
-        Ok(scene)
+            camera.frame_sphere(center, radius);
+        }
+
+        scene.camera = camera;
+        scene.physical_exposure = physical_exposure;
+
+        if let Some(animation) = &json.animation {
+            scene.animation = load_animation(animation, units)?;
+        }
+
+        if let Some(name) = &json.tonemap {
+            scene.tonemap = Tonemap::from_name(name)
+                .ok_or_else(|| LoaderError::Other(format!("unknown tonemap '{name}'")))?;
+        }
+
+        if let Some(burn_in) = &json.burn_in {
+            scene.burn_in = Some(load_burn_in(burn_in)?);
+        }
+
+        if let Some(slice) = &json.slice {
+            let normal = Vector3::from(slice.normal);
+
+            if normal.magnitude2() == 0.0 {
+                return Err(LoaderError::Other("slice normal must not be zero".into()));
+            }
+
+            scene.slice = Some(SlicePlane::new(Vector3::from(slice.point), normal));
+        }
+
+        if let Some(render) = &json.render {
+            scene.render = load_render_settings(render)?;
+        }
+
+        let lints = lint::lint(&json, &scene);
+
+        Ok((scene, lints))
+    }
+
+    /// Collects scene and all assets it references, with asset paths rewritten to be relative
+    /// to the packed scene.
+    pub fn pack<P: AsRef<Path>>(path: P) -> Result<PackedScene, LoaderError> {
+        let mut json = read_scene_file(&path)?;
+        let assets = asset_resolver(&path, &json);
+
+        let mut packed = PackedScene {
+            scene: String::new(),
+            assets: Vec::new(),
+        };
+
+        let shader_params = json.shaders.values_mut().map(|s| &mut s.parameters);
+        let object_params = json.objects.iter_mut().map(|o| &mut o.parameters);
+
+        for params in shader_params.chain(object_params) {
+            for value in params.iter_mut().flat_map(|p| p.values_mut()) {
+                if let ParameterValue::Text(asset) = value {
+                    let source = assets.resolve(asset)?;
+                    *asset = packed.add_asset(source);
+                }
+            }
+        }
+
+        for object in &mut json.objects {
+            pack_meshes(&mut object.shape, &assets, &mut packed)?;
+        }
+
+        json.search_paths = None;
+
+        packed.scene = serde_json::to_string_pretty(&json)
+            .map_err(|e| LoaderError::Other(format!("could not write scene: {e}")))?;
+
+        Ok(packed)
+    }
+}
+
+/// Hash of the shape description and contents of meshes it reads, telling baked grids apart.
+fn shape_key(shape: &Map<String, Value>, assets: &AssetResolver) -> Result<u64, LoaderError> {
+    fn meshes(shape: &Map<String, Value>, paths: &mut Vec<String>) {
+        for (name, stub) in shape {
+            let stub = match stub.as_object() {
+                Some(stub) => stub,
+                None => continue,
+            };
+
+            if name == "mesh" {
+                if let Some(Value::String(path)) = stub.get("path") {
+                    paths.push(path.clone());
+                }
+            }
+
+            for child in stub.values().filter_map(Value::as_object) {
+                meshes(child, paths);
+            }
+        }
+    }
+
+    let mut paths = Vec::new();
+    meshes(shape, &mut paths);
+
+    let contents = paths
+        .iter()
+        .map(|path| std::fs::read(assets.resolve(path)?).map_err(LoaderError::InputError))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(cache::content_hash(&(
+        Value::Object(shape.clone()).to_string(),
+        contents,
+    )))
+}
+
+/// Rewrites paths of meshes anywhere in the shape tree, including composite operands.
+fn pack_meshes(
+    shape: &mut Map<String, Value>,
+    assets: &AssetResolver,
+    packed: &mut PackedScene,
+) -> Result<(), LoaderError> {
+    for (name, stub) in shape.iter_mut() {
+        let stub = match stub.as_object_mut() {
+            Some(stub) => stub,
+            None => continue,
+        };
+
+        if name == "mesh" {
+            if let Some(Value::String(path)) = stub.get_mut("path") {
+                *path = packed.add_asset(assets.resolve(path)?);
+            }
+        }
+
+        for child in stub.values_mut().filter_map(Value::as_object_mut) {
+            pack_meshes(child, assets, packed)?;
+        }
     }
+
+    Ok(())
+}
+
+fn read_scene_file(path: impl AsRef<Path>) -> Result<SceneFile, LoaderError> {
+    let scene_str = std::fs::read_to_string(&path).map_err(LoaderError::InputError)?;
+    let parse = || json5::from_str(&scene_str).map_err(LoaderError::FormatError);
+
+    if !SNAPSHOTS.load(Ordering::Relaxed) {
+        return parse();
+    }
+
+    let path = path.as_ref();
+    let slot = cache::content_hash(&path.canonicalize().unwrap_or_else(|_| path.to_owned()));
+    let key = cache::content_hash(&(FILE_FORMAT, scene_str.as_str()));
+
+    cache::try_load_or_replace("scene", slot, key, parse)
+}
+
+fn asset_resolver(path: impl AsRef<Path>, json: &SceneFile) -> AssetResolver {
+    let mut assets = AssetResolver::new(path);
+
+    for search_path in json.search_paths.iter().flatten() {
+        assets.add_search_path(search_path);
+    }
+    assets.add_env_search_paths();
+
+    assets
 }
 
 fn build_background_shader(
     name: &str,
     params: Option<&HashMap<String, ParameterValue>>,
+    assets: &AssetResolver,
 ) -> Result<Arc<dyn BackgroundShader>, LoaderError> {
     match name {
-        "StarSkyShader" => Ok(Arc::new(build_shader::<StarSkyShader>(params))),
-        "SolidColorBackgroundShader" => {
-            Ok(Arc::new(build_shader::<SolidColorBackgroundShader>(params)))
+        "StarSkyShader" => Ok(Arc::new(build_shader::<StarSkyShader>(params, assets)?)),
+        "SolidColorBackgroundShader" => Ok(Arc::new(build_shader::<SolidColorBackgroundShader>(
+            params, assets,
+        )?)),
+        "DebugBackgroundShader" => Ok(Arc::new(build_shader::<DebugBackgroundShader>(
+            params, assets,
+        )?)),
+        "HdriBackgroundShader" => {
+            let shader = build_shader::<HdriBackgroundShader>(params, assets)?;
+
+            match shader.error() {
+                Some(e) => Err(LoaderError::Other(format!("HdriBackgroundShader: {e}"))),
+                None => Ok(Arc::new(shader)),
+            }
         }
-        "DebugBackgroundShader" => Ok(Arc::new(build_shader::<DebugBackgroundShader>(params))),
         _ => Err(LoaderError::Other("unknown background shader".into())),
     }
 }
@@ -143,19 +459,37 @@ fn build_background_shader(
 fn build_volumetric_shader(
     name: &str,
     params: Option<&HashMap<String, ParameterValue>>,
+    assets: &AssetResolver,
 ) -> Result<Arc<dyn VolumetricShader>, LoaderError> {
     match name {
-        "BlackHoleEmitterShader" => Ok(Arc::new(build_shader::<BlackHoleEmitterShader>(params))),
-        "BlackHoleScatterShader" => Ok(Arc::new(build_shader::<BlackHoleScatterShader>(params))),
-        "VolumeEmitterShader" => Ok(Arc::new(build_shader::<VolumeEmitterShader>(params))),
-        "SolidColorVolumeShader" => Ok(Arc::new(build_shader::<SolidColorVolumeShader>(params))),
+        "AccretionDiskShader" => Ok(Arc::new(build_shader::<AccretionDiskShader>(
+            params, assets,
+        )?)),
+        "BlackHoleEmitterShader" => Ok(Arc::new(build_shader::<BlackHoleEmitterShader>(
+            params, assets,
+        )?)),
+        "BlackHoleScatterShader" => Ok(Arc::new(build_shader::<BlackHoleScatterShader>(
+            params, assets,
+        )?)),
+        "TurbulentDiskShader" => Ok(Arc::new(build_shader::<TurbulentDiskShader>(
+            params, assets,
+        )?)),
+        "VolumeEmitterShader" => Ok(Arc::new(build_shader::<VolumeEmitterShader>(
+            params, assets,
+        )?)),
+        "PhotonRingShader" => Ok(Arc::new(build_shader::<PhotonRingShader>(params, assets)?)),
+        "SolidColorVolumeShader" => Ok(Arc::new(build_shader::<SolidColorVolumeShader>(
+            params, assets,
+        )?)),
         "SolidColorVolumeAbsorbShader" => Ok(Arc::new(
-            build_shader::<SolidColorVolumeAbsorbShader>(params),
+            build_shader::<SolidColorVolumeAbsorbShader>(params, assets)?,
         )),
         "SolidColorVolumeScatterShader" => Ok(Arc::new(build_shader::<
             SolidColorVolumeScatterShader,
-        >(params))),
-        "DebugNoiseVolumeShader" => Ok(Arc::new(build_shader::<DebugNoiseVolumeShader>(params))),
+        >(params, assets)?)),
+        "DebugNoiseVolumeShader" => Ok(Arc::new(build_shader::<DebugNoiseVolumeShader>(
+            params, assets,
+        )?)),
         _ => Err(LoaderError::Other("unknown volumetric shader".into())),
     }
 }
@@ -163,14 +497,33 @@ fn build_volumetric_shader(
 fn build_solid_shader(
     name: &str,
     params: Option<&HashMap<String, ParameterValue>>,
+    assets: &AssetResolver,
 ) -> Result<Arc<dyn SolidShader>, LoaderError> {
     match name {
-        "BasicSolidShader" => Ok(Arc::new(build_shader::<BasicSolidShader>(params))),
+        "BasicSolidShader" => {
+            let shader = build_shader::<BasicSolidShader>(params, assets)?;
+
+            match shader.error() {
+                Some(e) => Err(LoaderError::Other(format!("BasicSolidShader: {e}"))),
+                None => Ok(Arc::new(shader)),
+            }
+        }
+        "TexturedSolidShader" => {
+            let shader = build_shader::<TexturedSolidShader>(params, assets)?;
+
+            match shader.error() {
+                Some(e) => Err(LoaderError::Other(format!("TexturedSolidShader: {e}"))),
+                None => Ok(Arc::new(shader)),
+            }
+        }
         _ => Err(LoaderError::Other("unknown solid shader".into())),
     }
 }
 
-fn build_shader<T>(parameters: Option<&HashMap<String, ParameterValue>>) -> T
+fn build_shader<T>(
+    parameters: Option<&HashMap<String, ParameterValue>>,
+    assets: &AssetResolver,
+) -> Result<T, LoaderError>
 where
     T: Shader + Default,
 {
@@ -182,16 +535,20 @@ where
                 ParameterValue::Vec3(v) => Parameter::Vec3(Vector3::from(*v)),
                 ParameterValue::U64(u) => Parameter::Usize(*u as usize),
                 ParameterValue::Float(f) => Parameter::Float(*f),
+                ParameterValue::Text(path) => Parameter::Path(assets.resolve(path)?),
             };
 
             shader.set_parameter(name, value);
         }
     }
 
-    shader
+    Ok(shader)
 }
 
-fn build_shape(value: &Map<String, Value>) -> Result<Arc<dyn Shape>, LoaderError> {
+fn build_shape(
+    value: &Map<String, Value>,
+    assets: &AssetResolver,
+) -> Result<Arc<dyn Shape>, LoaderError> {
     if value.len() != 1 {
         return Err(LoaderError::Other("invalid shape format".into()));
     }
@@ -212,18 +569,31 @@ fn build_shape(value: &Map<String, Value>) -> Result<Arc<dyn Shape>, LoaderError
                     .ok_or(LoaderError::KeyError("a"))?
                     .as_object()
                     .ok_or(LoaderError::Other("invalid type".into()))?,
+                assets,
             )?;
             let b = build_shape(
                 stub.get("b")
                     .ok_or(LoaderError::KeyError("b"))?
                     .as_object()
                     .ok_or(LoaderError::Other("invalid type".into()))?,
+                assets,
             )?;
 
+            // blend radius of smooth operations
+            let k = || match stub.get("k") {
+                Some(k) => k
+                    .as_f64()
+                    .filter(|k| *k > 0.0)
+                    .ok_or(LoaderError::Other("k must be a positive number".into())),
+                None => Err(LoaderError::KeyError("k")),
+            };
+
             let composite = match op {
                 "diff" => Composite::diff(a, b),
                 "intersect" => Composite::intersect(a, b),
                 "union" => Composite::union(a, b),
+                "smooth_union" => Composite::smooth_union(a, b, k()?),
+                "smooth_diff" => Composite::smooth_diff(a, b, k()?),
                 _ => return Err(LoaderError::Other("invalid op".into())),
             };
 
@@ -237,6 +607,15 @@ fn build_shape(value: &Map<String, Value>) -> Result<Arc<dyn Shape>, LoaderError
 
             Arc::new(sphere) as Arc<dyn Shape>
         }
+        "shell" => {
+            let mut shell = Shell::new();
+
+            extract_vec3!(stub, shell, Shell::set_center, "center");
+            extract_float!(stub, shell, Shell::set_radius, "radius");
+            extract_float!(stub, shell, Shell::set_thickness, "thickness");
+
+            Arc::new(shell) as Arc<dyn Shape>
+        }
         "cylinder" => {
             let mut cylinder = Cylinder::new();
 
@@ -254,10 +633,101 @@ fn build_shape(value: &Map<String, Value>) -> Result<Arc<dyn Shape>, LoaderError
 
             Arc::new(cube) as Arc<dyn Shape>
         }
+        "repeated" => {
+            let shape = build_shape(
+                stub.get("shape")
+                    .ok_or(LoaderError::KeyError("shape"))?
+                    .as_object()
+                    .ok_or(LoaderError::Other("invalid type".into()))?,
+                assets,
+            )?;
+
+            let cell = arr_to_vec3(
+                stub.get("cell")
+                    .ok_or(LoaderError::KeyError("cell"))?
+                    .as_array()
+                    .ok_or(LoaderError::Other("wrong cell type".into()))?,
+            )?;
+
+            if cell.x <= 0.0 || cell.y <= 0.0 || cell.z <= 0.0 {
+                return Err(LoaderError::Other(
+                    "repeated cell size must be positive".into(),
+                ));
+            }
+
+            let copies = arr_to_vec3(
+                stub.get("copies")
+                    .ok_or(LoaderError::KeyError("copies"))?
+                    .as_array()
+                    .ok_or(LoaderError::Other("wrong copies type".into()))?,
+            )?;
+
+            let copies = [copies.x, copies.y, copies.z].map(|c| c.max(0.0) as u32);
+
+            Arc::new(Repeated::new(shape, cell, copies)) as Arc<dyn Shape>
+        }
+        "mesh" => {
+            let path = match stub.get("path") {
+                Some(path) => path
+                    .as_str()
+                    .ok_or(LoaderError::Other("invalid type for mesh path".into()))?,
+                None => return Err(LoaderError::KeyError("path")),
+            };
+
+            let mut triangles = read_triangles(assets.resolve(path)?)
+                .map_err(|e| LoaderError::Other(format!("could not read mesh '{path}': {e}")))?;
+
+            if let Some(item) = stub.get("center") {
+                let item = item
+                    .as_array()
+                    .ok_or_else(|| LoaderError::Other("wrong center type".into()))?;
+                let center = arr_to_vec3(item)?;
+
+                for corner in triangles.iter_mut().flatten() {
+                    *corner += center;
+                }
+            }
+
+            let mesh = MeshShape::new(&triangles);
+
+            if mesh.triangle_count() == 0 {
+                return Err(LoaderError::Other(format!(
+                    "mesh '{path}' has no triangles"
+                )));
+            }
+
+            Arc::new(mesh) as Arc<dyn Shape>
+        }
         _ => return Err(LoaderError::Other("invalid shape".into())),
     };
 
-    Ok(obj)
+    // any shape can be rotated and scaled around its center
+    if stub.get("rotation").is_none() && stub.get("scale").is_none() {
+        return Ok(obj);
+    }
+
+    let rotation = match stub.get("rotation") {
+        Some(item) => {
+            let item = item
+                .as_array()
+                .ok_or_else(|| LoaderError::Other("wrong rotation type".into()))?;
+
+            arr_to_vec3(item)?
+        }
+        None => Vector3::zero(),
+    };
+
+    let scale = match stub.get("scale") {
+        Some(item) => item
+            .as_f64()
+            .filter(|scale| *scale > 0.0 && scale.is_finite())
+            .ok_or_else(|| LoaderError::Other("scale must be a positive finite number".into()))?,
+        None => 1.0,
+    };
+
+    let pivot = obj.bounding_box().center();
+
+    Ok(Arc::new(Transformed::around(obj, pivot, rotation, scale)))
 }
 
 fn arr_to_vec3(arr: &Vec<Value>) -> Result<Vector3<f64>, LoaderError> {
@@ -277,44 +747,694 @@ fn arr_to_vec3(arr: &Vec<Value>) -> Result<Vector3<f64>, LoaderError> {
     Ok(Vector3::from(values))
 }
 
-fn load_distortions(stubs: &[DistortionStub]) -> Vec<Distortion> {
+/// Creates emitting and scattering volumes of an accretion disk around the distortion.
+fn build_disk(
+    distortion: &Distortion,
+    stub: &DiskStub,
+    seed: Option<u64>,
+    units: Option<&Units>,
+) -> Result<[Object; 2], LoaderError> {
+    let (inner, outer, thickness) = disk_size(stub, units)?;
+
+    let center = distortion.shape.center();
+    let axis = distortion.spin_axis;
+
+    // the scattering volume is a bit larger than the emitting one
+    let layers = [(outer, thickness), (outer * 1.05, thickness * 3.0)];
+
+    let [emitter_shape, scatter_shape] = layers.map(|(radius, thickness)| {
+        let mut hole = Sphere::new();
+        hole.set_center(center);
+        hole.set_radius(inner.max(0.001));
+
+        let mut bounds = Cylinder::new();
+        bounds.set_center(center);
+        bounds.set_axis(axis);
+        bounds.set_radius(radius);
+        bounds.set_height(thickness);
+
+        Arc::new(Composite::diff(Arc::new(bounds), Arc::new(hole))) as Arc<dyn Shape>
+    });
+
+    let cutoff = match stub.isco_cutoff {
+        Some(true) => distortion.isco_radius(),
+        _ => 0.0,
+    };
+
+    let disk_params = [
+        ("center", Parameter::Vec3(center)),
+        ("axis", Parameter::Vec3(axis)),
+        ("inner", Parameter::Float(inner)),
+        ("outer", Parameter::Float(outer)),
+        ("cutoff_radius", Parameter::Float(cutoff)),
+    ];
+
+    let mut emitter = AccretionDiskShader::new();
+    let mut scatter = BlackHoleScatterShader::new();
+
+    for (name, value) in disk_params {
+        emitter.set_parameter(name, value.clone());
+        scatter.set_parameter(name, value);
+    }
+
+    emitter.set_parameter("thickness", Parameter::Float(thickness));
+    scatter.set_parameter("thickness", Parameter::Float(thickness * 3.0));
+
+    if let Some(scale) = stub.temp_scale {
+        emitter.set_parameter("temp_scale", Parameter::Float(scale));
+    }
+
+    if let Some(temp) = stub.peak_temp {
+        if temp <= 0.0 {
+            return Err(LoaderError::Other(format!(
+                "disk peak temperature must be positive, got {temp}"
+            )));
+        }
+
+        emitter.set_parameter("peak_temp", Parameter::Float(temp));
+    }
+
+    if let Some(falloff) = stub.density_falloff {
+        emitter.set_parameter("falloff", Parameter::Float(falloff));
+    }
+
+    if let Some(density) = stub.density {
+        if density < 0.0 {
+            return Err(LoaderError::Other(format!(
+                "disk density must not be negative, got {density}"
+            )));
+        }
+
+        emitter.set_parameter("density", Parameter::Float(density));
+    }
+
+    if let Some(seed) = seed {
+        emitter.set_parameter("seed", Parameter::Usize(seed as usize));
+        scatter.set_parameter("seed", Parameter::Usize(seed as usize));
+    }
+
+    if let Some(noise) = &stub.noise {
+        if let Some(kind) = &noise.kind {
+            if !matches!(kind.as_str(), "perlin" | "worley" | "curl") {
+                return Err(LoaderError::Other(format!(
+                    "disk noise kind must be perlin, worley or curl, got '{kind}'"
+                )));
+            }
+
+            emitter.set_parameter("noise_kind", Parameter::Text(kind.clone()));
+            scatter.set_parameter("noise_kind", Parameter::Text(kind.clone()));
+        }
+
+        if let Some(scale) = noise.scale {
+            if scale <= 0.0 {
+                return Err(LoaderError::Other(format!(
+                    "disk noise scale must be positive, got {scale}"
+                )));
+            }
+
+            let emitter_scale = AccretionDiskShader::NOISE_SCALE * scale;
+            let scatter_scale = BlackHoleScatterShader::NOISE_SCALE * scale;
+
+            emitter.set_parameter("noise_scale", Parameter::Float(emitter_scale));
+            scatter.set_parameter("noise_scale", Parameter::Float(scatter_scale));
+        }
+
+        let fbm_params = [
+            ("noise_octaves", noise.octaves.map(Parameter::Usize)),
+            ("noise_lacunarity", noise.lacunarity.map(Parameter::Float)),
+            ("noise_gain", noise.gain.map(Parameter::Float)),
+        ];
+
+        for (name, value) in fbm_params {
+            if let Some(value) = value {
+                emitter.set_parameter(name, value.clone());
+                scatter.set_parameter(name, value);
+            }
+        }
+
+        // baked last, so the grid is sampled once
+        if let Some(resolution) = noise.bake {
+            if !(2..=MAX_NOISE_BAKE_RESOLUTION).contains(&resolution) {
+                return Err(LoaderError::Other(format!(
+                    "disk noise bake resolution must be between 2 and {MAX_NOISE_BAKE_RESOLUTION}"
+                )));
+            }
+
+            emitter.set_parameter("noise_bake", Parameter::Usize(resolution as usize));
+            scatter.set_parameter("noise_bake", Parameter::Usize(resolution as usize));
+        }
+    }
+
+    let mut objects = [
+        Object::volumetric(emitter_shape, Arc::new(emitter)),
+        Object::volumetric(scatter_shape, Arc::new(scatter)),
+    ];
+
+    if let Some(period) = stub.period {
+        if period == 0.0 {
+            return Err(LoaderError::Other("disk period must not be zero".into()));
+        }
+
+        let track = ObjectTrack::Orbit {
+            center,
+            axis,
+            period,
+        };
+        objects = objects.map(|object| object.animate(track));
+    }
+
+    Ok(objects)
+}
+
+/// Shell just outside of the photon sphere of the distortion, glowing along grazing rays.
+fn build_photon_ring(
+    distortion: &Distortion,
+    stub: &PhotonRingStub,
+) -> Result<Object, LoaderError> {
+    let thickness = stub.thickness.unwrap_or(0.05);
+    if thickness <= 0.0 {
+        return Err(LoaderError::Other(format!(
+            "photon ring thickness must be positive, got {thickness}"
+        )));
+    }
+
+    // rays inside the photon sphere are captured, so the shell starts at it
+    let photon_sphere = distortion.photon_sphere_radius();
+    let center = distortion.shape.center();
+
+    let mut shell = Shell::new();
+    shell.set_center(center);
+    shell.set_radius(photon_sphere * (1.0 + thickness / 2.0));
+    shell.set_thickness(photon_sphere * thickness);
+
+    let mut shader = PhotonRingShader::new();
+    shader.set_parameter("center", Parameter::Vec3(center));
+    // optically thin along grazing paths, which are about as long as the radius
+    shader.set_parameter("density", Parameter::Float(1.0 / photon_sphere));
+
+    let params = [
+        ("temp", stub.temp),
+        ("strength", stub.strength),
+        ("sharpness", stub.sharpness),
+    ];
+
+    for (name, value) in params {
+        if let Some(value) = value {
+            shader.set_parameter(name, Parameter::Float(value));
+        }
+    }
+
+    Ok(Object::volumetric(Arc::new(shell), Arc::new(shader)))
+}
+
+fn load_object_track(
+    stub: &ObjectTrackStub,
+    units: Option<&Units>,
+) -> Result<ObjectTrack, LoaderError> {
+    match stub {
+        ObjectTrackStub::Linear { velocity } => Ok(ObjectTrack::Linear {
+            velocity: resolve_vec3(velocity, units)?,
+        }),
+        ObjectTrackStub::Orbit {
+            center,
+            axis,
+            period,
+        } => {
+            if *period == 0.0 {
+                return Err(LoaderError::Other("orbit period must not be zero".into()));
+            }
+
+            let axis = Vector3::from(axis.unwrap_or([0.0, 1.0, 0.0]));
+            if axis.magnitude2() == 0.0 {
+                return Err(LoaderError::Other("orbit axis must not be zero".into()));
+            }
+
+            let center = match center {
+                Some(center) => resolve_vec3(center, units)?,
+                None => Vector3::zero(),
+            };
+
+            Ok(ObjectTrack::Orbit {
+                center,
+                axis,
+                period: *period,
+            })
+        }
+    }
+}
+
+/// Inner and outer radius and thickness of the disk, in scene units.
+fn disk_size(stub: &DiskStub, units: Option<&Units>) -> Result<(f64, f64, f64), LoaderError> {
+    let length = |length: &Option<Length>, default| match length {
+        Some(length) => length.resolve(units),
+        None => Ok(default),
+    };
+
+    Ok((
+        length(&stub.inner, 1.0)?,
+        length(&stub.outer, 4.0)?,
+        length(&stub.thickness, 0.02)?,
+    ))
+}
+
+/// Center and radius of the sphere around a distortion the camera is framed to, with margin.
+///
+/// The sphere holds the whole generated disk, or the distortion radius without one.
+fn framed_sphere(
+    stub: &FrameStub,
+    stubs: &[DistortionStub],
+    distortions: &[Distortion],
+    units: Option<&Units>,
+) -> Result<(Vector3<f64>, f64), LoaderError> {
+    let index = stub.distortion.unwrap_or(0);
+    let distortion = distortions
+        .get(index)
+        .ok_or_else(|| LoaderError::IndexError(index.to_string(), "distortions"))?;
+
+    let margin = stub.margin.unwrap_or(0.1);
+    if margin < 0.0 {
+        return Err(LoaderError::Other(
+            "camera frame margin must not be negative".into(),
+        ));
+    }
+
+    let radius = match &stubs[index].with_disk {
+        // the scattering volume reaches a bit further than the emitting one
+        Some(disk) => disk_size(disk, units)?.1 * 1.05,
+        None => distortion.shape.radius(),
+    };
+
+    Ok((distortion.shape.center(), radius * (1.0 + margin)))
+}
+
+fn load_distortions(
+    stubs: &[DistortionStub],
+    shaders_background: &HashMap<String, Arc<dyn BackgroundShader>>,
+    units: Option<&Units>,
+) -> Result<Vec<Distortion>, LoaderError> {
     stubs
         .iter()
         .map(|stub| {
             let mut distortion = Distortion::new();
-            if let Some(str) = stub.strength {
-                distortion.strength = str;
+
+            // without strength, distortions get the mass of the scene units
+            match (stub.strength, units) {
+                (Some(str), _) => distortion.strength = str,
+                (None, Some(units)) => distortion.strength = units.distortion_strength(),
+                (None, None) => {}
             }
 
-            if let Some(r) = stub.radius {
-                distortion.shape.set_radius(r);
+            if let Some(r) = &stub.radius {
+                distortion.shape.set_radius(r.resolve(units)?);
             }
 
             if let Some(center) = &stub.center {
-                let vec3 = Vector3::from(*center);
+                distortion.shape.set_center(resolve_vec3(center, units)?);
+            }
+
+            if let Some(axis) = stub.spin_axis {
+                let axis = Vector3::from(axis);
+
+                if axis.magnitude2() == 0.0 {
+                    return Err(LoaderError::Other(
+                        "distortion spin axis must not be zero".into(),
+                    ));
+                }
 
-                distortion.shape.set_center(vec3);
+                distortion.spin_axis = axis.normalize();
             }
 
-            distortion
+            if let Some(spin) = stub.spin {
+                if !(-1.0..=1.0).contains(&spin) {
+                    return Err(LoaderError::Other(format!(
+                        "distortion spin must be between -1 and 1, got {spin}"
+                    )));
+                }
+
+                distortion.spin = spin;
+            }
+
+            if let Some(horizon) = &stub.horizon_radius {
+                let radius = horizon.resolve(units)?;
+
+                if radius <= 0.0 || radius >= distortion.shape.radius() {
+                    return Err(LoaderError::Other(format!(
+                        "distortion horizon radius must be between 0 and its radius, got {radius}"
+                    )));
+                }
+
+                distortion.horizon_radius = Some(radius);
+            }
+
+            if let Some(name) = &stub.capture_shader {
+                let shader = shaders_background
+                    .get(name)
+                    .ok_or_else(|| LoaderError::IndexError(name.clone(), "background shaders"))?;
+
+                distortion.capture_shader = Some(shader.clone());
+            }
+
+            Ok(distortion)
         })
         .collect()
 }
 
-fn load_camera(stub: &CameraStub) -> Camera {
+fn load_render_settings(stub: &RenderStub) -> Result<RenderSettings, LoaderError> {
+    let counts = [
+        ("samples", stub.samples),
+        ("max_steps", stub.max_steps),
+        ("max_depth", stub.max_depth),
+    ];
+    let steps = [
+        ("volume_step", stub.volume_step),
+        ("volume_min_step", stub.volume_min_step),
+        ("distortion_step", stub.distortion_step),
+    ];
+
+    for (name, value) in counts {
+        if value == Some(0) {
+            return Err(LoaderError::Other(format!(
+                "render {name} must not be zero"
+            )));
+        }
+    }
+
+    for (name, value) in steps {
+        if value.is_some_and(|v| !(v > 0.0 && v.is_finite())) {
+            return Err(LoaderError::Other(format!(
+                "render {name} must be positive"
+            )));
+        }
+    }
+
+    Ok(RenderSettings {
+        samples: stub.samples,
+        max_steps: stub.max_steps,
+        max_depth: stub.max_depth,
+        volume_step: stub.volume_step,
+        volume_min_step: stub.volume_min_step,
+        distortion_step: stub.distortion_step,
+        background: stub.background,
+    })
+}
+
+fn load_animation(stub: &AnimationStub, units: Option<&Units>) -> Result<Animation, LoaderError> {
+    let mut animation = Animation::new();
+
+    if let Some(fps) = stub.fps {
+        if fps <= 0.0 {
+            return Err(LoaderError::Other("animation fps must be positive".into()));
+        }
+
+        animation.fps = fps;
+    }
+
+    animation.camera = match &stub.camera {
+        Some(CameraTrackStub::Keyframes(stubs)) => {
+            let mut keyframes = stubs
+                .iter()
+                .map(|k| {
+                    let orientation = match (k.rotation, k.look_at) {
+                        (_, Some(target)) => Orientation::Target(Vector3::from(target)),
+                        (Some(rotation), None) => Orientation::Rotation(Vector3::from(rotation)),
+                        (None, None) => return Err(LoaderError::KeyError("rotation")),
+                    };
+
+                    Ok(CameraKeyframe {
+                        time: k.time,
+                        location: resolve_vec3(&k.location, units)?,
+                        orientation,
+                        hor_fov: k.hor_fov,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+            Some(CameraTrack::Keyframes(keyframes))
+        }
+        Some(CameraTrackStub::Orbit(orbit)) => {
+            if orbit.period == 0.0 {
+                return Err(LoaderError::Other("orbit period must not be zero".into()));
+            }
+
+            let center = match &orbit.center {
+                Some(center) => resolve_vec3(center, units)?,
+                None => Vector3::zero(),
+            };
+            let height = match &orbit.height {
+                Some(height) => height.resolve(units)?,
+                None => 0.0,
+            };
+
+            Some(CameraTrack::Orbit(Orbit {
+                center,
+                radius: orbit.radius.resolve(units)?,
+                height,
+                period: orbit.period,
+                start_angle: Deg(orbit.start_angle.unwrap_or(0.0)),
+            }))
+        }
+        None => None,
+    };
+
+    if let Some(sky) = &stub.sky {
+        let pole = sky.pole.map_or(Vector3::unit_y(), Vector3::from);
+
+        if pole.magnitude2() == 0.0 {
+            return Err(LoaderError::Other("sky pole must not be zero".into()));
+        }
+
+        animation.sky = Some(SkyRotation {
+            pole,
+            rate: sky.rate,
+        });
+    }
+
+    if let Some(shutter) = &stub.shutter {
+        if shutter.close <= shutter.open {
+            return Err(LoaderError::Other(
+                "shutter must close after it opens".into(),
+            ));
+        }
+
+        animation.shutter = Some(Shutter {
+            open: shutter.open,
+            close: shutter.close,
+        });
+    }
+
+    Ok(animation)
+}
+
+fn load_camera(stub: &CameraStub, units: Option<&Units>) -> Result<Camera, LoaderError> {
     let mut cam = Camera::new();
 
-    if let Some(loc) = stub.location {
-        cam.location = Vector3::from(loc);
+    if let Some(loc) = &stub.location {
+        cam.location = resolve_vec3(loc, units)?;
     }
 
     if let Some(fw) = stub.rotation {
         cam.set_rotation(Vector3::from(fw));
     }
 
-    cam.hor_fov = stub.hor_fov;
+    cam.projection = match stub.projection.as_deref() {
+        None | Some("perspective") => {
+            cam.hor_fov = stub.hor_fov.ok_or(LoaderError::KeyError("hor_fov"))?;
+
+            Projection::Perspective
+        }
+        Some("parallel") => {
+            let width = stub
+                .plane_width
+                .ok_or(LoaderError::KeyError("plane_width"))?;
+
+            if width <= 0.0 {
+                return Err(LoaderError::Other("plane_width must be positive".into()));
+            }
+
+            Projection::Parallel { width }
+        }
+        Some("equirectangular") => Projection::Equirectangular,
+        Some("fisheye") => {
+            cam.hor_fov = stub.hor_fov.ok_or(LoaderError::KeyError("hor_fov"))?;
+
+            Projection::Fisheye
+        }
+        Some("stereo360") => {
+            let separation = stub
+                .eye_separation
+                .as_ref()
+                .ok_or(LoaderError::KeyError("eye_separation"))?
+                .resolve(units)?;
+
+            if separation < 0.0 {
+                return Err(LoaderError::Other(
+                    "eye_separation must not be negative".into(),
+                ));
+            }
+
+            Projection::Stereo360 { separation }
+        }
+        Some(other) => {
+            return Err(LoaderError::Other(format!(
+                "unknown camera projection '{other}'"
+            )))
+        }
+    };
+
+    if let Some(aperture) = stub.aperture {
+        if cam.projection.is_panoramic() {
+            return Err(LoaderError::Other(
+                "aperture needs a perspective or parallel camera".into(),
+            ));
+        }
+
+        if aperture < 0.0 {
+            return Err(LoaderError::Other("aperture must not be negative".into()));
+        }
+
+        let focus = stub
+            .focus_distance
+            .as_ref()
+            .ok_or(LoaderError::KeyError("focus_distance"))?
+            .resolve(units)?;
+
+        if focus <= 0.0 {
+            return Err(LoaderError::Other("focus_distance must be positive".into()));
+        }
+
+        cam.aperture = aperture;
+        cam.focus_distance = focus;
+    }
+
+    if let Some(clip_start) = stub.clip_start {
+        if clip_start < 0.0 {
+            return Err(LoaderError::Other("clip_start must not be negative".into()));
+        }
+
+        cam.clip_start = clip_start;
+    }
+
+    if let Some(clip_near) = stub.clip_near {
+        if clip_near < 0.0 {
+            return Err(LoaderError::Other("clip_near must not be negative".into()));
+        }
+
+        cam.clip_near = clip_near;
+    }
+
+    if let Some(clip_far) = stub.clip_far {
+        if clip_far <= cam.clip_near.max(cam.clip_start) {
+            return Err(LoaderError::Other(
+                "clip_far must be greater than clip_near and clip_start".into(),
+            ));
+        }
+
+        cam.clip_far = clip_far;
+    }
+
+    if let Some(exposure) = &stub.exposure {
+        cam.exposure = match exposure.ev100 {
+            Some(ev100) => Exposure::from_ev100(ev100),
+            None => Exposure::from_settings(
+                exposure.f_number.unwrap_or(1.0),
+                exposure.shutter.unwrap_or(1.0),
+                exposure.iso.unwrap_or(100.0),
+            ),
+        };
+
+        cam.exposure.ev100 -= exposure.compensation.unwrap_or(0.0);
+    }
+
+    Ok(cam)
+}
+
+fn load_burn_in(stub: &BurnInStub) -> Result<BurnIn, LoaderError> {
+    let mut burn_in = BurnIn::default();
+
+    if let Some(text) = &stub.text {
+        burn_in.text = text.clone();
+    }
+
+    if let Some(name) = &stub.corner {
+        burn_in.corner = Corner::from_name(name)
+            .ok_or_else(|| LoaderError::Other(format!("unknown burn-in corner '{name}'")))?;
+    }
+
+    if let Some(scale) = stub.scale {
+        if scale == 0 {
+            return Err(LoaderError::Other("burn-in scale must be positive".into()));
+        }
+
+        burn_in.scale = scale;
+    }
+
+    Ok(burn_in)
+}
+
+fn load_units(stub: &UnitsStub) -> Result<Units, LoaderError> {
+    let mut units = Units::default();
+
+    if let Some(radius) = stub.schwarzschild_radius {
+        if !(radius > 0.0 && radius.is_finite()) {
+            return Err(LoaderError::Other(
+                "units schwarzschild_radius must be positive".into(),
+            ));
+        }
+
+        units.schwarzschild_radius = radius;
+    }
+
+    if let Some(mass) = stub.mass {
+        if !(mass > 0.0 && mass.is_finite()) {
+            return Err(LoaderError::Other("units mass must be positive".into()));
+        }
+
+        units.mass = Some(mass);
+    }
+
+    Ok(units)
+}
+
+fn resolve_vec3(lengths: &[Length; 3], units: Option<&Units>) -> Result<Vector3<f64>, LoaderError> {
+    let [x, y, z] = lengths;
+
+    Ok(Vector3::new(
+        x.resolve(units)?,
+        y.resolve(units)?,
+        z.resolve(units)?,
+    ))
+}
+
+impl Length {
+    fn resolve(&self, units: Option<&Units>) -> Result<f64, LoaderError> {
+        let text = match self {
+            Self::Scene(value) => return Ok(*value),
+            Self::WithUnit(text) => text,
+        };
+
+        let (value, unit) = units::parse_length(text)
+            .ok_or_else(|| LoaderError::Other(format!("invalid length '{text}'")))?;
+
+        let units = match (units, unit) {
+            (_, units::LengthUnit::Scene) => return Ok(value),
+            (Some(units), _) => units,
+            (None, _) => {
+                return Err(LoaderError::Other(format!(
+                    "length '{text}' needs the `units` section"
+                )))
+            }
+        };
+
+        units.to_scene(value, unit).ok_or_else(|| {
+            LoaderError::Other(format!("length '{text}' needs the black hole `units.mass`"))
+        })
+    }
 
-    cam
+    fn vec3(v: Vector3<f64>) -> [Self; 3] {
+        [v.x, v.y, v.z].map(Self::Scene)
+    }
 }
 
 #[derive(Debug)]
@@ -323,6 +1443,7 @@ pub enum LoaderError {
     FormatError(json5::Error),
     IndexError(String, &'static str),
     KeyError(&'static str),
+    MissingAsset(String),
     Other(String),
 }
 
@@ -335,6 +1456,9 @@ impl Display for LoaderError {
                 f.write_fmt(format_args!("no index {index} found in {kind}"))
             }
             Self::KeyError(key) => f.write_fmt(format_args!("no key '{key}' found")),
+            Self::MissingAsset(path) => f.write_fmt(format_args!(
+                "asset '{path}' not found next to scene or in search paths"
+            )),
             Self::Other(e) => f.write_fmt(format_args!("{e}")),
         }
     }
@@ -350,52 +1474,59 @@ impl Error for LoaderError {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ObjectStub {
-    shader: String,
-    shape: Map<String, Value>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct ShaderStub {
-    class: String,
-    kind: String,
-    parameters: Option<HashMap<String, ParameterValue>>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct DistortionStub {
-    center: Option<[f64; 3]>,
-    strength: Option<f64>,
-    radius: Option<f64>,
+enum ShaderType {
+    Solid,
+    Volumetric,
+    Background,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct CameraStub {
-    location: Option<[f64; 3]>,
-    rotation: Option<[f64; 3]>,
-    hor_fov: f64,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blackhole::framebuffer::FrameBuffer;
+    use blackhole::marcher::{RayMarcher, Scratch};
+
+    /// Red channel of a small render of the scene with given seed.
+    fn render_seed(seed: u64) -> Vec<f32> {
+        let path = std::env::temp_dir().join(format!(
+            "blackhole-seed-{}-{seed}.json5",
+            std::process::id()
+        ));
+        let scene = format!(
+            r#"{{
+                seed: {seed},
+                shaders: {{
+                    sky: {{ class: "SolidColorBackgroundShader", kind: "background" }},
+                    fog: {{ class: "DebugNoiseVolumeShader", kind: "volumetric", parameters: {{ noise_scale: 1.0 }} }},
+                }},
+                objects: [{{ shader: "fog", shape: {{ sphere: {{ radius: 2.0, center: [0, 0, 0] }} }} }}],
+                distortions: [],
+                background: "sky",
+                camera: {{ location: [0, 0, 6], hor_fov: 40 }},
+            }}"#
+        );
+        std::fs::write(&path, scene).unwrap();
+
+        let scene = SceneLoader::load_from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+        let scene = scene.unwrap();
+
+        let marcher = RayMarcher::default();
+        let mut scratch = Scratch::new();
+        let mut fb = FrameBuffer::new(16, 16);
+
+        for (y, row) in fb.buffer_mut().chunks_mut(16).enumerate() {
+            marcher.render_row(&scene, y, 16, 2, row, &mut scratch);
+        }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SceneFile {
-    background: String,
-    shaders: BTreeMap<String, ShaderStub>,
-    objects: Vec<ObjectStub>,
-    distortions: Vec<DistortionStub>,
-    camera: CameraStub,
-}
+        fb.buffer().iter().map(|p| p.r).collect()
+    }
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
-#[serde(untagged)]
-enum ParameterValue {
-    Vec3([f64; 3]),
-    U64(u64),
-    Float(f64),
-}
+    #[test]
+    fn same_seed_gives_same_image() {
+        blackhole::cache::set_dir(Some(blackhole::cache::temporary_dir()));
 
-enum ShaderType {
-    Solid,
-    Volumetric,
-    Background,
+        assert_eq!(render_seed(7), render_seed(7));
+        assert_ne!(render_seed(7), render_seed(8));
+    }
 }
@@ -0,0 +1,371 @@
+//! Structures of the scene file, as read by serde.
+//!
+//! Binary snapshots of scene files are keyed by the source of this module, so any change of the
+//! structures invalidates the snapshots saved by older loaders.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+
+use blackhole::cache::Cacheable;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct ObjectStub {
+    pub(super) shader: String,
+    pub(super) shape: Map<String, Value>,
+    pub(super) seed: Option<u64>,
+    /// Distance the object travels while the shutter is open
+    pub(super) motion: Option<[f64; 3]>,
+    /// Overrides of the shader parameters for this object only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) parameters: Option<HashMap<String, ParameterValue>>,
+    /// Render layer, `default` if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) layer: Option<String>,
+    /// Cells along the longest side of a distance grid the shape is baked into
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) bake: Option<u32>,
+    /// Movement over time in sequence renders, blurred over the shutter interval
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) animation: Option<ObjectTrackStub>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum ObjectTrackStub {
+    /// Distance per second
+    Linear { velocity: [Length; 3] },
+    Orbit {
+        /// Origin by default
+        center: Option<[Length; 3]>,
+        /// `[0, 1, 0]` by default
+        axis: Option<[f64; 3]>,
+        /// Seconds per revolution
+        period: f64,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct ShaderStub {
+    pub(super) class: String,
+    pub(super) kind: String,
+    pub(super) parameters: Option<HashMap<String, ParameterValue>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct DistortionStub {
+    pub(super) center: Option<[Length; 3]>,
+    /// Mass of the `units` section by default
+    pub(super) strength: Option<f64>,
+    pub(super) radius: Option<Length>,
+    pub(super) capture_shader: Option<String>,
+    pub(super) spin_axis: Option<[f64; 3]>,
+    /// Dimensionless spin from -1 to 1
+    pub(super) spin: Option<f64>,
+    /// Rays crossing it end black with zero alpha, like `"1 rs"` for a Schwarzschild black hole
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) horizon_radius: Option<Length>,
+    pub(super) with_disk: Option<DiskStub>,
+    /// Glowing shell around the photon sphere, lighting up the photon ring
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) with_photon_ring: Option<PhotonRingStub>,
+}
+
+/// Photon ring generated around a distortion, see `PhotonRingShader`.
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct PhotonRingStub {
+    /// Thickness of the shell relative to the photon sphere radius, `0.05` by default
+    pub(super) thickness: Option<f64>,
+    pub(super) temp: Option<f64>,
+    pub(super) strength: Option<f64>,
+    /// Power of the sine of the grazing angle, larger values give thinner rings
+    pub(super) sharpness: Option<f64>,
+    /// Render layer, `default` if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) layer: Option<String>,
+}
+
+/// Accretion disk generated around a distortion.
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct DiskStub {
+    pub(super) inner: Option<Length>,
+    pub(super) outer: Option<Length>,
+    pub(super) thickness: Option<Length>,
+    pub(super) temp_scale: Option<f64>,
+    /// Removes matter inside the innermost stable circular orbit of the distortion
+    pub(super) isco_cutoff: Option<bool>,
+    /// Kelvin at the inner radius of the `AccretionDiskShader` the disk glows with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) peak_temp: Option<f64>,
+    /// Density of the emitting volume at the inner radius
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) density: Option<f64>,
+    /// Power of the radius density falls off with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) density_falloff: Option<f64>,
+    /// Render layer of both disk volumes, `default` if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) layer: Option<String>,
+    /// Seconds per revolution of the disk matter around the spin axis, negative for retrograde
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) period: Option<f64>,
+    /// Turbulence of the matter in both disk volumes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) noise: Option<NoiseStub>,
+}
+
+/// Noise of generated volumes, see the `noise_*` shader parameters.
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct NoiseStub {
+    /// Multiplier of the noise frequency, larger values give finer turbulence
+    pub(super) scale: Option<f64>,
+    pub(super) octaves: Option<usize>,
+    /// Frequency multiplier between octaves
+    pub(super) lacunarity: Option<f64>,
+    /// Amplitude multiplier between octaves
+    pub(super) gain: Option<f64>,
+    /// `perlin` by default, `worley` or `curl`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) kind: Option<String>,
+    /// Cells along the side of a grid the noise is baked into
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) bake: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct CameraStub {
+    pub(super) location: Option<[Length; 3]>,
+    pub(super) rotation: Option<[f64; 3]>,
+    /// `perspective` by default, `parallel`, `equirectangular`, `fisheye` or `stereo360`
+    pub(super) projection: Option<String>,
+    /// Required by perspective and fisheye cameras
+    pub(super) hor_fov: Option<f64>,
+    /// Width of the image plane of parallel cameras
+    pub(super) plane_width: Option<f64>,
+    /// Distance between eyes of stereo 360° cameras
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) eye_separation: Option<Length>,
+    /// Lens radius for depth of field, requires `focus_distance`
+    pub(super) aperture: Option<f64>,
+    pub(super) focus_distance: Option<Length>,
+    pub(super) exposure: Option<ExposureStub>,
+    /// Distance from the camera where rays start, to look out of objects around it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) clip_start: Option<f64>,
+    /// Path length from the camera under which objects are ignored, for cutaway views
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) clip_near: Option<f64>,
+    /// Path length from the camera where rays end as background
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) clip_far: Option<f64>,
+    /// Places the camera to fit a distortion and its disk into the image width. Location only
+    /// sets the direction the distortion is seen from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) frame: Option<FrameStub>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct FrameStub {
+    /// Index of the framed distortion, the first one by default
+    pub(super) distortion: Option<usize>,
+    /// Space around the framed disk relative to its radius, 0.1 by default
+    pub(super) margin: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct ExposureStub {
+    pub(super) ev100: Option<f64>,
+    pub(super) f_number: Option<f64>,
+    pub(super) shutter: Option<f64>,
+    pub(super) iso: Option<f64>,
+    /// Stops of brightening on top of the exposure value or camera settings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) compensation: Option<f64>,
+    /// Treats emission as radiance in W·sr⁻¹·m⁻² exposed like a camera sensor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) physical: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct SceneFile {
+    pub(super) background: String,
+    pub(super) shaders: BTreeMap<String, ShaderStub>,
+    pub(super) objects: Vec<ObjectStub>,
+    pub(super) distortions: Vec<DistortionStub>,
+    pub(super) camera: CameraStub,
+    /// Directories to look for assets in, relative to the scene file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) search_paths: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) animation: Option<AnimationStub>,
+    /// `reinhard` by default, `aces`, `uncharted2` or `linear`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) tonemap: Option<String>,
+    /// Plane cutting away objects on the side its normal points to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) slice: Option<SliceStub>,
+    /// Seed of procedural shaders which don't set their own `seed` parameter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) seed: Option<u64>,
+    /// Quality settings, command line flags take precedence
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) render: Option<RenderStub>,
+    /// Scale for lengths given with units
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) units: Option<UnitsStub>,
+    /// Text drawn over saved images
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) burn_in: Option<BurnInStub>,
+}
+
+impl Cacheable for SceneFile {
+    fn write_to(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        ciborium::into_writer(self, w).map_err(std::io::Error::other)
+    }
+
+    fn read_from(r: &mut dyn Read) -> std::io::Result<Self> {
+        ciborium::from_reader(r).map_err(std::io::Error::other)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct BurnInStub {
+    /// Template with placeholders, see [`BurnIn::text`]
+    pub(super) text: Option<String>,
+    /// `bottom_left` by default, `bottom_right`, `top_left` or `top_right`
+    pub(super) corner: Option<String>,
+    /// Image pixels per font pixel, 2 by default
+    pub(super) scale: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct UnitsStub {
+    /// Scene units per Schwarzschild radius of the black hole, 1 by default
+    pub(super) schwarzschild_radius: Option<f64>,
+    /// Mass of the black hole in solar masses, needed for lengths in `m`, `km`, `au` and `ly`
+    pub(super) mass: Option<f64>,
+}
+
+/// Length in scene units, or a string with a unit like `"6 rs"` or `"0.5 au"`, which needs the
+/// `units` section of the scene. Units are `M` or `rg`, `rs`, `m`, `km`, `au` and `ly`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(super) enum Length {
+    Scene(f64),
+    WithUnit(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct RenderStub {
+    pub(super) samples: Option<usize>,
+    pub(super) max_steps: Option<usize>,
+    /// Maximum ray bounces
+    pub(super) max_depth: Option<usize>,
+    /// Longest step inside volumes, 0.01 by default
+    pub(super) volume_step: Option<f64>,
+    /// Shortest step towards volumes, 0.002 by default
+    pub(super) volume_min_step: Option<f64>,
+    /// Longest step inside distortions, 0.1 by default
+    pub(super) distortion_step: Option<f64>,
+    /// Render with lower priority, false by default
+    pub(super) background: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct SliceStub {
+    pub(super) point: [f64; 3],
+    pub(super) normal: [f64; 3],
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct AnimationStub {
+    /// 24 by default
+    pub(super) fps: Option<f64>,
+    pub(super) camera: Option<CameraTrackStub>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) sky: Option<SkyRotationStub>,
+    /// Blurs the animated camera over this part of every frame
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) shutter: Option<ShutterStub>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum CameraTrackStub {
+    Keyframes(Vec<CameraKeyframeStub>),
+    Orbit(OrbitStub),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct CameraKeyframeStub {
+    /// Seconds from the start of the animation
+    pub(super) time: f64,
+    pub(super) location: [Length; 3],
+    pub(super) rotation: Option<[f64; 3]>,
+    /// Point to look at, used instead of `rotation`
+    pub(super) look_at: Option<[f64; 3]>,
+    pub(super) hor_fov: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct OrbitStub {
+    pub(super) center: Option<[Length; 3]>,
+    pub(super) radius: Length,
+    pub(super) height: Option<Length>,
+    /// Seconds per revolution
+    pub(super) period: f64,
+    /// Degrees
+    pub(super) start_angle: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct SkyRotationStub {
+    /// `[0, 1, 0]` by default
+    pub(super) pole: Option<[f64; 3]>,
+    /// Degrees per second
+    pub(super) rate: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct ShutterStub {
+    /// Frames from the frame time
+    pub(super) open: f64,
+    pub(super) close: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub(super) enum ParameterValue {
+    Vec3([f64; 3]),
+    U64(u64),
+    Float(f64),
+    /// Asset path, relative to the scene file or search paths
+    Text(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_keep_scene_files() {
+        for entry in std::fs::read_dir("../scenes").unwrap() {
+            let path = entry.unwrap().path();
+            let text = std::fs::read_to_string(&path).unwrap();
+            let json: SceneFile = json5::from_str(&text).unwrap();
+
+            let mut snapshot = Vec::new();
+            json.write_to(&mut snapshot).unwrap();
+            let read = SceneFile::read_from(&mut snapshot.as_slice()).unwrap();
+
+            assert_eq!(
+                serde_json::to_value(&read).unwrap(),
+                serde_json::to_value(&json).unwrap(),
+                "{}",
+                path.display()
+            );
+        }
+    }
+}
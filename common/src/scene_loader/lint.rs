@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use cgmath::Vector3;
+
+use blackhole::object::Shading;
+use blackhole::scene::Scene;
+
+use super::SceneFile;
+
+/// Distance from the camera under which volumes outside of distortions are reported.
+const NEAR_CAMERA: f64 = 1.0;
+
+/// Likely mistake in a scene description, which still loads and renders.
+#[derive(Debug, Clone)]
+pub struct Lint {
+    /// Path to the offending item in the scene file, like `objects[2]`
+    pub location: String,
+    pub message: String,
+    pub suggestion: String,
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}: {} ({})",
+            self.location, self.message, self.suggestion
+        ))
+    }
+}
+
+/// Checks loaded scene against the file it was loaded from.
+///
+/// Objects generated by the loader, like accretion disks, come after the objects of the file, so
+/// indices of `scene.objects` and `file.objects` match for the checked objects.
+pub(super) fn lint(file: &SceneFile, scene: &Scene) -> Vec<Lint> {
+    let mut lints = Vec::new();
+
+    unused_shaders(file, &mut lints);
+    duplicate_objects(file, &mut lints);
+    camera_inside_solid(file, scene, &mut lints);
+    volumes_near_camera(file, scene, &mut lints);
+    objects_out_of_reach(file, scene, &mut lints);
+
+    lints
+}
+
+fn unused_shaders(file: &SceneFile, lints: &mut Vec<Lint>) {
+    let mut used = HashSet::new();
+
+    used.insert(file.background.as_str());
+    used.extend(file.objects.iter().map(|o| o.shader.as_str()));
+    used.extend(
+        file.distortions
+            .iter()
+            .filter_map(|d| d.capture_shader.as_deref()),
+    );
+
+    for name in file.shaders.keys() {
+        if !used.contains(name.as_str()) {
+            lints.push(Lint {
+                location: format!("shaders.{name}"),
+                message: "shader is never used".into(),
+                suggestion: "remove it or assign it to an object".into(),
+            });
+        }
+    }
+}
+
+fn duplicate_objects(file: &SceneFile, lints: &mut Vec<Lint>) {
+    for (i, a) in file.objects.iter().enumerate() {
+        let original = file.objects[..i].iter().position(|b| {
+            a.shader == b.shader
+                && a.shape == b.shape
+                && a.motion == b.motion
+                && a.animation == b.animation
+                && a.parameters == b.parameters
+        });
+
+        if let Some(original) = original {
+            lints.push(Lint {
+                location: format!("objects[{i}]"),
+                message: format!("object is identical to objects[{original}]"),
+                suggestion: "remove one of them".into(),
+            });
+        }
+    }
+}
+
+fn camera_inside_solid(file: &SceneFile, scene: &Scene, lints: &mut Vec<Lint>) {
+    let location = scene.camera.location;
+    // objects cut by the near plane show their inside, which is fine for cutaways
+    let clip_start = scene.camera.clip_start.max(scene.camera.clip_near);
+
+    for (i, object) in scene.objects.iter().enumerate().take(file.objects.len()) {
+        if !matches!(object.shading, Shading::Solid(_)) {
+            continue;
+        }
+
+        let dist = object.shape.dist_fn(location);
+
+        // rays can leave the object before the clip start only if it's this close
+        if dist + clip_start < 0.0 {
+            lints.push(Lint {
+                location: "camera.location".into(),
+                message: format!(
+                    "camera at {} is {:.3} units inside solid objects[{i}], \
+                     only its emission will be visible",
+                    fmt_vec3(location),
+                    -dist
+                ),
+                suggestion: format!(
+                    "move the camera outside, or set camera.clip_start to at least {:.3}",
+                    -dist
+                ),
+            });
+        }
+    }
+}
+
+fn volumes_near_camera(file: &SceneFile, scene: &Scene, lints: &mut Vec<Lint>) {
+    let location = scene.camera.location;
+
+    for (i, object) in scene.objects.iter().enumerate().take(file.objects.len()) {
+        if !matches!(object.shading, Shading::Volumetric(_)) {
+            continue;
+        }
+
+        let dist = object.shape.dist_fn(location);
+
+        if dist > NEAR_CAMERA {
+            continue;
+        }
+
+        let bb = object.shape.bounding_box();
+        let distorted = scene
+            .distortions
+            .iter()
+            .any(|d| bb.distance(d.shape.center()) < d.shape.radius());
+
+        if !distorted {
+            lints.push(Lint {
+                location: format!("objects[{i}]"),
+                message: format!(
+                    "volume {:.3} units from the camera does not reach any distortion, \
+                     rays pass through it unbent in short steps",
+                    dist.max(0.0)
+                ),
+                suggestion: "move it into a distortion or away from the camera".into(),
+            });
+        }
+    }
+}
+
+fn objects_out_of_reach(file: &SceneFile, scene: &Scene, lints: &mut Vec<Lint>) {
+    let location = scene.camera.location;
+    let escape = scene.max_possible_step(location);
+
+    for (i, object) in scene.objects.iter().enumerate().take(file.objects.len()) {
+        let dist = object.shape.bounding_box().distance(location);
+
+        if dist > escape {
+            lints.push(Lint {
+                location: format!("objects[{i}]"),
+                message: format!(
+                    "object is {dist:.3} units from the camera, rays escape after {escape:.3}"
+                ),
+                suggestion: "move it closer to the rest of the scene".into(),
+            });
+        }
+    }
+}
+
+fn fmt_vec3(v: Vector3<f64>) -> String {
+    format!("[{}, {}, {}]", v.x, v.y, v.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene_loader::SceneLoader;
+
+    #[test]
+    fn mistakes_are_reported() {
+        blackhole::cache::set_dir(Some(blackhole::cache::temporary_dir()));
+
+        let path =
+            std::env::temp_dir().join(format!("blackhole-lint-{}.json5", std::process::id()));
+        let scene = r#"{
+            shaders: {
+                sky: { class: "SolidColorBackgroundShader", kind: "background" },
+                solid: { class: "BasicSolidShader", kind: "solid" },
+                unused: { class: "BasicSolidShader", kind: "solid" },
+            },
+            objects: [
+                { shader: "solid", shape: { sphere: { radius: 2.0, center: [0, 0, 0] } } },
+                { shader: "solid", shape: { sphere: { radius: 2.0, center: [0, 0, 0] } } },
+            ],
+            distortions: [],
+            background: "sky",
+            camera: { location: [0, 0, 1], hor_fov: 40 },
+        }"#;
+        std::fs::write(&path, scene).unwrap();
+
+        let lints = SceneLoader::load_with_lints(&path).map(|(_, lints)| lints);
+        std::fs::remove_file(&path).unwrap();
+
+        let lints = lints.unwrap();
+        let locations: Vec<_> = lints.iter().map(|l| l.location.as_str()).collect();
+
+        assert_eq!(
+            locations,
+            [
+                "shaders.unused",
+                "objects[1]",
+                "camera.location",
+                "camera.location"
+            ]
+        );
+        assert!(lints[1].message.contains("objects[0]"));
+    }
+}
@@ -0,0 +1,133 @@
+use std::path::Path;
+
+use blackhole::camera::{Camera, Projection};
+use blackhole::object::Distortion;
+use blackhole::postprocess::Tonemap;
+use blackhole::scene::Scene;
+
+use super::{read_scene_file, CameraStub, ExposureStub, Length, LoaderError, SceneFile};
+
+/// Writes scenes back to the scene file format.
+///
+/// Shaders and object shapes can't be read back from a loaded [`Scene`], so the writer starts
+/// from the file the scene was loaded from and replaces the parts which can. Comments and
+/// formatting of the original file are not kept, asset paths are written unchanged, so the result
+/// should be saved next to the original.
+pub struct SceneWriter {
+    file: SceneFile,
+}
+
+impl SceneWriter {
+    /// Starts from the scene file at given path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, LoaderError> {
+        Ok(Self {
+            file: read_scene_file(path)?,
+        })
+    }
+
+    /// Replaces camera, distortions, tonemapping and exposure with the ones of the scene.
+    ///
+    /// Distortions are matched to the file by order, the scene must come from the same file.
+    pub fn with_scene(self, scene: &Scene) -> Result<Self, LoaderError> {
+        self.with_camera(&scene.camera)
+            .with_distortions(&scene.distortions)
+            .map(|w| w.with_tonemap(scene.tonemap))
+            .map(|w| w.with_physical_exposure(scene.physical_exposure))
+    }
+
+    /// Replaces the camera, keeping whether the file exposes it like a camera sensor.
+    pub fn with_camera(mut self, camera: &Camera) -> Self {
+        let physical = self.file.camera.exposure.as_ref().and_then(|e| e.physical);
+        let (projection, hor_fov, plane_width, eye_separation) = match camera.projection {
+            Projection::Perspective => (None, Some(camera.hor_fov), None, None),
+            Projection::Parallel { width } => (Some("parallel"), None, Some(width), None),
+            Projection::Equirectangular => (Some("equirectangular"), None, None, None),
+            Projection::Fisheye => (Some("fisheye"), Some(camera.hor_fov), None, None),
+            Projection::Stereo360 { separation } => (
+                Some("stereo360"),
+                None,
+                None,
+                Some(Length::Scene(separation)),
+            ),
+        };
+
+        let (aperture, focus_distance) = if camera.aperture > 0.0 {
+            (
+                Some(camera.aperture),
+                Some(Length::Scene(camera.focus_distance)),
+            )
+        } else {
+            (None, None)
+        };
+
+        self.file.camera = CameraStub {
+            location: Some(Length::vec3(camera.location)),
+            rotation: Some(camera.rotation().into()),
+            projection: projection.map(String::from),
+            hor_fov,
+            plane_width,
+            eye_separation,
+            aperture,
+            focus_distance,
+            exposure: Some(ExposureStub {
+                ev100: Some(camera.exposure.ev100),
+                f_number: None,
+                shutter: None,
+                iso: None,
+                compensation: None,
+                physical,
+            }),
+            clip_start: (camera.clip_start > 0.0).then_some(camera.clip_start),
+            clip_near: (camera.clip_near > 0.0).then_some(camera.clip_near),
+            clip_far: camera.clip_far.is_finite().then_some(camera.clip_far),
+            frame: None,
+        };
+
+        self
+    }
+
+    /// Replaces placement, strength and spin of distortions, shaders and disks are kept.
+    pub fn with_distortions(mut self, distortions: &[Distortion]) -> Result<Self, LoaderError> {
+        if distortions.len() != self.file.distortions.len() {
+            return Err(LoaderError::Other(format!(
+                "scene has {} distortions, but the file has {}",
+                distortions.len(),
+                self.file.distortions.len()
+            )));
+        }
+
+        for (stub, distortion) in self.file.distortions.iter_mut().zip(distortions) {
+            stub.center = Some(Length::vec3(distortion.shape.center()));
+            stub.radius = Some(Length::Scene(distortion.shape.radius()));
+            stub.strength = Some(distortion.strength);
+            stub.spin_axis = Some(distortion.spin_axis.into());
+            stub.spin = Some(distortion.spin);
+        }
+
+        Ok(self)
+    }
+
+    pub fn with_tonemap(mut self, tonemap: Tonemap) -> Self {
+        self.file.tonemap = Some(tonemap.name().into());
+
+        self
+    }
+
+    pub fn with_physical_exposure(mut self, physical: bool) -> Self {
+        if let Some(exposure) = &mut self.file.camera.exposure {
+            exposure.physical = physical.then_some(true);
+        }
+
+        self
+    }
+
+    /// Serializes the scene as JSON, which is also valid JSON5.
+    pub fn to_json(&self) -> Result<String, LoaderError> {
+        serde_json::to_string_pretty(&self.file)
+            .map_err(|e| LoaderError::Other(format!("could not write scene: {e}")))
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), LoaderError> {
+        std::fs::write(path, self.to_json()?).map_err(LoaderError::InputError)
+    }
+}
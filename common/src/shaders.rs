@@ -1,26 +1,110 @@
-use cgmath::{Array, ElementWise, InnerSpace, Matrix3, Rad, Vector3, Zero};
+use cgmath::{Array, InnerSpace, Vector3, Zero};
 
 use blackhole::material::MaterialResult;
-use blackhole::math::{rand_unit, rand_unit_vector, sigmoid};
-use blackhole::shader::{BackgroundShader, Parameter, Shader, VolumetricShader};
-use blackhole::texture::{NoiseTexture3D, Texture3D};
+use blackhole::math::{rand_unit, rand_unit_vector};
+use blackhole::shader::{BackgroundShader, Parameter, ShadeContext, Shader, VolumetricShader};
+use blackhole::texture::{Fbm, NoiseTexture3D, Texture3D, TextureKind};
 use blackhole::BLACKBODY_LUT;
 use blackhole::{Ray, RayKind};
 
+mod accretion_disk;
 mod basic_solid;
+mod bump;
+mod disk;
+mod hdri;
+mod image;
+mod photon_ring;
 mod star_sky;
+mod textured_solid;
+mod turbulent_disk;
 
+pub use accretion_disk::AccretionDiskShader;
 pub use basic_solid::BasicSolidShader;
+pub use hdri::HdriBackgroundShader;
+pub use photon_ring::PhotonRingShader;
 pub use star_sky::StarSkyShader;
+pub use textured_solid::TexturedSolidShader;
+pub use turbulent_disk::TurbulentDiskShader;
 
+use disk::{
+    set_swirl_noise_parameter, swirl_coords, swirl_density, swirl_temperature, DiskFrame,
+    SWIRL_THICKNESS,
+};
+
+/// Phase function of scattering into uniformly random directions.
+const ISOTROPIC_PHASE: f64 = 1.0 / (4.0 * std::f64::consts::PI);
+
+/// Ratio of emission strengths, if the old one is not zero.
+fn strength_ratio(old: f64, new: f64) -> Option<Vector3<f64>> {
+    if old == 0.0 {
+        return None;
+    }
+
+    Some(Vector3::from_value(new / old))
+}
+
+/// Number of a parameter, floats written as whole numbers in scene files arrive as `Usize`.
+fn float_parameter(value: &Parameter) -> Option<f64> {
+    match value {
+        Parameter::Float(f) => Some(*f),
+        Parameter::Usize(u) => Some(*u as f64),
+        _ => None,
+    }
+}
+
+/// Handles parameters of the noise shaping a shader, returns `false` for other parameters.
+///
+/// Numbers are accepted as both floats and whole numbers, see [`float_parameter`].
+/// `noise_kind` switches between the built-in noises, see [`TextureKind::noise`].
+fn set_noise_parameter(noise: &mut TextureKind, name: &str, value: &Parameter) -> bool {
+    let float = float_parameter(value);
+    let fbm = noise.fbm();
+
+    match (name, value, float) {
+        ("seed", Parameter::Usize(s), _) => *noise = noise.with_seed(*s as u64),
+        ("noise_scale", _, Some(s)) if s > 0.0 => *noise = noise.with_scale(s),
+        ("noise_octaves", Parameter::Usize(o), _) => {
+            let octaves = (*o).clamp(1, 16) as u8;
+
+            *noise = noise.with_fbm(Fbm { octaves, ..fbm });
+        }
+        ("noise_lacunarity", _, Some(l)) if l > 0.0 => {
+            *noise = noise.with_fbm(Fbm {
+                lacunarity: l,
+                ..fbm
+            });
+        }
+        ("noise_gain", _, Some(g)) => *noise = noise.with_fbm(Fbm { gain: g, ..fbm }),
+        ("noise_kind", Parameter::Text(kind), _) => {
+            match TextureKind::noise(kind, noise.scale(), noise.seed(), fbm) {
+                Some(kind) => *noise = kind,
+                None => eprintln!("unknown noise kind '{kind}'"),
+            }
+        }
+        _ => return false,
+    }
+
+    true
+}
+
+#[derive(Clone)]
 pub struct BlackHoleEmitterShader {
-    noise: NoiseTexture3D,
+    noise: TextureKind,
+    strength: f64,
+    temp_scale: f64,
+    disk: DiskFrame,
 }
 
 impl BlackHoleEmitterShader {
+    /// Default of the `noise_scale` parameter.
+    pub(crate) const NOISE_SCALE: f64 = 10.0;
+
     pub fn new() -> Self {
         Self {
-            noise: NoiseTexture3D::new(10.0, 0, 1),
+            noise: NoiseTexture3D::new(Self::NOISE_SCALE, 0, 1).into(),
+            strength: 5.0,
+            temp_scale: 1.0,
+            disk: DiskFrame::new(SWIRL_THICKNESS),
         }
     }
 }
@@ -31,59 +115,73 @@ impl Default for BlackHoleEmitterShader {
     }
 }
 
-impl Shader for BlackHoleEmitterShader {}
-
-impl VolumetricShader for BlackHoleEmitterShader {
-    fn density_at(&self, position: Vector3<f64>) -> f64 {
-        let mag = position.magnitude();
-        let noise_coords = {
-            let norm = position.normalize();
-
-            let norm_rot = Matrix3::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), Rad(mag)) * norm;
-
-            let coords = Vector3::new(norm_rot.x, norm_rot.z, mag);
-
-            coords.mul_element_wise(Vector3::new(1.0, 1.0, 0.1))
-        };
+impl Shader for BlackHoleEmitterShader {
+    fn set_parameter(&mut self, name: &str, value: Parameter) {
+        if self.disk.set_parameter(name, &value)
+            || set_swirl_noise_parameter(&mut self.noise, name, &value)
+        {
+            return;
+        }
 
-        let len_factor = (-(2.0 / 5.0) * mag + 2.0).min(20.0 * mag - 20.0);
+        match (name, value) {
+            ("strength", Parameter::Float(f)) => self.strength = f,
+            ("temp_scale", Parameter::Float(f)) => self.temp_scale = f,
+            _ => {}
+        }
+    }
 
-        let noise_factor = self.noise.color_at(noise_coords) * len_factor;
+    fn emits_light(&self) -> bool {
+        self.strength > 0.0
+    }
 
-        let noise_factor = sigmoid(noise_factor, 30.0, 0.52);
+    fn parameter(&self, name: &str) -> Option<Parameter> {
+        match name {
+            "strength" => Some(Parameter::Float(self.strength)),
+            _ => None,
+        }
+    }
 
-        (0.02 - position.y.abs()) * 100.0 * (4.0 - position.xz().magnitude()) * noise_factor
+    fn emission_scale(&self, name: &str, value: &Parameter) -> Option<Vector3<f64>> {
+        match (name, value) {
+            ("strength", Parameter::Float(f)) => strength_ratio(self.strength, *f),
+            _ => None,
+        }
     }
+}
 
-    fn material_at(&self, ray: &Ray) -> (MaterialResult, Option<Ray>) {
-        let mag = ray.location.magnitude();
-        let noise_coords = {
-            let norm = ray.location.normalize();
+impl VolumetricShader for BlackHoleEmitterShader {
+    fn density_at(&self, position: Vector3<f64>, ctx: &ShadeContext) -> f64 {
+        let position = self.disk.local(position);
 
-            let norm_rot = Matrix3::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), Rad(mag)) * norm;
+        if self.disk.is_cut_off(position) {
+            return 0.0;
+        }
 
-            let coords = Vector3::new(norm_rot.x, norm_rot.z, mag);
+        let noise = self
+            .noise
+            .color_at(swirl_coords(position) + ctx.texture_offset());
 
-            coords.mul_element_wise(Vector3::new(1.0, 1.0, 0.1))
-        };
+        swirl_density(position, noise, self.disk.inner())
+    }
 
-        let noise_factor = self.noise.color_at(noise_coords) * 0.5 + 0.75;
+    fn material_at(&self, ray: &Ray, ctx: &ShadeContext) -> (MaterialResult, Option<Ray>) {
+        let location = self.disk.local(ray.location);
+        let noise = self
+            .noise
+            .color_at(swirl_coords(location) + ctx.texture_offset());
 
-        let temp = (0.02 - ray.location.y.abs())
-            * 50.0
-            * 2000.0
-            * (4.0 - ray.location.xz().magnitude())
-            * noise_factor;
+        let temp = swirl_temperature(location, noise) * self.temp_scale;
 
         let mat = MaterialResult {
             albedo: Vector3::zero(),
-            emission: BLACKBODY_LUT.lookup(temp) * 5.0,
+            emission: BLACKBODY_LUT.lookup(temp) * self.strength,
         };
 
         (mat, None)
     }
 }
 
+#[derive(Clone)]
 pub struct VolumeEmitterShader {
     temp: f64,
     density: f64,
@@ -115,14 +213,32 @@ impl Shader for VolumeEmitterShader {
             _ => {}
         }
     }
+
+    fn emits_light(&self) -> bool {
+        self.strength > 0.0
+    }
+
+    fn parameter(&self, name: &str) -> Option<Parameter> {
+        match name {
+            "strength" => Some(Parameter::Float(self.strength)),
+            _ => None,
+        }
+    }
+
+    fn emission_scale(&self, name: &str, value: &Parameter) -> Option<Vector3<f64>> {
+        match (name, value) {
+            ("strength", Parameter::Float(f)) => strength_ratio(self.strength, *f),
+            _ => None,
+        }
+    }
 }
 
 impl VolumetricShader for VolumeEmitterShader {
-    fn density_at(&self, _position: Vector3<f64>) -> f64 {
+    fn density_at(&self, _position: Vector3<f64>, _ctx: &ShadeContext) -> f64 {
         self.density
     }
 
-    fn material_at(&self, _ray: &Ray) -> (MaterialResult, Option<Ray>) {
+    fn material_at(&self, _ray: &Ray, _ctx: &ShadeContext) -> (MaterialResult, Option<Ray>) {
         let mat = MaterialResult {
             albedo: Vector3::zero(),
             emission: BLACKBODY_LUT.lookup(self.temp) * self.strength,
@@ -132,6 +248,7 @@ impl VolumetricShader for VolumeEmitterShader {
     }
 }
 
+#[derive(Clone)]
 pub struct SolidColorVolumeShader {
     albedo: Vector3<f64>,
     density: f64,
@@ -163,16 +280,20 @@ impl Shader for SolidColorVolumeShader {
 }
 
 impl VolumetricShader for SolidColorVolumeShader {
-    fn density_at(&self, _position: Vector3<f64>) -> f64 {
+    fn density_at(&self, _position: Vector3<f64>, _ctx: &ShadeContext) -> f64 {
         self.density
     }
 
-    fn material_at(&self, ray: &Ray) -> (MaterialResult, Option<Ray>) {
+    fn material_at(&self, ray: &Ray, ctx: &ShadeContext) -> (MaterialResult, Option<Ray>) {
         let mat = MaterialResult {
             albedo: self.albedo,
             emission: Vector3::zero(),
         };
 
+        if ctx.deterministic {
+            return (mat, None);
+        }
+
         let dir = rand_unit_vector();
 
         let ray = Ray {
@@ -183,8 +304,13 @@ impl VolumetricShader for SolidColorVolumeShader {
 
         (mat, Some(ray))
     }
+
+    fn phase(&self, _ray: &Ray, _scattered: &Ray, _direction: Vector3<f64>) -> Option<f64> {
+        Some(ISOTROPIC_PHASE)
+    }
 }
 
+#[derive(Clone)]
 pub struct SolidColorVolumeAbsorbShader {
     absorption: Vector3<f64>,
     density: f64,
@@ -216,11 +342,11 @@ impl Shader for SolidColorVolumeAbsorbShader {
 }
 
 impl VolumetricShader for SolidColorVolumeAbsorbShader {
-    fn density_at(&self, _position: Vector3<f64>) -> f64 {
+    fn density_at(&self, _position: Vector3<f64>, _ctx: &ShadeContext) -> f64 {
         self.density
     }
 
-    fn material_at(&self, ray: &Ray) -> (MaterialResult, Option<Ray>) {
+    fn material_at(&self, ray: &Ray, _ctx: &ShadeContext) -> (MaterialResult, Option<Ray>) {
         let mat = MaterialResult {
             albedo: self.absorption,
             emission: Vector3::zero(),
@@ -236,6 +362,7 @@ impl VolumetricShader for SolidColorVolumeAbsorbShader {
     }
 }
 
+#[derive(Clone)]
 pub struct SolidColorVolumeScatterShader {
     scatter: Vector3<f64>,
     absorption: Vector3<f64>,
@@ -270,11 +397,21 @@ impl Shader for SolidColorVolumeScatterShader {
 }
 
 impl VolumetricShader for SolidColorVolumeScatterShader {
-    fn density_at(&self, _position: Vector3<f64>) -> f64 {
+    fn density_at(&self, _position: Vector3<f64>, _ctx: &ShadeContext) -> f64 {
         self.density
     }
 
-    fn material_at(&self, ray: &Ray) -> (MaterialResult, Option<Ray>) {
+    fn material_at(&self, ray: &Ray, ctx: &ShadeContext) -> (MaterialResult, Option<Ray>) {
+        // half of the rays pass through, the other half scatters
+        if ctx.deterministic {
+            let mat = MaterialResult {
+                albedo: (self.absorption + self.scatter) / 2.0,
+                emission: Vector3::zero(),
+            };
+
+            return (mat, None);
+        }
+
         let rand = rand_unit();
 
         //let color_sum = self.scatter.sum() / 3.0;
@@ -307,16 +444,27 @@ impl VolumetricShader for SolidColorVolumeScatterShader {
             (mat, Some(ray))
         }
     }
+
+    fn phase(&self, ray: &Ray, scattered: &Ray, _direction: Vector3<f64>) -> Option<f64> {
+        // rays passing through keep their direction
+        (scattered.direction != ray.direction).then_some(ISOTROPIC_PHASE)
+    }
 }
 
+#[derive(Clone)]
 pub struct BlackHoleScatterShader {
-    noise: NoiseTexture3D,
+    noise: TextureKind,
+    disk: DiskFrame,
 }
 
 impl BlackHoleScatterShader {
+    /// Default of the `noise_scale` parameter.
+    pub(crate) const NOISE_SCALE: f64 = 5.0;
+
     pub fn new() -> Self {
         Self {
-            noise: NoiseTexture3D::new(5.0, 0, 1),
+            noise: NoiseTexture3D::new(Self::NOISE_SCALE, 0, 1).into(),
+            disk: DiskFrame::new(0.06),
         }
     }
 }
@@ -327,34 +475,42 @@ impl Default for BlackHoleScatterShader {
     }
 }
 
-impl Shader for BlackHoleScatterShader {}
+impl Shader for BlackHoleScatterShader {
+    fn set_parameter(&mut self, name: &str, value: Parameter) {
+        if !self.disk.set_parameter(name, &value) {
+            set_swirl_noise_parameter(&mut self.noise, name, &value);
+        }
+    }
+}
 
 impl VolumetricShader for BlackHoleScatterShader {
-    fn density_at(&self, position: Vector3<f64>) -> f64 {
-        let mag = position.magnitude();
-        let noise_coords = {
-            let norm = position.normalize();
+    fn density_at(&self, position: Vector3<f64>, ctx: &ShadeContext) -> f64 {
+        let position = self.disk.local(position);
 
-            let norm_rot = Matrix3::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), Rad(mag)) * norm;
-
-            let coords = Vector3::new(norm_rot.x, norm_rot.z, mag);
+        if self.disk.is_cut_off(position) {
+            return 0.0;
+        }
 
-            coords.mul_element_wise(Vector3::new(1.0, 1.0, 0.1))
-        };
+        let mag = position.magnitude();
+        let noise_coords = swirl_coords(position);
 
         let dist_factor = -0.09 * mag.powi(3) + 0.12 * mag.powi(2) + 0.97 * mag - 0.8;
 
-        let noise_factor = 1.0 - self.noise.color_at(noise_coords);
+        let noise_factor = 1.0 - self.noise.color_at(noise_coords + ctx.texture_offset());
 
         (0.06 - position.y.abs()) * 100.0 * noise_factor * dist_factor
     }
 
-    fn material_at(&self, ray: &Ray) -> (MaterialResult, Option<Ray>) {
+    fn material_at(&self, ray: &Ray, ctx: &ShadeContext) -> (MaterialResult, Option<Ray>) {
         let mat = MaterialResult {
             albedo: Vector3::new(0.6, 0.6, 0.6),
             emission: Vector3::zero(),
         };
 
+        if ctx.deterministic {
+            return (mat, None);
+        }
+
         let dir = rand_unit_vector();
 
         let ray = Ray {
@@ -365,16 +521,21 @@ impl VolumetricShader for BlackHoleScatterShader {
 
         (mat, Some(ray))
     }
+
+    fn phase(&self, _ray: &Ray, _scattered: &Ray, _direction: Vector3<f64>) -> Option<f64> {
+        Some(ISOTROPIC_PHASE)
+    }
 }
 
+#[derive(Clone)]
 pub struct DebugNoiseVolumeShader {
-    noise: NoiseTexture3D,
+    noise: TextureKind,
 }
 
 impl DebugNoiseVolumeShader {
     pub fn new() -> Self {
         Self {
-            noise: NoiseTexture3D::new(10.0, 0, 1),
+            noise: NoiseTexture3D::new(10.0, 0, 1).into(),
         }
     }
 }
@@ -385,19 +546,30 @@ impl Default for DebugNoiseVolumeShader {
     }
 }
 
-impl Shader for DebugNoiseVolumeShader {}
+impl Shader for DebugNoiseVolumeShader {
+    fn set_parameter(&mut self, name: &str, value: Parameter) {
+        set_noise_parameter(&mut self.noise, name, &value);
+    }
+}
 
 impl VolumetricShader for DebugNoiseVolumeShader {
-    fn density_at(&self, position: Vector3<f64>) -> f64 {
-        self.noise.color_at(position).powf(8.0) * 1000.0
+    fn density_at(&self, position: Vector3<f64>, ctx: &ShadeContext) -> f64 {
+        self.noise
+            .color_at(position + ctx.texture_offset())
+            .powf(8.0)
+            * 1000.0
     }
 
-    fn material_at(&self, ray: &Ray) -> (MaterialResult, Option<Ray>) {
+    fn material_at(&self, ray: &Ray, ctx: &ShadeContext) -> (MaterialResult, Option<Ray>) {
         let mat = MaterialResult {
             albedo: Vector3::new(0.9, 0.2, 0.1),
             emission: Vector3::zero(),
         };
 
+        if ctx.deterministic {
+            return (mat, None);
+        }
+
         let dir = rand_unit_vector();
 
         let ray = Ray {
@@ -408,8 +580,13 @@ impl VolumetricShader for DebugNoiseVolumeShader {
 
         (mat, Some(ray))
     }
+
+    fn phase(&self, _ray: &Ray, _scattered: &Ray, _direction: Vector3<f64>) -> Option<f64> {
+        Some(ISOTROPIC_PHASE)
+    }
 }
 
+#[derive(Clone)]
 pub struct SolidColorBackgroundShader {
     color: Vector3<f64>,
 }
@@ -443,6 +620,7 @@ impl BackgroundShader for SolidColorBackgroundShader {
     }
 }
 
+#[derive(Clone)]
 pub struct DebugBackgroundShader;
 
 impl Default for DebugBackgroundShader {
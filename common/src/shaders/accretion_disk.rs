@@ -0,0 +1,172 @@
+use cgmath::{InnerSpace, Vector3, Zero};
+
+use blackhole::material::MaterialResult;
+use blackhole::shader::{Parameter, ShadeContext, Shader, VolumetricShader};
+use blackhole::texture::{NoiseTexture3D, Texture3D, TextureKind};
+use blackhole::{Ray, BLACKBODY_LUT};
+
+use super::disk::{set_swirl_noise_parameter, swirl_coords, DiskFrame, BASE_OUTER};
+use super::strength_ratio;
+
+/// Half-thickness of the disk in the space of the default disk.
+const BASE_THICKNESS: f64 = 0.02;
+
+/// Width of the fade at the outer edge, relative to the outer radius.
+const EDGE_FADE: f64 = 0.1;
+
+/// Emitting accretion disk with the temperature of a thin disk, `T ∝ r^(-3/4)`.
+///
+/// The disk is hottest at the inner radius with `peak_temp` Kelvin. Every part glows with the
+/// color of its temperature and brightness scaled by `T^4`, so the outer parts are dim and red.
+/// Density falls off with radius by power `falloff` and tapers towards the disk surfaces, noise
+/// breaks it into clouds by `turbulence` between 0 and 1.
+#[derive(Clone)]
+pub struct AccretionDiskShader {
+    noise: TextureKind,
+    peak_temp: f64,
+    /// Density at the inner radius in the disk plane
+    density: f64,
+    falloff: f64,
+    turbulence: f64,
+    strength: f64,
+    temp_scale: f64,
+    disk: DiskFrame,
+}
+
+impl AccretionDiskShader {
+    /// Default of the `noise_scale` parameter.
+    pub(crate) const NOISE_SCALE: f64 = 10.0;
+
+    pub fn new() -> Self {
+        Self {
+            noise: NoiseTexture3D::new(Self::NOISE_SCALE, 0, 2).into(),
+            peak_temp: 6500.0,
+            density: 4.0,
+            falloff: 1.5,
+            turbulence: 0.5,
+            strength: 8.0,
+            temp_scale: 1.0,
+            disk: DiskFrame::new(BASE_THICKNESS),
+        }
+    }
+
+    /// Distance from the center relative to the inner radius, `None` outside of the disk.
+    fn relative_radius(&self, local: Vector3<f64>) -> Option<f64> {
+        let radius = local.xz().magnitude();
+        let inner = self.disk.inner().max(1e-3);
+
+        if radius < inner || radius > BASE_OUTER || self.disk.is_cut_off(local) {
+            return None;
+        }
+
+        Some(radius / inner)
+    }
+
+    /// Temperature at radius relative to the inner one.
+    fn temperature(&self, relative: f64) -> f64 {
+        self.peak_temp * self.temp_scale * relative.powf(-0.75)
+    }
+}
+
+impl Default for AccretionDiskShader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shader for AccretionDiskShader {
+    fn set_parameter(&mut self, name: &str, value: Parameter) {
+        if self.disk.set_parameter(name, &value)
+            || set_swirl_noise_parameter(&mut self.noise, name, &value)
+        {
+            return;
+        }
+
+        match (name, value) {
+            ("peak_temp", Parameter::Float(t)) if t > 0.0 => self.peak_temp = t,
+            ("density", Parameter::Float(d)) => self.density = d.max(0.0),
+            ("falloff", Parameter::Float(f)) => self.falloff = f,
+            ("turbulence", Parameter::Float(t)) => self.turbulence = t.clamp(0.0, 1.0),
+            ("strength", Parameter::Float(f)) => self.strength = f,
+            ("temp_scale", Parameter::Float(f)) => self.temp_scale = f,
+            _ => {}
+        }
+    }
+
+    fn emits_light(&self) -> bool {
+        self.strength > 0.0
+    }
+
+    fn parameter(&self, name: &str) -> Option<Parameter> {
+        match name {
+            "strength" => Some(Parameter::Float(self.strength)),
+            _ => None,
+        }
+    }
+
+    fn emission_scale(&self, name: &str, value: &Parameter) -> Option<Vector3<f64>> {
+        match (name, value) {
+            ("strength", Parameter::Float(f)) => strength_ratio(self.strength, *f),
+            _ => None,
+        }
+    }
+}
+
+impl VolumetricShader for AccretionDiskShader {
+    fn density_at(&self, position: Vector3<f64>, ctx: &ShadeContext) -> f64 {
+        let position = self.disk.local(position);
+
+        let Some(relative) = self.relative_radius(position) else {
+            return 0.0;
+        };
+
+        let vertical = (1.0 - position.y.abs() / BASE_THICKNESS).max(0.0);
+        let edge = ((BASE_OUTER - position.xz().magnitude()) / (BASE_OUTER * EDGE_FADE)).min(1.0);
+
+        let noise = self
+            .noise
+            .color_at(swirl_coords(position) + ctx.texture_offset());
+        let clouds = (1.0 - self.turbulence + 2.0 * self.turbulence * noise).max(0.0);
+
+        self.density * relative.powf(-self.falloff) * vertical * edge * clouds
+    }
+
+    fn material_at(&self, ray: &Ray, _ctx: &ShadeContext) -> (MaterialResult, Option<Ray>) {
+        let location = self.disk.local(ray.location);
+
+        let emission = match self.relative_radius(location) {
+            Some(relative) => {
+                let temp = self.temperature(relative);
+                let brightness = (temp / self.peak_temp).powi(4);
+
+                BLACKBODY_LUT.lookup(temp) * brightness * self.strength
+            }
+            None => Vector3::zero(),
+        };
+
+        let mat = MaterialResult {
+            albedo: Vector3::zero(),
+            emission,
+        };
+
+        (mat, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temperature_follows_thin_disk_profile() {
+        blackhole::cache::set_dir(Some(blackhole::cache::temporary_dir()));
+
+        let mut shader = AccretionDiskShader::new();
+        shader.set_parameter("peak_temp", Parameter::Float(10000.0));
+
+        assert_eq!(shader.temperature(1.0), 10000.0);
+
+        // 16 times the distance is 8 times colder
+        assert!((shader.temperature(16.0) - 1250.0).abs() < 1e-9);
+    }
+}
@@ -1,15 +1,28 @@
-use blackhole::material::MaterialResult;
-use blackhole::shader::{Parameter, Shader, SolidShader};
+use blackhole::color::spectrum_to_rgb;
+use blackhole::material::{MaterialResult, Subsurface};
+use blackhole::shader::{Parameter, ShadeContext, Shader, SolidShader};
 use blackhole::{Ray, RayKind};
 
-use cgmath::{InnerSpace, Vector3, Zero};
+use super::bump::SurfaceBump;
+
+use cgmath::{ElementWise, InnerSpace, Vector3, VectorSpace, Zero};
+
+use std::f64::consts::PI;
 
 use blackhole::math::{rand_unit, rand_unit_vector};
 
+#[derive(Clone)]
 pub struct BasicSolidShader {
     albedo: Vector3<f64>,
     emission: Vector3<f64>,
     metallic: f64,
+    subsurface: f64,
+    subsurface_radius: f64,
+    subsurface_color: Vector3<f64>,
+    /// Thickness of film on top of the metallic surface in nanometers
+    film_thickness: f64,
+    film_ior: f64,
+    bump: SurfaceBump,
 }
 
 impl Default for BasicSolidShader {
@@ -18,30 +31,144 @@ impl Default for BasicSolidShader {
             albedo: Vector3::new(0.8, 0.8, 0.8),
             emission: Vector3::zero(),
             metallic: 0.0,
+            subsurface: 0.0,
+            subsurface_radius: 0.1,
+            subsurface_color: Vector3::new(0.9, 0.9, 0.9),
+            film_thickness: 0.0,
+            film_ior: 1.33,
+            bump: SurfaceBump::new(),
+        }
+    }
+}
+
+impl BasicSolidShader {
+    /// Error of the last normal map load.
+    pub fn error(&self) -> Option<String> {
+        self.bump.error()
+    }
+
+    /// Albedo of the metallic surface under a thin film, colored by interference of light
+    /// reflected from both sides of the film.
+    ///
+    /// The metal is treated as a surface reflecting the same fraction of light as its albedo,
+    /// with phase shift of half a wave.
+    fn thin_film(&self, cos_theta: f64) -> Vector3<f64> {
+        let n = self.film_ior;
+
+        let sin_theta_film = (1.0 - cos_theta.powi(2)).sqrt() / n;
+        let cos_theta_film = (1.0 - sin_theta_film.powi(2)).sqrt();
+
+        // fresnel amplitudes on the top of the film, averaged over polarizations
+        let r_s = (cos_theta - n * cos_theta_film) / (cos_theta + n * cos_theta_film);
+        let r_p = (n * cos_theta - cos_theta_film) / (n * cos_theta + cos_theta_film);
+
+        let base = (self.albedo.x + self.albedo.y + self.albedo.z) / 3.0;
+        let r_metal = -base.sqrt();
+
+        let reflectance = |r_film: f64, phase: f64| {
+            let cross = 2.0 * r_film * r_metal * phase.cos();
+
+            (r_film.powi(2) + r_metal.powi(2) + cross) / (1.0 + (r_film * r_metal).powi(2) + cross)
+        };
+
+        let film = spectrum_to_rgb(|wavelength| {
+            let phase = 4.0 * PI * n * self.film_thickness * cos_theta_film / wavelength;
+
+            (reflectance(r_s, phase) + reflectance(r_p, phase)) / 2.0
+        });
+
+        if base <= 0.0 {
+            return film;
         }
+
+        // keep tint of the metal, the film only redistributes reflected light between colors
+        (self.albedo * (1.0 / base))
+            .mul_element_wise(film)
+            .map(|c| c.clamp(0.0, 1.0))
     }
 }
 
 impl Shader for BasicSolidShader {
     fn set_parameter(&mut self, name: &str, value: Parameter) {
+        if self.bump.set_parameter(name, &value) {
+            return;
+        }
+
         match (name, value) {
             ("albedo", Parameter::Vec3(v)) => self.albedo = v,
             ("emission", Parameter::Vec3(e)) => self.emission = e,
             ("metallic", Parameter::Float(m)) => self.metallic = m,
+            ("subsurface", Parameter::Float(s)) => self.subsurface = s.clamp(0.0, 1.0),
+            ("subsurface_radius", Parameter::Float(r)) => self.subsurface_radius = r.max(0.0),
+            ("subsurface_color", Parameter::Vec3(c)) => self.subsurface_color = c,
+            ("film_thickness", Parameter::Float(t)) => self.film_thickness = t.max(0.0),
+            ("film_ior", Parameter::Float(n)) => self.film_ior = n.max(1.0),
             _ => {}
         }
     }
+
+    fn emits_light(&self) -> bool {
+        self.emission != Vector3::zero()
+    }
+
+    fn parameter(&self, name: &str) -> Option<Parameter> {
+        match name {
+            "emission" => Some(Parameter::Vec3(self.emission)),
+            _ => None,
+        }
+    }
+
+    fn emission_scale(&self, name: &str, value: &Parameter) -> Option<Vector3<f64>> {
+        match (name, value) {
+            ("emission", Parameter::Vec3(e)) => {
+                let ratio = |old: f64, new: f64| match (old == 0.0, new == 0.0) {
+                    (true, true) => Some(1.0),
+                    (true, false) => None,
+                    _ => Some(new / old),
+                };
+
+                let old = self.emission;
+
+                Some(Vector3::new(
+                    ratio(old.x, e.x)?,
+                    ratio(old.y, e.y)?,
+                    ratio(old.z, e.z)?,
+                ))
+            }
+            _ => None,
+        }
+    }
 }
 
 impl SolidShader for BasicSolidShader {
-    fn material_at(&self, ray: &Ray, normal: Vector3<f64>) -> (MaterialResult, Option<Ray>) {
-        let num = rand_unit();
-
-        let mat = MaterialResult {
+    fn material_at(
+        &self,
+        ray: &Ray,
+        normal: Vector3<f64>,
+        ctx: &ShadeContext,
+    ) -> (MaterialResult, Option<Ray>) {
+        let mut mat = MaterialResult {
             albedo: self.albedo,
             emission: self.emission,
         };
 
+        // mirror for every surface, with albedo of both lobes weighted by their chance
+        if ctx.deterministic {
+            if self.film_thickness > 0.0 {
+                let cos_theta = -ray.direction.dot(normal);
+                let film = self.thin_film(cos_theta.max(0.0));
+
+                mat.albedo = self.albedo.lerp(film, self.metallic.clamp(0.0, 1.0));
+            }
+
+            let mut ray = ray.reflect(normal);
+            ray.advance(0.01);
+
+            return (mat, Some(ray));
+        }
+
+        let num = rand_unit();
+
         let mut ray = if num > self.metallic {
             let dir = rand_unit_vector();
 
@@ -51,6 +178,11 @@ impl SolidShader for BasicSolidShader {
                 ..*ray
             }
         } else {
+            if self.film_thickness > 0.0 {
+                let cos_theta = -ray.direction.dot(normal);
+                mat.albedo = self.thin_film(cos_theta.max(0.0));
+            }
+
             let mut ray = ray.reflect(normal);
             ray.kind = RayKind::Secondary;
             ray
@@ -60,4 +192,78 @@ impl SolidShader for BasicSolidShader {
 
         (mat, Some(ray))
     }
+
+    fn shading_normal(&self, ray: &Ray, normal: Vector3<f64>, ctx: &ShadeContext) -> Vector3<f64> {
+        self.bump.normal_at(ray.location, normal, ctx)
+    }
+
+    fn subsurface(&self) -> Option<Subsurface> {
+        if self.subsurface <= 0.0 || self.subsurface_radius <= 0.0 {
+            return None;
+        }
+
+        Some(Subsurface {
+            radius: self.subsurface_radius,
+            color: self.subsurface_color,
+            weight: self.subsurface,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shaders::{SolidColorBackgroundShader, SolidColorVolumeScatterShader};
+    use blackhole::marcher::{RayMarcher, Scratch};
+    use blackhole::math::{pixel_seed, seed_rng};
+    use blackhole::object::shape::Sphere;
+    use blackhole::scene::Scene;
+    use blackhole::RenderMode;
+
+    #[test]
+    fn deterministic_mode_ignores_seed() {
+        blackhole::cache::set_dir(Some(blackhole::cache::temporary_dir()));
+
+        let mut fog = Sphere::new();
+        fog.set_center(Vector3::new(0.0, 0.0, 1.5));
+
+        // metal under film with subsurface scattering, partly hidden by scattering fog
+        let scene = Scene::builder()
+            .background(SolidColorBackgroundShader::new())
+            .look_from(Vector3::new(0.0, 0.0, 6.0), Vector3::zero())
+            .object(|o| {
+                o.shape(Sphere::new())
+                    .solid(BasicSolidShader::default())
+                    .parameter("metallic", Parameter::Float(0.5))
+                    .parameter("film_thickness", Parameter::Float(300.0))
+                    .parameter("subsurface", Parameter::Float(0.5))
+            })
+            .object(|o| {
+                o.shape(fog)
+                    .volumetric(SolidColorVolumeScatterShader::new())
+            })
+            .build();
+
+        let marcher = RayMarcher {
+            mode: RenderMode::Deterministic,
+            ..Default::default()
+        };
+        let max_step = scene.max_possible_step(scene.camera.location);
+        let render = |seed: u64| {
+            let mut scratch = Scratch::new();
+
+            (0..64)
+                .map(|i| {
+                    let (x, y) = (i % 8, i / 8);
+                    seed_rng(pixel_seed(seed, 0, x, y));
+
+                    let ray = scene.camera.cast_ray(x as f64 / 8.0, y as f64 / 8.0, 1.0);
+                    marcher.color_for_ray(ray, &scene, max_step, 0, &mut scratch)
+                })
+                .map(|result| result.color)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(render(1), render(2));
+    }
 }
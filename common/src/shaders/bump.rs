@@ -0,0 +1,124 @@
+use cgmath::{InnerSpace, Vector3};
+
+use blackhole::shader::{Parameter, ShadeContext};
+use blackhole::texture::{ImageTexture2D, NoiseTexture3D, Texture3D, TextureKind, Triplanar};
+
+use super::image::read_data_image;
+use super::set_noise_parameter;
+
+/// Bumps of solid surfaces from noise or a normal map, shared by the solid shaders.
+///
+/// Only the shading normal is tilted, silhouettes of the shapes stay smooth.
+#[derive(Clone)]
+pub(crate) struct SurfaceBump {
+    noise: TextureKind,
+    /// Height of the noise bumps in scene units, 0 disables them
+    strength: f64,
+    normal_map: Option<Triplanar<ImageTexture2D>>,
+    normal_map_scale: f64,
+    normal_map_strength: f64,
+    /// Why the normal map couldn't be read, reported by the loader
+    error: Option<String>,
+}
+
+impl SurfaceBump {
+    pub fn new() -> Self {
+        Self {
+            noise: NoiseTexture3D::new(10.0, 0, 3).into(),
+            strength: 0.0,
+            normal_map: None,
+            normal_map_scale: 1.0,
+            normal_map_strength: 1.0,
+            error: None,
+        }
+    }
+
+    /// Handles parameters of the bumps, returns `false` for other parameters.
+    pub fn set_parameter(&mut self, name: &str, value: &Parameter) -> bool {
+        match (name, value) {
+            ("bump_strength", Parameter::Float(s)) => self.strength = *s,
+            ("bump_scale", _) => return set_noise_parameter(&mut self.noise, "noise_scale", value),
+            ("seed", _) => return set_noise_parameter(&mut self.noise, name, value),
+            ("normal_map", Parameter::Path(path)) => match read_data_image(path) {
+                Ok(image) => {
+                    let texture = ImageTexture2D::new(image);
+
+                    self.normal_map = Some(Triplanar::new(texture, self.normal_map_scale));
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(format!("could not read {}: {e}", path.display())),
+            },
+            ("normal_map_scale", Parameter::Float(s)) => {
+                self.normal_map_scale = *s;
+                if let Some(map) = &mut self.normal_map {
+                    map.set_scale(*s);
+                }
+            }
+            ("normal_map_strength", Parameter::Float(s)) => self.normal_map_strength = *s,
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Error of the last normal map load.
+    pub fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+
+    /// Unit `normal` at `position` tilted by the bumps.
+    pub fn normal_at(
+        &self,
+        position: Vector3<f64>,
+        normal: Vector3<f64>,
+        ctx: &ShadeContext,
+    ) -> Vector3<f64> {
+        let mut normal = normal;
+
+        if let Some(map) = &self.normal_map {
+            normal = map.normal_at(position, normal, self.normal_map_strength);
+        }
+
+        if self.strength != 0.0 {
+            let position = position + ctx.texture_offset();
+            let h = 0.01 / self.noise.scale();
+
+            let height = |offset: Vector3<f64>| self.noise.color_at(position + offset);
+            let gradient = Vector3::new(
+                height(Vector3::unit_x() * h) - height(-Vector3::unit_x() * h),
+                height(Vector3::unit_y() * h) - height(-Vector3::unit_y() * h),
+                height(Vector3::unit_z() * h) - height(-Vector3::unit_z() * h),
+            ) * (self.strength / (2.0 * h));
+
+            // surface displaced along the normal leans against the slope of the height
+            let slope = gradient - normal * gradient.dot(normal);
+            normal = (normal - slope).normalize();
+        }
+
+        normal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bumps_tilt_unit_normals() {
+        let mut bump = SurfaceBump::new();
+        let ctx = ShadeContext::default();
+        let position = Vector3::new(0.13, 0.27, -0.41);
+
+        assert_eq!(
+            bump.normal_at(position, Vector3::unit_y(), &ctx),
+            Vector3::unit_y()
+        );
+
+        bump.set_parameter("bump_strength", &Parameter::Float(0.05));
+        let normal = bump.normal_at(position, Vector3::unit_y(), &ctx);
+
+        assert!((normal.magnitude() - 1.0).abs() < 1e-12);
+        assert!(normal != Vector3::unit_y());
+        assert!(normal.y > 0.0);
+    }
+}
@@ -0,0 +1,168 @@
+use cgmath::{ElementWise, InnerSpace, Matrix3, Quaternion, Rad, SquareMatrix, Vector3, Zero};
+
+use blackhole::math::sigmoid;
+use blackhole::object::AABB;
+use blackhole::shader::Parameter;
+use blackhole::texture::TextureKind;
+
+use super::set_noise_parameter;
+
+/// Half-thickness of the default disk of the emitting swirl shaders.
+pub(crate) const SWIRL_THICKNESS: f64 = 0.02;
+
+/// Density in the disk plane per unit of distance inside the outer radius, of a disk fully
+/// covered by clouds.
+const SWIRL_DENSITY: f64 = 100.0;
+
+/// Temperature in Kelvin per unit of depth below the surface and of distance inside the outer
+/// radius, for average noise.
+const SWIRL_TEMPERATURE: f64 = 100_000.0;
+
+/// Steepness and midpoint of the sigmoid splitting noise into clouds and gaps.
+const CLOUD_STEEPNESS: f64 = 30.0;
+const CLOUD_MIDPOINT: f64 = 0.52;
+
+/// Range of [`swirl_coords`] of the default disk, where baked noise is sampled.
+const SWIRL_BOUNDS: AABB = AABB {
+    x_min: -1.0,
+    x_max: 1.0,
+    y_min: 0.0,
+    y_max: 0.5,
+    z_min: -1.0,
+    z_max: 1.0,
+};
+
+/// Outer radius of the default disk, the space disk shaders are written in.
+pub(crate) const BASE_OUTER: f64 = 4.0;
+
+/// Placement and size of an accretion disk, shared by the black hole shaders.
+///
+/// The shaders are written for a disk lying in the XZ plane around the origin, with outer radius
+/// of [`BASE_OUTER`] and half-thickness of `base_thickness`. Points are mapped into that space.
+#[derive(Clone)]
+pub(crate) struct DiskFrame {
+    center: Vector3<f64>,
+    /// Rotates the disk axis onto Y
+    rotation: Matrix3<f64>,
+    inner: f64,
+    outer: f64,
+    thickness: f64,
+    base_thickness: f64,
+    /// Radius in the disk plane without any density, 0 disables it
+    cutoff: f64,
+}
+
+impl DiskFrame {
+    pub fn new(base_thickness: f64) -> Self {
+        Self {
+            center: Vector3::zero(),
+            rotation: Matrix3::identity(),
+            inner: 1.0,
+            outer: BASE_OUTER,
+            thickness: base_thickness,
+            base_thickness,
+            cutoff: 0.0,
+        }
+    }
+
+    /// Handles parameters describing the disk, returns `false` for other parameters.
+    pub fn set_parameter(&mut self, name: &str, value: &Parameter) -> bool {
+        match (name, value) {
+            ("center", Parameter::Vec3(c)) => self.center = *c,
+            ("axis", Parameter::Vec3(a)) if a.magnitude2() > 0.0 => {
+                let from_arc = Quaternion::from_arc(a.normalize(), Vector3::unit_y(), None);
+                self.rotation = Matrix3::from(from_arc);
+            }
+            ("inner", Parameter::Float(r)) => self.inner = *r,
+            ("outer", Parameter::Float(r)) if *r > 0.0 => self.outer = *r,
+            ("thickness", Parameter::Float(t)) if *t > 0.0 => self.thickness = *t,
+            ("cutoff_radius", Parameter::Float(r)) => self.cutoff = r.max(0.0),
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Maps point to the space of the default disk.
+    pub fn local(&self, position: Vector3<f64>) -> Vector3<f64> {
+        let local = self.rotation * (position - self.center);
+
+        let radial_scale = BASE_OUTER / self.outer;
+        let height_scale = self.base_thickness / self.thickness;
+
+        Vector3::new(
+            local.x * radial_scale,
+            local.y * height_scale,
+            local.z * radial_scale,
+        )
+    }
+
+    /// Whether point in the space of the default disk is inside the cutoff radius.
+    pub fn is_cut_off(&self, local: Vector3<f64>) -> bool {
+        local.xz().magnitude() < self.cutoff * BASE_OUTER / self.outer
+    }
+
+    /// Inner radius in the space of the default disk.
+    pub fn inner(&self) -> f64 {
+        self.inner * BASE_OUTER / self.outer
+    }
+}
+
+/// Handles noise parameters of shaders sampling noise at [`swirl_coords`], returns `false` for
+/// other parameters.
+///
+/// Besides the ones of [`set_noise_parameter`], `noise_bake` samples the noise into a grid with
+/// given resolution.
+pub(crate) fn set_swirl_noise_parameter(
+    noise: &mut TextureKind,
+    name: &str,
+    value: &Parameter,
+) -> bool {
+    match (name, value) {
+        ("noise_bake", Parameter::Usize(resolution)) => {
+            *noise = noise.bake(SWIRL_BOUNDS, (*resolution).max(2));
+            true
+        }
+        _ => set_noise_parameter(noise, name, value),
+    }
+}
+
+/// Noise coordinates of a point in the space of the default disk, turned around the axis by its
+/// distance from the center, so noise features wind into spirals.
+pub(crate) fn swirl_coords(local: Vector3<f64>) -> Vector3<f64> {
+    let mag = local.magnitude();
+    let norm = local.normalize();
+
+    let norm_rot = Matrix3::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), Rad(mag)) * norm;
+
+    let coords = Vector3::new(norm_rot.x, norm_rot.z, mag);
+
+    coords.mul_element_wise(Vector3::new(1.0, 1.0, 0.1))
+}
+
+/// Density of clouds of the emitting swirl shaders at a point in the space of the default disk,
+/// where their `noise` is looked up.
+///
+/// Clouds grow from the inner radius and thin out towards the outer one.
+pub(crate) fn swirl_density(local: Vector3<f64>, noise: f64, inner: f64) -> f64 {
+    let mag = local.magnitude();
+    let radial = (-(2.0 / 5.0) * mag + 2.0).min(20.0 * (mag - inner));
+
+    let clouds = sigmoid(noise * radial, CLOUD_STEEPNESS, CLOUD_MIDPOINT);
+
+    (SWIRL_THICKNESS - local.y.abs())
+        * SWIRL_DENSITY
+        * (BASE_OUTER - local.xz().magnitude())
+        * clouds
+}
+
+/// Temperature of the emitting swirl shaders at a point in the space of the default disk, hottest
+/// in the middle of the disk and near the center.
+pub(crate) fn swirl_temperature(local: Vector3<f64>, noise: f64) -> f64 {
+    let noise_factor = noise * 0.5 + 0.75;
+
+    (SWIRL_THICKNESS - local.y.abs())
+        * SWIRL_TEMPERATURE
+        * (BASE_OUTER - local.xz().magnitude())
+        * noise_factor
+}
@@ -0,0 +1,116 @@
+use blackhole::framebuffer::{FrameBuffer, Pixel};
+use blackhole::math::equirect_uv;
+use blackhole::shader::{BackgroundShader, Parameter, Shader};
+use blackhole::Ray;
+
+use super::image::read_image;
+
+use cgmath::{Deg, Matrix3, Vector3, Zero};
+
+use std::io::Result;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Background from an equirectangular Radiance HDR, OpenEXR or PNG image.
+///
+/// The image center is seen looking along -Z, same as panoramas written by the renderer.
+#[derive(Clone)]
+pub struct HdriBackgroundShader {
+    image: Option<Arc<FrameBuffer>>,
+    /// Why the image couldn't be read, reported by the loader
+    error: Option<String>,
+    strength: f64,
+    /// Turn of the image around the Y axis
+    rotation: Matrix3<f64>,
+}
+
+impl HdriBackgroundShader {
+    pub fn new() -> Self {
+        Self {
+            image: None,
+            error: None,
+            strength: 1.0,
+            rotation: Matrix3::from_angle_y(Deg(0.0)),
+        }
+    }
+
+    /// Reads the image, replacing the current one.
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        self.image = Some(Arc::new(read_image(path)?));
+
+        Ok(())
+    }
+
+    /// Error of the last image load, or missing image.
+    pub fn error(&self) -> Option<String> {
+        match (&self.image, &self.error) {
+            (_, Some(error)) => Some(error.clone()),
+            (None, None) => Some("missing image path".into()),
+            (Some(_), None) => None,
+        }
+    }
+
+    fn texel(image: &FrameBuffer, x: usize, y: usize) -> Vector3<f64> {
+        let Pixel { r, g, b, .. } = image.buffer()[x + y * image.width()];
+
+        Vector3::new(r as f64, g as f64, b as f64)
+    }
+}
+
+impl Default for HdriBackgroundShader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shader for HdriBackgroundShader {
+    fn set_parameter(&mut self, name: &str, value: Parameter) {
+        match (name, value) {
+            ("path", Parameter::Path(path)) => {
+                self.error = self
+                    .load(&path)
+                    .err()
+                    .map(|e| format!("could not read {}: {e}", path.display()));
+            }
+            ("strength", Parameter::Float(v)) => self.strength = v,
+            ("rotation", Parameter::Float(v)) => self.rotation = Matrix3::from_angle_y(Deg(v)),
+            _ => {}
+        }
+    }
+
+    fn emission_scale(&self, name: &str, value: &Parameter) -> Option<Vector3<f64>> {
+        match (name, value) {
+            ("strength", Parameter::Float(v)) => super::strength_ratio(self.strength, *v),
+            _ => None,
+        }
+    }
+}
+
+impl BackgroundShader for HdriBackgroundShader {
+    fn emission_at(&self, ray: &Ray) -> Vector3<f64> {
+        let image = match &self.image {
+            Some(image) => image,
+            None => return Vector3::zero(),
+        };
+
+        let (w, h) = (image.width(), image.height());
+        let (u, v) = equirect_uv(self.rotation * ray.direction);
+
+        // bilinear filtering between pixel centers, wrapping around horizontally
+        let x = u * w as f64 - 0.5;
+        let y = (v * h as f64 - 0.5).clamp(0.0, (h - 1) as f64);
+
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let x0 = (x0 as isize).rem_euclid(w as isize) as usize;
+        let x1 = (x0 + 1) % w;
+        let y0 = y0 as usize;
+        let y1 = (y0 + 1).min(h - 1);
+
+        let top = Self::texel(image, x0, y0) * (1.0 - tx) + Self::texel(image, x1, y0) * tx;
+        let bottom = Self::texel(image, x0, y1) * (1.0 - tx) + Self::texel(image, x1, y1) * tx;
+
+        (top * (1.0 - ty) + bottom * ty) * self.strength
+    }
+}
@@ -0,0 +1,245 @@
+use blackhole::framebuffer::{FrameBuffer, Pixel};
+
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result};
+use std::path::Path;
+
+/// Largest image read, larger ones are rejected before allocating them.
+///
+/// 16k by 16k pixels, which take 4 GiB of memory.
+const MAX_PIXELS: usize = 1 << 28;
+
+/// Reads Radiance HDR, OpenEXR or PNG image, chosen by the file extension.
+///
+/// PNG images are decoded from 2.2 gamma to linear values, same as written by the renderer.
+pub fn read_image(path: &Path) -> Result<FrameBuffer> {
+    read_image_with_gamma(path, 2.2)
+}
+
+/// Reads image holding data instead of colors, like a normal map, PNG values are kept linear.
+pub fn read_data_image(path: &Path) -> Result<FrameBuffer> {
+    read_image_with_gamma(path, 1.0)
+}
+
+fn read_image_with_gamma(path: &Path, gamma: f32) -> Result<FrameBuffer> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+
+    let image = match extension.as_deref() {
+        Some("hdr") => read_hdr(path)?,
+        #[cfg(feature = "exr")]
+        Some("exr") => FrameBuffer::read_exr(path).map_err(|e| invalid(&e.to_string()))?,
+        Some("png") => read_png(path, gamma)?,
+        _ => {
+            return Err(invalid(
+                "unsupported image format, expected .hdr, .exr or .png",
+            ))
+        }
+    };
+
+    if image.width() == 0 || image.height() == 0 {
+        return Err(invalid("image is empty"));
+    }
+
+    Ok(image)
+}
+
+fn read_png(path: &Path, gamma: f32) -> Result<FrameBuffer> {
+    let mut decoder = png::Decoder::new(std::fs::File::open(path)?);
+    // palettes and 16 bit channels are expanded to 8 bit channels
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info().map_err(|e| invalid(&e.to_string()))?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut data)
+        .map_err(|e| invalid(&e.to_string()))?;
+
+    let channels = info.color_type.samples();
+    let (width, height) = (info.width as usize, info.height as usize);
+    check_size(width, height)?;
+
+    let mut image = FrameBuffer::new(width, height);
+
+    let decode = |v: u8| (v as f32 / 255.0).powf(gamma);
+
+    for (y, row) in data.chunks(info.line_size).take(height).enumerate() {
+        let pixels = &mut image.buffer_mut()[y * width..(y + 1) * width];
+
+        for (pixel, texel) in pixels.iter_mut().zip(row.chunks(channels)) {
+            *pixel = match *texel {
+                [l] => Pixel::new(decode(l), decode(l), decode(l), 1.0),
+                [l, a] => Pixel::new(decode(l), decode(l), decode(l), a as f32 / 255.0),
+                [r, g, b] => Pixel::new(decode(r), decode(g), decode(b), 1.0),
+                [r, g, b, a] => Pixel::new(decode(r), decode(g), decode(b), a as f32 / 255.0),
+                _ => return Err(invalid("unsupported PNG color type")),
+            };
+        }
+    }
+
+    Ok(image)
+}
+
+/// Reads Radiance RGBE image, flat or with run length encoded scanlines.
+fn read_hdr(path: &Path) -> Result<FrameBuffer> {
+    let mut reader = BufReader::new(std::fs::File::open(path)?);
+    let mut line = String::new();
+
+    reader.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid("not a Radiance HDR file"));
+    }
+
+    // header ends with an empty line
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("truncated header"));
+        }
+
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid("only RGBE pixel format is supported"));
+            }
+        }
+    }
+
+    line.clear();
+    reader.read_line(&mut line)?;
+    let (width, height) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", h, "+X", w] => (w.parse::<usize>(), h.parse::<usize>()),
+        _ => {
+            return Err(invalid(
+                "only top to bottom, left to right images are supported",
+            ))
+        }
+    };
+    let (width, height) = match (width, height) {
+        (Ok(w), Ok(h)) => (w, h),
+        _ => return Err(invalid("invalid image size")),
+    };
+    check_size(width, height)?;
+
+    let mut image = FrameBuffer::new(width, height);
+    let mut scanline = vec![[0_u8; 4]; width];
+
+    for y in 0..height {
+        read_scanline(&mut reader, &mut scanline)?;
+
+        let row = &mut image.buffer_mut()[y * width..(y + 1) * width];
+        for (pixel, rgbe) in row.iter_mut().zip(&scanline) {
+            *pixel = rgbe_to_pixel(*rgbe);
+        }
+    }
+
+    Ok(image)
+}
+
+fn read_scanline(reader: &mut impl Read, scanline: &mut [[u8; 4]]) -> Result<()> {
+    let mut first = [0; 4];
+    reader.read_exact(&mut first)?;
+
+    let width = scanline.len();
+    let encoded = (8..0x8000).contains(&width)
+        && first[0] == 2
+        && first[1] == 2
+        && ((first[2] as usize) << 8 | first[3] as usize) == width;
+
+    if !encoded {
+        scanline[0] = first;
+        for pixel in &mut scanline[1..] {
+            reader.read_exact(pixel)?;
+        }
+
+        return Ok(());
+    }
+
+    // channels are stored one after another, each as runs and literal spans
+    for channel in 0..4 {
+        let mut x = 0;
+
+        while x < width {
+            let mut count = [0; 1];
+            reader.read_exact(&mut count)?;
+
+            let (run, count) = match count[0] {
+                c if c > 128 => (true, c as usize - 128),
+                c => (false, c as usize),
+            };
+
+            if count == 0 || x + count > width {
+                return Err(invalid("corrupted scanline"));
+            }
+
+            if run {
+                let mut value = [0; 1];
+                reader.read_exact(&mut value)?;
+
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = value[0];
+                }
+            } else {
+                for pixel in &mut scanline[x..x + count] {
+                    let mut value = [0; 1];
+                    reader.read_exact(&mut value)?;
+                    pixel[channel] = value[0];
+                }
+            }
+
+            x += count;
+        }
+    }
+
+    Ok(())
+}
+
+fn rgbe_to_pixel([r, g, b, e]: [u8; 4]) -> Pixel {
+    if e == 0 {
+        return Pixel::new(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let scale = 2.0_f32.powi(e as i32 - 136);
+
+    Pixel::new(
+        (r as f32 + 0.5) * scale,
+        (g as f32 + 0.5) * scale,
+        (b as f32 + 0.5) * scale,
+        1.0,
+    )
+}
+
+fn check_size(width: usize, height: usize) -> Result<()> {
+    match width.checked_mul(height) {
+        Some(pixels) if pixels <= MAX_PIXELS => Ok(()),
+        _ => Err(invalid(&format!(
+            "image of {width}x{height} pixels is too large"
+        ))),
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn huge_hdr_is_rejected() {
+        let path = std::env::temp_dir().join("blackhole-huge-image.hdr");
+        std::fs::write(&path, "#?RADIANCE\n\n-Y 4000000000 +X 4000000000\n").unwrap();
+
+        let result = read_image(&path);
+        let _ = std::fs::remove_file(&path);
+
+        match result {
+            Err(e) => assert!(e.to_string().contains("too large"), "{e}"),
+            Ok(_) => panic!("huge image was read"),
+        }
+    }
+}
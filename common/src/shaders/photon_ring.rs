@@ -0,0 +1,134 @@
+use cgmath::{InnerSpace, Vector3, Zero};
+
+use blackhole::material::MaterialResult;
+use blackhole::shader::{Parameter, ShadeContext, Shader, VolumetricShader};
+use blackhole::{Ray, BLACKBODY_LUT};
+
+use super::strength_ratio;
+
+/// Thin glowing shell around the photon sphere of a distortion, seen as the photon ring.
+///
+/// Light is emitted along rays grazing the shell, scaled by the sine of the angle between the ray
+/// and the direction to `center` raised to `sharpness`. Rays crossing the shell head-on barely
+/// see it, ones skimming it on near orbits light up a thin ring at the edge of the shadow.
+#[derive(Clone)]
+pub struct PhotonRingShader {
+    center: Vector3<f64>,
+    temp: f64,
+    strength: f64,
+    density: f64,
+    sharpness: f64,
+}
+
+impl PhotonRingShader {
+    pub fn new() -> Self {
+        Self {
+            center: Vector3::zero(),
+            temp: 6500.0,
+            strength: 4.0,
+            density: 1.0,
+            sharpness: 64.0,
+        }
+    }
+
+    /// Sine of the angle between the ray and the radial direction, 1 for tangent rays.
+    ///
+    /// Rays at the center have no radial direction and get 0.
+    fn tangency(&self, ray: &Ray) -> f64 {
+        let radial = ray.location - self.center;
+
+        if radial.magnitude2() == 0.0 {
+            return 0.0;
+        }
+
+        let radial = radial.normalize();
+        let cos = ray.direction.normalize().dot(radial);
+
+        (1.0 - cos * cos).max(0.0).sqrt()
+    }
+}
+
+impl Default for PhotonRingShader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shader for PhotonRingShader {
+    fn set_parameter(&mut self, name: &str, value: Parameter) {
+        match (name, value) {
+            ("center", Parameter::Vec3(c)) => self.center = c,
+            ("temp", Parameter::Float(t)) => self.temp = t,
+            ("strength", Parameter::Float(f)) => self.strength = f,
+            ("density", Parameter::Float(d)) => self.density = d.max(0.0),
+            ("sharpness", Parameter::Float(s)) => self.sharpness = s.max(0.0),
+            _ => {}
+        }
+    }
+
+    fn emits_light(&self) -> bool {
+        self.strength > 0.0
+    }
+
+    fn parameter(&self, name: &str) -> Option<Parameter> {
+        match name {
+            "strength" => Some(Parameter::Float(self.strength)),
+            _ => None,
+        }
+    }
+
+    fn emission_scale(&self, name: &str, value: &Parameter) -> Option<Vector3<f64>> {
+        match (name, value) {
+            ("strength", Parameter::Float(f)) => strength_ratio(self.strength, *f),
+            _ => None,
+        }
+    }
+}
+
+impl VolumetricShader for PhotonRingShader {
+    fn density_at(&self, _position: Vector3<f64>, _ctx: &ShadeContext) -> f64 {
+        self.density
+    }
+
+    fn material_at(&self, ray: &Ray, _ctx: &ShadeContext) -> (MaterialResult, Option<Ray>) {
+        let glow = self.tangency(ray).powf(self.sharpness);
+
+        let mat = MaterialResult {
+            albedo: Vector3::zero(),
+            emission: BLACKBODY_LUT.lookup(self.temp) * self.strength * glow,
+        };
+
+        (mat, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blackhole::RayKind;
+
+    fn ray(location: Vector3<f64>, direction: Vector3<f64>) -> Ray {
+        Ray {
+            location,
+            direction,
+            steps_taken: 0,
+            kind: RayKind::Primary,
+            time: 0.5,
+        }
+    }
+
+    #[test]
+    fn grazing_rays_glow() {
+        let shader = PhotonRingShader::new();
+        let ctx = ShadeContext::default();
+        let location = Vector3::new(3.0, 0.0, 0.0);
+
+        let (tangent, _) = shader.material_at(&ray(location, Vector3::unit_y()), &ctx);
+        let (radial, _) = shader.material_at(&ray(location, -Vector3::unit_x()), &ctx);
+        let (center, _) = shader.material_at(&ray(Vector3::zero(), Vector3::unit_y()), &ctx);
+
+        assert!(tangent.emission.x > 0.0);
+        assert_eq!(radial.emission, Vector3::zero());
+        assert_eq!(center.emission, Vector3::zero());
+    }
+}
@@ -1,11 +1,18 @@
-use blackhole::math::{rand_unit, rand_unit_vector};
-use blackhole::shader::{BackgroundShader, Parameter, Shader};
+use blackhole::cache::{self, Cacheable};
+use blackhole::math::{mix_seed, orthonormal_basis, rand_unit_vector_with};
+use blackhole::shader::{BackgroundShader, Footprint, Parameter, Shader};
 use blackhole::{Ray, RayKind};
 
-use cgmath::{Array, ElementWise, InnerSpace, Vector3, VectorSpace, Zero};
+use cgmath::{ElementWise, InnerSpace, Vector3, VectorSpace, Zero};
 
 use blackhole::texture::{NoiseTexture3D, Texture3D, WorleyTexture3D};
 
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256StarStar;
+
+use std::io::{Read, Result, Write};
+use std::sync::Arc;
+
 #[derive(Debug, Clone)]
 struct Star {
     direction: Vector3<f64>,
@@ -13,56 +20,107 @@ struct Star {
     brightness: f64,
 }
 
-pub struct StarSkyShader {
-    stars: Vec<Vec<Star>>,
-    star_x_divisions: usize,
-    star_y_divisions: usize,
-    milky_way_color: Vector3<f64>,
-    noise: NoiseTexture3D,
-    worley: WorleyTexture3D,
+impl Cacheable for Star {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        self.direction.write_to(w)?;
+        self.color.write_to(w)?;
+        self.brightness.write_to(w)
+    }
+
+    fn read_from(r: &mut dyn Read) -> Result<Self> {
+        Ok(Self {
+            direction: Vector3::read_from(r)?,
+            color: Vector3::read_from(r)?,
+            brightness: f64::read_from(r)?,
+        })
+    }
 }
 
-impl StarSkyShader {
-    pub fn new() -> Self {
-        let mut shader = Self {
+/// Stars sorted by sector they fall into, brightest first within each sector.
+///
+/// Kept in a single array, so even catalogs with millions of stars stay compact.
+struct StarCatalog {
+    x_divisions: usize,
+    y_divisions: usize,
+    stars: Vec<Star>,
+    /// Index of first star of each sector, with one extra entry for the end of the last sector
+    offsets: Vec<u32>,
+}
+
+impl StarCatalog {
+    fn empty() -> Self {
+        Self {
+            x_divisions: 1,
+            y_divisions: 1,
             stars: Vec::new(),
-            milky_way_color: Vector3::new(0.2, 0.3, 0.4),
-            star_x_divisions: 256,
-            star_y_divisions: 128,
-            noise: NoiseTexture3D::new(20.0, 0, 4),
-            worley: WorleyTexture3D::new(8.0),
-        };
+            offsets: vec![0, 0],
+        }
+    }
 
-        shader.regenerate_stars(10_000);
+    /// Picks sector grid so sectors hold a few stars each, but are still larger than a star.
+    fn divisions(star_count: usize) -> (usize, usize) {
+        let y = ((star_count as f64 / 8.0).sqrt() as usize).clamp(128, 512);
 
-        shader
+        (y * 2, y)
     }
 
-    fn regenerate_stars(&mut self, new_stars: usize) {
-        let mut stars = vec![Vec::new(); self.star_x_divisions * self.star_y_divisions];
+    fn generate(star_count: usize, luminosity: LuminosityFunction, seed: u64) -> Self {
+        let (x_divisions, y_divisions) = Self::divisions(star_count);
+        let mut rng = Xoshiro256StarStar::seed_from_u64(seed);
 
-        for _star_index in 0..new_stars {
-            let dir = rand_unit_vector()
-                .mul_element_wise(Vector3::new(3.0, 1.0, 3.0))
-                .normalize();
+        let mut stars: Vec<(usize, Star)> = (0..star_count)
+            .map(|_| {
+                let dir = rand_unit_vector_with(&mut rng)
+                    .mul_element_wise(Vector3::new(3.0, 1.0, 3.0))
+                    .normalize();
 
-            let (x, y) = Self::sector_from_dir(self.star_x_divisions, self.star_y_divisions, &dir);
+                let (x, y) = Self::sector_from_dir(x_divisions, y_divisions, &dir);
 
-            let color_scale = rand_unit().powf(2.0);
+                let color_scale = rng.gen_range(0.0..1.0_f64).powf(2.0);
 
-            let color = Vector3::new(0.9, 0.6, 0.2).lerp(Vector3::new(0.6, 0.8, 1.0), color_scale);
-            let brightness = color_scale * 0.8 + 0.1;
+                let color =
+                    Vector3::new(0.9, 0.6, 0.2).lerp(Vector3::new(0.6, 0.8, 1.0), color_scale);
+                let magnitude = luminosity.sample(rng.gen_range(0.0..1.0));
+                let brightness = 10.0_f64.powf(-0.4 * (magnitude - REFERENCE_MAGNITUDE));
 
-            let star = Star {
-                direction: dir,
-                color,
-                brightness,
-            };
+                let star = Star {
+                    direction: dir,
+                    color,
+                    brightness,
+                };
 
-            stars[x + y * self.star_x_divisions].push(star);
+                (x + y * x_divisions, star)
+            })
+            .collect();
+
+        stars.sort_by(|(a_sector, a), (b_sector, b)| {
+            a_sector
+                .cmp(b_sector)
+                .then(b.brightness.total_cmp(&a.brightness))
+        });
+
+        let mut offsets = Vec::with_capacity(x_divisions * y_divisions + 1);
+        let mut index = 0;
+
+        for sector in 0..=(x_divisions * y_divisions) {
+            while index < stars.len() && stars[index].0 < sector {
+                index += 1;
+            }
+            offsets.push(index as u32);
         }
 
-        self.stars = stars;
+        Self {
+            x_divisions,
+            y_divisions,
+            stars: stars.into_iter().map(|(_, star)| star).collect(),
+            offsets,
+        }
+    }
+
+    fn sector(&self, x: usize, y: usize) -> &[Star] {
+        let index = x + y * self.x_divisions;
+
+        &self.stars[self.offsets[index] as usize..self.offsets[index + 1] as usize]
     }
 
     fn sector_from_dir(
@@ -81,12 +139,179 @@ impl StarSkyShader {
                 + star_x_divisions / 2
         };
 
-        let y = (((dir.y + 1.0) / 2.0) * star_y_divisions as f64).floor() as usize;
+        let y = ((((dir.y + 1.0) / 2.0) * star_y_divisions as f64).floor() as usize)
+            .min(star_y_divisions - 1);
 
         (x, y)
     }
 }
 
+impl Cacheable for StarCatalog {
+    fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        (self.x_divisions as u64).write_to(w)?;
+        (self.y_divisions as u64).write_to(w)?;
+        self.stars.write_to(w)?;
+        self.offsets.write_to(w)
+    }
+
+    fn read_from(r: &mut dyn Read) -> Result<Self> {
+        let catalog = Self {
+            x_divisions: u64::read_from(r)? as usize,
+            y_divisions: u64::read_from(r)? as usize,
+            stars: Vec::read_from(r)?,
+            offsets: Vec::read_from(r)?,
+        };
+
+        let valid = catalog.offsets.len() == catalog.x_divisions * catalog.y_divisions + 1
+            && catalog.offsets.windows(2).all(|w| w[0] <= w[1])
+            && catalog.offsets.last().copied() == Some(catalog.stars.len() as u32);
+
+        if !valid {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid star catalog",
+            ));
+        }
+
+        Ok(catalog)
+    }
+}
+
+/// Distribution of star magnitudes, with the count of stars brighter than magnitude `m` growing
+/// as `10^(slope * m)`.
+#[derive(Debug, Clone, Copy)]
+struct LuminosityFunction {
+    limiting_magnitude: f64,
+    slope: f64,
+}
+
+impl LuminosityFunction {
+    /// Magnitude for `u` uniform in `[0, 1)`, from inverted cumulative star count.
+    fn sample(&self, u: f64) -> f64 {
+        let brightest = 10.0_f64.powf(self.slope * BRIGHTEST_MAGNITUDE);
+        let faintest = 10.0_f64.powf(self.slope * self.limiting_magnitude);
+
+        (brightest + u * (faintest - brightest)).log10() / self.slope
+    }
+}
+
+impl Default for LuminosityFunction {
+    /// About the stars visible by naked eye.
+    fn default() -> Self {
+        Self {
+            limiting_magnitude: 6.5,
+            slope: 0.35,
+        }
+    }
+}
+
+/// Magnitude of the brightest generated stars, close to the brightest stars of the night sky.
+const BRIGHTEST_MAGNITUDE: f64 = -1.5;
+
+/// Magnitude of stars with `brightness` set by the shader parameter, brighter ones scale from it.
+const REFERENCE_MAGNITUDE: f64 = 6.0;
+
+/// Stars contributing less than this to a pixel are culled, if culling is enabled.
+const DEFAULT_CULL_THRESHOLD: f64 = 0.01;
+
+/// Exponent of star falloff for zero brightness, the star is `dot.powf((2 - brightness) * this)`,
+/// with brightness clamped to 1.
+const STAR_SHARPNESS: f64 = 8_000_000.0;
+
+#[derive(Clone)]
+pub struct StarSkyShader {
+    stars: Arc<StarCatalog>,
+    star_count: usize,
+    luminosity: LuminosityFunction,
+    /// Brightness of stars of [`REFERENCE_MAGNITUDE`]
+    brightness: f64,
+    /// Scale stars up by camera exposure, so they keep their look when exposure is lowered
+    /// for a bright accretion disk
+    auto_brightness: bool,
+    /// Exposure of the scene camera, set by the loader
+    camera_ev100: f64,
+    milky_way_color: Vector3<f64>,
+    noise: NoiseTexture3D,
+    worley: WorleyTexture3D,
+    cull_pixel_angle: f64,
+    cull_threshold: f64,
+    min_brightness: f64,
+    /// Seed of the star catalog and milky way textures
+    seed: u64,
+}
+
+impl StarSkyShader {
+    pub fn new() -> Self {
+        let mut shader = Self {
+            stars: Arc::new(StarCatalog::empty()),
+            star_count: 10_000,
+            luminosity: LuminosityFunction::default(),
+            brightness: 0.1,
+            auto_brightness: false,
+            camera_ev100: 0.0,
+            milky_way_color: Vector3::new(0.2, 0.3, 0.4),
+            noise: NoiseTexture3D::new(20.0, 0, 4),
+            worley: WorleyTexture3D::new(8.0, 0),
+            cull_pixel_angle: 0.0,
+            cull_threshold: DEFAULT_CULL_THRESHOLD,
+            min_brightness: 0.0,
+            seed: 0,
+        };
+
+        shader.regenerate_stars();
+
+        shader
+    }
+
+    fn regenerate_stars(&mut self) {
+        let (count, luminosity) = (self.star_count, self.luminosity);
+        let settings = [
+            count as u64,
+            luminosity.limiting_magnitude.to_bits(),
+            luminosity.slope.to_bits(),
+        ];
+
+        // stars depend only on the settings, the cache key also changes with the cache format
+        let seed = settings.into_iter().fold(self.seed, mix_seed);
+        let key = cache::content_hash(&(settings, StarCatalog::divisions(count), self.seed));
+
+        let stars = cache::load_or_create("star_sky", key, || {
+            StarCatalog::generate(count, luminosity, seed)
+        });
+
+        self.stars = Arc::new(stars);
+    }
+
+    /// Factor from catalog brightness to emitted light.
+    fn star_scale(&self) -> f64 {
+        if self.auto_brightness {
+            self.brightness * 2.0_f64.powf(self.camera_ev100)
+        } else {
+            self.brightness
+        }
+    }
+
+    /// Finds dimmest star which still adds at least `cull_threshold` to a pixel of
+    /// `cull_pixel_angle` size.
+    ///
+    /// Star is roughly a gaussian with variance `1 / pow`, so its total contribution is
+    /// `brightness * 2π / pow`, which is then spread over the pixel solid angle.
+    ///
+    /// The limit is in emitted brightness, not in catalog brightness.
+    fn update_culling(&mut self) {
+        if self.cull_pixel_angle <= 0.0 {
+            self.min_brightness = 0.0;
+            return;
+        }
+
+        let pixel_solid_angle = self.cull_pixel_angle.powi(2);
+        let k = self.cull_threshold * pixel_solid_angle * STAR_SHARPNESS / std::f64::consts::TAU;
+
+        // solves brightness / (2 - min(brightness, 1)) >= k
+        self.min_brightness = if k < 1.0 { 2.0 * k / (1.0 + k) } else { k };
+    }
+}
+
 impl Default for StarSkyShader {
     fn default() -> Self {
         Self::new()
@@ -97,7 +322,35 @@ impl Shader for StarSkyShader {
     fn set_parameter(&mut self, name: &str, value: Parameter) {
         match (name, value) {
             ("milky_way_color", Parameter::Vec3(c)) => self.milky_way_color = c,
-            ("star_count", Parameter::Usize(c)) => self.regenerate_stars(c),
+            ("star_count", Parameter::Usize(c)) => {
+                self.star_count = c;
+                self.regenerate_stars();
+            }
+            ("limiting_magnitude", Parameter::Float(m)) => {
+                self.luminosity.limiting_magnitude = m.max(BRIGHTEST_MAGNITUDE + 0.1);
+                self.regenerate_stars();
+            }
+            ("luminosity_slope", Parameter::Float(s)) => {
+                self.luminosity.slope = s.max(0.01);
+                self.regenerate_stars();
+            }
+            ("seed", Parameter::Usize(s)) => {
+                self.seed = s as u64;
+                self.noise = self.noise.with_seed(self.seed);
+                self.worley = self.worley.with_seed(self.seed);
+                self.regenerate_stars();
+            }
+            ("brightness", Parameter::Float(b)) => self.brightness = b,
+            ("auto_brightness", Parameter::Usize(a)) => self.auto_brightness = a != 0,
+            ("camera_ev100", Parameter::Float(ev)) => self.camera_ev100 = ev,
+            ("cull_pixel_angle", Parameter::Float(a)) => {
+                self.cull_pixel_angle = a;
+                self.update_culling();
+            }
+            ("cull_threshold", Parameter::Float(t)) => {
+                self.cull_threshold = t;
+                self.update_culling();
+            }
             _ => {}
         }
     }
@@ -105,6 +358,16 @@ impl Shader for StarSkyShader {
 
 impl BackgroundShader for StarSkyShader {
     fn emission_at(&self, ray: &Ray) -> Vector3<f64> {
+        self.emission(ray, None)
+    }
+
+    fn emission_over(&self, ray: &Ray, footprint: &Footprint) -> Vector3<f64> {
+        self.emission(ray, Some(footprint))
+    }
+}
+
+impl StarSkyShader {
+    fn emission(&self, ray: &Ray, footprint: Option<&Footprint>) -> Vector3<f64> {
         let mut color = Vector3::zero();
 
         let noise_factor = {
@@ -161,25 +424,39 @@ impl BackgroundShader for StarSkyShader {
         color += self.milky_way_color * noise_factor * 0.1;
 
         if let RayKind::Primary = ray.kind {
+            let stars = &self.stars;
+            let scale = self.star_scale();
+            // blurred stars have to stay within the searched sectors
+            let spread = footprint
+                .map(|f| PixelSpread::new(ray.direction, f, 1.0 / stars.y_divisions as f64));
+
             let (x, y) =
-                Self::sector_from_dir(self.star_x_divisions, self.star_y_divisions, &ray.direction);
+                StarCatalog::sector_from_dir(stars.x_divisions, stars.y_divisions, &ray.direction);
 
             for x_sector in (x as i32 - 1)..=(x as i32 + 1) {
                 for y_sector in (y as i32 - 1)..=(y as i32 + 1) {
                     let x_sector =
-                        (x_sector + self.star_x_divisions as i32) as usize % self.star_x_divisions;
-                    let y_sector = (y_sector.max(0) as usize).min(self.star_y_divisions - 1);
+                        (x_sector + stars.x_divisions as i32) as usize % stars.x_divisions;
+                    let y_sector = (y_sector.max(0) as usize).min(stars.y_divisions - 1);
+
+                    for star in stars.sector(x_sector, y_sector) {
+                        let brightness = star.brightness * scale;
+
+                        // sectors are sorted by brightness, the rest would be culled too
+                        if brightness < self.min_brightness {
+                            break;
+                        }
 
-                    for star in &self.stars[x_sector + y_sector * self.star_x_divisions] {
                         let dot = star.direction.dot(ray.direction);
+                        let pow = (2.0 - brightness.min(1.0)) * STAR_SHARPNESS;
 
-                        let pow = (2.0 - star.brightness) * 8_000_000.0;
+                        let value = match &spread {
+                            Some(spread) if dot > 0.9 => spread.star(star.direction, pow),
+                            None if dot > 0.999999 => dot.powf(pow),
+                            _ => continue,
+                        };
 
-                        if dot > 0.999999 {
-                            color += Vector3::from_value(dot.powf(pow))
-                                .mul_element_wise(star.color)
-                                * star.brightness;
-                        }
+                        color += star.color * (value * brightness);
                     }
                 }
             }
@@ -188,3 +465,84 @@ impl BackgroundShader for StarSkyShader {
         color
     }
 }
+
+/// Footprint of a pixel as a gaussian in the plane touching the sky at the ray direction.
+struct PixelSpread {
+    e1: Vector3<f64>,
+    e2: Vector3<f64>,
+    /// Covariance of the gaussian
+    xx: f64,
+    xy: f64,
+    yy: f64,
+}
+
+impl PixelSpread {
+    /// Spread with deviation of half the distance to neighbouring pixels, each of the footprint
+    /// vectors limited to `max_offset`.
+    fn new(direction: Vector3<f64>, footprint: &Footprint, max_offset: f64) -> Self {
+        let (e1, e2) = orthonormal_basis(direction);
+
+        let project = |v: Vector3<f64>| {
+            let (x, y) = (v.dot(e1), v.dot(e2));
+            let scale = max_offset / x.hypot(y).max(max_offset);
+
+            (x * scale, y * scale)
+        };
+
+        let (ax, ay) = project(footprint.dx);
+        let (bx, by) = project(footprint.dy);
+
+        Self {
+            e1,
+            e2,
+            xx: (ax * ax + bx * bx) / 4.0,
+            xy: (ax * ay + bx * by) / 4.0,
+            yy: (ay * ay + by * by) / 4.0,
+        }
+    }
+
+    /// Star of variance `1 / pow` blurred by the spread, with the same total light.
+    fn star(&self, direction: Vector3<f64>, pow: f64) -> f64 {
+        let variance = 1.0 / pow;
+        let (xx, xy, yy) = (self.xx + variance, self.xy, self.yy + variance);
+        let det = xx * yy - xy * xy;
+
+        let (x, y) = (direction.dot(self.e1), direction.dot(self.e2));
+        let distance = (yy * x * x - 2.0 * xy * x * y + xx * y * y) / det;
+
+        if distance > 50.0 {
+            return 0.0;
+        }
+
+        variance / det.sqrt() * (-distance / 2.0).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stars_of_seed_stay_in_place() {
+        cache::set_dir(Some(cache::temporary_dir()));
+
+        let mut shader = StarSkyShader::new();
+        shader.set_parameter("star_count", Parameter::Usize(1000));
+        shader.set_parameter("seed", Parameter::Usize(7));
+
+        // brightest stars of the first sectors, they move only if generation itself changes
+        let expected = [
+            Vector3::new(0.14894980893478799, -0.97786346020556, 0.14695920390740846),
+            Vector3::new(
+                -0.19612764317733347,
+                -0.9787584736788508,
+                -0.059714301331850544,
+            ),
+            Vector3::new(0.21715259222512961, -0.891035372129442, 0.3986235282870962),
+        ];
+
+        for (star, expected) in shader.stars.stars.iter().zip(expected) {
+            assert!((star.direction - expected).magnitude() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use blackhole::material::MaterialResult;
+use blackhole::math::rand_unit_vector;
+use blackhole::shader::{Parameter, ShadeContext, Shader, SolidShader};
+use blackhole::texture::{ImageTexture2D, Texture2D, Triplanar};
+use blackhole::{Ray, RayKind};
+
+use super::bump::SurfaceBump;
+use super::image::read_image;
+
+use cgmath::{Array, ElementWise, InnerSpace, Vector3, Zero};
+
+/// Diffuse surface with albedo from an image, placed by surface coordinates of the shape.
+///
+/// Shapes without surface coordinates get the image projected along the axes, see [`Triplanar`].
+#[derive(Clone)]
+pub struct TexturedSolidShader {
+    texture: Option<Triplanar<ImageTexture2D>>,
+    /// Why the image couldn't be read, reported by the loader
+    error: Option<String>,
+    /// Color multiplying the image
+    tint: Vector3<f64>,
+    /// Repetitions of the image along both surface coordinates, or per unit of length when
+    /// projected
+    tiling: f64,
+    /// Projects the image even on shapes with surface coordinates
+    triplanar: bool,
+    bump: SurfaceBump,
+}
+
+impl TexturedSolidShader {
+    pub fn new() -> Self {
+        Self {
+            texture: None,
+            error: None,
+            tint: Vector3::from_value(1.0),
+            tiling: 1.0,
+            triplanar: false,
+            bump: SurfaceBump::new(),
+        }
+    }
+
+    /// Error of the last image load, or missing image.
+    pub fn error(&self) -> Option<String> {
+        if let Some(error) = self.bump.error() {
+            return Some(error);
+        }
+
+        match (&self.texture, &self.error) {
+            (_, Some(error)) => Some(error.clone()),
+            (None, None) => Some("missing image path".into()),
+            (Some(_), None) => None,
+        }
+    }
+
+    fn albedo(&self, ray: &Ray, normal: Vector3<f64>, ctx: &ShadeContext) -> Vector3<f64> {
+        let color = match (&self.texture, ctx.uv) {
+            (Some(texture), Some(uv)) if !self.triplanar => {
+                texture.texture().color_at(uv * self.tiling)
+            }
+            (Some(texture), _) => texture.color_at(ray.location, normal),
+            (None, _) => Vector3::zero(),
+        };
+
+        color.mul_element_wise(self.tint)
+    }
+}
+
+impl Default for TexturedSolidShader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shader for TexturedSolidShader {
+    fn set_parameter(&mut self, name: &str, value: Parameter) {
+        if self.bump.set_parameter(name, &value) {
+            return;
+        }
+
+        match (name, value) {
+            ("path", Parameter::Path(path)) => match read_image(&path) {
+                Ok(image) => {
+                    let texture = ImageTexture2D::new(image);
+
+                    self.texture = Some(Triplanar::new(texture, self.tiling));
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(format!("could not read {}: {e}", path.display())),
+            },
+            ("tint", Parameter::Vec3(v)) => self.tint = v,
+            ("tiling", Parameter::Float(v)) => {
+                self.tiling = v;
+                if let Some(texture) = &mut self.texture {
+                    texture.set_scale(v);
+                }
+            }
+            ("triplanar", Parameter::Usize(t)) => self.triplanar = t != 0,
+            _ => {}
+        }
+    }
+}
+
+impl SolidShader for TexturedSolidShader {
+    fn shading_normal(&self, ray: &Ray, normal: Vector3<f64>, ctx: &ShadeContext) -> Vector3<f64> {
+        self.bump.normal_at(ray.location, normal, ctx)
+    }
+
+    fn material_at(
+        &self,
+        ray: &Ray,
+        normal: Vector3<f64>,
+        ctx: &ShadeContext,
+    ) -> (MaterialResult, Option<Ray>) {
+        let mat = MaterialResult {
+            albedo: self.albedo(ray, normal, ctx),
+            emission: Vector3::zero(),
+        };
+
+        let mut ray = if ctx.deterministic {
+            ray.reflect(normal)
+        } else {
+            Ray {
+                direction: (normal + rand_unit_vector()).normalize(),
+                kind: RayKind::Secondary,
+                ..*ray
+            }
+        };
+
+        ray.advance(0.01);
+
+        (mat, Some(ray))
+    }
+}
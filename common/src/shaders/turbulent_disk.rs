@@ -0,0 +1,152 @@
+use cgmath::{Vector3, Zero};
+
+use blackhole::material::MaterialResult;
+use blackhole::shader::{Parameter, ShadeContext, Shader, VolumetricShader};
+use blackhole::texture::{CurlNoiseTexture3D, Fbm, NoiseTexture3D, Texture3D, TextureKind};
+use blackhole::{Ray, BLACKBODY_LUT};
+
+use super::disk::{swirl_coords, swirl_density, swirl_temperature, DiskFrame, SWIRL_THICKNESS};
+use super::{float_parameter, set_noise_parameter, strength_ratio};
+
+/// Emitting accretion disk with matter pulled into streaks by swirling flow.
+///
+/// Like [`super::BlackHoleEmitterShader`], but the noise is looked up at points carried along
+/// curl noise, which stretches its clouds into thin filaments.
+#[derive(Clone)]
+pub struct TurbulentDiskShader {
+    noise: TextureKind,
+    curl: CurlNoiseTexture3D,
+    /// Distance in noise coordinates points are carried along the flow
+    advection: f64,
+    advection_steps: usize,
+    strength: f64,
+    temp_scale: f64,
+    disk: DiskFrame,
+}
+
+impl TurbulentDiskShader {
+    pub fn new() -> Self {
+        Self {
+            noise: NoiseTexture3D::new(6.0, 0, 2).into(),
+            curl: CurlNoiseTexture3D::new(1.0, 0, 2),
+            advection: 0.2,
+            advection_steps: 4,
+            strength: 5.0,
+            temp_scale: 1.0,
+            disk: DiskFrame::new(SWIRL_THICKNESS),
+        }
+    }
+
+    /// Noise coordinates of a point in the space of the default disk, moved along the flow.
+    fn advected_coords(&self, local: Vector3<f64>, ctx: &ShadeContext) -> Vector3<f64> {
+        let mut coords = swirl_coords(local) + ctx.texture_offset();
+        let step = self.advection / self.advection_steps as f64;
+
+        for _ in 0..self.advection_steps {
+            coords += self.curl.color_at(coords) * step;
+        }
+
+        coords
+    }
+}
+
+impl Default for TurbulentDiskShader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shader for TurbulentDiskShader {
+    fn set_parameter(&mut self, name: &str, value: Parameter) {
+        if let ("seed", Parameter::Usize(s)) = (name, &value) {
+            self.curl = self.curl.with_seed(*s as u64);
+        }
+
+        if self.disk.set_parameter(name, &value)
+            || set_noise_parameter(&mut self.noise, name, &value)
+        {
+            return;
+        }
+
+        let float = float_parameter(&value);
+
+        match (name, value, float) {
+            ("strength", Parameter::Float(f), _) => self.strength = f,
+            ("temp_scale", Parameter::Float(f), _) => self.temp_scale = f,
+            ("curl_scale", _, Some(s)) if s > 0.0 => self.curl = self.curl.with_scale(s),
+            ("curl_octaves", Parameter::Usize(o), _) => {
+                let octaves = o.clamp(1, 16) as u8;
+
+                self.curl = self.curl.with_fbm(Fbm {
+                    octaves,
+                    ..self.curl.fbm()
+                });
+            }
+            ("advection", _, Some(a)) => self.advection = a,
+            ("advection_steps", Parameter::Usize(s), _) => self.advection_steps = s.clamp(1, 64),
+            _ => {}
+        }
+    }
+
+    fn emits_light(&self) -> bool {
+        self.strength > 0.0
+    }
+
+    fn parameter(&self, name: &str) -> Option<Parameter> {
+        match name {
+            "strength" => Some(Parameter::Float(self.strength)),
+            _ => None,
+        }
+    }
+
+    fn emission_scale(&self, name: &str, value: &Parameter) -> Option<Vector3<f64>> {
+        match (name, value) {
+            ("strength", Parameter::Float(f)) => strength_ratio(self.strength, *f),
+            _ => None,
+        }
+    }
+}
+
+impl VolumetricShader for TurbulentDiskShader {
+    fn density_at(&self, position: Vector3<f64>, ctx: &ShadeContext) -> f64 {
+        let position = self.disk.local(position);
+
+        if self.disk.is_cut_off(position) {
+            return 0.0;
+        }
+
+        let noise = self.noise.color_at(self.advected_coords(position, ctx));
+
+        swirl_density(position, noise, self.disk.inner())
+    }
+
+    fn material_at(&self, ray: &Ray, ctx: &ShadeContext) -> (MaterialResult, Option<Ray>) {
+        let location = self.disk.local(ray.location);
+
+        let noise = self.noise.color_at(self.advected_coords(location, ctx));
+
+        let temp = swirl_temperature(location, noise) * self.temp_scale;
+
+        let mat = MaterialResult {
+            albedo: Vector3::zero(),
+            emission: BLACKBODY_LUT.lookup(temp) * self.strength,
+        };
+
+        (mat, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_numbers_set_float_parameters() {
+        let mut shader = TurbulentDiskShader::new();
+        shader.set_parameter("curl_scale", Parameter::Usize(3));
+        shader.set_parameter("advection", Parameter::Usize(1));
+
+        assert_eq!(shader.curl.scale(), 3.0);
+        assert_eq!(shader.advection, 1.0);
+    }
+}
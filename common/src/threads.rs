@@ -0,0 +1,82 @@
+//! Choice of rendering threads for the hardware the renderer runs on.
+
+use std::fmt::{Display, Formatter};
+
+/// Niceness of the process in background mode, from 0 for normal to 19 for lowest priority.
+#[cfg(unix)]
+const BACKGROUND_NICENESS: i32 = 10;
+
+/// Rendering threads picked for requested count and available cores.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ThreadCount {
+    pub threads: usize,
+    pub physical_cores: usize,
+    pub logical_cores: usize,
+    /// Count was picked from cores, not requested
+    pub automatic: bool,
+}
+
+impl ThreadCount {
+    /// Requested count, or with 0 one thread per physical core, or per logical core with `logical`.
+    ///
+    /// Hyper-threads of a core share most of its resources, so they add little to marching speed
+    /// and only make the desktop less responsive.
+    pub fn pick(requested: usize, logical: bool) -> Self {
+        let logical_cores = num_cpus::get().max(1);
+        let physical_cores = num_cpus::get_physical().clamp(1, logical_cores);
+
+        let threads = match (requested, logical) {
+            (0, false) => physical_cores,
+            (0, true) => logical_cores,
+            (n, _) => n,
+        };
+
+        Self {
+            threads,
+            physical_cores,
+            logical_cores,
+            automatic: requested == 0,
+        }
+    }
+
+    /// More threads than logical cores, they only take turns on them.
+    pub fn oversubscribed(&self) -> bool {
+        self.threads > self.logical_cores
+    }
+}
+
+impl Display for ThreadCount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} thread{}{} ({} physical, {} logical cores)",
+            self.threads,
+            if self.threads == 1 { "" } else { "s" },
+            if self.automatic { ", automatic" } else { "" },
+            self.physical_cores,
+            self.logical_cores
+        )
+    }
+}
+
+/// Lowers priority of the process, so rendering doesn't slow down other programs.
+///
+/// Affects threads started afterwards, call it before building thread pools.
+pub fn lower_priority() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        // `0` is the calling thread on Linux, or the whole process elsewhere
+        let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, BACKGROUND_NICENESS) };
+
+        if result != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
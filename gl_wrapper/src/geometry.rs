@@ -4,6 +4,7 @@ use thiserror::Error;
 pub struct GeometryBuilder<'a> {
     attributes: Vec<VertexAttribute>,
     data: &'a [f32],
+    primitive: Primitive,
 }
 
 impl<'a> GeometryBuilder<'a> {
@@ -11,6 +12,7 @@ impl<'a> GeometryBuilder<'a> {
         Self {
             data,
             attributes: Vec::new(),
+            primitive: Primitive::Triangles,
         }
     }
 
@@ -19,6 +21,11 @@ impl<'a> GeometryBuilder<'a> {
         self
     }
 
+    pub fn with_primitive(mut self, primitive: Primitive) -> Self {
+        self.primitive = primitive;
+        self
+    }
+
     pub fn build(self) -> Result<Geometry, GBError> {
         let total_len: usize = self.attributes.iter().map(|a| a.size()).sum();
 
@@ -63,7 +70,12 @@ impl<'a> GeometryBuilder<'a> {
 
         let vertices = self.data.len() / total_len;
 
-        Ok(Geometry { vao, vbo, vertices })
+        Ok(Geometry {
+            vao,
+            vbo,
+            vertices,
+            primitive: self.primitive,
+        })
     }
 }
 
@@ -89,10 +101,28 @@ impl VertexAttribute {
     }
 }
 
+#[derive(Copy, Clone)]
+pub enum Primitive {
+    Triangles,
+    LineStrip,
+    Lines,
+}
+
+impl Primitive {
+    pub fn to_gl_const(&self) -> u32 {
+        match self {
+            Self::Triangles => gl::TRIANGLES,
+            Self::LineStrip => gl::LINE_STRIP,
+            Self::Lines => gl::LINES,
+        }
+    }
+}
+
 pub struct Geometry {
     vao: u32,
     vbo: u32,
     vertices: usize,
+    primitive: Primitive,
 }
 
 impl Geometry {
@@ -102,6 +132,9 @@ impl Geometry {
     pub fn vertices(&self) -> usize {
         self.vertices
     }
+    pub fn primitive(&self) -> Primitive {
+        self.primitive
+    }
 }
 
 impl Drop for Geometry {
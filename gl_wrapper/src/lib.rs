@@ -10,6 +10,7 @@ pub const QUAD: [f32; 12] = [
 
 pub mod framebuffer;
 pub mod geometry;
+pub mod post;
 pub mod program;
 pub mod renderer;
 pub mod texture;
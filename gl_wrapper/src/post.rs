@@ -0,0 +1,160 @@
+use crate::framebuffer::FrameBuffer;
+use crate::geometry::Geometry;
+use crate::program::Program;
+use crate::renderer::GlRenderer;
+use crate::texture::{Texture2D, Texture3D, TextureFilter};
+
+/// Texture read by a post-processing pass.
+#[derive(Copy, Clone, Debug)]
+pub enum PassInput {
+    /// Image the pipeline was run on
+    Source,
+    /// Output of pass with given index
+    Pass(usize),
+}
+
+/// Fullscreen pass drawn with a single program into its own texture.
+///
+/// Inputs are bound to texture units in the order they were given, volume textures after them.
+pub struct PostPass {
+    program: Program,
+    inputs: Vec<PassInput>,
+    volumes: Vec<Texture3D>,
+    downscale: u32,
+    target: Option<(Texture2D, FrameBuffer)>,
+}
+
+impl PostPass {
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            inputs: vec![PassInput::Source],
+            volumes: Vec::new(),
+            downscale: 1,
+            target: None,
+        }
+    }
+
+    pub fn with_inputs(mut self, inputs: &[PassInput]) -> Self {
+        self.inputs = inputs.to_vec();
+        self
+    }
+
+    /// Adds volume texture owned by the pass, like a color lookup table.
+    pub fn with_volume(mut self, texture: Texture3D) -> Self {
+        self.volumes.push(texture);
+        self
+    }
+
+    /// Renders the pass at fraction of the pipeline resolution.
+    pub fn with_downscale(mut self, downscale: u32) -> Self {
+        self.downscale = downscale.max(1);
+        self
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    fn size(&self, width: u32, height: u32) -> (u32, u32) {
+        (
+            (width / self.downscale).max(1),
+            (height / self.downscale).max(1),
+        )
+    }
+}
+
+/// Chain of passes, the last one draws into the default framebuffer.
+pub struct PostPipeline {
+    passes: Vec<PostPass>,
+    width: u32,
+    height: u32,
+}
+
+impl PostPipeline {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            passes: Vec::new(),
+            width,
+            height,
+        }
+    }
+
+    /// Adds pass to the end of the pipeline and returns its index.
+    ///
+    /// Passes can only read outputs of passes added before them.
+    pub fn add_pass(&mut self, mut pass: PostPass) -> usize {
+        let index = self.passes.len();
+
+        assert!(
+            pass.inputs.iter().all(|input| match input {
+                PassInput::Source => true,
+                PassInput::Pass(i) => *i < index,
+            }),
+            "post pass reads output of a later pass"
+        );
+
+        let (w, h) = pass.size(self.width, self.height);
+        let texture = Texture2D::empty(w, h, TextureFilter::Linear);
+        let fb = FrameBuffer::from_texture(&texture).unwrap();
+        pass.target = Some((texture, fb));
+
+        self.passes.push(pass);
+
+        index
+    }
+
+    pub fn pass(&self, index: usize) -> &PostPass {
+        &self.passes[index]
+    }
+
+    /// Last pass, drawing to the window.
+    pub fn output(&self) -> &PostPass {
+        self.passes.last().expect("pipeline has passes")
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+
+        for pass in &self.passes {
+            let (w, h) = pass.size(width, height);
+
+            if let Some((texture, _)) = &pass.target {
+                texture.resize(w, h);
+            }
+        }
+    }
+
+    pub fn run(&self, renderer: &mut GlRenderer, quad: &Geometry, source: &Texture2D) {
+        let last = self.passes.len().saturating_sub(1);
+
+        for (i, pass) in self.passes.iter().enumerate() {
+            if i == last {
+                FrameBuffer::bind_default();
+                renderer.resize(self.width, self.height);
+            } else {
+                let (w, h) = pass.size(self.width, self.height);
+                pass.target.as_ref().unwrap().1.bind();
+                renderer.resize(w, h);
+            }
+
+            renderer.clear_color(0.0, 0.0, 0.0);
+
+            for (unit, input) in pass.inputs.iter().enumerate() {
+                match input {
+                    PassInput::Source => source.bind(unit as u8),
+                    PassInput::Pass(p) => {
+                        self.passes[*p].target.as_ref().unwrap().0.bind(unit as u8)
+                    }
+                }
+            }
+
+            for (i, volume) in pass.volumes.iter().enumerate() {
+                volume.bind((pass.inputs.len() + i) as u8);
+            }
+
+            renderer.draw(quad, &pass.program);
+        }
+    }
+}
@@ -133,6 +133,36 @@ impl Program {
     pub fn get_id(&self) -> GLuint {
         self.id
     }
+
+    /// Sets uniform value, unknown names are ignored.
+    pub fn set_uniform(&self, name: &str, value: Uniform) {
+        let name = CString::new(name).unwrap();
+
+        unsafe {
+            let location = gl::GetUniformLocation(self.id, name.as_ptr());
+
+            if location < 0 {
+                return;
+            }
+
+            match value {
+                Uniform::Float(v) => gl::ProgramUniform1f(self.id, location, v),
+                Uniform::Vec2(x, y) => gl::ProgramUniform2f(self.id, location, x, y),
+                Uniform::Vec3(x, y, z) => gl::ProgramUniform3f(self.id, location, x, y, z),
+                Uniform::Vec4(x, y, z, w) => gl::ProgramUniform4f(self.id, location, x, y, z, w),
+                Uniform::Int(v) => gl::ProgramUniform1i(self.id, location, v),
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Uniform {
+    Float(f32),
+    Vec2(f32, f32),
+    Vec3(f32, f32, f32),
+    Vec4(f32, f32, f32, f32),
+    Int(i32),
 }
 
 impl Drop for Program {
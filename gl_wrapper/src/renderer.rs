@@ -19,7 +19,11 @@ impl GlRenderer {
 
         unsafe {
             gl::BindVertexArray(geometry.vao());
-            gl::DrawArrays(gl::TRIANGLES, 0, geometry.vertices() as i32);
+            gl::DrawArrays(
+                geometry.primitive().to_gl_const(),
+                0,
+                geometry.vertices() as i32,
+            );
         }
     }
 
@@ -29,6 +33,13 @@ impl GlRenderer {
         }
     }
 
+    /// Draws into a part of the target, with `x` and `y` of its bottom left corner.
+    pub fn viewport(&self, x: u32, y: u32, width: u32, height: u32) {
+        unsafe {
+            gl::Viewport(x as i32, y as i32, width as i32, height as i32);
+        }
+    }
+
     pub fn clear_color(&self, r: f32, g: f32, b: f32) {
         unsafe {
             gl::ClearColor(r, g, b, 1.0);
@@ -53,6 +53,53 @@ impl Texture2D {
         Ok(Self { id })
     }
 
+    /// Creates texture with undefined content, to be rendered into.
+    pub fn empty(width: u32, height: u32, filter: TextureFilter) -> Self {
+        let mut id = 0;
+
+        unsafe {
+            gl::GenTextures(1, (&mut id) as *mut u32);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                filter.to_gl_const() as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MAG_FILTER,
+                filter.to_gl_const() as i32,
+            );
+        }
+
+        let texture = Self { id };
+        texture.resize(width, height);
+
+        texture
+    }
+
+    /// Reallocates texture storage, discarding its content.
+    pub fn resize(&self, width: u32, height: u32) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA32F as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGBA,
+                gl::FLOAT,
+                std::ptr::null(),
+            );
+        }
+    }
+
     pub fn update(
         &self,
         width: u32,
@@ -100,6 +147,63 @@ impl Drop for Texture2D {
     }
 }
 
+/// Volume texture of RGB values with linear filtering, like color lookup tables.
+pub struct Texture3D {
+    id: u32,
+}
+
+impl Texture3D {
+    /// Texture with `size` texels along each axis, `data` has x changing fastest, then y and z.
+    pub fn new_rgb(size: u32, data: &[f32]) -> Result<Self, TextureError> {
+        if (size as usize).pow(3) * 3 > data.len() {
+            return Err(TextureError::InvalidSrcLength);
+        }
+
+        let mut id = 0;
+
+        unsafe {
+            gl::GenTextures(1, (&mut id) as *mut u32);
+            gl::BindTexture(gl::TEXTURE_3D, id);
+
+            for wrap in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+                gl::TexParameteri(gl::TEXTURE_3D, wrap, gl::CLAMP_TO_EDGE as i32);
+            }
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+
+            gl::TexImage3D(
+                gl::TEXTURE_3D,
+                0,
+                gl::RGB32F as i32,
+                size as i32,
+                size as i32,
+                size as i32,
+                0,
+                gl::RGB,
+                gl::FLOAT,
+                data.as_ptr() as *const c_void,
+            );
+        }
+
+        Ok(Self { id })
+    }
+
+    pub fn bind(&self, unit: u8) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+            gl::BindTexture(gl::TEXTURE_3D, self.id)
+        }
+    }
+}
+
+impl Drop for Texture3D {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, (&self.id) as *const u32);
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum TextureError {
     #[error("Invalid source data length")]
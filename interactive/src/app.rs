@@ -14,30 +14,43 @@ use flume::{Receiver, Sender};
 
 use std::ffi::CString;
 use std::num::NonZeroU32;
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use cgmath::{Deg, InnerSpace, Matrix3};
+use cgmath::{Deg, InnerSpace, Matrix3, SquareMatrix, Vector3, Zero};
 
 use thiserror::Error;
 
 use winit::dpi::{PhysicalPosition, PhysicalSize, Size};
-use winit::event::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
+use winit::event::{ElementState, Event, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::{Window, WindowBuilder};
 
+use blackhole::animation::Animation;
+use blackhole::camera::Camera;
 use blackhole::framebuffer::FrameBuffer;
-use blackhole::scene::Scene;
-
-use blackhole_common::scene_loader::SceneLoader;
-
-use gl_wrapper::geometry::{GeometryBuilder, VertexAttribute};
-use gl_wrapper::program::ProgramBuilder;
+use blackhole::object::shape::{Shape, Transformed};
+use blackhole::object::Object;
+use blackhole::post::{CubeLut, PostSettings, SENSOR_SCALE};
+use blackhole::postprocess::Tonemap;
+use blackhole::scene::SlicePlane;
+use blackhole::shader::Parameter;
+
+use blackhole_common::scene_loader::{SceneLoader, SceneWriter};
+
+use gl_wrapper::geometry::{Geometry, GeometryBuilder, Primitive, VertexAttribute};
+use gl_wrapper::post::{PassInput, PostPass, PostPipeline};
+use gl_wrapper::program::{PBError, Program, ProgramBuilder, Uniform};
 use gl_wrapper::renderer::GlRenderer;
-use gl_wrapper::texture::{Texture2D, TextureFilter, TextureFormats};
+use gl_wrapper::texture::{Texture2D, Texture3D, TextureFilter, TextureFormats};
 use gl_wrapper::QUAD;
 
-use crate::renderer::{InteractiveRenderer, RenderInMsg, RenderOutMsg};
+use crate::framing::Framing;
+use crate::renderer::{InteractiveRenderer, PassTimings, RenderInMsg, RenderOutMsg};
+use crate::shaders::ShaderSources;
+use crate::timeline::Timeline;
 
 pub struct App {
     event_loop: EventLoop<()>,
@@ -47,10 +60,21 @@ pub struct App {
     tx_in: Sender<RenderInMsg>,
     rx_out: Receiver<RenderOutMsg>,
     cpu_framebuffer: Arc<RwLock<FrameBuffer>>,
+    post_settings: PostSettings,
+    /// Grading of the preview, not applied to saved images
+    lut: Option<CubeLut>,
+    framing: Framing,
+    shaders: ShaderSources,
 }
 
 impl App {
-    pub fn new(mut renderer: InteractiveRenderer) -> Result<Self, AppError> {
+    pub fn new(
+        mut renderer: InteractiveRenderer,
+        post_settings: PostSettings,
+        lut: Option<CubeLut>,
+        framing: Framing,
+        shaders: ShaderSources,
+    ) -> Result<Self, AppError> {
         let event_loop = EventLoop::new();
         let window_builder = WindowBuilder::new()
             .with_inner_size(Size::Physical(PhysicalSize::new(1280, 720)))
@@ -106,6 +130,10 @@ impl App {
             tx_in,
             rx_out,
             cpu_framebuffer,
+            post_settings,
+            lut,
+            framing,
+            shaders,
         };
 
         Ok(app)
@@ -118,22 +146,41 @@ impl App {
             .with_attribute(VertexAttribute::Vec2)
             .build()
             .unwrap();
-        let program = ProgramBuilder::new(
-            include_str!("gl_shaders/quad.glsl"),
-            include_str!("gl_shaders/output.glsl"),
-        )
-        .build()
-        .unwrap();
-
-        let program_copy = ProgramBuilder::new(
-            include_str!("gl_shaders/quad.glsl"),
-            include_str!("gl_shaders/copy.glsl"),
+        let mut framing = self.framing;
+        let window_size = self.gl_window.window.inner_size();
+
+        // tonemapping of the loaded scene, used when not set from command line
+        let mut scene_tonemap = Tonemap::default();
+        let mut programs = Programs::build(
+            &self.shaders,
+            &self.post_settings,
+            self.lut.as_ref(),
+            scene_tonemap,
+            &framing,
+            window_size,
         )
-        .build()
-        .unwrap();
+        .unwrap_or_else(|e| {
+            eprintln!("Could not build shaders, using built-in ones: {e}");
+
+            let shaders = ShaderSources::embedded();
+            Programs::build(
+                &shaders,
+                &self.post_settings,
+                self.lut.as_ref(),
+                scene_tonemap,
+                &framing,
+                window_size,
+            )
+            .unwrap()
+        });
 
         self.tx_in.send(RenderInMsg::Restart).unwrap();
 
+        let rect = framing.rect(window_size);
+        self.tx_in
+            .send(RenderInMsg::Resize(rect.width, rect.height))
+            .unwrap();
+
         let texture = {
             let read_lock = self.cpu_framebuffer.read().unwrap();
 
@@ -163,9 +210,30 @@ impl App {
         let mut last_pos = PhysicalPosition::new(0.0, 0.0);
         let mut lmb_pressed = false;
         let mut rmb_pressed = false;
-        let mut scene: Option<Scene> = None;
+        let mut camera: Option<Camera> = None;
+
+        // slice plane of the loaded scene, and whether the scene is cut by any plane
+        let mut scene_slice: Option<SlicePlane> = None;
+        let mut slicing = false;
+
+        let mut ray_debug = false;
+        let mut ray_paths: Vec<Vec<Vector3<f64>>> = Vec::new();
+        let mut ray_geometry: Vec<Geometry> = Vec::new();
+
+        // objects of the loaded scene as last sent to the renderer, and the one being edited
+        let mut objects: Vec<Object> = Vec::new();
+        let mut selection: Option<Selection> = None;
 
         let mut keys = ActiveKeys::default();
+        let mut modifiers = ModifiersState::empty();
+        let mut scene_path: Option<PathBuf> = None;
+
+        let mut animation = Animation::default();
+        let mut timeline: Option<Timeline> = None;
+        // animation time to send to the renderer after handling events
+        let mut pending_time: Option<f64> = None;
+        // time of texture uploads since the last timings report, with their count
+        let mut upload_time = (Duration::ZERO, 0);
 
         self.event_loop
             .run(move |event, _window_target, control_flow| {
@@ -182,6 +250,8 @@ impl App {
                                         read_lock.height() as u32 / scale.scale(),
                                     );
 
+                                    let start = Instant::now();
+
                                     texture
                                         .update(
                                             w,
@@ -190,12 +260,69 @@ impl App {
                                             TextureFormats::RgbaF32,
                                         )
                                         .unwrap();
+
+                                    upload_time.0 += start.elapsed();
+                                    upload_time.1 += 1;
                                 }
+                                RenderOutMsg::Timings(timings) => {
+                                    let upload = upload_time.0 / upload_time.1.max(1);
+                                    upload_time = (Duration::ZERO, 0);
+
+                                    self.gl_window
+                                        .window
+                                        .set_title(&timings_title(&timings, upload));
+                                }
+                                RenderOutMsg::RayPaths(paths) => {
+                                    ray_paths = paths;
+
+                                    if let Some(camera) = &camera {
+                                        let size = self.gl_window.window.inner_size();
+                                        ray_geometry =
+                                            ray_path_geometry(&ray_paths, camera, &framing, size);
+                                    }
+                                }
+                            }
+                        }
+
+                        if self.shaders.poll_changes() {
+                            let size = self.gl_window.window.inner_size();
+
+                            match Programs::build(
+                                &self.shaders,
+                                &self.post_settings,
+                                self.lut.as_ref(),
+                                scene_tonemap,
+                                &framing,
+                                size,
+                            ) {
+                                Ok(p) => {
+                                    programs = p;
+                                    eprintln!("Reloaded shaders");
+                                }
+                                Err(e) => eprintln!("Could not reload shaders: {e}"),
+                            }
+                        }
+
+                        if let Some(time) = timeline.as_mut().and_then(Timeline::tick) {
+                            pending_time = Some(time);
+                        }
+
+                        if let Some(time) = pending_time.take() {
+                            if let Some(camera) = &mut camera {
+                                if let Some(track) = &animation.camera {
+                                    track.apply(camera, time);
+                                }
+
+                                let size = self.gl_window.window.inner_size();
+                                ray_geometry =
+                                    ray_path_geometry(&ray_paths, camera, &framing, size);
                             }
+
+                            self.tx_in.send(RenderInMsg::TimeChange(time)).unwrap();
                         }
 
-                        if let Some(scene) = &mut scene {
-                            let camera_delta = {
+                        if let Some(camera) = &mut camera {
+                            let delta = {
                                 let mut x = 0.0;
                                 let mut y = 0.0;
                                 let mut z = 0.0;
@@ -223,17 +350,34 @@ impl App {
                                     z += 1.0;
                                 }
 
-                                scene.camera.side() * (x / 50.0)
-                                    + scene.camera.forward() * (y / 50.0)
-                                    + scene.camera.up() * (z / 50.0)
+                                camera.side() * (x / 50.0)
+                                    + camera.forward() * (y / 50.0)
+                                    + camera.up() * (z / 50.0)
                             };
 
-                            scene.camera.location += camera_delta;
+                            // with alt held the selected object moves instead of the camera
+                            let selected = selection.as_mut().filter(|_| modifiers.alt());
+
+                            if let Some(selected) = selected {
+                                if delta.magnitude2() != 0.0 {
+                                    selected.offset += delta;
+
+                                    let shape = selected.shape();
+                                    objects[selected.index].shape = Arc::clone(&shape);
+                                    self.tx_in
+                                        .send(RenderInMsg::ObjectTransform(selected.index, shape))
+                                        .unwrap();
+                                }
+                            } else if delta.magnitude2() != 0.0 {
+                                camera.location += delta;
 
-                            if camera_delta.magnitude2() != 0.0 {
                                 self.tx_in
-                                    .send(RenderInMsg::SceneChange(scene.clone()))
+                                    .send(RenderInMsg::CameraUpdate(camera.clone()))
                                     .unwrap();
+
+                                let size = self.gl_window.window.inner_size();
+                                ray_geometry =
+                                    ray_path_geometry(&ray_paths, camera, &framing, size);
                             }
                         }
 
@@ -252,6 +396,8 @@ impl App {
                                     NonZeroU32::new(size.height).unwrap(),
                                 );
                                 gl_renderer.resize(size.width, size.height);
+                                programs.post.resize(size.width, size.height);
+                                programs.set_frame(&framing, size);
                                 texture_fb
                                     .update(
                                         size.width,
@@ -260,26 +406,39 @@ impl App {
                                         TextureFormats::RgbaF32,
                                     )
                                     .unwrap();
+
+                                let rect = framing.rect(size);
                                 self.tx_in
-                                    .send(RenderInMsg::Resize(size.width, size.height))
+                                    .send(RenderInMsg::Resize(rect.width, rect.height))
                                     .unwrap();
                             }
                         }
                         WindowEvent::CursorMoved { position, .. } => {
                             let delta = (last_pos.x - position.x, last_pos.y - position.y);
 
-                            if let Some(scene) = &mut scene {
+                            if let Some(timeline) = &mut timeline {
+                                if timeline.scrubbing {
+                                    let size = self.gl_window.window.inner_size();
+                                    pending_time = Some(timeline.scrub_to(position.x, size));
+                                }
+                            }
+
+                            if let Some(camera) = &mut camera {
                                 if rmb_pressed {
                                     let rot = Matrix3::from_angle_y(Deg(delta.0 / 10.0))
                                         * Matrix3::from_axis_angle(
-                                            scene.camera.side(),
+                                            camera.side(),
                                             Deg(delta.1 / 10.0),
                                         );
 
-                                    scene.camera.rot_mat = rot * scene.camera.rot_mat;
+                                    camera.rot_mat = rot * camera.rot_mat;
                                     self.tx_in
-                                        .send(RenderInMsg::SceneChange(scene.clone()))
+                                        .send(RenderInMsg::CameraUpdate(camera.clone()))
                                         .unwrap();
+
+                                    let size = self.gl_window.window.inner_size();
+                                    ray_geometry =
+                                        ray_path_geometry(&ray_paths, camera, &framing, size);
                                 }
                             }
 
@@ -287,7 +446,36 @@ impl App {
                         }
                         WindowEvent::MouseInput { state, button, .. } => {
                             if let MouseButton::Left = button {
-                                lmb_pressed = state == ElementState::Pressed
+                                lmb_pressed = state == ElementState::Pressed;
+
+                                let size = self.gl_window.window.inner_size();
+
+                                if let Some(timeline) = &mut timeline {
+                                    timeline.scrubbing =
+                                        lmb_pressed && timeline.contains(last_pos.y, size);
+
+                                    if timeline.scrubbing {
+                                        pending_time = Some(timeline.scrub_to(last_pos.x, size));
+                                        return;
+                                    }
+                                }
+
+                                let (x, y) = framing.rect(size).relative(last_pos);
+                                let in_frame = (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y);
+
+                                if lmb_pressed && ray_debug && in_frame {
+                                    // small cross around the cursor
+                                    let d = 0.01;
+                                    let bundle = vec![
+                                        (x, y),
+                                        (x - d, y),
+                                        (x + d, y),
+                                        (x, y - d),
+                                        (x, y + d),
+                                    ];
+
+                                    self.tx_in.send(RenderInMsg::TraceRays(bundle)).unwrap();
+                                }
                             }
                             if let MouseButton::Right = button {
                                 rmb_pressed = state == ElementState::Pressed
@@ -300,6 +488,19 @@ impl App {
                             Some(VirtualKeyCode::A) => {
                                 keys.a = input.state == ElementState::Pressed
                             }
+                            Some(VirtualKeyCode::S)
+                                if modifiers.ctrl() && input.state == ElementState::Pressed =>
+                            {
+                                if let (Some(path), Some(camera)) = (&scene_path, &camera) {
+                                    let res = SceneWriter::from_path(path)
+                                        .and_then(|w| w.with_camera(camera).write(path));
+
+                                    match res {
+                                        Ok(()) => eprintln!("Saved camera to {:?}", path),
+                                        Err(e) => eprintln!("Could not save scene: {e}"),
+                                    }
+                                }
+                            }
                             Some(VirtualKeyCode::S) => {
                                 keys.s = input.state == ElementState::Pressed
                             }
@@ -312,18 +513,170 @@ impl App {
                             Some(VirtualKeyCode::E) => {
                                 keys.e = input.state == ElementState::Pressed
                             }
+                            Some(VirtualKeyCode::R) if input.state == ElementState::Pressed => {
+                                ray_debug = !ray_debug;
+                                eprintln!(
+                                    "Ray visualization {}, click to trace rays",
+                                    if ray_debug { "enabled" } else { "disabled" }
+                                );
+
+                                ray_paths.clear();
+                                ray_geometry.clear();
+                            }
+                            Some(VirtualKeyCode::X) if input.state == ElementState::Pressed => {
+                                let camera = match &camera {
+                                    Some(camera) => camera,
+                                    None => return,
+                                };
+
+                                slicing = !slicing;
+
+                                // without a plane in the scene, cut through the point on the
+                                // view axis nearest to the world origin
+                                let slice = slicing.then(|| {
+                                    scene_slice.unwrap_or_else(|| {
+                                        let forward = camera.forward();
+                                        let depth = -camera.location.dot(forward);
+                                        let point = camera.location + forward * depth.max(0.0);
+
+                                        SlicePlane::new(point, -forward)
+                                    })
+                                });
+
+                                eprintln!(
+                                    "Slice plane {}",
+                                    if slicing { "enabled" } else { "disabled" }
+                                );
+                                self.tx_in.send(RenderInMsg::SliceChange(slice)).unwrap();
+                            }
+                            Some(VirtualKeyCode::Tab) if input.state == ElementState::Pressed => {
+                                if objects.is_empty() {
+                                    return;
+                                }
+
+                                let index = selection.as_ref().map_or(0, |s| s.index + 1);
+                                selection = (index < objects.len())
+                                    .then(|| Selection::new(index, &objects[index]));
+
+                                match &selection {
+                                    Some(s) => eprintln!(
+                                        "Selected object {} ({}), alt with movement keys moves \
+                                         it, +/- change its emission",
+                                        s.index,
+                                        objects[s.index].shader_name()
+                                    ),
+                                    None => eprintln!("Deselected objects"),
+                                }
+                            }
+                            Some(
+                                key @ (VirtualKeyCode::Equals
+                                | VirtualKeyCode::Plus
+                                | VirtualKeyCode::NumpadAdd
+                                | VirtualKeyCode::Minus
+                                | VirtualKeyCode::NumpadSubtract),
+                            ) if input.state == ElementState::Pressed => {
+                                let index = match &selection {
+                                    Some(s) => s.index,
+                                    None => return,
+                                };
+
+                                // half a stop per press
+                                let factor = if matches!(
+                                    key,
+                                    VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract
+                                ) {
+                                    std::f64::consts::FRAC_1_SQRT_2
+                                } else {
+                                    std::f64::consts::SQRT_2
+                                };
+
+                                match scaled_emission(&objects[index], factor) {
+                                    Some((name, value)) => {
+                                        objects[index].set_shader_parameter(name, value.clone());
+                                        self.tx_in
+                                            .send(RenderInMsg::ShaderParam(
+                                                index,
+                                                name.to_string(),
+                                                value,
+                                            ))
+                                            .unwrap();
+                                    }
+                                    None => eprintln!("Object {index} has no emission to change"),
+                                }
+                            }
+                            Some(VirtualKeyCode::G) if input.state == ElementState::Pressed => {
+                                framing.guides = framing.guides.next();
+                                eprintln!("Composition guides: {:?}", framing.guides);
+                            }
+                            Some(VirtualKeyCode::Space) if input.state == ElementState::Pressed => {
+                                if let Some(timeline) = &mut timeline {
+                                    timeline.toggle_play();
+                                }
+                            }
+                            Some(VirtualKeyCode::Left) if input.state == ElementState::Pressed => {
+                                pending_time = timeline.as_mut().map(|t| t.step(-1));
+                            }
+                            Some(VirtualKeyCode::Right) if input.state == ElementState::Pressed => {
+                                pending_time = timeline.as_mut().map(|t| t.step(1));
+                            }
                             _ => {}
                         },
+                        WindowEvent::ModifiersChanged(state) => modifiers = state,
                         WindowEvent::DroppedFile(path) => {
-                            let scene_res = SceneLoader::load_from_path(&path);
+                            let scene_res = SceneLoader::load_with_lints(&path);
 
-                            scene = match scene_res {
-                                Ok(s) => {
+                            camera = match scene_res {
+                                Ok((mut s, lints)) => {
                                     eprintln!("Read scene file from {:?}", path);
+                                    for lint in lints {
+                                        eprintln!("warning: {lint}");
+                                    }
+
+                                    timeline = s
+                                        .animation
+                                        .duration()
+                                        .map(|d| Timeline::new(d, s.animation.fps));
+                                    animation = s.animation.clone();
+
+                                    if timeline.is_some() {
+                                        s.set_time(0.0);
+                                        eprintln!(
+                                            "Scene is animated, space to play, arrows to step \
+                                             frames, drag the bar at the bottom to scrub"
+                                        );
+                                    }
+
+                                    if s.tonemap != scene_tonemap
+                                        || s.physical_exposure != self.post_settings.physical
+                                    {
+                                        scene_tonemap = s.tonemap;
+                                        self.post_settings.physical = s.physical_exposure;
+
+                                        let size = self.gl_window.window.inner_size();
+                                        match Programs::build(
+                                            &self.shaders,
+                                            &self.post_settings,
+                                            self.lut.as_ref(),
+                                            scene_tonemap,
+                                            &framing,
+                                            size,
+                                        ) {
+                                            Ok(p) => programs = p,
+                                            Err(e) => eprintln!("Could not rebuild shaders: {e}"),
+                                        }
+                                    }
+
+                                    scene_path = Some(path);
+                                    scene_slice = s.slice;
+                                    slicing = s.slice.is_some();
+                                    objects = s.objects.clone();
+                                    selection = None;
+
+                                    let scene_camera = s.camera.clone();
                                     self.tx_in
-                                        .send(RenderInMsg::SceneChange(s.clone()))
+                                        .send(RenderInMsg::SceneChange(Box::new(s)))
                                         .unwrap();
-                                    Some(s)
+                                    Some(scene_camera)
                                 }
                                 Err(e) => {
                                     eprintln!("Could not read scene description: {e}");
@@ -339,19 +692,36 @@ impl App {
                         _ => (),
                     },
                     Event::RedrawRequested(_) => {
+                        let size = self.gl_window.window.inner_size();
+                        let rect = framing.rect(size);
+
                         gl_fb.bind();
 
                         gl_renderer.clear_color(0.0, 0.0, 0.0);
 
+                        // viewport starts at the bottom left corner
                         texture.bind(0);
-                        gl_renderer.draw(&quad, &program_copy);
-
-                        gl_wrapper::framebuffer::FrameBuffer::bind_default();
+                        gl_renderer.viewport(
+                            rect.x,
+                            size.height - rect.y - rect.height,
+                            rect.width,
+                            rect.height,
+                        );
+                        gl_renderer.draw(&quad, &programs.copy);
+
+                        programs.post.run(&mut gl_renderer, &quad, &texture_fb);
+
+                        for path in &ray_geometry {
+                            gl_renderer.draw(path, &programs.line);
+                        }
 
-                        gl_renderer.clear_color(0.0, 0.0, 0.0);
+                        if let Some(guides) = framing.guides_geometry(size) {
+                            gl_renderer.draw(&guides, &programs.ui);
+                        }
 
-                        texture_fb.bind(0);
-                        gl_renderer.draw(&quad, &program);
+                        if let Some(timeline) = &timeline {
+                            gl_renderer.draw(&timeline.geometry(size), &programs.ui);
+                        }
                     }
                     _ => (),
                 }
@@ -359,6 +729,250 @@ impl App {
     }
 }
 
+/// Object edited in the viewer, moved as a whole from where it was selected.
+struct Selection {
+    index: usize,
+    rest_shape: Arc<dyn Shape>,
+    offset: Vector3<f64>,
+}
+
+impl Selection {
+    fn new(index: usize, object: &Object) -> Self {
+        // animated objects stop following their track once moved, so they move from rest
+        let rest_shape = match &object.animation {
+            Some(animation) => Arc::clone(&animation.rest_shape),
+            None => Arc::clone(&object.shape),
+        };
+
+        Self {
+            index,
+            rest_shape,
+            offset: Vector3::zero(),
+        }
+    }
+
+    fn shape(&self) -> Arc<dyn Shape> {
+        let rest = Arc::clone(&self.rest_shape);
+
+        Arc::new(Transformed::new(
+            rest,
+            Matrix3::identity(),
+            1.0,
+            self.offset,
+        ))
+    }
+}
+
+/// Emission parameter of the object's shader scaled by `factor`, if the shader reports one.
+fn scaled_emission(object: &Object, factor: f64) -> Option<(&'static str, Parameter)> {
+    if let Some(Parameter::Float(strength)) = object.shader_parameter("strength") {
+        return Some(("strength", Parameter::Float(strength * factor)));
+    }
+
+    match object.shader_parameter("emission") {
+        Some(Parameter::Vec3(emission)) => Some(("emission", Parameter::Vec3(emission * factor))),
+        _ => None,
+    }
+}
+
+/// Projects ray paths to the frame showing the render, splitting them where they go behind the
+/// camera.
+fn ray_path_geometry(
+    paths: &[Vec<Vector3<f64>>],
+    camera: &Camera,
+    framing: &Framing,
+    size: PhysicalSize<u32>,
+) -> Vec<Geometry> {
+    let rect = framing.rect(size);
+
+    let mut strips = Vec::new();
+
+    for path in paths {
+        let mut strip = Vec::new();
+
+        for point in path {
+            match camera.project(*point, rect.aspect_ratio()) {
+                Some(position) => {
+                    let (x, y) = rect.to_clip(position, size);
+
+                    strip.push(x);
+                    strip.push(y);
+                }
+                None => strips.push(std::mem::take(&mut strip)),
+            }
+        }
+
+        strips.push(strip);
+    }
+
+    strips
+        .iter()
+        .filter(|s| s.len() >= 4)
+        .map(|s| {
+            GeometryBuilder::new(s)
+                .with_attribute(VertexAttribute::Vec2)
+                .with_primitive(Primitive::LineStrip)
+                .build()
+                .unwrap()
+        })
+        .collect()
+}
+
+/// Window title with time of progressive passes of the renderer and of uploading them to GPU.
+fn timings_title(timings: &PassTimings, upload: Duration) -> String {
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let scale = timings.scale.map_or(1, |s| s.scale());
+
+    format!(
+        "Black-hole renderer - 1/{scale} scale, {:.1} ms per pass \
+        (march {:.1}, shade {:.1}, accumulate {:.1}), upload {:.1} ms",
+        ms(timings.total()),
+        ms(timings.march),
+        ms(timings.shade),
+        ms(timings.accumulate),
+        ms(upload),
+    )
+}
+
+/// Runs passes of [`PostSettings::pipeline`] on GPU before displaying the render.
+///
+/// With the same settings the output matches images saved by the CLI renderer.
+fn build_post_pipeline(
+    settings: &PostSettings,
+    lut: Option<&CubeLut>,
+    shaders: &ShaderSources,
+    scene_tonemap: Tonemap,
+    width: u32,
+    height: u32,
+) -> Result<PostPipeline, PBError> {
+    let vert_src = shaders.get("quad.glsl");
+    let pass = |frag: &str| -> Result<PostPass, PBError> {
+        let program = ProgramBuilder::new(&vert_src, &shaders.get(frag)).build()?;
+
+        Ok(PostPass::new(program))
+    };
+
+    let mut exposure = 2.0_f32.powf(settings.exposure);
+    if settings.physical {
+        exposure *= SENSOR_SCALE;
+    }
+
+    let mut pipeline = PostPipeline::new(width, height);
+
+    let bright = pipeline.add_pass(pass("post_bright.glsl")?.with_downscale(4));
+    let blur_h = pipeline.add_pass(
+        pass("post_blur.glsl")?
+            .with_inputs(&[PassInput::Pass(bright)])
+            .with_downscale(4),
+    );
+    let blur_v = pipeline.add_pass(
+        pass("post_blur.glsl")?
+            .with_inputs(&[PassInput::Pass(blur_h)])
+            .with_downscale(4),
+    );
+    let mut output_pass =
+        pass("output.glsl")?.with_inputs(&[PassInput::Source, PassInput::Pass(blur_v)]);
+
+    if let Some(lut) = lut {
+        let data = lut.table().iter().flatten().copied().collect::<Vec<_>>();
+        let texture = Texture3D::new_rgb(lut.size() as u32, &data).expect("LUT has all entries");
+
+        output_pass = output_pass.with_volume(texture);
+    }
+
+    let output = pipeline.add_pass(output_pass);
+
+    let bright = pipeline.pass(bright).program();
+    bright.set_uniform("exposure", Uniform::Float(exposure));
+    bright.set_uniform("threshold", Uniform::Float(settings.bloom_threshold));
+
+    let blur_h = pipeline.pass(blur_h).program();
+    blur_h.set_uniform("direction", Uniform::Vec2(2.0, 0.0));
+
+    let blur_v = pipeline.pass(blur_v).program();
+    blur_v.set_uniform("direction", Uniform::Vec2(0.0, 2.0));
+
+    let output = pipeline.pass(output).program();
+    output.set_uniform("exposure", Uniform::Float(exposure));
+    output.set_uniform("bloom", Uniform::Float(settings.bloom));
+    output.set_uniform("vignette", Uniform::Float(settings.vignette));
+
+    let tonemapper = match settings.tonemap.unwrap_or(scene_tonemap) {
+        Tonemap::Reinhard => 0,
+        Tonemap::AcesFilmic => 1,
+        Tonemap::Uncharted2 => 2,
+        Tonemap::LinearClamp => 3,
+    };
+    output.set_uniform("tonemapper", Uniform::Int(tonemapper));
+    output.set_uniform("gamma", Uniform::Float(settings.gamma));
+    output.set_uniform("dither", Uniform::Int(settings.dither as i32));
+
+    if let Some(lut) = lut {
+        let (min, max) = lut.domain();
+
+        output.set_uniform("lut", Uniform::Int(1));
+        output.set_uniform("lut_size", Uniform::Float(lut.size() as f32));
+        output.set_uniform("lut_min", Uniform::Vec3(min.x, min.y, min.z));
+        output.set_uniform("lut_max", Uniform::Vec3(max.x, max.y, max.z));
+    }
+
+    Ok(pipeline)
+}
+
+/// All GPU programs used to display the render.
+struct Programs {
+    copy: Program,
+    line: Program,
+    ui: Program,
+    post: PostPipeline,
+}
+
+impl Programs {
+    fn build(
+        shaders: &ShaderSources,
+        post_settings: &PostSettings,
+        lut: Option<&CubeLut>,
+        scene_tonemap: Tonemap,
+        framing: &Framing,
+        size: PhysicalSize<u32>,
+    ) -> Result<Self, PBError> {
+        let copy =
+            ProgramBuilder::new(&shaders.get("quad.glsl"), &shaders.get("copy.glsl")).build()?;
+        let line = ProgramBuilder::new(&shaders.get("line.glsl"), &shaders.get("line_color.glsl"))
+            .build()?;
+        let ui =
+            ProgramBuilder::new(&shaders.get("ui.glsl"), &shaders.get("ui_color.glsl")).build()?;
+        let post = build_post_pipeline(
+            post_settings,
+            lut,
+            shaders,
+            scene_tonemap,
+            size.width,
+            size.height,
+        )?;
+
+        let programs = Self {
+            copy,
+            line,
+            ui,
+            post,
+        };
+        programs.set_frame(framing, size);
+
+        Ok(programs)
+    }
+
+    /// Limits the displayed image to the frame of the window, leaving black bars around it.
+    fn set_frame(&self, framing: &Framing, size: PhysicalSize<u32>) {
+        let [x0, y0, x1, y1] = framing.rect(size).uv_bounds(size);
+
+        self.post
+            .output()
+            .program()
+            .set_uniform("frame", Uniform::Vec4(x0, y0, x1, y1));
+    }
+}
+
 pub struct GlWindow {
     // XXX the surface must be dropped before the window.
     pub surface: Surface<WindowSurface>,
@@ -1,28 +1,110 @@
 use clap::{Parser, ValueEnum};
 
 use crate::renderer::Scaling;
+use blackhole::postprocess::Tonemap;
 use blackhole::RenderMode;
+use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
 pub struct ArgsInteractive {
     /// Render setting, used for debugging
     #[arg(value_enum, default_value_t = RenderModeArg::Shaded)]
     pub mode: RenderModeArg,
-    /// Amount of samples to render
-    #[arg(short, long, default_value_t = 128)]
-    pub samples: usize,
-    /// Threads to use for rendering (0 for automatic setting)
+    /// Amount of samples to render [default: 128, or from scene]
+    #[arg(short, long)]
+    pub samples: Option<usize>,
+    /// Seed of random numbers, the same seed gives the same noise pattern
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// Threads to use for rendering (0 for one per physical core)
     #[arg(short, long, default_value_t = 0)]
     pub threads: usize,
+    /// With `--threads 0`, use one thread per logical core including hyper-threads
+    #[arg(long)]
+    pub logical_cores: bool,
     #[arg(value_enum, short = 'X', default_value_t = ScalingArg::X1)]
     pub scaling: ScalingArg,
+    /// How fast max steps and depth drop at coarse preview scales (0 to disable)
+    #[arg(long, default_value_t = 1.0)]
+    pub lod_falloff: f64,
+    /// Exposure correction of the preview in EV
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    pub exposure: f32,
+    /// Strength of bloom in the preview
+    #[arg(long, default_value_t = 0.0)]
+    pub bloom: f32,
+    /// Luminance above which pixels bloom
+    #[arg(long, default_value_t = 1.0)]
+    pub bloom_threshold: f32,
+    /// Darkening of preview corners, from 0 to 1
+    #[arg(long, default_value_t = 0.0)]
+    pub vignette: f32,
+    /// Tonemapping operator of the preview, overrides the scene setting
+    #[arg(long, value_enum)]
+    pub tonemap: Option<TonemapArg>,
+    /// Add noise of one 8 bit step to the preview, hiding banding of smooth gradients
+    #[arg(long)]
+    pub dither: bool,
+    /// Grade the preview with a 3D `.cube` table. Images saved by the CLI renderer are only graded
+    /// with its own `--lut`
+    #[arg(long)]
+    pub lut: Option<PathBuf>,
+    /// Aspect ratio of the preview, as width:height or a single number. The render is letterboxed to
+    /// fit the window, press G to cycle composition guides [default: 16:9]
+    #[arg(long, value_parser = parse_aspect)]
+    pub aspect: Option<f64>,
+    /// Directory to load display shaders from, they are reloaded when changed
+    #[arg(long)]
+    pub dev_shaders: Option<PathBuf>,
+}
+
+fn parse_aspect(s: &str) -> Result<f64, String> {
+    let ratio = match s.split_once(':') {
+        Some((w, h)) => match (w.trim().parse::<f64>(), h.trim().parse::<f64>()) {
+            (Ok(w), Ok(h)) => w / h,
+            _ => return Err(format!("invalid ratio '{s}'")),
+        },
+        None => s
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid ratio '{s}'"))?,
+    };
+
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err("ratio must be positive".into())
+    }
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum TonemapArg {
+    Reinhard,
+    Aces,
+    Uncharted2,
+    Linear,
+}
+
+impl From<TonemapArg> for Tonemap {
+    fn from(t: TonemapArg) -> Self {
+        match t {
+            TonemapArg::Reinhard => Self::Reinhard,
+            TonemapArg::Aces => Self::AcesFilmic,
+            TonemapArg::Uncharted2 => Self::Uncharted2,
+            TonemapArg::Linear => Self::LinearClamp,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
 pub enum RenderModeArg {
     Samples,
     Normal,
+    /// Color of surfaces and volumes seen by camera rays, without lighting
+    Albedo,
     Shaded,
+    Deterministic,
+    Termination,
 }
 
 impl From<RenderModeArg> for RenderMode {
@@ -30,7 +112,10 @@ impl From<RenderModeArg> for RenderMode {
         match r {
             RenderModeArg::Samples => Self::Samples,
             RenderModeArg::Normal => Self::Normal,
+            RenderModeArg::Albedo => Self::Albedo,
             RenderModeArg::Shaded => Self::Shaded,
+            RenderModeArg::Deterministic => Self::Deterministic,
+            RenderModeArg::Termination => Self::Termination,
         }
     }
 }
@@ -0,0 +1,176 @@
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+use gl_wrapper::geometry::{Geometry, GeometryBuilder, Primitive, VertexAttribute};
+
+/// Part of the frame kept clear of important action, from its center.
+const ACTION_SAFE: f64 = 0.9;
+/// Part of the frame kept clear of titles, from its center.
+const TITLE_SAFE: f64 = 0.8;
+
+const GUIDE_COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+
+/// Overlays helping with composition of the shot, cycled through in the viewer.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Guides {
+    #[default]
+    Off,
+    Thirds,
+    Center,
+    /// Action and title safe areas
+    Safe,
+    All,
+}
+
+impl Guides {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Thirds,
+            Self::Thirds => Self::Center,
+            Self::Center => Self::Safe,
+            Self::Safe => Self::All,
+            Self::All => Self::Off,
+        }
+    }
+
+    fn thirds(self) -> bool {
+        matches!(self, Self::Thirds | Self::All)
+    }
+
+    fn center(self) -> bool {
+        matches!(self, Self::Center | Self::All)
+    }
+
+    fn safe(self) -> bool {
+        matches!(self, Self::Safe | Self::All)
+    }
+}
+
+/// Rectangle of the window showing the render, in pixels from the top left corner.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FrameRect {
+    pub fn aspect_ratio(self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    /// Cursor position relative to the frame, from 0 to 1 inside of it.
+    pub fn relative(self, position: PhysicalPosition<f64>) -> (f64, f64) {
+        (
+            (position.x - self.x as f64) / self.width as f64,
+            (position.y - self.y as f64) / self.height as f64,
+        )
+    }
+
+    /// Position relative to the frame converted to clip space of the whole window.
+    pub fn to_clip(self, (x, y): (f64, f64), window: PhysicalSize<u32>) -> (f32, f32) {
+        let x = (self.x as f64 + x * self.width as f64) / window.width as f64;
+        let y = (self.y as f64 + y * self.height as f64) / window.height as f64;
+
+        ((x * 2.0 - 1.0) as f32, (1.0 - y * 2.0) as f32)
+    }
+
+    /// Frame as minimum and maximum texture coordinates of the window, with origin at the bottom.
+    pub fn uv_bounds(self, window: PhysicalSize<u32>) -> [f32; 4] {
+        let (w, h) = (window.width as f32, window.height as f32);
+        let bottom = h - (self.y + self.height) as f32;
+
+        [
+            self.x as f32 / w,
+            bottom / h,
+            (self.x + self.width) as f32 / w,
+            (bottom + self.height as f32) / h,
+        ]
+    }
+}
+
+/// Fits the render into the window, keeping its aspect ratio with black bars around it.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Framing {
+    /// Width to height ratio of the render, the whole window is used when not set
+    pub aspect: Option<f64>,
+    pub guides: Guides,
+}
+
+impl Framing {
+    /// Largest centered rectangle of the window with the aspect ratio.
+    pub fn rect(&self, window: PhysicalSize<u32>) -> FrameRect {
+        let (w, h) = (window.width.max(1), window.height.max(1));
+
+        let (width, height) = match self.aspect {
+            Some(aspect) if aspect > w as f64 / h as f64 => {
+                (w, ((w as f64 / aspect).round() as u32).clamp(1, h))
+            }
+            Some(aspect) => (((h as f64 * aspect).round() as u32).clamp(1, w), h),
+            None => (w, h),
+        };
+
+        FrameRect {
+            x: (w - width) / 2,
+            y: (h - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    /// Lines of enabled guides, with positions in clip space and colors per vertex.
+    pub fn guides_geometry(&self, window: PhysicalSize<u32>) -> Option<Geometry> {
+        if self.guides == Guides::Off {
+            return None;
+        }
+
+        let rect = self.rect(window);
+        let mut data = Vec::new();
+        let mut line = |a: (f64, f64), b: (f64, f64)| {
+            for point in [a, b] {
+                let (x, y) = rect.to_clip(point, window);
+
+                data.extend_from_slice(&[x, y]);
+                data.extend_from_slice(&GUIDE_COLOR);
+            }
+        };
+
+        if self.guides.thirds() {
+            for t in [1.0 / 3.0, 2.0 / 3.0] {
+                line((t, 0.0), (t, 1.0));
+                line((0.0, t), (1.0, t));
+            }
+        }
+
+        if self.guides.center() {
+            // cross of the same size in pixels along both axes
+            let arm = 0.03 * rect.width.min(rect.height) as f64;
+            let dx = arm / rect.width as f64;
+            let dy = arm / rect.height as f64;
+
+            line((0.5 - dx, 0.5), (0.5 + dx, 0.5));
+            line((0.5, 0.5 - dy), (0.5, 0.5 + dy));
+        }
+
+        if self.guides.safe() {
+            for size in [ACTION_SAFE, TITLE_SAFE] {
+                let min = (1.0 - size) / 2.0;
+                let max = 1.0 - min;
+
+                line((min, min), (max, min));
+                line((max, min), (max, max));
+                line((max, max), (min, max));
+                line((min, max), (min, min));
+            }
+        }
+
+        let geometry = GeometryBuilder::new(&data)
+            .with_attribute(VertexAttribute::Vec2)
+            .with_attribute(VertexAttribute::Vec3)
+            .with_primitive(Primitive::Lines)
+            .build()
+            .unwrap();
+
+        Some(geometry)
+    }
+}
@@ -0,0 +1,7 @@
+#version 450
+
+out vec4 FragColor;
+
+void main() {
+    FragColor = vec4(1.0, 0.3, 0.1, 1.0);
+}
@@ -1,28 +1,95 @@
 #version 450
 
 layout (binding = 0) uniform sampler2D tex;
+layout (binding = 1) uniform sampler2D bloom_tex;
+layout (binding = 2) uniform sampler3D lut_tex;
+
+uniform float exposure;
+uniform float bloom;
+uniform float vignette;
+uniform float gamma;
+uniform int dither;
+// grading of encoded colors by a lookup table, when set
+uniform int lut;
+uniform float lut_size;
+uniform vec3 lut_min;
+uniform vec3 lut_max;
+// part of the window showing the render as minimum and maximum uv, the rest is black
+uniform vec4 frame;
+// 0 reinhard, 1 aces, 2 uncharted2, 3 linear clamp
+uniform int tonemapper;
 
 in vec2 uv;
 
 out vec4 FragColor;
 
-void main() {
-    vec2 uv_centered = vec2(uv.x, - uv.y + 1.0) - 0.5;
+// pseudo random value from 0 to 1 for the pixel position
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+vec3 uncharted2_curve(vec3 x) {
+    const float A = 0.15;
+    const float B = 0.50;
+    const float C = 0.10;
+    const float D = 0.20;
+    const float E = 0.02;
+    const float F = 0.30;
 
-    vec2 uv_r = (uv_centered * 1.001) + 0.5;
-    vec2 uv_g = (uv_centered) + 0.5;
-    vec2 uv_b = (uv_centered * 0.999) + 0.5;
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
 
-    vec3 t = vec3(texture(tex, uv_g).r, texture(tex, uv_g).g, texture(tex, uv_g).b);
+vec3 tonemap(vec3 color) {
+    if (tonemapper == 1) {
+        vec3 x = max(color, 0.0);
+        return clamp(x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+    } else if (tonemapper == 2) {
+        vec3 white = uncharted2_curve(vec3(11.2));
+        return clamp(uncharted2_curve(max(color, 0.0) * 2.0) / white, 0.0, 1.0);
+    } else if (tonemapper == 3) {
+        return clamp(color, 0.0, 1.0);
+    }
 
-    float luminance = dot(t.rgb, vec3(0.2126, 0.7152, 0.0722));
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
 
     float new_luminance = luminance / (luminance + 1.0);
 
-    vec3 tonemapped = t.rgb * (new_luminance / luminance);
+    return color * (new_luminance / luminance);
+}
+
+void main() {
+    vec2 uv_frame = (uv - frame.xy) / (frame.zw - frame.xy);
+
+    if (any(lessThan(uv_frame, vec2(0.0))) || any(greaterThan(uv_frame, vec2(1.0)))) {
+        FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec2 uv_flipped = vec2(uv.x, - uv.y + 1.0);
+
+    vec3 t = texture(tex, uv_flipped).rgb * exposure;
+    t += texture(bloom_tex, uv_flipped).rgb * bloom;
+
+    vec3 tonemapped = tonemap(t.rgb);
+
+    // squared distance from center of the frame, 1.0 in its corners
+    vec2 uv_centered = uv_frame - 0.5;
+    float r2 = dot(uv_centered, uv_centered) * 2.0;
+    tonemapped *= mix(1.0, 1.0 - r2, vignette);
+
+    vec3 srgb = pow(max(tonemapped, 0.0), vec3(1.0 / gamma));
+
+    if (lut != 0) {
+        vec3 t = clamp((srgb - lut_min) / (lut_max - lut_min), 0.0, 1.0);
+        // first and last entries are at texel centers
+        srgb = texture(lut_tex, (t * (lut_size - 1.0) + 0.5) / lut_size).rgb;
+    }
 
-    float gamma = 1.0 / 2.2;
-    vec3 srgb = pow(tonemapped, vec3(gamma));
+    // noise of one 8 bit step hides banding of smooth gradients
+    if (dither != 0) {
+        vec3 noise = vec3(hash(gl_FragCoord.xy), hash(gl_FragCoord.xy + 17.0), hash(gl_FragCoord.xy + 41.0));
+        srgb += (noise - 0.5) / 255.0;
+    }
 
     FragColor = vec4(srgb, 1.0);
 }
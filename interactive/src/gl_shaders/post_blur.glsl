@@ -0,0 +1,25 @@
+#version 450
+
+layout (binding = 0) uniform sampler2D tex;
+
+// blur axis, in texels
+uniform vec2 direction;
+
+in vec2 uv;
+
+out vec4 FragColor;
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec2 step = direction / vec2(textureSize(tex, 0));
+
+    vec3 sum = texture(tex, uv).rgb * weights[0];
+
+    for (int i = 1; i < 5; i++) {
+        sum += texture(tex, uv + step * float(i)).rgb * weights[i];
+        sum += texture(tex, uv - step * float(i)).rgb * weights[i];
+    }
+
+    FragColor = vec4(sum, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 450
+
+layout (binding = 0) uniform sampler2D tex;
+
+uniform float exposure;
+uniform float threshold;
+
+in vec2 uv;
+
+out vec4 FragColor;
+
+void main() {
+    vec3 color = texture(tex, uv).rgb * exposure;
+
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+
+    float weight = max(luminance - threshold, 0.0) / max(luminance, 0.0001);
+
+    FragColor = vec4(color * weight, 1.0);
+}
@@ -1,31 +1,81 @@
 use clap::Parser;
 
-use blackhole::marcher::RayMarcher;
+use blackhole::marcher::{RayMarcher, RenderSettings};
+use blackhole::post::{CubeLut, PostSettings};
+use blackhole_common::threads::ThreadCount;
 
 mod app;
 mod args;
+mod framing;
 mod renderer;
+mod shaders;
+mod timeline;
 
 use app::App;
 use args::ArgsInteractive;
+use framing::Framing;
 use renderer::InteractiveRenderer;
+use shaders::ShaderSources;
 
 fn main() {
     // clion needs help in trait annotation
     let args = <ArgsInteractive as Parser>::parse();
 
+    let threads = ThreadCount::pick(args.threads, args.logical_cores);
+    eprintln!("Rendering with {threads}");
+    if threads.oversubscribed() {
+        eprintln!(
+            "warning: {} threads are more than {} logical cores",
+            threads.threads, threads.logical_cores
+        );
+    }
+
     let renderer = InteractiveRenderer {
         ray_marcher: RayMarcher {
             mode: args.mode.into(),
+            profile: true,
+            ..Default::default()
+        },
+        overrides: RenderSettings {
+            samples: args.samples,
             ..Default::default()
         },
-        samples: args.samples,
-        threads: args.threads,
+        seed: args.seed,
+        threads: threads.threads,
         scaling: args.scaling.into(),
+        lod_falloff: args.lod_falloff,
+        ..Default::default()
+    };
+
+    let post_settings = PostSettings {
+        exposure: args.exposure,
+        bloom: args.bloom,
+        bloom_threshold: args.bloom_threshold,
+        vignette: args.vignette,
+        tonemap: args.tonemap.map(Into::into),
+        dither: args.dither,
+        ..Default::default()
+    };
+
+    let shaders = match args.dev_shaders {
+        Some(dir) => ShaderSources::from_dir(dir),
+        None => ShaderSources::embedded(),
+    };
+
+    let lut = args.lut.map(|path| {
+        CubeLut::read(&path).unwrap_or_else(|e| {
+            eprintln!("Could not read LUT {}: {e}", path.display());
+            std::process::exit(-1);
+        })
+    });
+
+    // without a requested ratio the image keeps the one it's rendered with
+    let framing = Framing {
+        aspect: Some(args.aspect.unwrap_or(renderer.frame.aspect_ratio())),
         ..Default::default()
     };
 
-    let app = App::new(renderer).unwrap();
+    let app = App::new(renderer, post_settings, lut, framing, shaders).unwrap();
 
     app.run();
 }
@@ -5,9 +5,9 @@ static MAX_STEPS_PER_SAMPLE: AtomicUsize = AtomicUsize::new(0);
 
 mod interactive;
 
-pub use interactive::{InteractiveRenderer, RenderInMsg, RenderOutMsg};
+pub use interactive::{InteractiveRenderer, PassTimings, RenderInMsg, RenderOutMsg};
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Scaling {
     X1,
     X2,
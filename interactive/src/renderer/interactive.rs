@@ -1,26 +1,39 @@
+use blackhole::camera::Camera;
 use blackhole::filter::{BlackmanHarrisFilter, PixelFilter};
 use blackhole::frame::{Frame, Region};
 use blackhole::framebuffer::{FrameBuffer, Pixel};
-use blackhole::marcher::RayMarcher;
-use blackhole::scene::Scene;
+use blackhole::marcher::{MarchStats, RayMarcher, RenderSettings, Scratch};
+use blackhole::math::{pixel_seed, seed_rng};
+use blackhole::object::shape::Shape;
+use blackhole::postprocess::{self, StepLevels};
+use blackhole::scene::{Scene, SlicePlane};
+use blackhole::shader::Parameter;
 use blackhole::RenderMode;
 
+use cgmath::{Vector3, Zero};
+
 use flume::{Receiver, RecvError, Sender};
 
 use rayon::prelude::*;
 
 use std::sync::{Arc, RwLock};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::renderer::Scaling;
 
 pub struct InteractiveRenderer {
     pub ray_marcher: RayMarcher,
     pub samples: usize,
+    /// Settings from the command line, taking precedence over the ones of loaded scenes
+    pub overrides: RenderSettings,
+    /// Seed of random numbers, mixed with pixel position and sample index
+    pub seed: u64,
     pub threads: usize,
     pub frame: Frame,
     pub filter: Box<dyn PixelFilter>,
     pub scaling: Scaling,
+    /// Exponent of `max_steps` and `max_depth` reduction at coarse preview scales, 0 disables it
+    pub lod_falloff: f64,
 }
 
 impl InteractiveRenderer {
@@ -38,17 +51,71 @@ impl InteractiveRenderer {
             .num_threads(self.threads)
             .build()
             .expect("Failed to build rendering threadpool");
+        // used by the single threaded path, parallel jobs get their own
+        let mut scratch = LineScratch::default();
 
-        let mut current_scale;
+        let mut current_scale = Scaling::X8;
+        let mut sample = 0;
+        // samples the current scale was seeded with from the previous one
+        let mut seed_weight = 0.0;
         let mut window_size = (self.frame.width, self.frame.height);
 
         let mut last_update = Instant::now();
+        // timings of passes since they were last reported
+        let mut timings = PassTimings::default();
+        let mut last_report = Instant::now();
+
+        // marcher and samples without settings of the scene
+        let base = (self.ray_marcher.clone(), self.samples);
+        let mut limits = (self.ray_marcher.max_steps, self.ray_marcher.max_depth);
+
+        // light accumulated separately for emissive objects once their emission was edited, or
+        // mean step counts in samples mode, row by row
+        let mut sources: Vec<Pixel> = Vec::new();
+        // objects with a plane in `sources`, in order of the planes
+        let mut source_objects: Vec<usize> = Vec::new();
+        // emission of the scene was edited, so later edits can rescale light instead
+        let mut track_sources = false;
 
         'jobs: loop {
             let msg = rx.recv();
 
-            match Self::msg_to_actions(msg) {
+            let mut action = Self::msg_to_actions(msg);
+
+            if let RendererActions::Restart {
+                scene_change: Some(SceneEdit::ShaderParam(index, name, value)),
+                resize_buffers: None,
+            } = &action
+            {
+                if let Some(scene) = &mut scene {
+                    let plane = source_objects.iter().position(|o| o == index);
+
+                    if self.rescale_source(
+                        scene,
+                        &front_fb,
+                        &mut sources,
+                        plane,
+                        *index,
+                        name,
+                        value,
+                    ) {
+                        scene.objects[*index].set_shader_parameter(name, value.clone());
+                        scene.update_lights();
+                        tx.send(RenderOutMsg::Update(current_scale)).unwrap();
+
+                        action = RendererActions::Continue;
+                    } else {
+                        track_sources |= scene
+                            .objects
+                            .get(*index)
+                            .is_some_and(|o| o.emission_scale(name, value).is_some());
+                    }
+                }
+            }
+
+            match action {
                 RendererActions::Exit => break 'jobs,
+                RendererActions::Continue => {}
                 RendererActions::Restart {
                     resize_buffers,
                     scene_change,
@@ -63,8 +130,19 @@ impl InteractiveRenderer {
                         }
                     }
 
-                    if let Some(scene_new) = scene_change {
-                        scene = Some(scene_new);
+                    match (scene_change, &mut scene) {
+                        (Some(SceneEdit::Whole(scene_new)), _) => {
+                            let settings = self.overrides.or(scene_new.render);
+
+                            self.ray_marcher = base.0.clone().with_settings(&settings);
+                            self.samples = settings.samples.unwrap_or(base.1);
+                            limits = (self.ray_marcher.max_steps, self.ray_marcher.max_depth);
+
+                            scene = Some(*scene_new);
+                            track_sources = false;
+                        }
+                        (Some(edit), Some(scene)) => edit.apply(scene),
+                        _ => {}
                     }
 
                     current_scale = Scaling::X8;
@@ -75,67 +153,147 @@ impl InteractiveRenderer {
 
                     self.frame.width = w as usize;
                     self.frame.height = h as usize;
+                    self.apply_lod(current_scale, limits);
+
+                    source_objects = match &scene {
+                        Some(scene) if track_sources => self.emissive_objects(scene),
+                        _ => Vec::new(),
+                    };
+                    sources = self.source_buffer(&source_objects);
+
+                    sample = 0;
+                    seed_weight = 0.0;
+                    self.filter.reset();
+                }
+                RendererActions::TraceRays(points) => {
+                    if let Some(scene) = &scene {
+                        let max_step = scene.max_possible_step(scene.camera.location);
+                        let aspect_ratio = window_size.0 as f64 / window_size.1 as f64;
+
+                        let paths = points
+                            .iter()
+                            .map(|(x, y)| {
+                                let ray = scene.camera.cast_ray(*x, *y, aspect_ratio);
+
+                                self.ray_marcher.trace_path(ray, scene, max_step)
+                            })
+                            .collect();
+
+                        tx.send(RenderOutMsg::RayPaths(paths)).unwrap();
+                    }
                 }
             }
             if let Some(scene) = &scene {
                 let max_step = scene.max_possible_step(scene.camera.location);
 
-                let mut sample = 0;
-                self.filter.reset();
-
                 'sample: loop {
                     if sample >= self.samples || !rx.is_empty() {
                         break 'sample;
                     }
 
-                    let offset = self.filter.next().unwrap();
-
-                    {
+                    let pass = Pass {
+                        scene,
+                        sources: &source_objects,
+                        max_step,
+                        sample,
+                        seed_weight,
+                        offset: self.filter.next().unwrap(),
+                    };
+                    let pass_start = Instant::now();
+
+                    // marching counters of the pass and time threads spent on it
+                    let (stats, busy) = {
                         let read_lock = front_fb.read().unwrap();
 
+                        let source_row = self.source_row(&source_objects);
+
                         if self.threads == 1 {
-                            for (y, (slice_out, slice_in)) in back_fb
+                            for (y, ((slice_out, slice_in), slice_sources)) in back_fb
                                 .buffer_mut()
                                 .chunks_mut(self.frame.width)
                                 .zip(read_lock.buffer().chunks(self.frame.width))
+                                .zip(sources.chunks_mut(source_row))
                                 .enumerate()
                                 .take(self.frame.height)
                             {
                                 self.scanline(
-                                    scene, max_step, y, slice_in, slice_out, sample, offset,
+                                    &pass,
+                                    y,
+                                    slice_in,
+                                    slice_out,
+                                    slice_sources,
+                                    &mut scratch,
                                 );
                             }
+
+                            (scratch.marcher.take_stats(), pass_start.elapsed())
                         } else {
                             pool.install(|| {
                                 back_fb
                                     .buffer_mut()
                                     .par_chunks_mut(self.frame.width)
                                     .zip(read_lock.buffer().par_chunks(self.frame.width))
+                                    .zip(sources.par_chunks_mut(source_row))
                                     .enumerate()
                                     .take(self.frame.height)
-                                    .for_each(|(y, (slice_out, slice_in))| {
-                                        self.scanline(
-                                            scene, max_step, y, slice_in, slice_out, sample, offset,
-                                        )
-                                    })
-                            });
+                                    .map_init(
+                                        LineScratch::default,
+                                        |scratch, (y, ((slice_out, slice_in), slice_sources))| {
+                                            let start = Instant::now();
+
+                                            self.scanline(
+                                                &pass,
+                                                y,
+                                                slice_in,
+                                                slice_out,
+                                                slice_sources,
+                                                scratch,
+                                            );
+
+                                            (scratch.marcher.take_stats(), start.elapsed())
+                                        },
+                                    )
+                                    .reduce(
+                                        || (MarchStats::new(), Duration::ZERO),
+                                        |(mut stats, busy), (other, other_busy)| {
+                                            stats.merge(&other);
+                                            (stats, busy + other_busy)
+                                        },
+                                    )
+                            })
                         }
+                    };
+
+                    timings.add_pass(pass_start.elapsed(), &stats, busy);
+
+                    if let RenderMode::Samples = self.ray_marcher.mode {
+                        self.show_steps(&mut back_fb, &sources);
+                    }
+
+                    {
+                        let mut write_lock = front_fb.write().unwrap();
+
+                        std::mem::swap(&mut back_fb, &mut write_lock);
                     }
 
                     let now = Instant::now();
 
                     if (now - last_update).as_millis() > 8 {
                         last_update = now;
-                        {
-                            let mut write_lock = front_fb.write().unwrap();
-
-                            std::mem::swap(&mut back_fb, &mut write_lock);
-                        }
 
                         tx.send(RenderOutMsg::Update(current_scale)).unwrap();
                     }
 
+                    if (now - last_report).as_millis() > 500 {
+                        last_report = now;
+
+                        let report = std::mem::take(&mut timings).average(current_scale);
+                        tx.send(RenderOutMsg::Timings(report)).unwrap();
+                    }
+
                     if current_scale != self.scaling {
+                        let previous = (self.frame.width, self.frame.height);
+
                         current_scale = current_scale.lower();
                         let (w, h) = (
                             window_size.0 as u32 / current_scale.scale(),
@@ -144,6 +302,29 @@ impl InteractiveRenderer {
 
                         self.frame.width = w as usize;
                         self.frame.height = h as usize;
+                        self.apply_lod(current_scale, limits);
+
+                        let old_sources =
+                            std::mem::replace(&mut sources, self.source_buffer(&source_objects));
+
+                        if !matches!(self.ray_marcher.mode, RenderMode::Samples) {
+                            self.seed_from_previous(
+                                &front_fb,
+                                previous,
+                                &old_sources,
+                                &mut sources,
+                                source_objects.len(),
+                            );
+
+                            // every coarse sample covers more pixels, so it counts as less
+                            let area_ratio = (w as f32 * h as f32)
+                                / (previous.0 as f32 * previous.1 as f32).max(1.0);
+                            seed_weight = (seed_weight + (sample + 1) as f32) / area_ratio;
+
+                            tx.send(RenderOutMsg::Update(current_scale)).unwrap();
+                        } else {
+                            seed_weight = 0.0;
+                        }
 
                         sample = 0;
                         continue 'sample;
@@ -155,17 +336,186 @@ impl InteractiveRenderer {
         }
     }
 
+    /// Fills accumulation buffers of a finer scale by bilinearly upscaling the previous one, so the
+    /// new scale continues from its mean instead of starting over.
+    fn seed_from_previous(
+        &self,
+        front_fb: &RwLock<FrameBuffer>,
+        previous: (usize, usize),
+        old_sources: &[Pixel],
+        sources: &mut [Pixel],
+        planes: usize,
+    ) {
+        let size = (self.frame.width, self.frame.height);
+
+        {
+            let mut write_lock = front_fb.write().unwrap();
+            let buffer = write_lock.buffer_mut();
+            let old = buffer[..previous.0 * previous.1].to_vec();
+
+            upscale(&old, previous, buffer, size, 1);
+        }
+
+        if planes > 0 && old_sources.len() == previous.0 * previous.1 * planes {
+            upscale(old_sources, previous, sources, size, planes);
+        }
+    }
+
+    /// Colors mean step counts of the first source from green to red by their levels in the image,
+    /// same as the command line renderer.
+    fn show_steps(&self, fb: &mut FrameBuffer, sources: &[Pixel]) {
+        let (width, height) = (self.frame.width, self.frame.height);
+
+        if sources.len() != width * height {
+            return;
+        }
+
+        let mut steps = FrameBuffer::new(width, height);
+        steps.buffer_mut().copy_from_slice(sources);
+
+        let levels = StepLevels::measure(&steps);
+        postprocess::steps_display(&mut steps, &levels);
+
+        fb.buffer_mut()[..width * height].copy_from_slice(steps.buffer());
+    }
+
+    /// Lowers marcher limits for preview scales coarser than the target scaling.
+    fn apply_lod(&mut self, scale: Scaling, limits: (usize, usize)) {
+        let relative_scale = scale.scale() as f64 / self.scaling.scale() as f64;
+        let factor = relative_scale.powf(self.lod_falloff);
+
+        self.ray_marcher.max_steps = ((limits.0 as f64 / factor) as usize).max(1);
+        self.ray_marcher.max_depth = ((limits.1 as f64 / factor).ceil() as usize).max(1);
+    }
+
+    /// Objects whose light is kept apart, the ones emitting light in the shaded mode.
+    fn emissive_objects(&self, scene: &Scene) -> Vec<usize> {
+        if !matches!(self.ray_marcher.mode, RenderMode::Shaded) {
+            return Vec::new();
+        }
+
+        (0..scene.objects.len())
+            .filter(|&i| scene.objects[i].emits_light())
+            .collect()
+    }
+
+    /// Pixels of one row of the source buffer, samples mode keeps a single plane of step counts.
+    ///
+    /// Rows without planes still keep a pixel, so they pair up with rows of the image.
+    fn source_row(&self, objects: &[usize]) -> usize {
+        let planes = match self.ray_marcher.mode {
+            RenderMode::Samples => 1,
+            _ => objects.len(),
+        };
+
+        (self.frame.width * planes).max(1)
+    }
+
+    fn source_buffer(&self, objects: &[usize]) -> Vec<Pixel> {
+        vec![Pixel::black(); self.source_row(objects) * self.frame.height]
+    }
+
+    /// Applies emission change of an object by scaling its accumulated light in `plane` of the
+    /// sources, if possible.
+    ///
+    /// Returns `false` when the render has to be restarted instead.
+    #[allow(clippy::too_many_arguments)]
+    fn rescale_source(
+        &self,
+        scene: &Scene,
+        front_fb: &RwLock<FrameBuffer>,
+        sources: &mut [Pixel],
+        plane: Option<usize>,
+        index: usize,
+        name: &str,
+        value: &Parameter,
+    ) -> bool {
+        // volumes in deterministic mode don't attribute their light to themselves
+        if !matches!(self.ray_marcher.mode, RenderMode::Shaded) {
+            return false;
+        }
+
+        let factor = match scene.objects.get(index) {
+            Some(object) => match object.emission_scale(name, value) {
+                Some(factor) => factor,
+                None => return false,
+            },
+            None => return false,
+        };
+
+        // light of the object isn't kept apart yet
+        let Some(plane) = plane else {
+            return false;
+        };
+
+        let width = self.frame.width;
+        let planes = sources.len() / (width * self.frame.height).max(1);
+
+        if plane >= planes || sources.len() != width * planes * self.frame.height {
+            return false;
+        }
+        let source_row = width * planes;
+
+        let mut write_lock = front_fb.write().unwrap();
+
+        for (row, sources_row) in write_lock
+            .buffer_mut()
+            .chunks_mut(width)
+            .zip(sources.chunks_mut(source_row))
+        {
+            let object_row = &mut sources_row[plane * width..(plane + 1) * width];
+
+            for (pixel, source) in row.iter_mut().zip(object_row) {
+                let scaled = Pixel::new(
+                    source.r * factor.x as f32,
+                    source.g * factor.y as f32,
+                    source.b * factor.z as f32,
+                    source.a,
+                );
+
+                pixel.r += scaled.r - source.r;
+                pixel.g += scaled.g - source.g;
+                pixel.b += scaled.b - source.b;
+
+                *source = scaled;
+            }
+        }
+
+        true
+    }
+
     fn msg_to_actions(msg: Result<RenderInMsg, RecvError>) -> RendererActions {
         match msg {
             Err(RecvError::Disconnected) | Ok(RenderInMsg::Exit) => RendererActions::Exit,
             Ok(RenderInMsg::SceneChange(scene)) => RendererActions::Restart {
-                scene_change: Some(scene),
+                scene_change: Some(SceneEdit::Whole(scene)),
+                resize_buffers: None,
+            },
+            Ok(RenderInMsg::CameraUpdate(camera)) => RendererActions::Restart {
+                scene_change: Some(SceneEdit::Camera(camera)),
+                resize_buffers: None,
+            },
+            Ok(RenderInMsg::ObjectTransform(index, shape)) => RendererActions::Restart {
+                scene_change: Some(SceneEdit::ObjectShape(index, shape)),
+                resize_buffers: None,
+            },
+            Ok(RenderInMsg::ShaderParam(index, name, value)) => RendererActions::Restart {
+                scene_change: Some(SceneEdit::ShaderParam(index, name, value)),
                 resize_buffers: None,
             },
             Ok(RenderInMsg::Resize(x, y)) => RendererActions::Restart {
                 scene_change: None,
                 resize_buffers: Some((x, y)),
             },
+            Ok(RenderInMsg::TimeChange(time)) => RendererActions::Restart {
+                scene_change: Some(SceneEdit::Time(time)),
+                resize_buffers: None,
+            },
+            Ok(RenderInMsg::SliceChange(slice)) => RendererActions::Restart {
+                scene_change: Some(SceneEdit::Slice(slice)),
+                resize_buffers: None,
+            },
+            Ok(RenderInMsg::TraceRays(points)) => RendererActions::TraceRays(points),
             Ok(RenderInMsg::Restart) => RendererActions::Restart {
                 scene_change: None,
                 resize_buffers: None,
@@ -175,49 +525,130 @@ impl InteractiveRenderer {
 
     fn scanline(
         &self,
-        scene: &Scene,
-        max_step: f64,
+        pass: &Pass,
         y: usize,
         slice_input: &[Pixel],
         slice_output: &mut [Pixel],
-        sample: usize,
-        offset: (f64, f64),
+        slice_sources: &mut [Pixel],
+        scratch: &mut LineScratch,
     ) {
-        if let Region::Window { y_min, y_max, .. } = self.frame.region {
+        let Pass {
+            scene,
+            sources,
+            max_step,
+            sample,
+            seed_weight,
+            offset,
+        } = *pass;
+
+        let region = self.frame.traced_region();
+
+        if let Region::Window { y_min, y_max, .. } = region {
             if y >= y_max || y < y_min {
                 return;
             }
         }
 
         let rel_y = (y as f64 + offset.1) / (self.frame.height as f64);
+        let exposure = scene.camera.exposure.multiplier();
+        let width = self.frame.width;
+
+        let count = seed_weight + sample as f32;
+        let (keep, add) = (count / (count + 1.0), 1.0 / (count + 1.0));
+
+        let contributions = &mut scratch.contributions;
+        contributions.resize(scene.objects.len() + 1, Vector3::zero());
 
         for (x, pixel) in slice_input.iter().enumerate() {
-            if let Region::Window { x_min, x_max, .. } = self.frame.region {
-                if x >= x_max || x < x_min {
-                    continue;
-                }
+            if !region.contains(x, y) {
+                continue;
             }
 
             let rel_x = (x as f64 + offset.0) / (self.frame.width as f64);
 
-            let sample_info = self.ray_marcher.color_for_ray(
-                scene
-                    .camera
-                    .cast_ray(rel_x, rel_y, self.frame.aspect_ratio()),
-                scene,
-                max_step,
-                0,
-            );
+            seed_rng(pixel_seed(self.seed, sample, x, y));
+
+            let ray = scene
+                .camera
+                .cast_ray(rel_x, rel_y, self.frame.aspect_ratio());
+
+            let sample_info = if sources.is_empty() {
+                self.ray_marcher
+                    .color_for_ray(ray, scene, max_step, 0, &mut scratch.marcher)
+            } else {
+                contributions.fill(Vector3::zero());
+
+                self.ray_marcher.color_for_ray_by_source(
+                    ray,
+                    scene,
+                    max_step,
+                    contributions,
+                    &mut scratch.marcher,
+                )
+            };
 
             if let RenderMode::Samples = self.ray_marcher.mode {
-                slice_output[x] += Pixel::new(sample_info.steps as f32, 0.0, 0.0, 0.0);
+                // mean step count goes to the first source, see `show_steps`
+                let steps = Pixel::new(sample_info.steps as f32, 0.0, 0.0, 1.0);
+
+                slice_sources[x] = slice_sources[x] * keep + steps * add;
             } else {
                 let base = *pixel;
 
-                let color = Pixel::from(sample_info.color);
+                let color = Pixel::from(sample_info.color * exposure);
+
+                slice_output[x] = base * keep + color * add;
+
+                for (plane, &object) in sources.iter().enumerate() {
+                    let source = &mut slice_sources[plane * width + x];
+                    let color = Pixel::from(contributions[object] * exposure);
 
-                slice_output[x] = base * (sample as f32 / (sample as f32 + 1.0))
-                    + color * (1.0 / (sample as f32 + 1.0));
+                    *source = *source * keep + color * add;
+                }
+            }
+        }
+    }
+}
+
+/// Bilinear upscale of images with `planes` rows of given width stored after each other per line.
+///
+/// Pixel centers of both images are aligned, so the upscaled image doesn't shift.
+fn upscale(
+    src: &[Pixel],
+    src_size: (usize, usize),
+    dst: &mut [Pixel],
+    dst_size: (usize, usize),
+    planes: usize,
+) {
+    let (src_w, src_h) = src_size;
+    let (dst_w, dst_h) = dst_size;
+
+    if src_w == 0 || src_h == 0 {
+        return;
+    }
+
+    let coord = |i: usize, src: usize, dst: usize| {
+        let pos = ((i as f32 + 0.5) * src as f32 / dst as f32 - 0.5).clamp(0.0, (src - 1) as f32);
+        let low = pos.floor() as usize;
+
+        (low, (low + 1).min(src - 1), pos - low as f32)
+    };
+
+    for y in 0..dst_h {
+        let (y0, y1, fy) = coord(y, src_h, dst_h);
+
+        for plane in 0..planes {
+            let row0 = (y0 * planes + plane) * src_w;
+            let row1 = (y1 * planes + plane) * src_w;
+            let out = (y * planes + plane) * dst_w;
+
+            for x in 0..dst_w {
+                let (x0, x1, fx) = coord(x, src_w, dst_w);
+
+                let top = src[row0 + x0] * (1.0 - fx) + src[row0 + x1] * fx;
+                let bottom = src[row1 + x0] * (1.0 - fx) + src[row1 + x1] * fx;
+
+                dst[out + x] = top * (1.0 - fy) + bottom * fy;
             }
         }
     }
@@ -228,33 +659,239 @@ impl Default for InteractiveRenderer {
         Self {
             ray_marcher: RayMarcher::default(),
             samples: 128,
+            overrides: RenderSettings::default(),
+            seed: 0,
             threads: 0,
             frame: Frame {
                 width: 1280,
                 height: 720,
                 region: Region::Whole,
+                border: 0,
             },
             filter: Box::new(BlackmanHarrisFilter::new(1.5)),
             scaling: Default::default(),
+            lod_falloff: 1.0,
         }
     }
 }
 
+/// Buffers reused by all scanlines traced on one thread.
+#[derive(Default)]
+struct LineScratch {
+    marcher: Scratch,
+    /// Light of the current pixel split by its source
+    contributions: Vec<Vector3<f64>>,
+}
+
+/// Settings shared by all scanlines of one progressive pass.
+struct Pass<'a> {
+    scene: &'a Scene,
+    /// Objects whose light is accumulated apart, see `InteractiveRenderer::emissive_objects`
+    sources: &'a [usize],
+    max_step: f64,
+    sample: usize,
+    /// Samples the accumulated image was seeded with from the previous scale
+    seed_weight: f32,
+    /// Position of the samples within pixels
+    offset: (f64, f64),
+}
+
 pub enum RendererActions {
     Exit,
+    /// Keep sampling, the change was applied without restarting
+    Continue,
     Restart {
         resize_buffers: Option<(u32, u32)>,
-        scene_change: Option<Scene>,
+        scene_change: Option<SceneEdit>,
     },
+    TraceRays(Vec<(f64, f64)>),
+}
+
+/// Change applied to the scene retained by the render thread.
+pub enum SceneEdit {
+    Whole(Box<Scene>),
+    Camera(Camera),
+    ObjectShape(usize, Arc<dyn Shape>),
+    ShaderParam(usize, String, Parameter),
+    Time(f64),
+    Slice(Option<SlicePlane>),
+}
+
+impl SceneEdit {
+    fn apply(self, scene: &mut Scene) {
+        match self {
+            Self::Whole(new_scene) => *scene = *new_scene,
+            // cameras moved by hand stand still while the shutter is open
+            Self::Camera(camera) => {
+                scene.camera = Camera {
+                    motion: None,
+                    ..camera
+                }
+            }
+            // objects moved by hand stop following their track
+            Self::ObjectShape(index, shape) => match scene.objects.get_mut(index) {
+                Some(object) => {
+                    object.shape = shape;
+                    object.animation = None;
+                }
+                None => eprintln!("No object with index {index} to transform"),
+            },
+            Self::ShaderParam(index, name, value) => match scene.objects.get_mut(index) {
+                Some(object) => object.set_shader_parameter(&name, value),
+                None => eprintln!("No object with index {index} to change shader of"),
+            },
+            Self::Time(time) => scene.set_time(time),
+            Self::Slice(slice) => scene.slice = slice,
+        }
+
+        scene.update_lights();
+    }
 }
 
 pub enum RenderInMsg {
     Resize(u32, u32),
-    SceneChange(Scene),
+    /// Replaces the whole scene, should only be used on scene load
+    SceneChange(Box<Scene>),
+    CameraUpdate(Camera),
+    /// Replaces shape of object with given index
+    ObjectTransform(usize, Arc<dyn Shape>),
+    /// Sets parameter of shader used by object with given index
+    ShaderParam(usize, String, Parameter),
+    /// Poses animated parts of the scene at given time in seconds
+    TimeChange(f64),
+    /// Sets or removes the plane cutting the scene open
+    SliceChange(Option<SlicePlane>),
+    /// Traces paths of rays going through given relative image coordinates
+    TraceRays(Vec<(f64, f64)>),
     Restart,
     Exit,
 }
 
 pub enum RenderOutMsg {
     Update(Scaling),
+    RayPaths(Vec<Vec<Vector3<f64>>>),
+    /// Average time of recent progressive passes
+    Timings(PassTimings),
+}
+
+/// Wall time of progressive passes split into their parts.
+///
+/// Threads only measure how long they were busy with each part, their shares split the time of
+/// the whole pass.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PassTimings {
+    pub scale: Option<Scaling>,
+    pub passes: u32,
+    /// Marching rays through the scene
+    pub march: Duration,
+    /// Running shaders of hit objects and the background
+    pub shade: Duration,
+    /// Casting camera rays and accumulating samples into the image
+    pub accumulate: Duration,
+}
+
+impl PassTimings {
+    fn add_pass(&mut self, wall: Duration, stats: &MarchStats, busy: Duration) {
+        let share = |part: Duration| {
+            let ratio = part.as_secs_f64() / busy.as_secs_f64().max(1e-9);
+
+            wall.mul_f64(ratio.min(1.0))
+        };
+
+        let march = share(stats.march_time);
+        let shade = share(stats.shade_time);
+
+        self.march += march;
+        self.shade += shade;
+        self.accumulate += wall.saturating_sub(march + shade);
+        self.passes += 1;
+    }
+
+    /// Timings of a single pass, averaged over the added ones.
+    fn average(&self, scale: Scaling) -> Self {
+        let passes = self.passes.max(1);
+
+        Self {
+            scale: Some(scale),
+            passes: self.passes,
+            march: self.march / passes,
+            shade: self.shade / passes,
+            accumulate: self.accumulate / passes,
+        }
+    }
+
+    pub fn total(&self) -> Duration {
+        self.march + self.shade + self.accumulate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upscale_keeps_pixel_centers_and_planes() {
+        let gray = |v: f32| Pixel::new(v, v, v, 1.0);
+
+        // two planes of 2x1 pixels, a ramp and a flat one
+        let src = [gray(0.0), gray(1.0), gray(2.0), gray(2.0)];
+        let mut dst = [gray(-1.0); 16];
+
+        upscale(&src, (2, 1), &mut dst, (4, 2), 2);
+
+        let red = dst.map(|p| p.r);
+        for y in 0..2 {
+            let ramp = &red[y * 8..y * 8 + 4];
+            let flat = &red[y * 8 + 4..y * 8 + 8];
+
+            assert_eq!(ramp, [0.0, 0.25, 0.75, 1.0]);
+            assert_eq!(flat, [2.0; 4]);
+        }
+    }
+
+    #[test]
+    fn rescaled_source_changes_only_its_light() {
+        use blackhole::object::shape::Sphere;
+        use blackhole_common::shaders::BasicSolidShader;
+
+        let renderer = InteractiveRenderer {
+            frame: Frame {
+                width: 2,
+                height: 1,
+                region: Region::Whole,
+                border: 0,
+            },
+            ..Default::default()
+        };
+        let emission = |e: f64| Parameter::Vec3(Vector3::new(e, e, e));
+        let scene = Scene::builder()
+            .object(|o| o.shape(Sphere::new()).solid(BasicSolidShader::default()))
+            .object(|o| {
+                o.shape(Sphere::new())
+                    .solid(BasicSolidShader::default())
+                    .parameter("emission", emission(1.0))
+            })
+            .build();
+        assert_eq!(renderer.emissive_objects(&scene), [1]);
+
+        // light of the second object in the only plane, the rest comes from elsewhere
+        let gray = |v: f32| Pixel::new(v, v, v, 1.0);
+        let mut sources = [gray(0.25), gray(0.0)];
+        let mut fb = FrameBuffer::new(2, 1);
+        fb.buffer_mut().copy_from_slice(&[gray(1.0), gray(0.5)]);
+        let front_fb = RwLock::new(fb);
+
+        let rescale = |sources: &mut [Pixel], plane, index, value| {
+            renderer.rescale_source(&scene, &front_fb, sources, plane, index, "emission", &value)
+        };
+
+        // objects without a plane and emission turned on from zero need a restart
+        assert!(!rescale(&mut sources, None, 1, emission(3.0)));
+        assert!(!rescale(&mut sources, Some(0), 0, emission(3.0)));
+
+        assert!(rescale(&mut sources, Some(0), 1, emission(3.0)));
+        assert_eq!(sources.map(|p| p.r), [0.75, 0.0]);
+        assert_eq!(front_fb.read().unwrap().buffer()[0].r, 1.5);
+        assert_eq!(front_fb.read().unwrap().buffer()[1].r, 0.5);
+    }
 }
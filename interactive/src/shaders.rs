@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Shader sources built into the binary.
+const EMBEDDED: &[(&str, &str)] = &[
+    ("quad.glsl", include_str!("gl_shaders/quad.glsl")),
+    ("copy.glsl", include_str!("gl_shaders/copy.glsl")),
+    ("line.glsl", include_str!("gl_shaders/line.glsl")),
+    (
+        "line_color.glsl",
+        include_str!("gl_shaders/line_color.glsl"),
+    ),
+    ("ui.glsl", include_str!("gl_shaders/ui.glsl")),
+    ("ui_color.glsl", include_str!("gl_shaders/ui_color.glsl")),
+    ("output.glsl", include_str!("gl_shaders/output.glsl")),
+    ("post_blur.glsl", include_str!("gl_shaders/post_blur.glsl")),
+    (
+        "post_bright.glsl",
+        include_str!("gl_shaders/post_bright.glsl"),
+    ),
+];
+
+/// How often the shader directory is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// GLSL sources of the viewer, optionally read from a directory so they can be edited at runtime.
+///
+/// Shaders missing from the directory are taken from the binary.
+pub struct ShaderSources {
+    dir: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl ShaderSources {
+    pub fn embedded() -> Self {
+        Self {
+            dir: None,
+            modified: None,
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn from_dir(dir: PathBuf) -> Self {
+        let mut sources = Self {
+            dir: Some(dir),
+            modified: None,
+            last_poll: Instant::now(),
+        };
+        sources.modified = sources.last_modified();
+
+        sources
+    }
+
+    /// Returns source of shader with given file name.
+    pub fn get(&self, name: &str) -> String {
+        if let Some(dir) = &self.dir {
+            let path = dir.join(name);
+
+            if path.is_file() {
+                match std::fs::read_to_string(&path) {
+                    Ok(src) => return src,
+                    Err(e) => eprintln!("Could not read shader {}: {e}", path.display()),
+                }
+            }
+        }
+
+        EMBEDDED
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, src)| (*src).to_owned())
+            .unwrap_or_else(|| panic!("unknown shader {name}"))
+    }
+
+    /// Checks whether any shader in the directory changed since the last call.
+    ///
+    /// The directory is read at most every [`POLL_INTERVAL`].
+    pub fn poll_changes(&mut self) -> bool {
+        if self.dir.is_none() || self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+
+        self.last_poll = Instant::now();
+
+        let modified = self.last_modified();
+        let changed = modified != self.modified;
+        self.modified = modified;
+
+        changed
+    }
+
+    fn last_modified(&self) -> Option<SystemTime> {
+        let dir = self.dir.as_ref()?;
+
+        EMBEDDED
+            .iter()
+            .filter_map(|(name, _)| dir.join(name).metadata().ok()?.modified().ok())
+            .max()
+    }
+}
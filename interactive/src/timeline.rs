@@ -0,0 +1,121 @@
+use std::time::Instant;
+
+use winit::dpi::PhysicalSize;
+
+use gl_wrapper::geometry::{Geometry, GeometryBuilder, VertexAttribute};
+
+/// Height of the timeline bar at the bottom of the window, in pixels.
+const BAR_HEIGHT: f64 = 14.0;
+/// Width of the playhead, in pixels.
+const PLAYHEAD_WIDTH: f64 = 3.0;
+
+/// Playback of the scene animation, shown as a bar at the bottom of the window.
+pub struct Timeline {
+    duration: f64,
+    fps: f64,
+    time: f64,
+    playing: bool,
+    /// Left mouse button was pressed over the bar and is still held
+    pub scrubbing: bool,
+    last_tick: Instant,
+}
+
+impl Timeline {
+    pub fn new(duration: f64, fps: f64) -> Self {
+        Self {
+            duration,
+            fps,
+            time: 0.0,
+            playing: false,
+            scrubbing: false,
+            last_tick: Instant::now(),
+        }
+    }
+
+    pub fn toggle_play(&mut self) {
+        self.playing = !self.playing;
+        self.last_tick = Instant::now();
+    }
+
+    /// Advances time by real time passed while playing, looping at the end.
+    ///
+    /// Returns the new time if it changed.
+    pub fn tick(&mut self) -> Option<f64> {
+        let now = Instant::now();
+        let elapsed = (now - self.last_tick).as_secs_f64();
+        self.last_tick = now;
+
+        if !self.playing || self.scrubbing {
+            return None;
+        }
+
+        self.time = (self.time + elapsed) % self.duration;
+
+        Some(self.time)
+    }
+
+    /// Moves by whole frames, returns the new time.
+    pub fn step(&mut self, frames: i64) -> f64 {
+        let frame = (self.time * self.fps).round() + frames as f64;
+
+        self.time = (frame / self.fps).clamp(0.0, self.duration);
+        self.time
+    }
+
+    /// Moves to the time under cursor at given horizontal position, returns the new time.
+    pub fn scrub_to(&mut self, x: f64, size: PhysicalSize<u32>) -> f64 {
+        self.time = (x / size.width as f64).clamp(0.0, 1.0) * self.duration;
+        self.time
+    }
+
+    /// Checks whether cursor at given vertical position is over the bar.
+    pub fn contains(&self, y: f64, size: PhysicalSize<u32>) -> bool {
+        y >= size.height as f64 - BAR_HEIGHT
+    }
+
+    /// Bar with played part and playhead, with positions in clip space and colors per vertex.
+    pub fn geometry(&self, size: PhysicalSize<u32>) -> Geometry {
+        let top = (-1.0 + 2.0 * BAR_HEIGHT / size.height as f64) as f32;
+        let progress = (self.time / self.duration) as f32;
+        let head = (PLAYHEAD_WIDTH / size.width as f64) as f32;
+        let x = progress * 2.0 - 1.0;
+
+        let played = if self.playing {
+            [0.9, 0.45, 0.15]
+        } else {
+            [0.55, 0.55, 0.55]
+        };
+
+        let mut data = Vec::new();
+        rectangle(&mut data, (-1.0, -1.0), (1.0, top), [0.1, 0.1, 0.1]);
+        rectangle(&mut data, (-1.0, -1.0), (x, top), played);
+        rectangle(
+            &mut data,
+            (x - head, -1.0),
+            (x + head, top),
+            [1.0, 1.0, 1.0],
+        );
+
+        GeometryBuilder::new(&data)
+            .with_attribute(VertexAttribute::Vec2)
+            .with_attribute(VertexAttribute::Vec3)
+            .build()
+            .unwrap()
+    }
+}
+
+fn rectangle(data: &mut Vec<f32>, min: (f32, f32), max: (f32, f32), color: [f32; 3]) {
+    let corners = [
+        (min.0, min.1),
+        (max.0, min.1),
+        (max.0, max.1),
+        (min.0, min.1),
+        (max.0, max.1),
+        (min.0, max.1),
+    ];
+
+    for (x, y) in corners {
+        data.extend_from_slice(&[x, y]);
+        data.extend_from_slice(&color);
+    }
+}
@@ -16,4 +16,6 @@ pub struct Test {
     pub width: usize,
     pub height: usize,
     pub samples: usize,
+    /// Seed of the render, the same one as of the original makes them identical
+    pub seed: Option<u64>,
 }
@@ -1,4 +1,3 @@
-use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -22,15 +21,8 @@ fn main() {
         println!("Testing {:?}", test.scene_path);
 
         let test_res = execute_test(&test_path, test).unwrap();
-        let new_img = read_image(&test_path, &test_res);
-        let old_img = read_image(&test_path, &test.original_image);
 
-        let comp = compare(&new_img, &old_img);
-
-        println!(
-            "Total error: {}\nPercentage error: {}%",
-            comp.total_err, comp.percentage_err
-        );
+        compare(&test_path, &test.original_image, &test_res);
     }
 }
 
@@ -51,8 +43,8 @@ fn execute_test(wd: impl AsRef<Path>, test: &Test) -> Result<PathBuf, ()> {
 
     let output_name = test.original_image.with_file_name(file_name);
 
-    let mut cmd = Command::new("../target/release/blackhole-cli")
-        .current_dir(wd)
+    let mut cmd = Command::new("../target/release/blackhole-cli");
+    cmd.current_dir(wd)
         .arg(&test.scene_path)
         .args([
             "--width",
@@ -63,50 +55,31 @@ fn execute_test(wd: impl AsRef<Path>, test: &Test) -> Result<PathBuf, ()> {
             &test.samples.to_string(),
             "--output",
         ])
-        .arg(&output_name)
-        .spawn()
-        .unwrap();
-
-    cmd.wait().unwrap();
-
-    Ok(output_name)
-}
-
-fn read_image(test_path: impl AsRef<Path>, path: impl AsRef<Path>) -> Vec<u8> {
-    let mut img_path = test_path.as_ref().to_owned();
-    img_path.push(path.as_ref());
-    let file = File::open(img_path).unwrap();
-
-    let decoder = png::Decoder::new(file);
-
-    let mut reader = decoder.read_info().unwrap();
+        .arg(&output_name);
 
-    let mut buf = vec![0; reader.output_buffer_size()];
-    let info = reader.next_frame(&mut buf).unwrap();
-
-    buf
-}
-
-fn compare(new_img: &[u8], old_img: &[u8]) -> Comparison {
-    if new_img.len() != old_img.len() {
-        panic!("sizes do not match");
+    if let Some(seed) = test.seed {
+        cmd.args(["--seed", &seed.to_string()]);
     }
 
-    let mut total_err = 0.0;
+    cmd.spawn().unwrap().wait().unwrap();
 
-    for (n, o) in new_img.iter().zip(old_img.iter()) {
-        total_err += n.abs_diff(*o) as f32 / 255.0;
-    }
+    Ok(output_name)
+}
 
-    let percentage_err = (total_err / new_img.len() as f32) * 100.0;
+/// Prints relative error of the new render and saves false color difference next to it.
+fn compare(wd: impl AsRef<Path>, original: &Path, output: &Path) {
+    let mut file_name = original.file_stem().unwrap().to_owned();
+    file_name.push("_diff.png");
 
-    Comparison {
-        total_err,
-        percentage_err,
-    }
-}
+    let mut cmd = Command::new("../target/release/blackhole-cli")
+        .current_dir(wd)
+        .arg("diff")
+        .arg(original)
+        .arg(output)
+        .arg("--output")
+        .arg(original.with_file_name(file_name))
+        .spawn()
+        .unwrap();
 
-struct Comparison {
-    pub total_err: f32,
-    pub percentage_err: f32,
+    cmd.wait().unwrap();
 }